@@ -0,0 +1,127 @@
+extern crate yaml_rust_formatter;
+
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::process;
+use yaml_rust_formatter::diff::{self, Change, Path, Segment};
+use yaml_rust_formatter::mask::{self, MaskRules};
+use yaml_rust_formatter::report::ExitStatus;
+use yaml_rust_formatter::yaml::{YamlInput, YamlLoader};
+use yaml_rust_formatter::YamlEmitter;
+
+const USAGE: &str = "usage: yaml-diff [--patch] [--no-color] [--show-secrets] OLD NEW
+
+Prints the changes between the documents of OLD and NEW, one per line:
+  + path: value      added
+  - path             removed
+  ~ path: old -> new changed
+
+  --patch          print the changes as a JSON Patch (RFC 6902) in YAML instead
+  --no-color       do not colorize the output (also disabled by NO_COLOR)
+  --show-secrets   do not mask the values of secret-looking keys";
+
+fn load(path: &str) -> Vec<YamlInput> {
+    let mut s = String::new();
+    let read = File::open(path).and_then(|mut f| f.read_to_string(&mut s));
+    if let Err(e) = read {
+        eprintln!("{}: {}", path, e);
+        process::exit(ExitStatus::Failure.code());
+    }
+    match YamlLoader::load_from_str(&s) {
+        Ok(docs) => docs,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(ExitStatus::Failure.code());
+        }
+    }
+}
+
+/// Render `value` on one line, masked if the last key of `path` looks secret.
+fn show(path: &Path, value: &YamlInput, rules: &MaskRules) -> String {
+    match path.segments().last() {
+        Some(Segment::Key(ref key)) if rules.is_masked(key) => rules.get_mask().to_owned(),
+        _ => mask::to_log_string(value, rules),
+    }
+}
+
+fn paint(color: bool, code: &str, line: &str) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", code, line)
+    } else {
+        line.to_owned()
+    }
+}
+
+fn main() {
+    let mut patch = false;
+    let mut color = env::var_os("NO_COLOR").is_none();
+    let mut rules = MaskRules::new();
+    let mut files = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--patch" => patch = true,
+            "--no-color" => color = false,
+            "--show-secrets" => rules.clear_keys(),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => files.push(arg),
+        }
+    }
+    if files.len() != 2 {
+        eprintln!("{}", USAGE);
+        process::exit(ExitStatus::Failure.code());
+    }
+    let old = load(&files[0]);
+    let new = load(&files[1]);
+
+    let (old_count, new_count) = (old.len(), new.len());
+    let mut changed = false;
+    for i in 0..old_count.max(new_count) {
+        let old = old.get(i).unwrap_or(&YamlInput::Null);
+        let new = new.get(i).unwrap_or(&YamlInput::Null);
+        let changes = diff::diff(old, new);
+        changed |= !changes.is_empty();
+
+        if patch {
+            let mut out = String::new();
+            YamlEmitter::new(&mut out)
+                .dump(&diff::to_patch(&changes))
+                .unwrap();
+            println!("{}", out);
+            continue;
+        }
+        if changes.is_empty() {
+            continue;
+        }
+        if old_count.max(new_count) > 1 {
+            println!("{}", paint(color, "1", &format!("document {}", i + 1)));
+        }
+        for change in &changes {
+            let line = match *change {
+                Change::Added(ref path, ref value) => paint(
+                    color,
+                    "32",
+                    &format!("+ {}: {}", path, show(path, value, &rules)),
+                ),
+                Change::Removed(ref path) => paint(color, "31", &format!("- {}", path)),
+                Change::Changed(ref path, ref from, ref to) => paint(
+                    color,
+                    "33",
+                    &format!(
+                        "~ {}: {} -> {}",
+                        path,
+                        show(path, from, &rules),
+                        show(path, to, &rules)
+                    ),
+                ),
+            };
+            println!("{}", line);
+        }
+    }
+    if changed {
+        process::exit(ExitStatus::Findings.code());
+    }
+}
@@ -0,0 +1,498 @@
+use crate::parser::{Event, Parser};
+use crate::scanner::{Marker, ScanError, TScalarStyle, TokenType};
+use crate::yaml::{
+    custom_tag, parse_f64, plain_value, resolve_scalar, Expansion, LoadOptions, YamlInput,
+};
+use linked_hash_map::LinkedHashMap;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Index;
+
+/// A YAML node borrowing its scalars from the source it is loaded from, see
+/// `YamlLoader::load_from_str_borrowed`.
+///
+/// Only the data is kept: anchors, custom tags and comments are dropped, and an
+/// alias is a copy of the node it refers to, which copies no text as long as the
+/// scalars of that node are borrowed. The copies count against the alias limits of
+/// the `LoadOptions`, see `YamlLoader::load_from_str_borrowed_with_options`.
+///
+/// # Examples
+///
+/// ```
+/// use std::borrow::Cow;
+/// use yaml_rust_formatter::borrowed::Yaml;
+/// use yaml_rust_formatter::YamlLoader;
+///
+/// let source = "host: example.com\nnote: \"tab\\there\"\n";
+/// let docs = YamlLoader::load_from_str_borrowed(source).unwrap();
+/// assert_eq!(docs[0]["host"].as_str(), Some("example.com"));
+/// // a scalar with escapes is not spelled as it is in the source
+/// assert!(matches!(docs[0]["host"], Yaml::String(Cow::Borrowed(_))));
+/// assert!(matches!(docs[0]["note"], Yaml::String(Cow::Owned(_))));
+/// ```
+#[derive(Clone, PartialEq, PartialOrd, Debug, Eq, Ord, Hash)]
+pub enum Yaml<'a> {
+    /// Float types are stored as their text and parsed on demand.
+    Real(Cow<'a, str>),
+    /// YAML int is stored as i64.
+    Integer(i64),
+    /// YAML scalar, borrowed when it is written in the source as it reads.
+    String(Cow<'a, str>),
+    /// YAML bool, e.g. `true` or `false`.
+    Boolean(bool),
+    /// YAML array, can be accessed as a `Vec`.
+    Array(Vec<Yaml<'a>>),
+    /// YAML hash, in the order of the source.
+    Hash(LinkedHashMap<Yaml<'a>, Yaml<'a>>),
+    /// The bytes of a `!!binary` scalar, decoded from base64.
+    Binary(Vec<u8>),
+    /// YAML null, e.g. `null` or `~`.
+    Null,
+    /// Accessing a nonexistent node via the Index trait returns `BadValue`.
+    BadValue,
+}
+
+impl<'a> Yaml<'a> {
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Yaml::String(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Yaml::Integer(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Yaml::Real(ref v) => parse_f64(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Yaml::Boolean(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_vec(&self) -> Option<&Vec<Yaml<'a>>> {
+        match *self {
+            Yaml::Array(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_hash(&self) -> Option<&LinkedHashMap<Yaml<'a>, Yaml<'a>>> {
+        match *self {
+            Yaml::Hash(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(*self, Yaml::Null)
+    }
+
+    pub fn is_badvalue(&self) -> bool {
+        matches!(*self, Yaml::BadValue)
+    }
+
+    /// Copy the borrowed scalars, so that the node outlives its source.
+    pub fn into_owned(self) -> Yaml<'static> {
+        match self {
+            Yaml::Real(v) => Yaml::Real(Cow::Owned(v.into_owned())),
+            Yaml::Integer(v) => Yaml::Integer(v),
+            Yaml::String(v) => Yaml::String(Cow::Owned(v.into_owned())),
+            Yaml::Boolean(v) => Yaml::Boolean(v),
+            Yaml::Array(v) => Yaml::Array(v.into_iter().map(Yaml::into_owned).collect()),
+            Yaml::Hash(h) => Yaml::Hash(
+                h.into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect(),
+            ),
+            Yaml::Binary(v) => Yaml::Binary(v),
+            Yaml::Null => Yaml::Null,
+            Yaml::BadValue => Yaml::BadValue,
+        }
+    }
+}
+
+impl<'a> From<Yaml<'a>> for YamlInput {
+    fn from(node: Yaml<'a>) -> YamlInput {
+        match node {
+            Yaml::Real(v) => YamlInput::Real(v.into_owned()),
+            Yaml::Integer(v) => YamlInput::Integer(v),
+            Yaml::String(v) => YamlInput::String(v.into_owned()),
+            Yaml::Boolean(v) => YamlInput::Boolean(v),
+            Yaml::Array(v) => YamlInput::Array(v.into_iter().map(YamlInput::from).collect()),
+            Yaml::Hash(h) => {
+                YamlInput::Hash(h.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+            }
+            Yaml::Binary(v) => YamlInput::Binary(v),
+            Yaml::Null => YamlInput::Null,
+            Yaml::BadValue => YamlInput::BadValue,
+        }
+    }
+}
+
+/// Look up a key of a mapping without copying it, which takes a key that lives as
+/// long as the source; a shorter lived one can be looked up in `as_hash` as a
+/// `Yaml::String(Cow::Owned(..))`.
+impl<'a> Index<&'a str> for Yaml<'a> {
+    type Output = Yaml<'a>;
+
+    fn index(&self, idx: &'a str) -> &Yaml<'a> {
+        let key = Yaml::String(Cow::Borrowed(idx));
+        match self.as_hash().and_then(|h| h.get(&key)) {
+            Some(v) => v,
+            None => &Yaml::BadValue,
+        }
+    }
+}
+
+impl<'a> Index<usize> for Yaml<'a> {
+    type Output = Yaml<'a>;
+
+    fn index(&self, idx: usize) -> &Yaml<'a> {
+        let node = match *self {
+            Yaml::Array(ref v) => v.get(idx),
+            Yaml::Hash(ref h) => h.get(&Yaml::Integer(idx as i64)),
+            _ => None,
+        };
+        node.unwrap_or(&Yaml::BadValue)
+    }
+}
+
+/// Builds the documents of `YamlLoader::load_from_str_borrowed`.
+pub(crate) struct BorrowedLoader<'a> {
+    source: &'a str,
+    // the last marker index converted to a byte offset, and that offset
+    cursor: (usize, usize),
+    max_expanded_nodes: Option<usize>,
+    max_alias_depth: Option<usize>,
+    pub(crate) docs: Vec<Yaml<'a>>,
+    // (current node, anchor, expansion) tuple
+    doc_stack: Vec<(Yaml<'a>, Option<String>, Expansion)>,
+    key_stack: Vec<Yaml<'a>>,
+    anchor_map: HashMap<String, (Yaml<'a>, Expansion)>,
+    // nodes copied by the aliases of the current document
+    expanded_nodes: usize,
+}
+
+impl<'a> BorrowedLoader<'a> {
+    pub(crate) fn load(
+        source: &'a str,
+        options: &LoadOptions,
+    ) -> Result<BorrowedLoader<'a>, ScanError> {
+        let mut loader = BorrowedLoader {
+            source,
+            cursor: (0, 0),
+            max_expanded_nodes: options.get_max_expanded_nodes(),
+            max_alias_depth: options.get_max_alias_depth(),
+            docs: Vec::new(),
+            doc_stack: Vec::new(),
+            key_stack: Vec::new(),
+            anchor_map: HashMap::new(),
+            expanded_nodes: 0,
+        };
+        let mut parser = Parser::new(source.chars());
+        parser.keep_scalar_spans(true);
+        loop {
+            let (ev, mark) = parser.next()?;
+            let span = match ev {
+                Event::StreamEnd => return Ok(loader),
+                Event::Scalar(..) => parser.take_scalar_span(),
+                _ => None,
+            };
+            loader.on_event(ev, mark, span)?;
+        }
+    }
+
+    /// The byte offset of the character at `index`, counting on from the last one
+    /// asked for since nodes mostly come in the order of the source.
+    fn byte_offset(&mut self, index: usize) -> usize {
+        let (mut chars, mut bytes) = if index < self.cursor.0 {
+            (0, 0)
+        } else {
+            self.cursor
+        };
+        let mut rest = self.source[bytes..].chars();
+        while chars < index {
+            match rest.next() {
+                Some(c) => bytes += c.len_utf8(),
+                None => break,
+            }
+            chars += 1;
+        }
+        self.cursor = (chars, bytes);
+        bytes
+    }
+
+    /// The scalar of an event at `mark`, whose text is `v`, or the `span` bytes of
+    /// the source there when the scanner left it out of the event.
+    fn scalar(
+        &mut self,
+        v: String,
+        span: Option<usize>,
+        style: TScalarStyle,
+        tag: &Option<TokenType>,
+        mark: Marker,
+    ) -> Yaml<'a> {
+        let text = match span {
+            Some(len) => {
+                // the text of a quoted scalar starts after its quote
+                let skip = if style == TScalarStyle::Plain { 0 } else { 1 };
+                let start = self.byte_offset(mark.index()) + skip;
+                Cow::Borrowed(&self.source[start..start + len])
+            }
+            None => Cow::Owned(v),
+        };
+        // a custom tag is dropped, leaving the scalar as it is without it
+        let tag = if custom_tag(tag).is_some() {
+            &None
+        } else {
+            tag
+        };
+        let node = match *tag {
+            None if style == TScalarStyle::Plain => match plain_value(&text) {
+                Some(node) => node,
+                None if parse_f64(&text).is_some() => return Yaml::Real(text),
+                None => return Yaml::String(text),
+            },
+            None => return Yaml::String(text),
+            Some(_) => resolve_scalar(text.to_string(), style, tag),
+        };
+        match node {
+            // the tags keeping the text keep it as it is
+            YamlInput::Real(_) => Yaml::Real(text),
+            YamlInput::Integer(v) => Yaml::Integer(v),
+            YamlInput::String(_) => Yaml::String(text),
+            YamlInput::Boolean(v) => Yaml::Boolean(v),
+            YamlInput::Binary(v) => Yaml::Binary(v),
+            YamlInput::Null => Yaml::Null,
+            _ => Yaml::BadValue,
+        }
+    }
+
+    /// A copy of the node anchored as `name` for an alias at `mark`, unless the copy
+    /// would go over the expansion limits, which is then an error.
+    fn alias_target(
+        &mut self,
+        name: &str,
+        mark: Marker,
+    ) -> Result<(Yaml<'a>, Expansion), ScanError> {
+        let (node, expansion) = match self.anchor_map.get(name) {
+            Some(anchored) => anchored,
+            None => return Ok((Yaml::BadValue, Expansion { nodes: 1, depth: 0 })),
+        };
+        let refusal = match (self.max_expanded_nodes, self.max_alias_depth) {
+            (Some(max), _) if self.expanded_nodes + expansion.nodes > max => {
+                format!("aliases expand to more than {} nodes", max)
+            }
+            (_, Some(max)) if expansion.depth + 1 > max => {
+                format!("aliases are nested more than {} deep", max)
+            }
+            _ => {
+                self.expanded_nodes += expansion.nodes;
+                let expansion = Expansion {
+                    depth: expansion.depth + 1,
+                    ..*expansion
+                };
+                return Ok((node.clone(), expansion));
+            }
+        };
+        Err(ScanError::new(mark, &refusal))
+    }
+
+    fn insert_new_node(&mut self, node: (Yaml<'a>, Option<String>, Expansion)) {
+        if let Some(anchor) = node.1 {
+            self.anchor_map.insert(anchor, (node.0.clone(), node.2));
+        }
+        match self.doc_stack.last_mut() {
+            None => self.doc_stack.push((node.0, None, node.2)),
+            Some((ref mut parent, _, ref mut expansion)) => {
+                expansion.nodes += node.2.nodes;
+                expansion.depth = expansion.depth.max(node.2.depth);
+                match *parent {
+                    Yaml::Array(ref mut v) => v.push(node.0),
+                    Yaml::Hash(ref mut h) => {
+                        let cur_key = self.key_stack.last_mut().unwrap();
+                        // current node is a key
+                        if cur_key.is_badvalue() {
+                            *cur_key = node.0;
+                        // current node is a value
+                        } else {
+                            let key = std::mem::replace(cur_key, Yaml::BadValue);
+                            h.insert(key, node.0);
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+
+    fn on_event(&mut self, ev: Event, mark: Marker, span: Option<usize>) -> Result<(), ScanError> {
+        let leaf = Expansion { nodes: 1, depth: 0 };
+        match ev {
+            Event::DocumentStart => {
+                self.expanded_nodes = 0;
+            }
+            Event::DocumentEnd => {
+                let doc = self.doc_stack.pop().map_or(Yaml::BadValue, |node| node.0);
+                self.docs.push(doc);
+            }
+            Event::SequenceStart(aid, _) => {
+                self.doc_stack.push((Yaml::Array(Vec::new()), aid, leaf));
+            }
+            Event::MappingStart(aid, _) => {
+                self.doc_stack
+                    .push((Yaml::Hash(LinkedHashMap::new()), aid, leaf));
+                self.key_stack.push(Yaml::BadValue);
+            }
+            Event::SequenceEnd => {
+                let node = self.doc_stack.pop().unwrap();
+                self.insert_new_node(node);
+            }
+            Event::MappingEnd => {
+                self.key_stack.pop().unwrap();
+                let node = self.doc_stack.pop().unwrap();
+                self.insert_new_node(node);
+            }
+            Event::Scalar(v, style, aid, tag) => {
+                let node = self.scalar(v, span, style, &tag, mark);
+                self.insert_new_node((node, aid, leaf));
+            }
+            Event::Alias(name) => {
+                let (node, expansion) = self.alias_target(&name, mark)?;
+                self.insert_new_node((node, None, expansion));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::yaml::YamlLoader;
+
+    fn is_borrowed(node: &Yaml) -> bool {
+        matches!(
+            *node,
+            Yaml::String(Cow::Borrowed(_)) | Yaml::Real(Cow::Borrowed(_))
+        )
+    }
+
+    #[test]
+    fn test_load_borrowed() {
+        let source = "\
+name: café
+ratio: 0.5
+port: 8080
+quoted: 'it''s'
+double: \"plain text\"
+folded: one
+  two
+block: |
+  text
+base: &b {x: é}
+copy: *b
+list: [a, 'b', ~, true]
+empty:
+\"after empty\": [{: x}, 'ü']
+";
+        let docs = YamlLoader::load_from_str_borrowed(source).unwrap();
+        let doc = &docs[0];
+        assert!(is_borrowed(&doc["name"]));
+        assert!(is_borrowed(&doc["ratio"]));
+        assert_eq!(doc["ratio"].as_f64(), Some(0.5));
+        assert_eq!(doc["port"].as_i64(), Some(8080));
+        assert_eq!(doc["quoted"].as_str(), Some("it's"));
+        assert!(!is_borrowed(&doc["quoted"]));
+        assert!(is_borrowed(&doc["double"]));
+        assert_eq!(doc["folded"].as_str(), Some("one two"));
+        assert!(!is_borrowed(&doc["folded"]));
+        assert!(!is_borrowed(&doc["block"]));
+        assert!(is_borrowed(&doc["copy"]["x"]));
+        assert!(is_borrowed(&doc["list"][1]));
+        assert!(doc["list"][2].is_null());
+        assert!(doc["missing"].is_badvalue());
+        assert!(doc["empty"].is_null());
+        // the empty scalars the parser adds take no text from the source
+        let flow = doc["after empty"][0].as_hash().unwrap();
+        assert_eq!(flow.get(&Yaml::Null).and_then(Yaml::as_str), Some("x"));
+        assert_eq!(doc["after empty"][1].as_str(), Some("ü"));
+        for (key, _) in doc.as_hash().unwrap() {
+            assert!(is_borrowed(key));
+        }
+
+        // a key living as long as the source is looked up without a copy
+        let key = String::from("name");
+        let text = format!("{}: café", key);
+        let docs = YamlLoader::load_from_str_borrowed(&text).unwrap();
+        assert_eq!(docs[0][key.as_str()].as_str(), Some("café"));
+
+        let doc = doc.clone().into_owned();
+        assert!(!is_borrowed(&doc["name"]));
+        assert_eq!(doc["name"].as_str(), Some("café"));
+
+        // the same data as the owned model
+        let source = "a: [1, 2.5, 'x', \"y\\n\"]\nb: {c: null, 3: !!binary aGk=}\n";
+        let borrowed = YamlLoader::load_from_str_borrowed(source).unwrap();
+        let owned = YamlLoader::load_from_str(source).unwrap();
+        let borrowed: Vec<YamlInput> = borrowed.into_iter().map(YamlInput::from).collect();
+        assert_eq!(borrowed, owned);
+    }
+
+    #[test]
+    fn test_borrowed_custom_tags() {
+        let source = "a: !t 1\nb: !t true\nc: !t ~\nd: !t 'x'\ne: !<tag:x> 0.5\nf: !!str 1\n";
+        let borrowed = YamlLoader::load_from_str_borrowed(source).unwrap();
+        let owned = YamlLoader::load_from_str(source).unwrap();
+        for key in ["a", "b", "c", "d", "e", "f"] {
+            let value = owned[0][key].inner();
+            assert_eq!(YamlInput::from(borrowed[0][key].clone()), *value);
+        }
+        assert_eq!(borrowed[0]["a"].as_i64(), Some(1));
+        assert!(borrowed[0]["c"].is_null());
+        assert!(is_borrowed(&borrowed[0]["d"]));
+    }
+
+    #[test]
+    fn test_borrowed_alias_limits() {
+        let source = "a: &a [x, x]\nb: &b [*a, *a]\nc: [*b, *b]\n";
+        let mut options = LoadOptions::new();
+        options.max_expanded_nodes(Some(20));
+        let docs = YamlLoader::load_from_str_borrowed_with_options(source, &options).unwrap();
+        assert_eq!(docs[0]["c"][1][0][1].as_str(), Some("x"));
+        assert!(is_borrowed(&docs[0]["c"][1][0][1]));
+
+        options.max_expanded_nodes(Some(19));
+        let err = YamlLoader::load_from_str_borrowed_with_options(source, &options).unwrap_err();
+        assert_eq!(err.info(), "aliases expand to more than 19 nodes");
+        assert_eq!(err.marker().line(), 3);
+
+        options.max_expanded_nodes(None);
+        options.max_alias_depth(Some(1));
+        let err = YamlLoader::load_from_str_borrowed_with_options(source, &options).unwrap_err();
+        assert_eq!(err.info(), "aliases are nested more than 1 deep");
+        assert_eq!(err.marker().line(), 3);
+
+        // the same limits as the owned model
+        for max in 0..25 {
+            options.max_alias_depth(None);
+            options.max_expanded_nodes(Some(max));
+            let owned = YamlLoader::load_from_str_with_options(source, &options);
+            let borrowed = YamlLoader::load_from_str_borrowed_with_options(source, &options);
+            assert_eq!(owned.is_ok(), borrowed.is_ok());
+        }
+    }
+}
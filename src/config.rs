@@ -0,0 +1,455 @@
+//! Read the `FormatOptions` of a project from its configuration files, so that every
+//! tool formatting its files agrees on the style.
+//!
+//! A configuration is a mapping of settings, named after the methods of
+//! `FormatOptions`:
+//!
+//! ```yaml
+//! profile: kubernetes-manifest   # see StyleProfile, applied before the other settings
+//! indent: 2
+//! indent_sequences: false
+//! quote_style: single-when-needed  # plain, double-when-needed, always-single, always-double
+//! keep_scalar_style: false
+//! ascii_only: true
+//! line_width: 100                  # or ~ for none
+//! key_spacing: 1
+//! keep_blank_lines: 1
+//! align_values: 10
+//! line_ending: lf                  # crlf, cr
+//! normalize_scalars: true
+//! inline_anchors: 1
+//! embedded_yaml: ["data.*.yaml"]
+//! root: true                       # do not read the configurations of the parent directories
+//! ```
+//!
+//! It is read from a `.yamlfmt.yaml` file, or a `yamlfmt.toml` one with the `toml`
+//! feature. The files of the parent directories apply too, the nearest file overriding
+//! the settings of the farther ones, see `options_for`.
+
+use crate::format::{FormatOptions, StyleProfile};
+use crate::line_ending::LineEnding;
+use crate::scanner::ScanError;
+use crate::yaml::{YamlInput, YamlLoader};
+use crate::QuoteStyle;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The names of the configuration files, in the order they are looked for in a
+/// directory: only the first one found there is read.
+pub const CONFIG_FILE_NAMES: [&str; 2] = [".yamlfmt.yaml", "yamlfmt.toml"];
+
+/// The reason a configuration could not be read.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Scan(ScanError),
+    #[cfg(feature = "toml")]
+    Toml(toml::de::Error),
+    /// A setting that does not exist, or has a value it cannot take.
+    Invalid(String),
+    /// An error in a configuration file.
+    File(PathBuf, Box<ConfigError>),
+}
+
+impl Error for ConfigError {
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            ConfigError::Io(ref err) => Some(err),
+            ConfigError::Scan(ref err) => Some(err),
+            #[cfg(feature = "toml")]
+            ConfigError::Toml(ref err) => Some(err),
+            ConfigError::Invalid(_) => None,
+            ConfigError::File(_, ref err) => Some(&**err),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref err) => fmt::Display::fmt(err, formatter),
+            ConfigError::Scan(ref err) => fmt::Display::fmt(err, formatter),
+            #[cfg(feature = "toml")]
+            ConfigError::Toml(ref err) => fmt::Display::fmt(err, formatter),
+            ConfigError::Invalid(ref info) => formatter.write_str(info),
+            ConfigError::File(ref path, ref err) => {
+                write!(formatter, "{}: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> ConfigError {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<ScanError> for ConfigError {
+    fn from(err: ScanError) -> ConfigError {
+        ConfigError::Scan(err)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> ConfigError {
+        ConfigError::Toml(err)
+    }
+}
+
+/// Apply the settings of the YAML configuration `source` to `options`, leaving the
+/// others as they are.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::config;
+/// use yaml_rust_formatter::format::FormatOptions;
+///
+/// let mut options = FormatOptions::new();
+/// config::apply_yaml("profile: github-actions\nline_width: 100", &mut options).unwrap();
+/// assert_eq!(options.get_line_width(), Some(100));
+///
+/// let err = config::apply_yaml("indent: 12", &mut options).unwrap_err();
+/// assert_eq!(err.to_string(), "indent: 12 is not an integer between 1 and 9");
+/// ```
+pub fn apply_yaml(source: &str, options: &mut FormatOptions) -> Result<(), ConfigError> {
+    apply(&read_yaml(source)?, options)
+}
+
+/// Apply the settings of the TOML configuration `source` to `options`, leaving the
+/// others as they are. A `~` of YAML is written by leaving the setting out.
+#[cfg(feature = "toml")]
+pub fn apply_toml(source: &str, options: &mut FormatOptions) -> Result<(), ConfigError> {
+    apply(&read_toml(source)?, options)
+}
+
+/// Apply the settings of the configuration file at `path` to `options`. The file is
+/// read as TOML when its name ends with `.toml`, and as YAML otherwise.
+pub fn apply_file(path: &Path, options: &mut FormatOptions) -> Result<(), ConfigError> {
+    read_file(path)
+        .and_then(|config| apply(&config, options))
+        .map_err(|err| ConfigError::File(path.to_owned(), Box::new(err)))
+}
+
+/// The configuration file of the directory `dir` itself, if it has one.
+pub fn find_config(dir: &Path) -> Option<PathBuf> {
+    CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// The options of the file or directory at `path`: the settings of the configuration
+/// files found in its directory and in the parent directories, applied from the
+/// farthest to the nearest, so that a directory overrides the settings of its parents.
+/// The search stops at the first file setting `root: true`.
+///
+/// A file that does not exist gets the options of the directory it would be in.
+pub fn options_for(path: &Path) -> Result<FormatOptions, ConfigError> {
+    let dir = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or_else(|| Path::new(""))
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let dir = dir.canonicalize()?;
+    let mut configs = Vec::new();
+    for dir in dir.ancestors() {
+        if let Some(path) = find_config(dir) {
+            let config =
+                read_file(&path).map_err(|err| ConfigError::File(path.clone(), Box::new(err)))?;
+            let root = setting(&config, "root").and_then(YamlInput::as_bool) == Some(true);
+            configs.push((path, config));
+            if root {
+                break;
+            }
+        }
+    }
+    let mut options = FormatOptions::new();
+    for (path, config) in configs.iter().rev() {
+        apply(config, &mut options)
+            .map_err(|err| ConfigError::File(path.clone(), Box::new(err)))?;
+    }
+    Ok(options)
+}
+
+fn read_file(path: &Path) -> Result<YamlInput, ConfigError> {
+    let source = fs::read_to_string(path)?;
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "toml")
+    {
+        read_toml(&source)
+    } else {
+        read_yaml(&source)
+    }
+}
+
+fn read_yaml(source: &str) -> Result<YamlInput, ConfigError> {
+    Ok(YamlLoader::load_from_str(source)?
+        .into_iter()
+        .next()
+        .unwrap_or(YamlInput::Null))
+}
+
+#[cfg(feature = "toml")]
+fn read_toml(source: &str) -> Result<YamlInput, ConfigError> {
+    fn convert(value: toml::Value) -> YamlInput {
+        match value {
+            toml::Value::String(v) => YamlInput::String(v),
+            toml::Value::Integer(v) => YamlInput::Integer(v),
+            toml::Value::Float(v) => YamlInput::Real(v.to_string()),
+            toml::Value::Boolean(v) => YamlInput::Boolean(v),
+            toml::Value::Datetime(v) => YamlInput::String(v.to_string()),
+            toml::Value::Array(v) => YamlInput::Array(v.into_iter().map(convert).collect()),
+            toml::Value::Table(v) => YamlInput::Hash(
+                v.into_iter()
+                    .map(|(k, v)| (YamlInput::String(k), convert(v)))
+                    .collect(),
+            ),
+        }
+    }
+    Ok(convert(toml::from_str(source)?))
+}
+
+#[cfg(not(feature = "toml"))]
+fn read_toml(_source: &str) -> Result<YamlInput, ConfigError> {
+    Err(ConfigError::Invalid(
+        "TOML configurations need the `toml` feature".to_owned(),
+    ))
+}
+
+fn setting<'a>(config: &'a YamlInput, name: &str) -> Option<&'a YamlInput> {
+    config
+        .as_hash()
+        .and_then(|hash| hash.get(&YamlInput::String(name.to_owned())))
+}
+
+fn apply(config: &YamlInput, options: &mut FormatOptions) -> Result<(), ConfigError> {
+    let hash = match *config {
+        YamlInput::Null => return Ok(()),
+        YamlInput::Hash(ref hash) => hash,
+        _ => {
+            return Err(ConfigError::Invalid(
+                "the configuration is not a mapping".to_owned(),
+            ))
+        }
+    };
+    // the profile goes first, for the other settings to override it
+    if let Some(profile) = setting(config, "profile") {
+        options.profile(match profile.as_str() {
+            Some("default") => StyleProfile::Default,
+            Some("google-style") => StyleProfile::GoogleStyle,
+            Some("kubernetes-manifest") => StyleProfile::KubernetesManifest,
+            Some("github-actions") => StyleProfile::GithubActions,
+            Some("ansible-playbook") => StyleProfile::AnsiblePlaybook,
+            _ => return Err(invalid("profile", profile, "a known profile")),
+        });
+    }
+    for (key, value) in hash {
+        let name = match key.as_str() {
+            Some(name) => name,
+            None => {
+                return Err(ConfigError::Invalid(format!(
+                    "{} is not a setting",
+                    describe(key)
+                )))
+            }
+        };
+        let boolean = || {
+            value
+                .as_bool()
+                .ok_or_else(|| invalid(name, value, "a boolean"))
+        };
+        let count = || {
+            value
+                .as_i64()
+                .and_then(|v| usize::try_from(v).ok())
+                .ok_or_else(|| invalid(name, value, "a positive integer"))
+        };
+        match name {
+            "profile" => {}
+            "root" => {
+                boolean()?;
+            }
+            "indent" => match value.as_i64() {
+                Some(indent @ 1..=9) => options.indent(indent as usize),
+                _ => return Err(invalid(name, value, "an integer between 1 and 9")),
+            },
+            "indent_sequences" => options.indent_sequences(boolean()?),
+            "quote_style" => options.quote_style(match value.as_str() {
+                Some("plain") => QuoteStyle::Plain,
+                Some("single-when-needed") => QuoteStyle::SingleWhenNeeded,
+                Some("double-when-needed") => QuoteStyle::DoubleWhenNeeded,
+                Some("always-single") => QuoteStyle::AlwaysSingle,
+                Some("always-double") => QuoteStyle::AlwaysDouble,
+                _ => return Err(invalid(name, value, "a known quote style")),
+            }),
+            "keep_scalar_style" => options.keep_scalar_style(boolean()?),
+            "ascii_only" => options.ascii_only(boolean()?),
+            "line_width" => options.line_width(if value.is_null() {
+                None
+            } else {
+                Some(count()?)
+            }),
+            "key_spacing" => options.key_spacing(count()?),
+            "keep_blank_lines" => options.keep_blank_lines(count()?),
+            "align_values" => options.align_values(count()?),
+            "line_ending" => options.line_ending(match value.as_str() {
+                Some("lf") => LineEnding::Lf,
+                Some("crlf") => LineEnding::CrLf,
+                Some("cr") => LineEnding::Cr,
+                _ => return Err(invalid(name, value, "lf, crlf or cr")),
+            }),
+            "normalize_scalars" => options.normalize_scalars(boolean()?),
+            "inline_anchors" => options.inline_anchors(count()?),
+            "embedded_yaml" => {
+                let patterns = value
+                    .as_vec()
+                    .filter(|patterns| patterns.iter().all(|p| p.as_str().is_some()))
+                    .ok_or_else(|| invalid(name, value, "a list of strings"))?;
+                for pattern in patterns {
+                    options.embedded_yaml(pattern.as_str().unwrap_or_default());
+                }
+            }
+            _ => return Err(ConfigError::Invalid(format!("unknown setting {}", name))),
+        }
+    }
+    Ok(())
+}
+
+fn invalid(name: &str, value: &YamlInput, expected: &str) -> ConfigError {
+    ConfigError::Invalid(format!("{}: {} is not {}", name, describe(value), expected))
+}
+
+/// A short text of a configuration value, for error messages.
+fn describe(value: &YamlInput) -> String {
+    match *value {
+        YamlInput::String(ref v) => format!("{:?}", v),
+        YamlInput::Integer(v) => v.to_string(),
+        YamlInput::Real(ref v) => v.clone(),
+        YamlInput::Boolean(v) => v.to_string(),
+        YamlInput::Null => "~".to_owned(),
+        YamlInput::Array(_) => "a list".to_owned(),
+        YamlInput::Hash(_) => "a mapping".to_owned(),
+        _ => "this value".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply_yaml() {
+        let mut options = FormatOptions::new();
+        apply_yaml(
+            "
+profile: kubernetes-manifest
+quote_style: always-single
+keep_scalar_style: false
+ascii_only: true
+line_width: ~
+key_spacing: 1
+keep_blank_lines: 2
+align_values: 5
+line_ending: crlf
+embedded_yaml: [data.*.yaml, config]
+",
+            &mut options,
+        )
+        .unwrap();
+        assert!(!options.is_indent_sequences());
+        assert_eq!(options.get_quote_style(), QuoteStyle::AlwaysSingle);
+        assert!(!options.is_keep_scalar_style());
+        assert!(options.is_ascii_only());
+        assert_eq!(options.get_line_width(), None);
+        assert_eq!(options.get_key_spacing(), 1);
+        assert_eq!(options.get_keep_blank_lines(), 2);
+        assert_eq!(options.get_align_values(), Some(5));
+        assert_eq!(options.get_line_ending(), Some(LineEnding::CrLf));
+        assert_eq!(options.embedded_yaml_patterns(), ["data.*.yaml", "config"]);
+
+        apply_yaml("", &mut options).unwrap();
+        let errors = [
+            ("- indent", "the configuration is not a mapping"),
+            ("indnet: 2", "unknown setting indnet"),
+            ("1: 2", "1 is not a setting"),
+            ("profile: pep8", "profile: \"pep8\" is not a known profile"),
+            (
+                "key_spacing: -1",
+                "key_spacing: -1 is not a positive integer",
+            ),
+            ("root: yes", "root: \"yes\" is not a boolean"),
+            (
+                "embedded_yaml: [1]",
+                "embedded_yaml: a list is not a list of strings",
+            ),
+        ];
+        for &(source, message) in errors.iter() {
+            let err = apply_yaml(source, &mut options).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_options_for() {
+        let dir = std::env::temp_dir().join(format!("yaml-config-{}", std::process::id()));
+        let nested = dir.join("charts").join("web");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            dir.join(".yamlfmt.yaml"),
+            "root: true\nindent: 4\nkey_spacing: 1\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("charts").join(".yamlfmt.yaml"),
+            "profile: kubernetes-manifest\nkey_spacing: 0\n",
+        )
+        .unwrap();
+
+        let options = options_for(&dir.join("values.yaml")).unwrap();
+        assert_eq!(options.get_indent(), Some(4));
+        assert_eq!(options.get_key_spacing(), 1);
+        assert!(options.is_indent_sequences());
+
+        // the profile of the nearer file sets the indentation again
+        let options = options_for(&nested.join("deployment.yaml")).unwrap();
+        assert_eq!(options.get_indent(), Some(2));
+        assert_eq!(options.get_key_spacing(), 0);
+        assert!(!options.is_indent_sequences());
+
+        fs::write(nested.join(".yamlfmt.yaml"), "indent: zero\n").unwrap();
+        let err = options_for(&nested).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}: indent: \"zero\" is not an integer between 1 and 9",
+                nested
+                    .canonicalize()
+                    .unwrap()
+                    .join(".yamlfmt.yaml")
+                    .display()
+            )
+        );
+
+        fs::remove_file(nested.join(".yamlfmt.yaml")).unwrap();
+        fs::write(nested.join("yamlfmt.toml"), "line_width = 120\n").unwrap();
+        let result = options_for(&nested);
+        #[cfg(feature = "toml")]
+        assert_eq!(result.unwrap().get_line_width(), Some(120));
+        #[cfg(not(feature = "toml"))]
+        assert!(result.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+use crate::diff::{key_segment, Path, Segment};
+use crate::yaml::{yaml_1_1_scalar, ResolutionScheme, YamlInput, YamlOutput};
+
+/// A plain scalar `convert_schema` writes differently, because its text resolves to
+/// another value under the target scheme.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConversionNote {
+    path: Path,
+    key: bool,
+    text: String,
+    value: YamlInput,
+    written: String,
+}
+
+impl ConversionNote {
+    /// The path of the scalar, or of the mapping entry for a key.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Determine if the scalar is a key rather than a value.
+    pub fn is_key(&self) -> bool {
+        self.key
+    }
+
+    /// The text of the scalar in the source.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The value of the scalar under the source scheme, which the new text keeps.
+    pub fn value(&self) -> &YamlInput {
+        &self.value
+    }
+
+    /// How the scalar is written for the target scheme.
+    pub fn written(&self) -> &str {
+        &self.written
+    }
+}
+
+/// Convert `doc`, read under the `from` scheme, to a document that means the same under
+/// the `to` scheme, e.g. to migrate a YAML 1.1 file to YAML 1.2 semantics.
+///
+/// Only plain scalars whose text is known can change meaning, so `doc` should be loaded
+/// with `LoadOptions::raw_scalars`. A string that would become a null or a boolean is
+/// quoted, and a null or boolean that would become a string is written in its canonical
+/// form. Every such scalar is reported, in document order.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::convert::convert_schema;
+/// use yaml_rust_formatter::{LoadOptions, ResolutionScheme, YamlEmitter, YamlLoader};
+///
+/// let mut options = LoadOptions::new();
+/// options.raw_scalars(true);
+/// options.resolution_scheme(ResolutionScheme::Yaml11);
+/// let doc = &YamlLoader::load_from_str_with_options("debug: on\nname: n\n", &options).unwrap()[0];
+/// let (output, notes) = convert_schema(doc, ResolutionScheme::Yaml11, ResolutionScheme::Core);
+///
+/// let mut out = String::new();
+/// YamlEmitter::new(&mut out).dump(&output).unwrap();
+/// assert_eq!(out, "---\ndebug: true\nname: false");
+/// assert_eq!(notes.len(), 2);
+/// assert_eq!(notes[0].path().to_string(), "debug");
+/// assert_eq!(notes[0].written(), "true");
+/// ```
+pub fn convert_schema(
+    doc: &YamlInput,
+    from: ResolutionScheme,
+    to: ResolutionScheme,
+) -> (YamlOutput, Vec<ConversionNote>) {
+    let mut converter = Converter {
+        from,
+        to,
+        notes: Vec::new(),
+    };
+    let output = converter.node(doc, &Path::new(), false);
+    (output, converter.notes)
+}
+
+/// The value of the plain scalar `text` under `scheme`.
+fn resolve_plain(text: &str, scheme: ResolutionScheme) -> YamlInput {
+    match scheme {
+        ResolutionScheme::Core => YamlInput::from_str(text),
+        ResolutionScheme::Yaml11 => {
+            yaml_1_1_scalar(text).unwrap_or_else(|| YamlInput::from_str(text))
+        }
+    }
+}
+
+struct Converter {
+    from: ResolutionScheme,
+    to: ResolutionScheme,
+    notes: Vec<ConversionNote>,
+}
+
+impl Converter {
+    /// Convert the plain scalar `text`, noting it if its value has to be kept by
+    /// writing it differently.
+    fn plain(&mut self, text: &str, path: &Path, key: bool) -> Option<YamlOutput> {
+        let value = resolve_plain(text, self.from);
+        if resolve_plain(text, self.to) == value {
+            return None;
+        }
+        let output = match value {
+            // the emitter leaves the single letter booleans of YAML 1.1 unquoted
+            YamlInput::String(ref v) => YamlOutput::Spelled(
+                Box::new(YamlOutput::String(v.clone())),
+                format!("\"{}\"", v),
+            ),
+            ref value => value.clone().into(),
+        };
+        let written = match output {
+            YamlOutput::Spelled(_, ref spelling) => spelling.clone(),
+            YamlOutput::Boolean(v) => v.to_string(),
+            _ => "~".to_owned(),
+        };
+        self.notes.push(ConversionNote {
+            path: path.clone(),
+            key,
+            text: text.to_owned(),
+            value,
+            written,
+        });
+        Some(output)
+    }
+
+    fn node(&mut self, node: &YamlInput, path: &Path, key: bool) -> YamlOutput {
+        match *node {
+            YamlInput::Raw(ref text) | YamlInput::Spelled(_, ref text) => self
+                .plain(text, path, key)
+                .unwrap_or_else(|| node.clone().into()),
+            YamlInput::Anchored(ref name, ref node) => {
+                YamlOutput::Anchored(name.clone(), Box::new(self.node(node, path, key)))
+            }
+            // a core tag gives the scalar its type under either scheme
+            YamlInput::Tagged(ref tag, _) if tag.starts_with("!!") => node.clone().into(),
+            YamlInput::Tagged(ref tag, ref node) => {
+                YamlOutput::Tagged(tag.clone(), Box::new(self.node(node, path, key)))
+            }
+            YamlInput::Commented(ref node, ref comments) => {
+                YamlOutput::Commented(Box::new(self.node(node, path, key)), comments.clone())
+            }
+            YamlInput::Array(ref v) => YamlOutput::Array(
+                v.iter()
+                    .enumerate()
+                    .map(|(i, x)| self.node(x, &path.child(Segment::Index(i)), false))
+                    .collect(),
+            ),
+            YamlInput::Hash(ref h) => YamlOutput::Hash(
+                h.iter()
+                    .map(|(k, v)| {
+                        let child = path.child(key_segment(k));
+                        (self.node(k, &child, true), self.node(v, &child, false))
+                    })
+                    .collect(),
+            ),
+            _ => node.clone().into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emitter::YamlEmitter;
+    use crate::yaml::{LoadOptions, YamlLoader};
+
+    fn convert(
+        source: &str,
+        from: ResolutionScheme,
+        to: ResolutionScheme,
+    ) -> (String, Vec<ConversionNote>) {
+        let mut options = LoadOptions::new();
+        options.raw_scalars(true);
+        options.resolution_scheme(from);
+        let doc = &YamlLoader::load_from_str_with_options(source, &options).unwrap()[0];
+        let (output, notes) = convert_schema(doc, from, to);
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump(&output).unwrap();
+        // the result means under `to` what the source meant under `from`
+        options.raw_scalars(false);
+        options.resolution_scheme(to);
+        let converted = &YamlLoader::load_from_str_with_options(&out, &options).unwrap()[0];
+        options.resolution_scheme(from);
+        let original = &YamlLoader::load_from_str_with_options(source, &options).unwrap()[0];
+        assert_eq!(converted, original);
+        (out, notes)
+    }
+
+    #[test]
+    fn test_convert_schema() {
+        let source = "on: yes\nflags: [y, No, OFF, True, true, ~, x]\nmode: 0o644\n";
+        let (out, notes) = convert(source, ResolutionScheme::Yaml11, ResolutionScheme::Core);
+        assert_eq!(
+            out,
+            "---\ntrue: true\nflags:\n  - true\n  - false\n  - false\n  - true\n  - true\n  - ~\n  - x\nmode: 0o644"
+        );
+        let written: Vec<_> = notes
+            .iter()
+            .map(|note| (note.path().to_string(), note.is_key(), note.written()))
+            .collect();
+        assert_eq!(
+            written,
+            [
+                // a path names a key by its value, which is `true` in YAML 1.1
+                ("true".to_owned(), true, "true"),
+                ("true".to_owned(), false, "true"),
+                ("flags[0]".to_owned(), false, "true"),
+                ("flags[1]".to_owned(), false, "false"),
+                ("flags[2]".to_owned(), false, "false"),
+                ("flags[3]".to_owned(), false, "true"),
+            ]
+        );
+        assert_eq!(notes[2].text(), "y");
+        assert_eq!(notes[2].value(), &YamlInput::Boolean(true));
+
+        let (out, notes) = convert(source, ResolutionScheme::Core, ResolutionScheme::Yaml11);
+        assert_eq!(
+            out,
+            "---\n\"on\": \"yes\"\nflags:\n  - \"y\"\n  - \"No\"\n  - \"OFF\"\n  - \"True\"\n  - true\n  - ~\n  - x\nmode: 0o644"
+        );
+        assert_eq!(notes.len(), 6);
+
+        let (_, notes) = convert(source, ResolutionScheme::Core, ResolutionScheme::Core);
+        assert!(notes.is_empty());
+        // a scalar typed by its tag means the same under either scheme
+        let (out, notes) = convert(
+            "a: !!int 010\n",
+            ResolutionScheme::Yaml11,
+            ResolutionScheme::Core,
+        );
+        assert_eq!(out, "---\na: !!int 010");
+        assert!(notes.is_empty());
+    }
+}
@@ -0,0 +1,1231 @@
+//! A lossless concrete syntax tree of a source, for edits that change one node and keep
+//! every other byte of the file: whitespace, comments, quotes, anchors, tags and
+//! directives included.
+//!
+//! The tree comes in two layers. The green tree, made of `GreenNode`s and
+//! `GreenToken`s, holds the text and is immutable and shared: a node is the same
+//! wherever it appears. The red tree, made of `SyntaxNode`s and `SyntaxToken`s, is built
+//! on demand over it and knows the offset and the parent of each node.
+//!
+//! # Examples
+//!
+//! ```
+//! use yaml_rust_formatter::cst::Cst;
+//! use yaml_rust_formatter::diff::Path;
+//!
+//! let source = "# the service\nname:   web   # keep me\nports: [80,  443]\n";
+//! let cst = Cst::parse(source).unwrap();
+//! assert_eq!(cst.green().to_string(), source);
+//!
+//! let doc = cst.documents().next().unwrap();
+//! let name = doc.lookup(&Path::parse("name")).unwrap();
+//! assert_eq!(name.text(), "web");
+//! let edited = cst.replace(&name, "'api'").unwrap();
+//! assert_eq!(
+//!     edited.text(),
+//!     "# the service\nname:   'api'   # keep me\nports: [80,  443]\n"
+//! );
+//! ```
+
+use crate::diff::{key_segment, Path, Segment};
+use crate::emitter::{EmitError, YamlEmitter};
+use crate::scanner::{ScanError, Scanner, TScalarStyle, TokenType};
+use crate::yaml::{YamlInput, YamlLoader, YamlOutput};
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// The kind of a token or of a node of the tree.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    /// Spaces and tabs, or a byte order mark.
+    Whitespace,
+    /// A line break: LF, CR LF or CR.
+    Newline,
+    /// A `#` comment, without its line break.
+    Comment,
+    /// A `%YAML` or `%TAG` directive.
+    Directive,
+    /// `---`
+    DocumentStart,
+    /// `...`
+    DocumentEnd,
+    /// The `-` of a block sequence entry.
+    Dash,
+    /// The `?` of an explicit key.
+    Question,
+    Colon,
+    Comma,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    /// `&name`
+    Anchor,
+    /// `!tag`, `!!str` or `!<verbatim>`
+    Tag,
+    /// `*name`
+    AliasName,
+    Plain,
+    SingleQuoted,
+    DoubleQuoted,
+    /// The header of a literal block scalar, like `|-`.
+    Literal,
+    /// The header of a folded block scalar, like `>2`.
+    Folded,
+    /// The lines of a block scalar, without the line break ending the last one.
+    BlockContent,
+    /// Text the tree could not place.
+    Error,
+
+    /// The root of the tree.
+    Stream,
+    Document,
+    BlockMapping,
+    BlockSequence,
+    FlowMapping,
+    FlowSequence,
+    /// An entry of a mapping, holding its `Key` and its `Value`.
+    Entry,
+    /// The key of an entry, with its `?` if it has one.
+    Key,
+    /// The `:` of an entry and its value.
+    Value,
+    /// An entry of a sequence, with its `-` in block style.
+    Item,
+    /// A scalar with its properties.
+    Scalar,
+    /// An alias.
+    Alias,
+}
+
+impl SyntaxKind {
+    /// Whether the kind is the kind of a token rather than of a node.
+    pub fn is_token(self) -> bool {
+        (self as u8) <= (SyntaxKind::Error as u8)
+    }
+
+    /// Whether the kind is whitespace, a line break or a comment.
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment
+        )
+    }
+
+    /// Whether the kind is the kind of a node holding a YAML node, such as a mapping.
+    pub fn is_content(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::BlockMapping
+                | SyntaxKind::BlockSequence
+                | SyntaxKind::FlowMapping
+                | SyntaxKind::FlowSequence
+                | SyntaxKind::Scalar
+                | SyntaxKind::Alias
+        )
+    }
+}
+
+/// A token of the green tree: its kind and its text.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: String,
+}
+
+impl GreenToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// A child of a `GreenNode`.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    /// The length of the text of the element, in bytes.
+    pub fn text_len(&self) -> usize {
+        match *self {
+            GreenElement::Node(ref node) => node.text_len(),
+            GreenElement::Token(ref token) => token.text.len(),
+        }
+    }
+}
+
+/// A node of the green tree: its kind and its children, without position. Displaying
+/// it writes its text.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> GreenNode {
+        GreenNode {
+            kind,
+            len: children.iter().map(GreenElement::text_len).sum(),
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    /// The length of the text of the node, in bytes.
+    pub fn text_len(&self) -> usize {
+        self.len
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+}
+
+impl fmt::Display for GreenNode {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for child in &self.children {
+            match *child {
+                GreenElement::Node(ref node) => fmt::Display::fmt(node, formatter)?,
+                GreenElement::Token(ref token) => formatter.write_str(&token.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct NodeData {
+    green: Arc<GreenNode>,
+    offset: usize,
+    parent: Option<SyntaxNode>,
+    source: Arc<str>,
+}
+
+/// A node of the red tree: a `GreenNode` at its place in the source.
+#[derive(Clone)]
+pub struct SyntaxNode(Arc<NodeData>);
+
+/// A token of the red tree: a `GreenToken` at its place in the source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxToken {
+    green: Arc<GreenToken>,
+    offset: usize,
+    parent: SyntaxNode,
+}
+
+/// A child of a `SyntaxNode`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.0.green
+    }
+
+    /// The byte offsets of the node in the source.
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.len
+    }
+
+    pub fn text(&self) -> &str {
+        &self.0.source[self.text_range()]
+    }
+
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    /// The column the node starts at, in characters.
+    pub fn column(&self) -> usize {
+        let before = &self.0.source[..self.0.offset];
+        let line = before.rfind(['\n', '\r']).map_or(0, |i| i + 1);
+        before[line..].chars().count()
+    }
+
+    pub fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        self.0
+            .green
+            .children
+            .iter()
+            .map(|child| {
+                let element = match *child {
+                    GreenElement::Node(ref green) => {
+                        SyntaxElement::Node(SyntaxNode(Arc::new(NodeData {
+                            green: green.clone(),
+                            offset,
+                            parent: Some(self.clone()),
+                            source: self.0.source.clone(),
+                        })))
+                    }
+                    GreenElement::Token(ref green) => SyntaxElement::Token(SyntaxToken {
+                        green: green.clone(),
+                        offset,
+                        parent: self.clone(),
+                    }),
+                };
+                offset += child.text_len();
+                element
+            })
+            .collect()
+    }
+
+    pub fn children(&self) -> Vec<SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|child| match child {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|child| match child {
+                SyntaxElement::Token(token) => Some(token),
+                SyntaxElement::Node(_) => None,
+            })
+            .collect()
+    }
+
+    /// The node and every node below it, in the order of the source.
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut nodes = vec![self.clone()];
+        for child in self.children() {
+            nodes.extend(child.descendants());
+        }
+        nodes
+    }
+
+    /// The node holding the YAML node of a `Document`, `Key`, `Value` or `Item`, or the
+    /// node itself if it holds one. `None` when the YAML node is empty.
+    pub fn content(&self) -> Option<SyntaxNode> {
+        if self.kind().is_content() {
+            return Some(self.clone());
+        }
+        self.children()
+            .into_iter()
+            .find(|child| child.kind().is_content())
+    }
+
+    /// The node at `path` below the YAML node of this node, found like
+    /// `YamlInput::get_at` finds it, except that aliases are not followed. `None` if
+    /// there is no such node, or it is empty.
+    pub fn lookup(&self, path: &Path) -> Option<SyntaxNode> {
+        path.segments()
+            .iter()
+            .try_fold(self.content()?, |node, segment| match node.kind() {
+                SyntaxKind::BlockMapping | SyntaxKind::FlowMapping => {
+                    let key = match *segment {
+                        Segment::Index(i) => Segment::Key(i.to_string()),
+                        Segment::Key(_) => segment.clone(),
+                    };
+                    node.children()
+                        .into_iter()
+                        .filter(|entry| entry.kind() == SyntaxKind::Entry)
+                        .find(|entry| entry_key(entry).as_ref() == Some(&key))
+                        .and_then(|entry| child_of_kind(&entry, SyntaxKind::Value))
+                        .and_then(|value| value.content())
+                }
+                SyntaxKind::BlockSequence | SyntaxKind::FlowSequence => match *segment {
+                    Segment::Index(i) => node
+                        .children()
+                        .into_iter()
+                        .filter(|item| item.kind() == SyntaxKind::Item)
+                        .nth(i)
+                        .and_then(|item| item.content()),
+                    Segment::Key(_) => None,
+                },
+                _ => None,
+            })
+    }
+
+    /// Load the text of the node as YAML. A node below the root is read at its column,
+    /// without the anchors and directives around it: an alias to an anchor outside the
+    /// node is left unresolved. The whitespace after the node is read with it, as the
+    /// value of a block scalar depends on the line breaks after its lines.
+    pub fn to_yaml(&self) -> Result<YamlInput, ScanError> {
+        let rest = &self.0.source[self.text_range().end..];
+        let trailing = &rest[..rest
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(rest.len())];
+        let text = format!(
+            "{:width$}{}{}",
+            "",
+            self.text(),
+            trailing,
+            width = self.column()
+        );
+        let mut docs = YamlLoader::load_from_str(&text)?;
+        Ok(if docs.is_empty() {
+            YamlInput::Null
+        } else {
+            docs.swap_remove(0)
+        })
+    }
+}
+
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &SyntaxNode) -> bool {
+        Arc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+impl Eq for SyntaxNode {}
+
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{:?}@{:?}", self.kind(), self.text_range())
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    /// The byte offsets of the token in the source.
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text.len()
+    }
+
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+}
+
+fn child_of_kind(node: &SyntaxNode, kind: SyntaxKind) -> Option<SyntaxNode> {
+    node.children()
+        .into_iter()
+        .find(|child| child.kind() == kind)
+}
+
+/// The key of `entry` as a path segment, if it has a key that can be read.
+fn entry_key(entry: &SyntaxNode) -> Option<Segment> {
+    let key = child_of_kind(entry, SyntaxKind::Key)?.content()?;
+    key.to_yaml().ok().map(|key| key_segment(&key))
+}
+
+/// The concrete syntax tree of a source.
+#[derive(Clone)]
+pub struct Cst {
+    source: Arc<str>,
+    green: Arc<GreenNode>,
+}
+
+impl Cst {
+    /// Build the tree of `source`, which must be valid YAML. The text of the tree is
+    /// `source`, byte for byte.
+    pub fn parse(source: &str) -> Result<Cst, ScanError> {
+        YamlLoader::load_from_str(source)?;
+        let mut scanner = Scanner::new(source.chars());
+        let tokens: Vec<_> = scanner.by_ref().collect();
+        if let Some(err) = scanner.get_error() {
+            return Err(err);
+        }
+        // the markers count characters
+        let mut offsets: Vec<usize> = source.char_indices().map(|(i, _)| i).collect();
+        offsets.push(source.len());
+        let lexed = lex(source, &tokens, &offsets);
+        let mut tree = TreeParser {
+            lexed: &lexed,
+            next: 0,
+            builder: Builder {
+                source,
+                pos: 0,
+                stack: vec![(SyntaxKind::Stream, Vec::new())],
+            },
+        };
+        tree.stream();
+        Ok(Cst {
+            source: Arc::from(source),
+            green: Arc::new(tree.builder.finish_root()),
+        })
+    }
+
+    /// Write `doc` with the default `YamlEmitter` and build the tree of the output.
+    pub fn from_yaml(doc: &YamlInput) -> Result<Cst, EmitError> {
+        let output: YamlOutput = doc.clone().into();
+        let mut text = String::new();
+        YamlEmitter::new(&mut text).dump(&output)?;
+        Ok(Cst::parse(&text).expect("the emitter writes valid YAML"))
+    }
+
+    /// The source of the tree.
+    pub fn text(&self) -> &str {
+        &self.source
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.green
+    }
+
+    /// The `Stream` node at the root of the tree.
+    pub fn root(&self) -> SyntaxNode {
+        SyntaxNode(Arc::new(NodeData {
+            green: self.green.clone(),
+            offset: 0,
+            parent: None,
+            source: self.source.clone(),
+        }))
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = SyntaxNode> {
+        self.root()
+            .children()
+            .into_iter()
+            .filter(|node| node.kind() == SyntaxKind::Document)
+    }
+
+    /// The documents of the source, as `YamlLoader::load_from_str` loads them.
+    pub fn to_yaml(&self) -> Vec<YamlInput> {
+        YamlLoader::load_from_str(&self.source).expect("the source was loaded when parsed")
+    }
+
+    /// The tree of the source with the bytes in `range` replaced by `text`, if that is
+    /// still valid YAML. The rest of the source is kept as it is.
+    ///
+    /// # Panics
+    ///
+    /// When `range` does not lie on character boundaries of the source.
+    pub fn edit(&self, range: Range<usize>, text: &str) -> Result<Cst, ScanError> {
+        assert!(
+            self.source.get(range.clone()).is_some(),
+            "{:?} is not a range of the source",
+            range
+        );
+        let mut source = String::with_capacity(self.source.len() + text.len());
+        source.push_str(&self.source[..range.start]);
+        source.push_str(text);
+        source.push_str(&self.source[range.end..]);
+        Cst::parse(&source)
+    }
+
+    /// The tree of the source with the text of `node` replaced by `text`, see `edit`.
+    pub fn replace(&self, node: &SyntaxNode, text: &str) -> Result<Cst, ScanError> {
+        self.edit(node.text_range(), text)
+    }
+}
+
+impl fmt::Debug for Cst {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        fn write_node(
+            formatter: &mut fmt::Formatter,
+            node: &SyntaxNode,
+            depth: usize,
+        ) -> fmt::Result {
+            writeln!(formatter, "{:width$}{:?}", "", node, width = depth * 2)?;
+            for child in node.children_with_tokens() {
+                match child {
+                    SyntaxElement::Node(ref node) => write_node(formatter, node, depth + 1)?,
+                    SyntaxElement::Token(ref token) => writeln!(
+                        formatter,
+                        "{:width$}{:?}@{:?} {:?}",
+                        "",
+                        token.kind(),
+                        token.text_range(),
+                        token.text(),
+                        width = depth * 2 + 2
+                    )?,
+                }
+            }
+            Ok(())
+        }
+        write_node(formatter, &self.root(), 0)
+    }
+}
+
+/// A token of the scanner with the bytes it covers; empty for the tokens the scanner
+/// adds without text, such as the start of a block mapping.
+struct Lexed {
+    token: TokenType,
+    range: Range<usize>,
+    // the lines of a block scalar, after its header
+    block: Option<Range<usize>>,
+}
+
+fn lex(source: &str, tokens: &[crate::scanner::Token], offsets: &[usize]) -> Vec<Lexed> {
+    let mark = |token: &crate::scanner::Token| offsets[token.0.index().min(offsets.len() - 1)];
+    let has_text: Vec<bool> = tokens
+        .iter()
+        .map(|token| match token.1 {
+            TokenType::NoToken
+            | TokenType::StreamStart(_)
+            | TokenType::StreamEnd
+            | TokenType::BlockSequenceStart
+            | TokenType::BlockMappingStart
+            | TokenType::BlockEnd => false,
+            TokenType::Key => is_explicit_key(&source[mark(token)..]),
+            _ => true,
+        })
+        .collect();
+    // the scanner marks a block scalar where its lines start, after its header
+    let mut starts = Vec::with_capacity(tokens.len());
+    let mut previous = 0;
+    for (token, &has_text) in tokens.iter().zip(&has_text) {
+        let start = match token.1 {
+            TokenType::Scalar(TScalarStyle::Literal, _)
+            | TokenType::Scalar(TScalarStyle::Foled, _) => {
+                block_header_start(source, previous, mark(token))
+            }
+            _ => mark(token),
+        };
+        if has_text {
+            previous = start;
+        }
+        starts.push(start);
+    }
+    let mut lexed = Vec::with_capacity(tokens.len());
+    let mut pos = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        let from = starts[i].max(pos);
+        if !has_text[i] {
+            lexed.push(Lexed {
+                token: token.1.clone(),
+                range: from..from,
+                block: None,
+            });
+            continue;
+        }
+        // the text of a token ends before the next token with text
+        let limit = (i + 1..tokens.len())
+            .find(|&next| has_text[next])
+            .map_or(source.len(), |next| starts[next])
+            .max(from);
+        let mut block = None;
+        let end = match token.1 {
+            TokenType::DocumentStart | TokenType::DocumentEnd => from + 3,
+            TokenType::Key
+            | TokenType::Value
+            | TokenType::BlockEntry
+            | TokenType::FlowEntry
+            | TokenType::FlowSequenceStart
+            | TokenType::FlowSequenceEnd
+            | TokenType::FlowMappingStart
+            | TokenType::FlowMappingEnd => from + 1,
+            TokenType::Scalar(TScalarStyle::SingleQuoted, _) => {
+                quoted_end(source, from, limit, '\'')
+            }
+            TokenType::Scalar(TScalarStyle::DoubleQuoted, _) => {
+                quoted_end(source, from, limit, '"')
+            }
+            TokenType::Scalar(TScalarStyle::Literal, _)
+            | TokenType::Scalar(TScalarStyle::Foled, _) => {
+                let (end, lines) = block_end(source, from, limit);
+                block = lines;
+                end
+            }
+            _ => trimmed_end(source, from, limit),
+        };
+        pos = block.as_ref().map_or(end, |lines| lines.end);
+        lexed.push(Lexed {
+            token: token.1.clone(),
+            range: from..end,
+            block,
+        });
+    }
+    lexed
+}
+
+/// Where the header of a block scalar starts, between the start of the token before it
+/// and its lines: at the first `|` or `>` after a space or a line break, as the tokens
+/// that can come before a block scalar have no spaces.
+fn block_header_start(source: &str, previous: usize, lines: usize) -> usize {
+    let mut after_space = previous == 0;
+    for (i, c) in source[previous..lines].char_indices() {
+        if after_space && (c == '|' || c == '>') {
+            return previous + i;
+        }
+        after_space = c.is_whitespace();
+    }
+    lines
+}
+
+/// Whether a key token starting `rest` is a `?` rather than the start of a simple key.
+fn is_explicit_key(rest: &str) -> bool {
+    let mut chars = rest.chars();
+    chars.next() == Some('?')
+        && !chars
+            .next()
+            .is_some_and(|c| !c.is_whitespace() && !",[]{}".contains(c))
+}
+
+/// The end of the text starting at `from` and followed by trivia up to `limit`.
+fn trimmed_end(source: &str, from: usize, limit: usize) -> usize {
+    let mut end = from;
+    let mut in_comment = false;
+    let mut after_space = false;
+    for (i, c) in source[from..limit].char_indices() {
+        match c {
+            '\n' | '\r' => {
+                in_comment = false;
+                after_space = true;
+            }
+            _ if in_comment => {}
+            '#' if after_space => in_comment = true,
+            ' ' | '\t' | '\u{feff}' => after_space = true,
+            _ => {
+                end = from + i + c.len_utf8();
+                after_space = false;
+            }
+        }
+    }
+    end
+}
+
+/// The end of the quoted scalar starting at `from`, after its closing quote.
+fn quoted_end(source: &str, from: usize, limit: usize, quote: char) -> usize {
+    let mut chars = source[from..limit].char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if quote == '\'' && c == '\'' {
+            if chars.peek().map(|&(_, c)| c) == Some('\'') {
+                chars.next();
+                continue;
+            }
+            return from + i + 1;
+        }
+        if quote == '"' {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '"' => return from + i + 1,
+                _ => {}
+            }
+        }
+    }
+    limit
+}
+
+/// The end of the header of the block scalar starting at `from`, and the range of its
+/// lines, without the blank lines after the last of them.
+fn block_end(source: &str, from: usize, limit: usize) -> (usize, Option<Range<usize>>) {
+    let line_end = source[from..limit]
+        .find(['\n', '\r'])
+        .map_or(limit, |i| from + i);
+    let header_end = trimmed_end(source, from, line_end);
+    let mut content_start = line_end;
+    if source[line_end..limit].starts_with("\r\n") {
+        content_start += 2;
+    } else if line_end < limit {
+        content_start += 1;
+    }
+    // only trivia can follow the lines before the next token: blank lines, and comments
+    // indented less than the lines
+    let mut lines = Vec::new();
+    let mut line_start = content_start;
+    for line in source[content_start..limit].split_inclusive(['\n', '\r']) {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let spaces = text.len() - text.trim_start_matches(' ').len();
+        if spaces < text.len() {
+            lines.push((
+                line_start + text.len(),
+                spaces,
+                text[spaces..].starts_with('#'),
+            ));
+        }
+        line_start += line.len();
+    }
+    let indent = lines
+        .iter()
+        .filter(|&&(_, _, comment)| !comment)
+        .map(|&(_, spaces, _)| spaces)
+        .min()
+        .or_else(|| lines.first().map(|&(_, spaces, _)| spaces));
+    let content_end = lines
+        .iter()
+        .rev()
+        .find(|&&(_, spaces, _)| Some(spaces) >= indent)
+        .map(|&(end, _, _)| end);
+    (header_end, content_end.map(|end| content_start..end))
+}
+
+struct Builder<'s> {
+    source: &'s str,
+    // the end of the text already in the tree
+    pos: usize,
+    stack: Vec<(SyntaxKind, Vec<GreenElement>)>,
+}
+
+impl<'s> Builder<'s> {
+    fn push(&mut self, kind: SyntaxKind, range: Range<usize>) {
+        let text = self.source[range].to_owned();
+        self.stack
+            .last_mut()
+            .expect("the root is open")
+            .1
+            .push(GreenElement::Token(Arc::new(GreenToken { kind, text })));
+    }
+
+    /// Add the trivia up to `upto` to the open node.
+    fn trivia(&mut self, upto: usize) {
+        while self.pos < upto {
+            let rest = &self.source[self.pos..upto];
+            let first = rest.chars().next().expect("the trivia is not empty");
+            let (kind, len) = match first {
+                '\r' if rest.starts_with("\r\n") => (SyntaxKind::Newline, 2),
+                '\n' | '\r' => (SyntaxKind::Newline, 1),
+                '#' => (
+                    SyntaxKind::Comment,
+                    rest.find(['\n', '\r']).unwrap_or(rest.len()),
+                ),
+                ' ' | '\t' | '\u{feff}' => (
+                    SyntaxKind::Whitespace,
+                    rest.find(|c| !matches!(c, ' ' | '\t' | '\u{feff}'))
+                        .unwrap_or(rest.len()),
+                ),
+                _ => (
+                    SyntaxKind::Error,
+                    rest.find(char::is_whitespace).unwrap_or(rest.len()),
+                ),
+            };
+            self.push(kind, self.pos..self.pos + len);
+            self.pos += len;
+        }
+    }
+
+    fn token(&mut self, kind: SyntaxKind, range: Range<usize>) {
+        self.trivia(range.start);
+        if !range.is_empty() {
+            self.pos = range.end;
+            self.push(kind, range);
+        }
+    }
+
+    /// Open a node starting at `at`, after the trivia before it.
+    fn start(&mut self, kind: SyntaxKind, at: usize) {
+        self.trivia(at);
+        self.stack.push((kind, Vec::new()));
+    }
+
+    /// The place of a node that may be opened later with `start_at`, starting at `at`.
+    fn checkpoint(&mut self, at: usize) -> usize {
+        self.trivia(at);
+        self.stack.last().expect("the root is open").1.len()
+    }
+
+    /// Open a node holding the children added since `checkpoint`.
+    fn start_at(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        let children = self
+            .stack
+            .last_mut()
+            .expect("the root is open")
+            .1
+            .split_off(checkpoint);
+        self.stack.push((kind, children));
+    }
+
+    fn finish(&mut self) {
+        let (kind, children) = self.stack.pop().expect("a node is open");
+        self.stack
+            .last_mut()
+            .expect("the root is open")
+            .1
+            .push(GreenElement::Node(Arc::new(GreenNode::new(kind, children))));
+    }
+
+    fn finish_root(mut self) -> GreenNode {
+        self.trivia(self.source.len());
+        let (kind, children) = self.stack.pop().expect("the root is open");
+        GreenNode::new(kind, children)
+    }
+}
+
+/// Builds the tree from the tokens of the scanner, which has already checked them.
+struct TreeParser<'l, 's> {
+    lexed: &'l [Lexed],
+    next: usize,
+    builder: Builder<'s>,
+}
+
+impl<'l, 's> TreeParser<'l, 's> {
+    fn peek(&self) -> &TokenType {
+        self.lexed
+            .get(self.next)
+            .map_or(&TokenType::StreamEnd, |lexed| &lexed.token)
+    }
+
+    /// Where the next token starts.
+    fn at(&self) -> usize {
+        self.lexed
+            .get(self.next)
+            .map_or(self.builder.source.len(), |lexed| lexed.range.start)
+            .max(self.builder.pos)
+    }
+
+    fn bump(&mut self) {
+        let lexed = match self.lexed.get(self.next) {
+            Some(lexed) => lexed,
+            None => return,
+        };
+        self.next += 1;
+        let kind = match lexed.token {
+            TokenType::VersionDirective(..) | TokenType::TagDirective(..) => SyntaxKind::Directive,
+            TokenType::DocumentStart => SyntaxKind::DocumentStart,
+            TokenType::DocumentEnd => SyntaxKind::DocumentEnd,
+            TokenType::BlockEntry => SyntaxKind::Dash,
+            TokenType::Key => SyntaxKind::Question,
+            TokenType::Value => SyntaxKind::Colon,
+            TokenType::FlowEntry => SyntaxKind::Comma,
+            TokenType::FlowSequenceStart => SyntaxKind::LBracket,
+            TokenType::FlowSequenceEnd => SyntaxKind::RBracket,
+            TokenType::FlowMappingStart => SyntaxKind::LBrace,
+            TokenType::FlowMappingEnd => SyntaxKind::RBrace,
+            TokenType::Anchor(_) => SyntaxKind::Anchor,
+            TokenType::Tag(..) => SyntaxKind::Tag,
+            TokenType::Alias(_) => SyntaxKind::AliasName,
+            TokenType::Scalar(style, _) => match style {
+                TScalarStyle::SingleQuoted => SyntaxKind::SingleQuoted,
+                TScalarStyle::DoubleQuoted => SyntaxKind::DoubleQuoted,
+                TScalarStyle::Literal => SyntaxKind::Literal,
+                TScalarStyle::Foled => SyntaxKind::Folded,
+                TScalarStyle::Any | TScalarStyle::Plain => SyntaxKind::Plain,
+            },
+            _ => SyntaxKind::Error,
+        };
+        self.builder.token(kind, lexed.range.clone());
+        if let Some(ref lines) = lexed.block {
+            self.builder.token(SyntaxKind::BlockContent, lines.clone());
+        }
+    }
+
+    /// Add the next token to the tree as text it could not place.
+    fn error(&mut self) {
+        let at = self.at();
+        self.builder.start(SyntaxKind::Error, at);
+        self.bump();
+        self.builder.finish();
+    }
+
+    fn is_node_start(&self) -> bool {
+        matches!(
+            *self.peek(),
+            TokenType::Anchor(_)
+                | TokenType::Tag(..)
+                | TokenType::Alias(_)
+                | TokenType::Scalar(..)
+                | TokenType::BlockSequenceStart
+                | TokenType::BlockMappingStart
+                | TokenType::FlowSequenceStart
+                | TokenType::FlowMappingStart
+        )
+    }
+
+    fn stream(&mut self) {
+        while self.next < self.lexed.len() {
+            let before = self.next;
+            match *self.peek() {
+                TokenType::StreamStart(_) | TokenType::StreamEnd => self.bump(),
+                _ => self.document(),
+            }
+            if self.next == before {
+                self.error();
+            }
+        }
+    }
+
+    fn document(&mut self) {
+        let at = self.at();
+        self.builder.start(SyntaxKind::Document, at);
+        while let TokenType::VersionDirective(..) | TokenType::TagDirective(..) = *self.peek() {
+            self.bump();
+        }
+        if *self.peek() == TokenType::DocumentStart {
+            self.bump();
+        }
+        if self.is_node_start() {
+            self.node(false);
+        }
+        while *self.peek() == TokenType::DocumentEnd {
+            self.bump();
+        }
+        self.builder.finish();
+    }
+
+    /// A node with its properties. `indentless` allows a block sequence without
+    /// indentation, as the value of a block mapping.
+    fn node(&mut self, indentless: bool) {
+        let at = self.at();
+        let checkpoint = self.builder.checkpoint(at);
+        let before = self.next;
+        while let TokenType::Anchor(_) | TokenType::Tag(..) = *self.peek() {
+            self.bump();
+        }
+        match *self.peek() {
+            TokenType::Alias(_) => {
+                self.builder.start_at(checkpoint, SyntaxKind::Alias);
+                self.bump();
+            }
+            TokenType::Scalar(..) => {
+                self.builder.start_at(checkpoint, SyntaxKind::Scalar);
+                self.bump();
+            }
+            TokenType::BlockSequenceStart => {
+                self.builder.start_at(checkpoint, SyntaxKind::BlockSequence);
+                self.bump();
+                loop {
+                    match *self.peek() {
+                        TokenType::BlockEntry => self.block_item(),
+                        TokenType::BlockEnd => {
+                            self.bump();
+                            break;
+                        }
+                        TokenType::StreamEnd => break,
+                        _ => self.error(),
+                    }
+                }
+            }
+            TokenType::BlockEntry if indentless => {
+                self.builder.start_at(checkpoint, SyntaxKind::BlockSequence);
+                while *self.peek() == TokenType::BlockEntry {
+                    self.block_item();
+                }
+            }
+            TokenType::BlockMappingStart => {
+                self.builder.start_at(checkpoint, SyntaxKind::BlockMapping);
+                self.bump();
+                loop {
+                    match *self.peek() {
+                        TokenType::Key | TokenType::Value => self.entry(false),
+                        TokenType::BlockEnd => {
+                            self.bump();
+                            break;
+                        }
+                        TokenType::StreamEnd => break,
+                        _ => self.error(),
+                    }
+                }
+            }
+            TokenType::FlowSequenceStart => {
+                self.builder.start_at(checkpoint, SyntaxKind::FlowSequence);
+                self.bump();
+                loop {
+                    match *self.peek() {
+                        TokenType::FlowSequenceEnd => {
+                            self.bump();
+                            break;
+                        }
+                        TokenType::FlowEntry => self.bump(),
+                        TokenType::Key | TokenType::Value => {
+                            let at = self.at();
+                            self.builder.start(SyntaxKind::Item, at);
+                            self.entry(true);
+                            self.builder.finish();
+                        }
+                        TokenType::StreamEnd => break,
+                        _ if self.is_node_start() => {
+                            let at = self.at();
+                            self.builder.start(SyntaxKind::Item, at);
+                            self.node(false);
+                            self.builder.finish();
+                        }
+                        _ => self.error(),
+                    }
+                }
+            }
+            TokenType::FlowMappingStart => {
+                self.builder.start_at(checkpoint, SyntaxKind::FlowMapping);
+                self.bump();
+                loop {
+                    match *self.peek() {
+                        TokenType::FlowMappingEnd => {
+                            self.bump();
+                            break;
+                        }
+                        TokenType::FlowEntry => self.bump(),
+                        TokenType::Key | TokenType::Value => self.entry(true),
+                        TokenType::StreamEnd => break,
+                        _ if self.is_node_start() => self.entry(true),
+                        _ => self.error(),
+                    }
+                }
+            }
+            // only properties, on an empty scalar
+            _ if self.next > before => self.builder.start_at(checkpoint, SyntaxKind::Scalar),
+            _ => return,
+        }
+        self.builder.finish();
+    }
+
+    fn block_item(&mut self) {
+        let at = self.at();
+        self.builder.start(SyntaxKind::Item, at);
+        self.bump();
+        if self.is_node_start() {
+            self.node(false);
+        }
+        self.builder.finish();
+    }
+
+    fn entry(&mut self, flow: bool) {
+        let at = self.at();
+        self.builder.start(SyntaxKind::Entry, at);
+        if *self.peek() == TokenType::Key || self.is_node_start() {
+            let at = self.at();
+            self.builder.start(SyntaxKind::Key, at);
+            if *self.peek() == TokenType::Key {
+                self.bump();
+            }
+            if self.is_node_start() {
+                self.node(false);
+            }
+            self.builder.finish();
+        }
+        if *self.peek() == TokenType::Value {
+            let at = self.at();
+            self.builder.start(SyntaxKind::Value, at);
+            self.bump();
+            if self.is_node_start() || (!flow && *self.peek() == TokenType::BlockEntry) {
+                self.node(!flow);
+            }
+            self.builder.finish();
+        }
+        self.builder.finish();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn kinds(node: &SyntaxNode) -> Vec<SyntaxKind> {
+        node.descendants().iter().map(SyntaxNode::kind).collect()
+    }
+
+    #[test]
+    fn test_lossless() {
+        let sources = [
+            "",
+            "# only a comment\n",
+            "a",
+            "%YAML 1.2\n%TAG !e! tag:example.com,2000: # prefix\n--- !e!root\nkey: value\n...\n",
+            "a:   1   # one\r\nb:\r\n  - x\r\n  -   'y''s'\r\n",
+            "? complex key\n: value\n? [a, b]\n: {c: d}\n",
+            "base: &b {x: 1, y: \"two\\\"\"}\nuse: *b\nlist: [ *b , !!str 3 ]\n",
+            "text: |-\n  line one\n\n  # not a comment\n   more\n# a comment\nnext: >2\n   folded\n  text\n\n\n",
+            "- a\n- - b\n  - c\n-\n  d: e\n- !tag\n",
+            "key:\n- indentless\n- sequence\nother: plain scalar\n  on two lines # comment\n",
+            "--- first\n--- second\n...\n# trailing\n",
+            "{a: [1, {b: c}], 'd': , e}\n",
+            "\u{feff}a: 1\n",
+            "ключ: значение # комментарий\n",
+        ];
+        for source in sources.iter() {
+            let cst = Cst::parse(source).unwrap();
+            assert_eq!(cst.green().to_string(), *source, "{:?}", cst);
+            assert!(
+                cst.root()
+                    .descendants()
+                    .iter()
+                    .flat_map(|node| node.children_with_tokens())
+                    .all(|child| match child {
+                        SyntaxElement::Node(node) => node.kind() != SyntaxKind::Error,
+                        SyntaxElement::Token(token) => token.kind() != SyntaxKind::Error,
+                    }),
+                "{:?}",
+                cst
+            );
+            assert_eq!(cst.to_yaml(), YamlLoader::load_from_str(source).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_structure() {
+        let cst = Cst::parse("a: &x [1, 2] # c\nb:\n  c: |\n    text\n").unwrap();
+        let doc = cst.documents().next().unwrap();
+        assert_eq!(
+            kinds(&doc),
+            vec![
+                SyntaxKind::Document,
+                SyntaxKind::BlockMapping,
+                SyntaxKind::Entry,
+                SyntaxKind::Key,
+                SyntaxKind::Scalar,
+                SyntaxKind::Value,
+                SyntaxKind::FlowSequence,
+                SyntaxKind::Item,
+                SyntaxKind::Scalar,
+                SyntaxKind::Item,
+                SyntaxKind::Scalar,
+                SyntaxKind::Entry,
+                SyntaxKind::Key,
+                SyntaxKind::Scalar,
+                SyntaxKind::Value,
+                SyntaxKind::BlockMapping,
+                SyntaxKind::Entry,
+                SyntaxKind::Key,
+                SyntaxKind::Scalar,
+                SyntaxKind::Value,
+                SyntaxKind::Scalar,
+            ]
+        );
+        let list = doc.lookup(&Path::parse("a")).unwrap();
+        assert_eq!(list.text(), "&x [1, 2]");
+        assert_eq!(list.tokens()[0].kind(), SyntaxKind::Anchor);
+        assert_eq!(doc.lookup(&Path::parse("a[1]")).unwrap().text(), "2");
+        let text = doc.lookup(&Path::parse("b.c")).unwrap();
+        assert_eq!(text.text(), "|\n    text");
+        assert_eq!(text.column(), 5);
+        assert_eq!(
+            text.to_yaml().unwrap(),
+            YamlInput::String("text\n".to_owned())
+        );
+        let b = doc.lookup(&Path::parse("b")).unwrap();
+        let parent = b.parent().unwrap();
+        assert_eq!(parent.kind(), SyntaxKind::Value);
+        assert_eq!(b.to_yaml().unwrap()["c"].as_str(), Some("text\n"));
+        assert!(doc.lookup(&Path::parse("a[2]")).is_none());
+        assert!(doc.lookup(&Path::parse("missing")).is_none());
+    }
+
+    #[test]
+    fn test_edit() {
+        let source = "# config\nports: {http: 80}   # public\nname: web\n";
+        let cst = Cst::parse(source).unwrap();
+        let doc = cst.documents().next().unwrap();
+        let port = doc.lookup(&Path::parse("ports.http")).unwrap();
+        let edited = cst.replace(&port, "8080").unwrap();
+        assert_eq!(
+            edited.text(),
+            "# config\nports: {http: 8080}   # public\nname: web\n"
+        );
+        assert_eq!(edited.to_yaml()[0]["ports"]["http"].as_i64(), Some(8080));
+        assert!(cst.replace(&port, "[").is_err());
+
+        let doc = YamlLoader::load_from_str("a: [1, 2]").unwrap().remove(0);
+        let cst = Cst::from_yaml(&doc).unwrap();
+        assert_eq!(cst.text(), "---\na:\n  - 1\n  - 2");
+        assert_eq!(cst.to_yaml(), vec![doc]);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a range of the source")]
+    fn test_edit_out_of_source() {
+        let cst = Cst::parse("a: é\n").unwrap();
+        cst.edit(3..4, "b").unwrap();
+    }
+}
@@ -0,0 +1,338 @@
+//! Deserialize typed values from YAML with serde, see `from_str`.
+
+use crate::scanner::ScanError;
+use crate::yaml::{parse_f64, YamlInput, YamlLoader};
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Unexpected, Visitor};
+use std::error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    Scan(ScanError),
+    /// The source holds more than one document.
+    MultipleDocuments,
+    /// A value does not fit the type it is deserialized into.
+    Message(String),
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Scan(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Scan(ref err) => fmt::Display::fmt(err, formatter),
+            Error::MultipleDocuments => formatter.write_str("expected a single document"),
+            Error::Message(ref message) => formatter.write_str(message),
+        }
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl From<ScanError> for Error {
+    fn from(err: ScanError) -> Error {
+        Error::Scan(err)
+    }
+}
+
+/// Deserialize a `T` from the single document of `source`. Aliases are replaced by
+/// the nodes they refer to, merge keys are resolved as by
+/// `YamlInput::resolve_merge_keys`, and an empty source is read as a null.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct Service {
+///     name: String,
+///     ports: Vec<u16>,
+/// }
+///
+/// let source = "
+/// base: &base {name: web, ports: [80, 443]}
+/// service: *base
+/// admin: {<<: *base, name: admin}
+/// ";
+/// let doc: std::collections::HashMap<String, Service> =
+///     yaml_rust_formatter::from_str(source).unwrap();
+/// assert_eq!(doc["service"].ports, vec![80, 443]);
+/// assert_eq!(doc["admin"].name, "admin");
+/// ```
+pub fn from_str<T: DeserializeOwned>(source: &str) -> Result<T, Error> {
+    let mut docs = YamlLoader::load_from_str(source)?;
+    if docs.len() > 1 {
+        return Err(Error::MultipleDocuments);
+    }
+    let mut doc = docs.pop().unwrap_or(YamlInput::Null);
+    doc.resolve_merge_keys();
+    T::deserialize(doc)
+}
+
+/// Strip the anchors, aliases, tags, comments and spellings around the value of
+/// `node`.
+fn resolve(node: YamlInput) -> Result<YamlInput, Error> {
+    match node {
+        YamlInput::Anchored(_, node)
+        | YamlInput::Tagged(_, node)
+        | YamlInput::Aliased(_, Some(node))
+        | YamlInput::Commented(node, _)
+        | YamlInput::Spelled(node, _)
+        | YamlInput::Styled(node, _) => resolve(*node),
+        YamlInput::Aliased(name, None) => {
+            Err(de::Error::custom(format_args!("unknown anchor `{}`", name)))
+        }
+        YamlInput::Raw(ref text) => resolve(YamlInput::from_str(text)),
+        node => Ok(node),
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for YamlInput {
+    type Deserializer = YamlInput;
+
+    fn into_deserializer(self) -> YamlInput {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for YamlInput {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match resolve(self)? {
+            // integers beyond `i64` are loaded as reals
+            YamlInput::Real(ref v) => match (v.parse::<u64>(), parse_f64(v)) {
+                (Ok(u), _) => visitor.visit_u64(u),
+                (_, Some(f)) => visitor.visit_f64(f),
+                _ => Err(de::Error::invalid_value(Unexpected::Str(v), &visitor)),
+            },
+            YamlInput::Integer(v) => visitor.visit_i64(v),
+            YamlInput::String(v) => visitor.visit_string(v),
+            YamlInput::Boolean(v) => visitor.visit_bool(v),
+            YamlInput::Array(v) => {
+                let mut seq = SeqDeserializer::new(v.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            YamlInput::Hash(h) => {
+                let mut map = MapDeserializer::new(h.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            YamlInput::Binary(v) => visitor.visit_byte_buf(v),
+            YamlInput::Null => visitor.visit_unit(),
+            _ => Err(de::Error::custom("invalid YAML value")),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match resolve(self)? {
+            YamlInput::Null => visitor.visit_none(),
+            node => visitor.visit_some(node),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// A unit variant is written as its name, any other as a mapping from its name
+    /// to its content.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match resolve(self)? {
+            YamlInput::Hash(h) if h.len() == 1 => {
+                let (variant, value) = h.into_iter().next().unwrap();
+                visitor.visit_enum(Enum {
+                    variant,
+                    value: Some(value),
+                })
+            }
+            node @ YamlInput::String(_) => visitor.visit_enum(Enum {
+                variant: node,
+                value: None,
+            }),
+            _ => Err(de::Error::custom(
+                "expected an enum variant name, or a mapping with a single entry",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+/// The variant of an enum and its content, if it is not a unit variant.
+struct Enum {
+    variant: YamlInput,
+    value: Option<YamlInput>,
+}
+
+impl<'de> de::EnumAccess<'de> for Enum {
+    type Error = Error;
+    type Variant = Enum;
+
+    fn variant_seed<S: de::DeserializeSeed<'de>>(
+        mut self,
+        seed: S,
+    ) -> Result<(S::Value, Enum), Error> {
+        let variant = std::mem::replace(&mut self.variant, YamlInput::Null);
+        Ok((seed.deserialize(variant)?, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Enum {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.value.map(resolve).transpose()? {
+            None | Some(YamlInput::Null) => Ok(()),
+            Some(_) => Err(de::Error::invalid_type(
+                Unexpected::NewtypeVariant,
+                &"unit variant",
+            )),
+        }
+    }
+
+    fn newtype_variant_seed<S: de::DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, Error> {
+        match self.value {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Some(value) => de::Deserializer::deserialize_seq(value, visitor),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value {
+            Some(value) => de::Deserializer::deserialize_map(value, visitor),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum Action {
+        Stop,
+        Wait(u32),
+        Move { x: i32, y: i32 },
+        Swap(String, String),
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config {
+        name: String,
+        version: f64,
+        debug: bool,
+        retries: Option<u8>,
+        timeout: Option<u8>,
+        actions: Vec<Action>,
+        env: BTreeMap<String, String>,
+    }
+
+    #[test]
+    fn test_from_str() {
+        let source = "
+name: &name deploy # the name
+version: 1.5
+debug: true
+retries: ~
+timeout: 30
+actions:
+  - Stop
+  - Wait: 5
+  - Move: {x: 1, y: -2}
+  - Swap: [a, *name]
+env: {HOME: /root}
+";
+        let config: Config = from_str(source).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                name: "deploy".to_owned(),
+                version: 1.5,
+                debug: true,
+                retries: None,
+                timeout: Some(30),
+                actions: vec![
+                    Action::Stop,
+                    Action::Wait(5),
+                    Action::Move { x: 1, y: -2 },
+                    Action::Swap("a".to_owned(), "deploy".to_owned()),
+                ],
+                env: vec![("HOME".to_owned(), "/root".to_owned())]
+                    .into_iter()
+                    .collect(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_str_errors() {
+        assert_eq!(from_str::<Option<u8>>("").unwrap(), None);
+        assert_eq!(from_str::<f64>("-.inf").unwrap(), f64::NEG_INFINITY);
+        assert_eq!(
+            from_str::<u8>("a\n---\nb").unwrap_err(),
+            Error::MultipleDocuments
+        );
+        assert!(matches!(from_str::<u8>("[").unwrap_err(), Error::Scan(_)));
+        assert_eq!(
+            from_str::<u8>("300").unwrap_err().to_string(),
+            "invalid value: integer `300`, expected u8"
+        );
+        assert_eq!(
+            from_str::<Vec<u8>>("[1, *nope]").unwrap_err().to_string(),
+            "unknown anchor `nope`"
+        );
+    }
+}
@@ -0,0 +1,836 @@
+#[cfg(feature = "diff")]
+use crate::emitter::key_name;
+#[cfg(feature = "diff")]
+use crate::parser::{Event, Parser};
+#[cfg(feature = "diff")]
+use crate::scanner::TScalarStyle;
+#[cfg(feature = "diff")]
+use crate::yaml::{expand, resolve_scalar, Entries, YamlOutput};
+use crate::yaml::{resolve, YamlInput};
+#[cfg(feature = "diff")]
+use linked_hash_map::LinkedHashMap;
+#[cfg(feature = "diff")]
+use std::borrow::Cow;
+#[cfg(feature = "diff")]
+use std::error::Error;
+use std::fmt;
+
+/// One step of a `Path`.
+#[derive(Clone, PartialEq, Debug, Eq, Hash)]
+pub enum Segment {
+    /// A mapping key, in its scalar form.
+    Key(String),
+    /// A sequence index.
+    Index(usize),
+}
+
+/// The location of a node inside a document, displayed like `spec.containers[0].image`.
+#[derive(Clone, PartialEq, Debug, Eq, Hash, Default)]
+pub struct Path {
+    segments: Vec<Segment>,
+}
+
+impl Path {
+    pub fn new() -> Path {
+        Path::default()
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Read a path written like `spec.containers[0].image`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::diff::{Path, Segment};
+    ///
+    /// let path = Path::parse("spec.containers[0]");
+    /// assert_eq!(path.segments()[2], Segment::Index(0));
+    /// assert_eq!(path.to_string(), "spec.containers[0]");
+    /// ```
+    pub fn parse(path: &str) -> Path {
+        let mut segments = Vec::new();
+        for part in path.split('.').filter(|part| !part.is_empty()) {
+            let mut indices = part.split('[');
+            let key = indices.next().unwrap_or("");
+            if !key.is_empty() {
+                segments.push(Segment::Key(key.to_owned()));
+            }
+            for index in indices {
+                let index = index.trim_end_matches(']');
+                segments.push(match index.parse() {
+                    Ok(i) => Segment::Index(i),
+                    Err(_) => Segment::Key(index.to_owned()),
+                });
+            }
+        }
+        Path { segments }
+    }
+
+    /// Extend the path with the mapping key `key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::diff::Path;
+    ///
+    /// let path = Path::new().key("spec").key("containers").index(0).key("image");
+    /// assert_eq!(path, Path::parse("spec.containers[0].image"));
+    /// ```
+    pub fn key(mut self, key: &str) -> Path {
+        self.segments.push(Segment::Key(key.to_owned()));
+        self
+    }
+
+    /// Extend the path with the sequence index `index`.
+    pub fn index(mut self, index: usize) -> Path {
+        self.segments.push(Segment::Index(index));
+        self
+    }
+
+    /// Determine if `prefix` is this path or one of its ancestors.
+    pub fn starts_with(&self, prefix: &Path) -> bool {
+        self.segments.starts_with(&prefix.segments)
+    }
+
+    pub(crate) fn push(&mut self, segment: Segment) {
+        self.segments.push(segment);
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.segments.pop();
+    }
+
+    pub(crate) fn child(&self, segment: Segment) -> Path {
+        let mut segments = self.segments.clone();
+        segments.push(segment);
+        Path { segments }
+    }
+
+    /// Format the path as a JSON Pointer (RFC 6901), e.g. `/spec/containers/0/image`.
+    pub fn to_pointer(&self) -> String {
+        let mut pointer = String::new();
+        for segment in &self.segments {
+            pointer.push('/');
+            match *segment {
+                Segment::Key(ref k) => pointer.push_str(&k.replace('~', "~0").replace('/', "~1")),
+                Segment::Index(i) => pointer.push_str(&i.to_string()),
+            }
+        }
+        pointer
+    }
+
+    /// Read a JSON Pointer (RFC 6901), e.g. `/spec/containers/0/image`. Segments made
+    /// of digits are indices. `None` if `pointer` is neither empty nor starts with `/`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::diff::Path;
+    ///
+    /// let path = Path::from_pointer("/spec/containers/0/a~1b").unwrap();
+    /// assert_eq!(path.to_string(), "spec.containers[0].a/b");
+    /// assert_eq!(path.to_pointer(), "/spec/containers/0/a~1b");
+    /// ```
+    pub fn from_pointer(pointer: &str) -> Option<Path> {
+        if pointer.is_empty() {
+            return Some(Path::new());
+        }
+        let segments = pointer
+            .strip_prefix('/')?
+            .split('/')
+            .map(|segment| {
+                let segment = segment.replace("~1", "/").replace("~0", "~");
+                // `0` or digits without a leading zero, unlike `+1` or `01`
+                let index = segment.bytes().all(|b| b.is_ascii_digit())
+                    && (segment == "0" || !segment.starts_with('0'));
+                match segment.parse() {
+                    Ok(i) if index => Segment::Index(i),
+                    _ => Segment::Key(segment),
+                }
+            })
+            .collect();
+        Some(Path { segments })
+    }
+
+    /// Extend the path with `segments`.
+    #[cfg(feature = "diff")]
+    fn extend(mut self, segments: &[Segment]) -> Path {
+        self.segments.extend_from_slice(segments);
+        self
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match *segment {
+                Segment::Key(ref k) if i == 0 => write!(formatter, "{}", k)?,
+                Segment::Key(ref k) => write!(formatter, ".{}", k)?,
+                Segment::Index(index) => write!(formatter, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A difference between two documents. Values have their aliases expanded.
+#[cfg(feature = "diff")]
+#[derive(Clone, PartialEq, Debug)]
+pub enum Change {
+    Added(Path, YamlInput),
+    Removed(Path),
+    Changed(Path, YamlInput, YamlInput),
+}
+
+#[cfg(feature = "diff")]
+impl Change {
+    pub fn path(&self) -> &Path {
+        match *self {
+            Change::Added(ref path, _) | Change::Removed(ref path) => path,
+            Change::Changed(ref path, ..) => path,
+        }
+    }
+}
+
+pub(crate) fn key_segment(key: &YamlInput) -> Segment {
+    Segment::Key(match *resolve(key) {
+        YamlInput::String(ref v) | YamlInput::Real(ref v) | YamlInput::Raw(ref v) => v.clone(),
+        YamlInput::Integer(v) => v.to_string(),
+        YamlInput::Boolean(v) => v.to_string(),
+        YamlInput::Null => "~".to_owned(),
+        ref key => format!("{:?}", key),
+    })
+}
+
+/// Compute the changes turning `old` into `new`.
+///
+/// Mappings are compared by key, so reordered keys are not reported. Sequences are compared
+/// index by index; removals from a sequence are listed from the last index down, so the
+/// changes can be applied one after the other.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::diff::{self, Change};
+/// use yaml_rust_formatter::YamlLoader;
+///
+/// let old = &YamlLoader::load_from_str("{a: 1, b: [x, y]}").unwrap()[0];
+/// let new = &YamlLoader::load_from_str("{b: [x], a: 2}").unwrap()[0];
+/// let changes = diff::diff(old, new);
+/// assert_eq!(changes.len(), 2);
+/// assert_eq!(changes[0].path().to_string(), "a");
+/// assert!(matches!(changes[1], Change::Removed(ref path) if path.to_pointer() == "/b/1"));
+/// ```
+#[cfg(feature = "diff")]
+pub fn diff(old: &YamlInput, new: &YamlInput) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_node(&Path::new(), old, new, &mut changes);
+    changes
+}
+
+#[cfg(feature = "diff")]
+fn diff_node(path: &Path, old: &YamlInput, new: &YamlInput, changes: &mut Vec<Change>) {
+    match (resolve(old), resolve(new)) {
+        (YamlInput::Hash(old), YamlInput::Hash(new)) => {
+            let new_keys: LinkedHashMap<_, _> =
+                Entries::new(new).map(|(k, v)| (expand(k), v)).collect();
+            let mut old_keys = Vec::new();
+            for (k, v) in Entries::new(old) {
+                let k = expand(k);
+                let child = path.child(key_segment(&k));
+                match new_keys.get(&k) {
+                    Some(new) => diff_node(&child, v, new, changes),
+                    None => changes.push(Change::Removed(child)),
+                }
+                old_keys.push(k);
+            }
+            for (k, v) in new_keys {
+                if !old_keys.contains(&k) {
+                    changes.push(Change::Added(path.child(key_segment(&k)), expand(v)));
+                }
+            }
+        }
+        (YamlInput::Array(old), YamlInput::Array(new)) => {
+            for (i, (old, new)) in old.iter().zip(new).enumerate() {
+                diff_node(&path.child(Segment::Index(i)), old, new, changes);
+            }
+            for i in (new.len()..old.len()).rev() {
+                changes.push(Change::Removed(path.child(Segment::Index(i))));
+            }
+            for (i, new) in new.iter().enumerate().skip(old.len()) {
+                changes.push(Change::Added(path.child(Segment::Index(i)), expand(new)));
+            }
+        }
+        (old, new) => {
+            if expand(old) != expand(new) {
+                changes.push(Change::Changed(path.clone(), expand(old), expand(new)));
+            }
+        }
+    }
+}
+
+/// Describe `changes` as a JSON Patch (RFC 6902) document of `add`, `remove` and
+/// `replace` operations, ready to be emitted.
+#[cfg(feature = "diff")]
+pub fn to_patch(changes: &[Change]) -> YamlOutput {
+    let op = |name: &str, path: &Path, value: Option<&YamlInput>| {
+        let mut op = LinkedHashMap::new();
+        op.insert(
+            YamlOutput::String("op".to_owned()),
+            YamlOutput::String(name.to_owned()),
+        );
+        op.insert(
+            YamlOutput::String("path".to_owned()),
+            YamlOutput::String(path.to_pointer()),
+        );
+        if let Some(value) = value {
+            op.insert(YamlOutput::String("value".to_owned()), value.clone().into());
+        }
+        YamlOutput::Hash(op)
+    };
+    YamlOutput::Array(
+        changes
+            .iter()
+            .map(|change| match *change {
+                Change::Added(ref path, ref value) => op("add", path, Some(value)),
+                Change::Removed(ref path) => op("remove", path, None),
+                Change::Changed(ref path, _, ref value) => op("replace", path, Some(value)),
+            })
+            .collect(),
+    )
+}
+
+/// One operation of a patch, see `apply_patch`.
+#[cfg(feature = "diff")]
+#[derive(Clone, PartialEq, Debug)]
+pub enum PatchOp {
+    /// Insert a value in a sequence, the index of its length appending to it, or set
+    /// the value of a key of a mapping.
+    Add(Path, YamlOutput),
+    Remove(Path),
+    /// Set a value that exists.
+    Replace(Path, YamlOutput),
+    /// Remove the value at the first path and add it at the second one.
+    Move(Path, Path),
+}
+
+#[cfg(feature = "diff")]
+impl PatchOp {
+    /// The name of the operation in a JSON Patch, e.g. `add`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PatchOp::Add(..) => "add",
+            PatchOp::Remove(_) => "remove",
+            PatchOp::Replace(..) => "replace",
+            PatchOp::Move(..) => "move",
+        }
+    }
+}
+
+/// An operation of a patch that could not be applied or read.
+#[cfg(feature = "diff")]
+#[derive(Clone, PartialEq, Debug)]
+pub struct PatchError {
+    index: usize,
+    op: Option<Box<PatchOp>>,
+    message: String,
+}
+
+#[cfg(feature = "diff")]
+impl PatchError {
+    fn new(index: usize, op: Option<&PatchOp>, message: &str) -> PatchError {
+        PatchError {
+            index,
+            op: op.map(|op| Box::new(op.clone())),
+            message: message.to_owned(),
+        }
+    }
+
+    /// The position of the operation in the patch, from 0.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The operation that failed, `None` when it could not be read.
+    pub fn op(&self) -> Option<&PatchOp> {
+        self.op.as_deref()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+#[cfg(feature = "diff")]
+impl Error for PatchError {}
+
+#[cfg(feature = "diff")]
+impl fmt::Display for PatchError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.op {
+            Some(ref op) => write!(
+                formatter,
+                "operation {} ({}): {}",
+                self.index,
+                op.name(),
+                self.message
+            ),
+            None => write!(formatter, "operation {}: {}", self.index, self.message),
+        }
+    }
+}
+
+/// Apply `ops` one after the other to a copy of `doc`, the inverse of `diff`. The
+/// first operation that cannot be applied stops the patch.
+///
+/// Paths go through anchors, tags and comments, which are kept. A key added to a
+/// mapping is read as a plain scalar, so `80` is the integer, as `diff` writes it.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::diff::{self, Path, PatchOp};
+/// use yaml_rust_formatter::{YamlLoader, YamlOutput};
+///
+/// let doc: YamlOutput = YamlLoader::load_from_str("spec: {image: web, ports: [80]}")
+///     .unwrap()
+///     .remove(0)
+///     .into();
+/// let patched = diff::apply_patch(
+///     &doc,
+///     &[
+///         PatchOp::Replace(Path::parse("spec.image"), "web:1.2".into()),
+///         PatchOp::Add(Path::parse("spec.ports[1]"), 443.into()),
+///         PatchOp::Move(Path::parse("spec.ports"), Path::parse("ports")),
+///     ],
+/// )
+/// .unwrap();
+/// let expected = YamlLoader::load_from_str("{spec: {image: web:1.2}, ports: [80, 443]}");
+/// assert_eq!(patched, expected.unwrap().remove(0).into());
+///
+/// let err = diff::apply_patch(&doc, &[PatchOp::Remove(Path::parse("spec.tag"))]).unwrap_err();
+/// assert_eq!(err.to_string(), "operation 0 (remove): spec.tag does not exist");
+/// ```
+#[cfg(feature = "diff")]
+pub fn apply_patch(doc: &YamlOutput, ops: &[PatchOp]) -> Result<YamlOutput, PatchError> {
+    let mut doc = doc.clone();
+    for (index, op) in ops.iter().enumerate() {
+        apply_op(&mut doc, op).map_err(|message| PatchError::new(index, Some(op), &message))?;
+    }
+    Ok(doc)
+}
+
+#[cfg(feature = "diff")]
+fn apply_op(doc: &mut YamlOutput, op: &PatchOp) -> Result<(), String> {
+    match *op {
+        PatchOp::Add(ref path, ref value) => add(doc, path, value.clone()),
+        PatchOp::Remove(ref path) => remove(doc, path).map(|_| ()),
+        PatchOp::Replace(ref path, ref value) => {
+            *node_mut(doc, path.segments()).ok_or_else(|| missing(path))? = value.clone();
+            Ok(())
+        }
+        PatchOp::Move(ref from, ref to) => {
+            if to.starts_with(from) && to != from {
+                return Err(format!("cannot move {} into itself", from));
+            }
+            let value = remove(doc, from)?;
+            add(doc, to, value)
+        }
+    }
+}
+
+#[cfg(feature = "diff")]
+fn missing(path: &Path) -> String {
+    if path.segments().is_empty() {
+        "the root does not exist".to_owned()
+    } else {
+        format!("{} does not exist", path)
+    }
+}
+
+/// The text of a segment, to find it among the keys of a mapping.
+#[cfg(feature = "diff")]
+fn segment_text(segment: &Segment) -> Cow<'_, str> {
+    match *segment {
+        Segment::Key(ref k) => Cow::Borrowed(k),
+        Segment::Index(i) => Cow::Owned(i.to_string()),
+    }
+}
+
+#[cfg(feature = "diff")]
+fn node_mut<'a>(node: &'a mut YamlOutput, segments: &[Segment]) -> Option<&'a mut YamlOutput> {
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return Some(node),
+    };
+    let child = if node.as_vec_mut().is_some() {
+        let index = match *segment {
+            Segment::Index(i) => i,
+            Segment::Key(ref k) => k.parse().ok()?,
+        };
+        node.as_vec_mut()?.get_mut(index)?
+    } else {
+        let text = segment_text(segment);
+        node.as_hash_mut()?
+            .iter_mut()
+            .find(|(k, _)| key_name(k) == text)
+            .map(|(_, v)| v)?
+    };
+    node_mut(child, rest)
+}
+
+/// The collection holding the node at `path`, and the last segment of `path`.
+#[cfg(feature = "diff")]
+fn parent_mut<'a, 'p>(
+    doc: &'a mut YamlOutput,
+    path: &'p Path,
+) -> Result<(&'a mut YamlOutput, &'p Segment), String> {
+    let (last, parent) = path
+        .segments()
+        .split_last()
+        .ok_or_else(|| "the root cannot be added or removed".to_owned())?;
+    match node_mut(doc, parent) {
+        Some(node) => Ok((node, last)),
+        None => Err(missing(&Path::new().extend(parent))),
+    }
+}
+
+#[cfg(feature = "diff")]
+fn add(doc: &mut YamlOutput, path: &Path, value: YamlOutput) -> Result<(), String> {
+    let (parent, last) = parent_mut(doc, path)?;
+    if let Some(v) = parent.as_vec_mut() {
+        let index = match *last {
+            Segment::Index(i) => i,
+            Segment::Key(ref k) if k == "-" => v.len(),
+            Segment::Key(ref k) => k
+                .parse()
+                .map_err(|_| format!("{} is not an index of a sequence", path))?,
+        };
+        if index > v.len() {
+            return Err(format!("{} is past the end of the sequence", path));
+        }
+        v.insert(index, value);
+        return Ok(());
+    }
+    let h = parent
+        .as_hash_mut()
+        .ok_or_else(|| format!("{} is not in a collection", path))?;
+    let text = segment_text(last);
+    match h.iter_mut().find(|(k, _)| key_name(k) == text) {
+        Some((_, v)) => *v = value,
+        None => {
+            h.insert(YamlInput::from_str(&text).into(), value);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "diff")]
+fn remove(doc: &mut YamlOutput, path: &Path) -> Result<YamlOutput, String> {
+    let (parent, last) = parent_mut(doc, path)?;
+    if let Some(v) = parent.as_vec_mut() {
+        let index = match *last {
+            Segment::Index(i) => Some(i),
+            Segment::Key(ref k) => k.parse().ok(),
+        };
+        return match index {
+            Some(i) if i < v.len() => Ok(v.remove(i)),
+            _ => Err(missing(path)),
+        };
+    }
+    let h = parent.as_hash_mut().ok_or_else(|| missing(path))?;
+    let text = segment_text(last);
+    let key = h.keys().find(|k| key_name(k) == text).cloned();
+    key.and_then(|key| h.remove(&key))
+        .ok_or_else(|| missing(path))
+}
+
+/// Read the operations of a JSON Patch (RFC 6902) document, such as the ones written
+/// by `to_patch`: a sequence of mappings with an `op`, a `path` given as a JSON
+/// Pointer, and a `value` or a `from`. Only `add`, `remove`, `replace` and `move`
+/// are known.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::diff;
+/// use yaml_rust_formatter::{YamlLoader, YamlOutput};
+///
+/// let old = &YamlLoader::load_from_str("{a: 1, b: [x]}").unwrap()[0];
+/// let new = &YamlLoader::load_from_str("{a: 2, b: [x, y]}").unwrap()[0];
+/// let ops = diff::parse_patch(&diff::to_patch(&diff::diff(old, new))).unwrap();
+/// let patched = diff::apply_patch(&old.clone().into(), &ops).unwrap();
+/// assert_eq!(patched, YamlOutput::from(new.clone()));
+/// ```
+#[cfg(feature = "diff")]
+pub fn parse_patch(patch: &YamlOutput) -> Result<Vec<PatchOp>, PatchError> {
+    let ops = match *patch {
+        YamlOutput::Array(ref ops) => ops,
+        _ => {
+            return Err(PatchError::new(
+                0,
+                None,
+                "a patch is a sequence of operations",
+            ))
+        }
+    };
+    ops.iter()
+        .enumerate()
+        .map(|(index, op)| parse_op(op).map_err(|message| PatchError::new(index, None, &message)))
+        .collect()
+}
+
+#[cfg(feature = "diff")]
+fn parse_op(op: &YamlOutput) -> Result<PatchOp, String> {
+    let pointer = |name: &str| match op[name] {
+        YamlOutput::String(ref p) => {
+            Path::from_pointer(p).ok_or_else(|| format!("{} is not a JSON Pointer", p))
+        }
+        _ => Err(format!("the operation has no {}", name)),
+    };
+    let value = || match op["value"] {
+        YamlOutput::BadValue => Err("the operation has no value".to_owned()),
+        ref value => Ok(value.clone()),
+    };
+    match op["op"] {
+        YamlOutput::String(ref name) => match name.as_str() {
+            "add" => Ok(PatchOp::Add(pointer("path")?, value()?)),
+            "remove" => Ok(PatchOp::Remove(pointer("path")?)),
+            "replace" => Ok(PatchOp::Replace(pointer("path")?, value()?)),
+            "move" => Ok(PatchOp::Move(pointer("from")?, pointer("path")?)),
+            name => Err(format!("unknown operation {}", name)),
+        },
+        _ => Err("the operation has no op".to_owned()),
+    }
+}
+
+/// How the text of a source changed, see `classify_change`.
+#[cfg(feature = "diff")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Unchanged,
+    /// Only the layout changed, such as whitespace, indentation, quoting or a flow
+    /// collection written as a block.
+    Whitespace,
+    /// Comments changed, and maybe the layout, but not the content.
+    Comment,
+    /// The content changed, or one of the texts does not parse.
+    Structural,
+}
+
+/// The content events of `source`, with the style of the scalars holding strings
+/// forgotten, and its comments, or `None` if it does not parse.
+#[cfg(feature = "diff")]
+fn layout_free_events(source: &str) -> Option<(Vec<Event>, Vec<Event>)> {
+    let mut parser = Parser::new(source.chars());
+    parser.keep_comments(true);
+    let (mut content, mut comments) = (Vec::new(), Vec::new());
+    loop {
+        match parser.next().ok()? {
+            (Event::StreamEnd, _) => return Some((content, comments)),
+            (event @ Event::Comment(..), _) => comments.push(event),
+            (Event::Scalar(v, style, anchor, tag), _)
+                if matches!(resolve_scalar(v.clone(), style, &tag), YamlInput::String(_)) =>
+            {
+                content.push(Event::Scalar(v, TScalarStyle::Any, anchor, tag))
+            }
+            (event, _) => content.push(event),
+        }
+    }
+}
+
+/// Tell whether the change from `old` to `new` leaves the content and the comments of
+/// the documents as they were, so an editor or a watcher can decide to only reformat
+/// a source rather than validate it again.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::diff::{self, ChangeKind};
+///
+/// let old = "a: [1, 2] # sizes\n";
+/// assert_eq!(diff::classify_change(old, "a: [1,   2] # sizes\n"), ChangeKind::Whitespace);
+/// assert_eq!(diff::classify_change(old, "a: [1, 2] # widths\n"), ChangeKind::Comment);
+/// assert_eq!(diff::classify_change(old, "a: [1, 3] # sizes\n"), ChangeKind::Structural);
+/// ```
+#[cfg(feature = "diff")]
+pub fn classify_change(old: &str, new: &str) -> ChangeKind {
+    if old == new {
+        return ChangeKind::Unchanged;
+    }
+    match (layout_free_events(old), layout_free_events(new)) {
+        (Some(old), Some(new)) if old.0 == new.0 => {
+            if old.1 == new.1 {
+                ChangeKind::Whitespace
+            } else {
+                ChangeKind::Comment
+            }
+        }
+        _ => ChangeKind::Structural,
+    }
+}
+
+#[cfg(all(test, feature = "diff"))]
+mod test {
+    use super::*;
+    use crate::emitter::YamlEmitter;
+    use crate::yaml::YamlLoader;
+
+    #[test]
+    fn test_classify_change() {
+        let old = "# config\nname: 'web'\nports: [80, 443]\n";
+        let cases = [
+            (old, ChangeKind::Unchanged),
+            (
+                "---\n# config\nname:   \"web\"\nports:\n  - 80\n  - 443\n",
+                ChangeKind::Whitespace,
+            ),
+            (
+                "# config\nname: web\nports: [80, 443]\n",
+                ChangeKind::Whitespace,
+            ),
+            ("name: 'web'\nports: [80, 443]\n", ChangeKind::Comment),
+            (
+                "# config\nname: 'web' # public\nports: [80, 443]\n",
+                ChangeKind::Comment,
+            ),
+            (
+                "# config\nname: 'web'\nports: ['80', 443]\n",
+                ChangeKind::Structural,
+            ),
+            (
+                "# config\nports: [80, 443]\nname: 'web'\n",
+                ChangeKind::Structural,
+            ),
+            (
+                "# config\nname: 'web'\nports: [80, 443\n",
+                ChangeKind::Structural,
+            ),
+        ];
+        for &(new, kind) in &cases {
+            assert_eq!(classify_change(old, new), kind, "{:?}", new);
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = "
+name: web
+base: &base {image: nginx}
+containers:
+- *base
+- image: redis
+- image: db
+labels: {a/b: 1}
+";
+        let new = "
+containers:
+- image: nginx
+- image: redis:6
+labels: {a/b: 1, c~d: 2}
+name: web
+";
+        let old = &YamlLoader::load_from_str(old).unwrap()[0];
+        let new = &YamlLoader::load_from_str(new).unwrap()[0];
+        let changes: Vec<_> = diff(old, new)
+            .iter()
+            .map(|change| match *change {
+                Change::Added(ref path, _) => format!("+ {}", path),
+                Change::Removed(ref path) => format!("- {}", path),
+                Change::Changed(ref path, ..) => format!("~ {}", path),
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "- base",
+                "~ containers[1].image",
+                "- containers[2]",
+                "+ labels.c~d",
+            ]
+        );
+        assert!(diff(new, new).is_empty());
+    }
+
+    #[test]
+    fn test_to_patch() {
+        let old = &YamlLoader::load_from_str("{a: [1, 2, 3], b: {c: 1}}").unwrap()[0];
+        let new = &YamlLoader::load_from_str("{a: [1], b: {c: 2, d/e~f: [x]}}").unwrap()[0];
+        let mut out = String::new();
+        YamlEmitter::new(&mut out)
+            .dump(&to_patch(&diff(old, new)))
+            .unwrap();
+        assert_eq!(
+            out,
+            "---
+- op: remove
+  path: /a/2
+- op: remove
+  path: /a/1
+- op: replace
+  path: /b/c
+  value: 2
+- op: add
+  path: /b/d~1e~0f
+  value:
+    - x"
+        );
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let old = &YamlLoader::load_from_str(
+            "{a: [1, 2, 3], b: &b {c: 1, 80: http}, 'd/e~f': [x], g: !t {h: 1}}",
+        )
+        .unwrap()[0];
+        let new = &YamlLoader::load_from_str(
+            "{a: [1], b: {c: 2, 80: https, 443: tls}, 'd/e~f': [x, y], g: !t {i: 1}}",
+        )
+        .unwrap()[0];
+        let patch = to_patch(&diff(old, new));
+        let ops = parse_patch(&patch).unwrap();
+        let patched = apply_patch(&old.clone().into(), &ops).unwrap();
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump(&patched).unwrap();
+        assert_eq!(
+            out,
+            "---
+a:
+  - 1
+b: &b
+  c: 2
+  80: https
+  443: tls
+d/e~f:
+  - x
+  - y
+g: !t
+  i: 1"
+        );
+
+        let doc: YamlOutput = old.clone().into();
+        let ops = [
+            PatchOp::Add(Path::from_pointer("/a/-").unwrap(), 4.into()),
+            PatchOp::Move(Path::parse("a"), Path::parse("a[0]")),
+        ];
+        let err = apply_patch(&doc, &ops).unwrap_err();
+        assert_eq!(err.index(), 1);
+        assert_eq!(err.op(), Some(&ops[1]));
+        assert_eq!(err.message(), "cannot move a into itself");
+        let err = apply_patch(&doc, &[PatchOp::Add(Path::parse("a[5]"), 4.into())]);
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "operation 0 (add): a[5] is past the end of the sequence"
+        );
+        let err = apply_patch(&doc, &[PatchOp::Remove(Path::new())]).unwrap_err();
+        assert_eq!(err.message(), "the root cannot be added or removed");
+
+        let bad = YamlLoader::load_from_str("[{op: copy, path: /a, from: /b}]").unwrap();
+        let err = parse_patch(&bad[0].clone().into()).unwrap_err();
+        assert_eq!(err.to_string(), "operation 0: unknown operation copy");
+        assert_eq!(err.op(), None);
+    }
+}
@@ -1,17 +1,41 @@
-use crate::yaml::{HashOutput, YamlOutput};
+use crate::base64;
+use crate::diff::{Path, Segment};
+use crate::metrics;
+use crate::scanner::TScalarStyle;
+use crate::yaml::{
+    has_line_break, parse_f64, Comments, Entries, HashOutput, YamlInput, YamlOutput,
+};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::convert::From;
 use std::error::Error;
-use std::fmt::{self, Display};
+use std::fmt::{self, Display, Write as _};
+#[cfg(feature = "unicode")]
+use unicode_width::UnicodeWidthStr;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
 pub enum EmitError {
     FmtError(fmt::Error),
     BadHashmapKey,
+    /// The error that stopped `YamlEmitter::dump` partway through a document, with
+    /// where it stopped.
+    Node(Box<NodeError>),
+    /// A character other than ASCII in a comment, anchor or alias, where
+    /// `YamlEmitter::ascii_only` has no way to escape it.
+    NotAscii(char),
+    /// A `StreamBuilder` method called out of order, like a mapping value without
+    /// its key, with what it needed first. Nothing is written for the call.
+    OutOfOrder(&'static str),
 }
 
 impl Error for EmitError {
-    fn cause(&self) -> Option<&dyn Error> {
-        None
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            EmitError::Node(ref err) => Some(&err.error),
+            _ => None,
+        }
     }
 }
 
@@ -20,7 +44,50 @@ impl Display for EmitError {
         match *self {
             EmitError::FmtError(ref err) => Display::fmt(err, formatter),
             EmitError::BadHashmapKey => formatter.write_str("bad hashmap key"),
+            EmitError::Node(ref err) => Display::fmt(err, formatter),
+            EmitError::NotAscii(c) => write!(
+                formatter,
+                "{:?} is not ASCII and cannot be escaped in a comment, anchor or alias",
+                c
+            ),
+            EmitError::OutOfOrder(needed) => write!(formatter, "out of order: {}", needed),
+        }
+    }
+}
+
+/// Where `YamlEmitter::dump` stopped, see `EmitError::Node`.
+#[derive(Clone, Debug)]
+pub struct NodeError {
+    path: Path,
+    written: usize,
+    error: EmitError,
+}
+
+impl NodeError {
+    /// The node being written, the root for the markers and directives.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The number of bytes written for the document before the error, which a caller
+    /// can cut from the end of its output to drop the partial document.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    pub fn error(&self) -> &EmitError {
+        &self.error
+    }
+}
+
+impl Display for NodeError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.segments().is_empty() {
+            write!(formatter, "{} at the root", self.error)?;
+        } else {
+            write!(formatter, "{} at {}", self.error, self.path)?;
         }
+        write!(formatter, ", after {} bytes", self.written)
     }
 }
 
@@ -30,60 +97,625 @@ impl From<fmt::Error> for EmitError {
     }
 }
 
+/// Output conventions of other YAML libraries that the emitter can reproduce.
+///
+/// Selecting a profile adjusts document markers, indentation, quoting, the
+/// spelling of null and key order so that, for the supported subset, the
+/// output is byte-for-byte identical to what the named tool writes by default.
+/// The supported subset is documents made of mappings with scalar keys,
+/// sequences, and single-line scalars that do not need escapes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EmitProfile {
+    /// The native output of this crate: `---` header, `~` for null and
+    /// double quotes where quoting is needed.
+    Native,
+    /// PyYAML's `yaml.safe_dump(data)`: sorted keys, single quotes, `null`,
+    /// sequences flush with their parent key and a trailing newline.
+    PyYamlSafeDump,
+    /// go-yaml v3's `yaml.Marshal`: four space indentation, double quotes,
+    /// `null` and a trailing newline.
+    GoYamlV3,
+    /// serde_yaml's `to_string`: single quotes, `null`, sequences flush with
+    /// their parent key and a trailing newline.
+    SerdeYaml,
+}
+
+/// The length of the lines base64 data is wrapped at, as in MIME.
+const BINARY_LINE_WIDTH: usize = 76;
+
+/// The width a string kept in the folded style is folded at when no fold width is set.
+const STYLED_FOLD_WIDTH: usize = 80;
+
+/// The number of columns `s` takes on a terminal: East Asian wide characters take
+/// two, combining marks none.
+#[cfg(feature = "unicode")]
+fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// The number of columns `s` takes on a terminal, counted as one for each character
+/// that does not extend the one before it without the Unicode tables.
+#[cfg(not(feature = "unicode"))]
+fn display_width(s: &str) -> usize {
+    s.chars().filter(|&c| !extends_grapheme(c)).count()
+}
+
+/// A writer keeping track of the column where the next character goes.
+struct ColumnWriter<'a> {
+    inner: &'a mut dyn fmt::Write,
+    column: usize,
+    // the number of bytes written successfully
+    written: usize,
+}
+
+impl<'a> fmt::Write for ColumnWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match s.rfind('\n') {
+            Some(i) => self.column = display_width(&s[i + 1..]),
+            None => self.column += display_width(s),
+        }
+        self.inner.write_str(s)?;
+        self.written += s.len();
+        Ok(())
+    }
+}
+
+/// How the emitter quotes strings, see [`YamlEmitter::quote_style`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum QuoteStyle {
+    /// Plain where possible, otherwise in the quotes that keep the string as written:
+    /// single quotes unless it holds a `'` or needs escapes.
+    Plain,
+    /// Plain where possible, otherwise single quotes unless the string needs escapes.
+    SingleWhenNeeded,
+    /// Plain where possible, otherwise double quotes.
+    #[default]
+    DoubleWhenNeeded,
+    /// Every string in single quotes, unless it needs escapes.
+    AlwaysSingle,
+    /// Every string in double quotes.
+    AlwaysDouble,
+}
+
+/// How nulls are written, see [`YamlEmitter::null_style`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum NullStyle {
+    /// `~`
+    #[default]
+    Tilde,
+    /// `null`
+    Lowercase,
+    /// `Null`
+    Capitalized,
+    /// Nothing after the key or `-` of a block collection entry, as in `key:`, and
+    /// `null` where a null cannot be empty: as a key, in a flow collection, after an
+    /// anchor or a tag, or alone in a document.
+    Empty,
+}
+
+impl NullStyle {
+    /// The text of a null, where it cannot be empty.
+    fn text(self) -> &'static str {
+        match self {
+            NullStyle::Tilde => "~",
+            NullStyle::Lowercase | NullStyle::Empty => "null",
+            NullStyle::Capitalized => "Null",
+        }
+    }
+}
+
+/// How reals are written, see [`YamlEmitter::float_format`]. By default a real is
+/// written as it is spelled, so `1.50` stays `1.50`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FloatFormat {
+    preserve_source: bool,
+    precision: Option<usize>,
+    exponent_threshold: Option<i32>,
+    canonical_specials: bool,
+}
+
+impl Default for FloatFormat {
+    fn default() -> FloatFormat {
+        FloatFormat {
+            preserve_source: true,
+            precision: None,
+            exponent_threshold: None,
+            canonical_specials: false,
+        }
+    }
+}
+
+impl FloatFormat {
+    pub fn new() -> FloatFormat {
+        FloatFormat::default()
+    }
+
+    /// Write reals as they are spelled, the default. Otherwise a real is written from
+    /// its value, as `precision` and `exponent_threshold` say, with the shortest text
+    /// that reads back as the same value when no precision is set.
+    pub fn preserve_source(&mut self, preserve_source: bool) {
+        self.preserve_source = preserve_source;
+    }
+
+    /// Determine if reals are written as they are spelled.
+    pub fn is_preserve_source(&self) -> bool {
+        self.preserve_source
+    }
+
+    /// Write reals with this many digits after the decimal point, when they are not
+    /// written as spelled.
+    pub fn precision(&mut self, precision: Option<usize>) {
+        self.precision = precision;
+    }
+
+    pub fn get_precision(&self) -> Option<usize> {
+        self.precision
+    }
+
+    /// Use scientific notation, like `1.5e12`, for a real of at least `10^n` or below
+    /// `10^-n` in magnitude, when reals are not written as spelled.
+    pub fn exponent_threshold(&mut self, n: Option<i32>) {
+        self.exponent_threshold = n;
+    }
+
+    pub fn get_exponent_threshold(&self) -> Option<i32> {
+        self.exponent_threshold
+    }
+
+    /// Write infinities and NaN as `.inf`, `-.inf` and `.nan` even when reals are
+    /// written as spelled, instead of `.Inf` or `+.INF`.
+    pub fn canonical_specials(&mut self, canonical_specials: bool) {
+        self.canonical_specials = canonical_specials;
+    }
+
+    /// Determine if infinities and NaN are always written in their canonical form.
+    pub fn is_canonical_specials(&self) -> bool {
+        self.canonical_specials
+    }
+
+    /// The text of the real spelled `v`.
+    fn text(self, v: &str) -> Cow<'_, str> {
+        let value = match parse_f64(v) {
+            Some(value) => value,
+            None => return Cow::Borrowed(v),
+        };
+        let canonical = self.canonical_specials || !self.preserve_source;
+        if value.is_nan() && canonical {
+            return Cow::Borrowed(".nan");
+        }
+        if value.is_infinite() && canonical {
+            return Cow::Borrowed(if value > 0.0 { ".inf" } else { "-.inf" });
+        }
+        if self.preserve_source || !value.is_finite() {
+            return Cow::Borrowed(v);
+        }
+        let exponent = self.exponent_threshold.is_some_and(|n| {
+            let magnitude = value.abs();
+            magnitude != 0.0 && (magnitude >= 10f64.powi(n) || magnitude < 10f64.powi(-n))
+        });
+        let mut text = match (exponent, self.precision) {
+            (true, Some(precision)) => format!("{:.*e}", precision, value),
+            (true, None) => format!("{:e}", value),
+            (false, Some(precision)) => format!("{:.*}", precision, value),
+            (false, None) => value.to_string(),
+        };
+        // without a point or an exponent the text would read back as an integer
+        if !text.contains(['.', 'e']) {
+            text.push_str(if self.precision == Some(0) { "." } else { ".0" });
+        }
+        Cow::Owned(text)
+    }
+}
+
+/// The order in which the entries of mappings are emitted, see
+/// [`YamlEmitter::sort_keys`].
+#[derive(Copy, Clone, Debug, Default)]
+pub enum KeyOrder {
+    /// The order of the mapping, see [`Entries`](crate::yaml::Entries).
+    #[default]
+    Input,
+    /// Sorted by key, integers before strings.
+    Ascending,
+    /// Sorted by key, in the reverse order of `Ascending`.
+    Descending,
+    /// Sorted by key with the given comparison. Entries with equal keys keep their
+    /// input order.
+    Custom(fn(&YamlOutput, &YamlOutput) -> Ordering),
+}
+
+/// How a string has to be written to be read back as the same string, see
+/// [`needs_quoting`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QuoteRequirement {
+    /// As it is, without quotes.
+    Plain,
+    /// In single or double quotes.
+    Quotes,
+    /// In double quotes, as it holds characters only their escapes can express.
+    DoubleQuotes,
+    /// As a mapping key, only after a `?` indicator, as its text is longer than the
+    /// 1024 characters of a key written before its `:` alone.
+    ExplicitKey,
+}
+
+/// Where a string is written, see [`needs_quoting`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScalarContext {
+    Value,
+    Key,
+    /// A mapping key that YAML 1.1 readers must also read as a string, as written
+    /// with [`YamlEmitter::quote_yaml11_keys`].
+    Yaml11Key,
+}
+
+/// Chooses the names an emitter writes for anchors, see [`YamlEmitter::anchor_namer`].
+pub trait AnchorNamer {
+    /// The name to write for the anchor named `name`. Called once for each anchor, in
+    /// the order they appear; the aliases of the anchor are written with the name
+    /// returned.
+    fn name(&mut self, name: &str) -> String;
+
+    /// Called at the start of each document, whose anchors are apart from the ones
+    /// of the documents before it. Does nothing by default.
+    fn start_document(&mut self) {}
+}
+
+/// Names the anchors of each document `{prefix}1`, `{prefix}2` and so on, in the order
+/// they appear.
+#[derive(Clone, Debug)]
+pub struct NumberedAnchors {
+    prefix: String,
+    count: usize,
+}
+
+impl NumberedAnchors {
+    pub fn new(prefix: &str) -> NumberedAnchors {
+        NumberedAnchors {
+            prefix: prefix.to_owned(),
+            count: 0,
+        }
+    }
+}
+
+impl AnchorNamer for NumberedAnchors {
+    fn name(&mut self, _name: &str) -> String {
+        self.count += 1;
+        format!("{}{}", self.prefix, self.count)
+    }
+
+    fn start_document(&mut self) {
+        self.count = 0;
+    }
+}
+
+/// Keeps the names of anchors, replacing the characters an anchor cannot hold, such
+/// as spaces and flow indicators, by `_`. A name already used in the document gets
+/// the suffix `_2`, `_3` and so on, so that documents merged together keep every
+/// anchor apart.
+#[derive(Clone, Debug, Default)]
+pub struct UniqueAnchors {
+    used: HashSet<String>,
+}
+
+impl UniqueAnchors {
+    pub fn new() -> UniqueAnchors {
+        UniqueAnchors::default()
+    }
+}
+
+impl AnchorNamer for UniqueAnchors {
+    fn name(&mut self, name: &str) -> String {
+        let mut name: String = name
+            .chars()
+            .map(|c| match c {
+                ',' | '[' | ']' | '{' | '}' => '_',
+                c if c.is_whitespace() || c.is_control() => '_',
+                c => c,
+            })
+            .collect();
+        if name.is_empty() {
+            name.push('_');
+        }
+        let mut n = 1;
+        let mut unique = name.clone();
+        while self.used.contains(&unique) {
+            n += 1;
+            unique = format!("{}_{}", name, n);
+        }
+        self.used.insert(unique.clone());
+        unique
+    }
+
+    fn start_document(&mut self) {
+        self.used.clear();
+    }
+}
+
+/// Writes numbers the way the data they belong to wants them, such as IDs padded with
+/// zeros or amounts with two decimals, see [`YamlEmitter::number_formatter`].
+///
+/// The text returned is written as is, so it should read back as the number it
+/// stands for. Numbers loaded with their spelling, see `LoadOptions::keep_spelling`,
+/// keep it.
+pub trait NumberFormatter {
+    /// The text of the integer `value`, the value of `key` when it is in a mapping, or
+    /// `None` to write it as usual, the default.
+    fn integer(&mut self, value: i64, key: Option<&YamlOutput>) -> Option<String> {
+        let _ = (value, key);
+        None
+    }
+
+    /// The text of the real spelled `text`, the value of `key` when it is in a
+    /// mapping, or `None` to write it as usual, the default.
+    fn real(&mut self, text: &str, key: Option<&YamlOutput>) -> Option<String> {
+        let _ = (text, key);
+        None
+    }
+}
+
+/// The directives written before a document, see [`YamlEmitter::directives`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Directives {
+    yaml_version: Option<(u32, u32)>,
+    tags: Vec<(String, String)>,
+}
+
+impl Directives {
+    pub fn new() -> Directives {
+        Directives::default()
+    }
+
+    /// Declare the YAML version of the document with a `%YAML` directive, such as
+    /// `%YAML 1.2` for `Some((1, 2))`.
+    pub fn yaml_version(&mut self, version: Option<(u32, u32)>) {
+        self.yaml_version = version;
+    }
+
+    pub fn get_yaml_version(&self) -> Option<(u32, u32)> {
+        self.yaml_version
+    }
+
+    /// Declare the tag handle `handle`, like `!e!`, as a shorthand for `prefix` with a
+    /// `%TAG` directive. Tags are written as they are named in the document either way.
+    pub fn tag(&mut self, handle: &str, prefix: &str) {
+        self.tags.push((handle.to_owned(), prefix.to_owned()));
+    }
+
+    /// The handles and prefixes of the `%TAG` directives, in the order they are written.
+    pub fn get_tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.yaml_version.is_none() && self.tags.is_empty()
+    }
+}
+
+/// Documentation written as comment lines before the entries of mappings, see
+/// [`YamlEmitter::key_comments`].
+///
+/// Entries are found by the keys on their path, written like `spec.containers.image`.
+/// Sequence indices are left out, so that path documents the `image` of every
+/// container.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyComments {
+    comments: HashMap<Vec<String>, String>,
+}
+
+impl KeyComments {
+    pub fn new() -> KeyComments {
+        KeyComments::default()
+    }
+
+    /// Document the entries at `path` with `comment`, one comment line for each of
+    /// its lines.
+    pub fn insert(&mut self, path: &str, comment: &str) {
+        self.comments
+            .insert(key_names(Path::parse(path).segments()), comment.to_owned());
+    }
+
+    /// The comment documenting the entries at `path`.
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.comments
+            .get(&key_names(Path::parse(path).segments()))
+            .map(String::as_str)
+    }
+
+    /// The `description` of each property of a JSON Schema, read from `properties`
+    /// and `items` as `Schema::from_yaml` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::emitter::KeyComments;
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let schema = YamlLoader::load_from_str("
+    /// type: object
+    /// properties:
+    ///   hosts:
+    ///     description: Machines to deploy to.
+    ///     type: array
+    ///     items:
+    ///       type: object
+    ///       properties:
+    ///         port: {type: integer, description: Port of the agent.}
+    /// ").unwrap();
+    /// let comments = KeyComments::from_schema(&schema[0]);
+    /// assert_eq!(comments.get("hosts"), Some("Machines to deploy to."));
+    /// assert_eq!(comments.get("hosts.port"), Some("Port of the agent."));
+    /// ```
+    pub fn from_schema(schema: &YamlInput) -> KeyComments {
+        let mut comments = KeyComments::new();
+        comments.read_schema(schema, &mut Vec::new());
+        comments
+    }
+
+    fn read_schema(&mut self, schema: &YamlInput, path: &mut Vec<String>) {
+        let field = |name: &str| schema.as_hash()?.get(&YamlInput::String(name.to_owned()));
+        if let Some(description) = field("description").and_then(YamlInput::as_str) {
+            if !path.is_empty() {
+                self.comments.insert(path.clone(), description.to_owned());
+            }
+        }
+        if let Some(items) = field("items") {
+            self.read_schema(items, path);
+        }
+        if let Some(properties) = field("properties").and_then(YamlInput::as_hash) {
+            for (k, v) in Entries::new(properties) {
+                if let Some(k) = k.as_str() {
+                    path.push(k.to_owned());
+                    self.read_schema(v, path);
+                    path.pop();
+                }
+            }
+        }
+    }
+}
+
+/// The keys among `segments`.
+fn key_names(segments: &[Segment]) -> Vec<String> {
+    segments
+        .iter()
+        .filter_map(|segment| match *segment {
+            Segment::Key(ref k) => Some(k.clone()),
+            Segment::Index(_) => None,
+        })
+        .collect()
+}
+
+/// Writes documents to a borrowed `fmt::Write`. Since the writer and the hooks are
+/// borrowed, an emitter is not `Send`: create it on the thread or in the task that
+/// writes, while the documents it writes can come from anywhere.
 pub struct YamlEmitter<'a> {
-    writer: &'a mut dyn fmt::Write,
+    writer: ColumnWriter<'a>,
     best_indent: usize,
+    key_spacing: usize,
+    align_values: Option<usize>,
     compact: bool,
     multiline_strings: bool,
+    explicit_start: bool,
+    explicit_end: bool,
+    trailing_newline: bool,
+    indent_sequences: bool,
+    key_order: KeyOrder,
+    quote_style: QuoteStyle,
+    ascii_only: bool,
+    binary_strings: bool,
+    fold_width: Option<usize>,
+    conservative_folding: bool,
+    quote_yaml11_keys: bool,
+    flow_width: Option<usize>,
+    flow_scalar_collections: bool,
+    max_line_width: Option<usize>,
+    null_style: NullStyle,
+    float_format: FloatFormat,
+    canonical: bool,
+    truncate_strings: Option<usize>,
+    anchor_namer: Option<&'a mut dyn AnchorNamer>,
+    number_formatter: Option<&'a mut dyn NumberFormatter>,
+    key_comments: Option<&'a KeyComments>,
+    directives: Option<&'a Directives>,
+    inline_root_scalars: bool,
+    empty_null_documents: bool,
+    // whether the output ends with a literal whose text ends with a line break
+    open_literal: bool,
+    // whether the output ends with a `---` whose line the root may continue
+    open_marker: bool,
+    // the node being written, for errors
+    path: Path,
 
-    level: isize,
+    // column of the entries of the block collection being emitted, -1 at the document root
+    indent: isize,
 }
 
 pub type EmitResult = Result<(), EmitError>;
 
 // from serialize::json
-fn escape_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
+pub(crate) fn escape_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
+    escape_str_with(wr, v, false)
+}
+
+/// `tag` with its characters other than ASCII percent-encoded, which the scanner
+/// decodes when reading it back.
+fn ascii_tag(tag: &str) -> Cow<'_, str> {
+    if tag.is_ascii() {
+        return Cow::Borrowed(tag);
+    }
+    let mut escaped = String::with_capacity(tag.len() * 3);
+    for c in tag.chars() {
+        if c.is_ascii() {
+            escaped.push(c);
+        } else {
+            for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                escaped.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Fail on the first character of `text` that is not ASCII, see `EmitError::NotAscii`.
+fn check_ascii_text(text: &str) -> EmitResult {
+    match text.chars().find(|c| !c.is_ascii()) {
+        Some(c) => Err(EmitError::NotAscii(c)),
+        None => Ok(()),
+    }
+}
+
+/// Write `v` in double quotes, escaping the characters other than ASCII too when
+/// `ascii_only` is set.
+fn escape_str_with(wr: &mut dyn fmt::Write, v: &str, ascii_only: bool) -> fmt::Result {
     wr.write_str("\"")?;
 
     let mut start = 0;
+    let mut code = String::new();
 
-    for (i, byte) in v.bytes().enumerate() {
-        let escaped = match byte {
-            b'"' => "\\\"",
-            b'\\' => "\\\\",
-            b'\x00' => "\\u0000",
-            b'\x01' => "\\u0001",
-            b'\x02' => "\\u0002",
-            b'\x03' => "\\u0003",
-            b'\x04' => "\\u0004",
-            b'\x05' => "\\u0005",
-            b'\x06' => "\\u0006",
-            b'\x07' => "\\u0007",
-            b'\x08' => "\\b",
-            b'\t' => "\\t",
-            b'\n' => "\\n",
-            b'\x0b' => "\\u000b",
-            b'\x0c' => "\\f",
-            b'\r' => "\\r",
-            b'\x0e' => "\\u000e",
-            b'\x0f' => "\\u000f",
-            b'\x10' => "\\u0010",
-            b'\x11' => "\\u0011",
-            b'\x12' => "\\u0012",
-            b'\x13' => "\\u0013",
-            b'\x14' => "\\u0014",
-            b'\x15' => "\\u0015",
-            b'\x16' => "\\u0016",
-            b'\x17' => "\\u0017",
-            b'\x18' => "\\u0018",
-            b'\x19' => "\\u0019",
-            b'\x1a' => "\\u001a",
-            b'\x1b' => "\\u001b",
-            b'\x1c' => "\\u001c",
-            b'\x1d' => "\\u001d",
-            b'\x1e' => "\\u001e",
-            b'\x1f' => "\\u001f",
-            b'\x7f' => "\\u007f",
+    for (i, c) in v.char_indices() {
+        let escaped = match c {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\x00' => "\\u0000",
+            '\x01' => "\\u0001",
+            '\x02' => "\\u0002",
+            '\x03' => "\\u0003",
+            '\x04' => "\\u0004",
+            '\x05' => "\\u0005",
+            '\x06' => "\\u0006",
+            '\x07' => "\\u0007",
+            '\x08' => "\\b",
+            '\t' => "\\t",
+            '\n' => "\\n",
+            '\x0b' => "\\u000b",
+            '\x0c' => "\\f",
+            '\r' => "\\r",
+            '\x0e' => "\\u000e",
+            '\x0f' => "\\u000f",
+            '\x10' => "\\u0010",
+            '\x11' => "\\u0011",
+            '\x12' => "\\u0012",
+            '\x13' => "\\u0013",
+            '\x14' => "\\u0014",
+            '\x15' => "\\u0015",
+            '\x16' => "\\u0016",
+            '\x17' => "\\u0017",
+            '\x18' => "\\u0018",
+            '\x19' => "\\u0019",
+            '\x1a' => "\\u001a",
+            '\x1b' => "\\u001b",
+            '\x1c' => "\\u001c",
+            '\x1d' => "\\u001d",
+            '\x1e' => "\\u001e",
+            '\x1f' => "\\u001f",
+            '\x7f' => "\\u007f",
+            c if ascii_only && !c.is_ascii() => {
+                code.clear();
+                match u32::from(c) {
+                    point @ 0..=0xffff => write!(code, "\\u{:04x}", point)?,
+                    point => write!(code, "\\U{:08x}", point)?,
+                }
+                &code
+            }
             _ => continue,
         };
 
@@ -93,7 +725,7 @@ fn escape_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
 
         wr.write_str(escaped)?;
 
-        start = i + 1;
+        start = i + c.len_utf8();
     }
 
     if start != v.len() {
@@ -105,585 +737,4887 @@ fn escape_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
 }
 
 impl<'a> YamlEmitter<'a> {
-    pub fn new(writer: &'a mut dyn fmt::Write) -> YamlEmitter {
+    pub fn new(writer: &'a mut dyn fmt::Write) -> YamlEmitter<'a> {
         YamlEmitter {
-            writer,
+            writer: ColumnWriter {
+                inner: writer,
+                column: 0,
+                written: 0,
+            },
             best_indent: 2,
+            key_spacing: 0,
+            align_values: None,
             compact: true,
-            level: -1,
             multiline_strings: false,
+            explicit_start: true,
+            explicit_end: false,
+            trailing_newline: false,
+            indent_sequences: true,
+            key_order: KeyOrder::Input,
+            quote_style: QuoteStyle::DoubleWhenNeeded,
+            ascii_only: false,
+            binary_strings: false,
+            fold_width: None,
+            conservative_folding: false,
+            quote_yaml11_keys: false,
+            flow_width: None,
+            flow_scalar_collections: false,
+            max_line_width: None,
+            null_style: NullStyle::Tilde,
+            float_format: FloatFormat::new(),
+            canonical: false,
+            truncate_strings: None,
+            anchor_namer: None,
+            number_formatter: None,
+            key_comments: None,
+            directives: None,
+            inline_root_scalars: false,
+            empty_null_documents: false,
+            open_literal: false,
+            open_marker: false,
+            path: Path::new(),
+            indent: -1,
         }
     }
 
-    /// Set 'compact inline notation' on or off, as described for block
-    /// [sequences](http://www.yaml.org/spec/1.2/spec.html#id2797382)
-    /// and
-    /// [mappings](http://www.yaml.org/spec/1.2/spec.html#id2798057).
-    ///
-    /// In this form, blocks cannot have any properties (such as anchors
-    /// or tags), which should be OK, because this emitter doesn't
-    /// (currently) emit those anyways.
-    pub fn compact(&mut self, compact: bool) {
-        self.compact = compact;
-    }
-
-    /// Determine if this emitter is using 'compact inline notation'.
-    pub fn is_compact(&self) -> bool {
-        self.compact
-    }
-
-    /// Render strings containing multiple lines in [literal style].
+    /// Reproduce the default output of another YAML library, see [`EmitProfile`].
     ///
     /// # Examples
     ///
     /// ```rust
-    /// use yaml_rust_formatter::{YamlOutput, YamlEmitter, YamlLoader};
+    /// use yaml_rust_formatter::{EmitProfile, YamlEmitter, YamlLoader};
     ///
-    /// let input = r#"{foo: "bar!\nbar!", baz: 42}"#;
-    /// let parsed = YamlLoader::load_from_str(input).unwrap();
-    /// eprintln!("{:?}", parsed);
+    /// let parsed = YamlLoader::load_from_str("{b: [1, 2], a: 'true'}").unwrap();
     /// let yaml = parsed[0].clone().into();
     /// let mut output = String::new();
     /// # {
     /// let mut emitter = YamlEmitter::new(&mut output);
-    /// emitter.multiline_strings(true);
+    /// emitter.profile(EmitProfile::PyYamlSafeDump);
     /// emitter.dump(&yaml).unwrap();
     /// # }
     ///
-    /// assert_eq!(output.as_str(), "\
-    /// ---
-    /// foo: |
-    ///   bar!
-    ///   bar!
-    /// baz: 42");
+    /// assert_eq!(output, "a: 'true'\nb:\n- 1\n- 2\n");
     /// ```
+    pub fn profile(&mut self, profile: EmitProfile) {
+        let (best_indent, explicit_start, indent_sequences, sort_keys, single_quotes, null_style) =
+            match profile {
+                EmitProfile::Native => (2, true, true, false, false, NullStyle::Tilde),
+                EmitProfile::PyYamlSafeDump => (2, false, false, true, true, NullStyle::Lowercase),
+                EmitProfile::GoYamlV3 => (4, false, true, false, false, NullStyle::Lowercase),
+                EmitProfile::SerdeYaml => (2, false, false, false, true, NullStyle::Lowercase),
+            };
+        let quote_style = if single_quotes {
+            QuoteStyle::SingleWhenNeeded
+        } else {
+            QuoteStyle::DoubleWhenNeeded
+        };
+        self.best_indent = best_indent;
+        self.compact = true;
+        self.explicit_start = explicit_start;
+        self.trailing_newline = !explicit_start;
+        self.indent_sequences = indent_sequences;
+        self.key_order = if sort_keys {
+            KeyOrder::Ascending
+        } else {
+            KeyOrder::Input
+        };
+        self.quote_style = quote_style;
+        self.null_style = null_style;
+    }
+
+    /// Choose how nulls are written, `NullStyle::Tilde` by default. Nulls loaded with
+    /// their spelling, see `LoadOptions::keep_spelling`, keep it.
     ///
-    /// [literal style]: https://yaml.org/spec/1.2/spec.html#id2795688
-    pub fn multiline_strings(&mut self, multiline_strings: bool) {
-        self.multiline_strings = multiline_strings
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::emitter::NullStyle;
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader, YamlOutput};
+    ///
+    /// let doc: YamlOutput = YamlLoader::load_from_str("{a: ~, b: [~], c: {~: ~}}").unwrap()[0]
+    ///     .clone()
+    ///     .into();
+    /// let emit = |style: NullStyle| {
+    ///     let mut output = String::new();
+    ///     let mut emitter = YamlEmitter::new(&mut output);
+    ///     emitter.null_style(style);
+    ///     emitter.dump(&doc).unwrap();
+    ///     output
+    /// };
+    /// assert_eq!(emit(NullStyle::Capitalized), "---\na: Null\nb:\n  - Null\nc:\n  Null: Null");
+    /// assert_eq!(emit(NullStyle::Empty), "---\na:\nb:\n  -\nc:\n  null:");
+    /// ```
+    pub fn null_style(&mut self, null_style: NullStyle) {
+        self.null_style = null_style;
     }
 
-    /// Determine if this emitter will emit multiline strings when appropriate.
-    pub fn is_multiline_strings(&self) -> bool {
-        self.multiline_strings
+    pub fn get_null_style(&self) -> NullStyle {
+        self.null_style
     }
 
-    pub fn dump(&mut self, doc: &YamlOutput) -> EmitResult {
-        // write DocumentStart
-        writeln!(self.writer, "---")?;
-        self.level = -1;
-        self.emit_node(doc)
+    /// Choose how reals are written, as they are spelled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::emitter::FloatFormat;
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let parsed = YamlLoader::load_from_str("[1.50, 2.0e3, .Inf, 0.000012, 3.]").unwrap();
+    /// let yaml = parsed[0].clone().into();
+    /// let emit = |format| {
+    ///     let mut output = String::new();
+    ///     let mut emitter = YamlEmitter::new(&mut output);
+    ///     emitter.float_format(format);
+    ///     emitter.dump(&yaml).unwrap();
+    ///     output
+    /// };
+    ///
+    /// let mut format = FloatFormat::new();
+    /// assert_eq!(emit(format), "---\n- 1.50\n- 2.0e3\n- .Inf\n- 0.000012\n- 3.");
+    /// format.canonical_specials(true);
+    /// assert_eq!(emit(format), "---\n- 1.50\n- 2.0e3\n- .inf\n- 0.000012\n- 3.");
+    /// format.preserve_source(false);
+    /// assert_eq!(emit(format), "---\n- 1.5\n- 2000.0\n- .inf\n- 0.000012\n- 3.0");
+    /// format.precision(Some(2));
+    /// format.exponent_threshold(Some(3));
+    /// assert_eq!(emit(format), "---\n- 1.50\n- 2.00e3\n- .inf\n- 1.20e-5\n- 3.00");
+    /// ```
+    pub fn float_format(&mut self, float_format: FloatFormat) {
+        self.float_format = float_format;
     }
 
-    fn write_indent(&mut self) -> EmitResult {
-        if self.level <= 0 {
-            return Ok(());
-        }
-        for _ in 0..self.level {
-            for _ in 0..self.best_indent {
-                write!(self.writer, " ")?;
-            }
-        }
-        Ok(())
+    pub fn get_float_format(&self) -> FloatFormat {
+        self.float_format
     }
 
-    fn emit_node(&mut self, node: &YamlOutput) -> EmitResult {
-        match *node {
-            YamlOutput::Array(ref v) => self.emit_array(v),
-            YamlOutput::Hash(ref h) => self.emit_hash(h),
-            YamlOutput::String(ref v) => {
-                if self.multiline_strings && v.contains('\n') {
-                    write!(self.writer, "|")?;
-                    self.level += 1;
-                    for line in v.lines() {
-                        writeln!(self.writer)?;
-                        self.write_indent()?;
-                        // It's literal text, so don't escape special chars!
-                        write!(self.writer, "{}", line)?;
-                    }
-                    self.level -= 1;
-                } else if need_quotes(v) {
-                    escape_str(self.writer, v)?;
-                } else {
-                    write!(self.writer, "{}", v)?;
-                }
+    /// Choose how strings are quoted, `QuoteStyle::DoubleWhenNeeded` by default.
+    ///
+    /// Whatever the style, strings that would read back as something else, such as
+    /// `"012"`, `"null"`, `"%x"` or `"a: b"`, are quoted, and strings needing escapes
+    /// are double quoted. Keys are only quoted when needed, in the quotes of the style.
+    /// Block scalars chosen by `multiline_strings` or `fold_width` come first.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::emitter::QuoteStyle;
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let parsed = YamlLoader::load_from_str("{a: x, b: '1', c: \"it's: y\"}").unwrap();
+    /// let yaml = parsed[0].clone().into();
+    /// let emit = |style| {
+    ///     let mut output = String::new();
+    ///     let mut emitter = YamlEmitter::new(&mut output);
+    ///     emitter.quote_style(style);
+    ///     emitter.dump(&yaml).unwrap();
+    ///     output
+    /// };
+    ///
+    /// assert_eq!(emit(QuoteStyle::Plain), "---\na: x\nb: '1'\nc: \"it's: y\"");
+    /// assert_eq!(emit(QuoteStyle::SingleWhenNeeded), "---\na: x\nb: '1'\nc: 'it''s: y'");
+    /// assert_eq!(emit(QuoteStyle::AlwaysDouble), "---\na: \"x\"\nb: \"1\"\nc: \"it's: y\"");
+    /// ```
+    pub fn quote_style(&mut self, quote_style: QuoteStyle) {
+        self.quote_style = quote_style;
+    }
 
-                Ok(())
-            }
-            YamlOutput::Boolean(v) => {
+    pub fn get_quote_style(&self) -> QuoteStyle {
+        self.quote_style
+    }
+
+    /// Write the strings holding characters other than ASCII in double quotes, with
+    /// those characters escaped as `\uXXXX` or `\UXXXXXXXX`, so that the output is 7-bit
+    /// clean. Off by default.
+    ///
+    /// Tags get those characters percent-encoded. Comments, anchors and aliases cannot
+    /// escape them, so writing a node with one of them there fails with
+    /// `EmitError::NotAscii`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let parsed = YamlLoader::load_from_str("café: crème brûlée\nemoji: '🦀'\nplain: ascii").unwrap();
+    /// let yaml = parsed[0].clone().into();
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.ascii_only(true);
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(
+    ///     output,
+    ///     "---\n\"caf\\u00e9\": \"cr\\u00e8me br\\u00fbl\\u00e9e\"\nemoji: \"\\U0001f980\"\nplain: ascii"
+    /// );
+    /// assert_eq!(YamlLoader::load_from_str(&output).unwrap(), parsed);
+    /// ```
+    pub fn ascii_only(&mut self, ascii_only: bool) {
+        self.ascii_only = ascii_only;
+    }
+
+    pub fn is_ascii_only(&self) -> bool {
+        self.ascii_only
+    }
+
+    /// Emit the entries of mappings in input order, see [`Entries`](crate::yaml::Entries).
+    /// On by default; when off they are sorted by key, as with
+    /// `sort_keys(KeyOrder::Ascending)`. Only the `PyYamlSafeDump` profile turns it off.
+    pub fn preserve_input_order(&mut self, preserve_input_order: bool) {
+        self.key_order = if preserve_input_order {
+            KeyOrder::Input
+        } else {
+            KeyOrder::Ascending
+        };
+    }
+
+    /// Determine if this emitter keeps mappings in input order.
+    pub fn is_preserve_input_order(&self) -> bool {
+        matches!(self.key_order, KeyOrder::Input)
+    }
+
+    /// The order in which the entries of every mapping are emitted, so that generated
+    /// documents come out the same whatever order their keys were inserted in.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::emitter::KeyOrder;
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader, YamlOutput};
+    ///
+    /// let doc: YamlOutput = YamlLoader::load_from_str("{bb: 1, c: 2, a: 3}").unwrap()[0]
+    ///     .clone()
+    ///     .into();
+    /// let emit = |order: KeyOrder| {
+    ///     let mut output = String::new();
+    ///     let mut emitter = YamlEmitter::new(&mut output);
+    ///     emitter.sort_keys(order);
+    ///     emitter.dump(&doc).unwrap();
+    ///     output
+    /// };
+    /// assert_eq!(emit(KeyOrder::Ascending), "---\na: 3\nbb: 1\nc: 2");
+    /// assert_eq!(emit(KeyOrder::Descending), "---\nc: 2\nbb: 1\na: 3");
+    /// let by_length = |a: &YamlOutput, b: &YamlOutput| match (a, b) {
+    ///     (YamlOutput::String(a), YamlOutput::String(b)) => a.len().cmp(&b.len()),
+    ///     _ => a.cmp(b),
+    /// };
+    /// assert_eq!(emit(KeyOrder::Custom(by_length)), "---\nc: 2\na: 3\nbb: 1");
+    /// ```
+    pub fn sort_keys(&mut self, key_order: KeyOrder) {
+        self.key_order = key_order;
+    }
+
+    pub fn get_sort_keys(&self) -> KeyOrder {
+        self.key_order
+    }
+
+    /// Set 'compact inline notation' on or off, as described for block
+    /// [sequences](http://www.yaml.org/spec/1.2/spec.html#id2797382)
+    /// and
+    /// [mappings](http://www.yaml.org/spec/1.2/spec.html#id2798057).
+    ///
+    /// In this form, blocks cannot have any properties (such as anchors
+    /// or tags), which should be OK, because this emitter doesn't
+    /// (currently) emit those anyways.
+    pub fn compact(&mut self, compact: bool) {
+        self.compact = compact;
+    }
+
+    /// Determine if this emitter is using 'compact inline notation'.
+    pub fn is_compact(&self) -> bool {
+        self.compact
+    }
+
+    /// Indent nested block collections by `indent` spaces, 2 by default.
+    ///
+    /// # Panics
+    ///
+    /// When `indent` is not between 1 and 9, the indentations a block scalar can state.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let parsed = YamlLoader::load_from_str("spec: {ports: [80]}").unwrap();
+    /// let yaml = parsed[0].clone().into();
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.indent(4);
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\nspec:\n    ports:\n        - 80");
+    /// ```
+    pub fn indent(&mut self, indent: usize) {
+        assert!(
+            (1..=9).contains(&indent),
+            "indent {} is not between 1 and 9",
+            indent
+        );
+        self.best_indent = indent;
+    }
+
+    pub fn get_indent(&self) -> usize {
+        self.best_indent
+    }
+
+    /// Indent the block sequences held by mappings under their key, `true` by default.
+    /// Otherwise their dashes are flush with the key, as in Kubernetes manifests.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let parsed = YamlLoader::load_from_str("spec: {ports: [80]}").unwrap();
+    /// let yaml = parsed[0].clone().into();
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.indent_sequences(false);
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\nspec:\n  ports:\n  - 80");
+    /// ```
+    pub fn indent_sequences(&mut self, indent_sequences: bool) {
+        self.indent_sequences = indent_sequences;
+    }
+
+    pub fn is_indent_sequences(&self) -> bool {
+        self.indent_sequences
+    }
+
+    /// Write `lines` blank lines between the entries of a mapping at the root of a
+    /// document, before the comments of an entry. None by default. Canonical and
+    /// streamed output are not spaced.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let parsed = YamlLoader::load_from_str("a: {b: 1, c: 2}\nd: 3").unwrap();
+    /// let yaml = parsed[0].clone().into();
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.key_spacing(1);
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\na:\n  b: 1\n  c: 2\n\nd: 3");
+    /// ```
+    pub fn key_spacing(&mut self, lines: usize) {
+        self.key_spacing = lines;
+    }
+
+    pub fn get_key_spacing(&self) -> usize {
+        self.key_spacing
+    }
+
+    /// Pad the values of block mappings after their `:` so that they start on the same
+    /// column, by groups of at most `group` entries in a row. The entries holding a
+    /// block collection or a complex key, and the blank lines before an entry, end a
+    /// group. None by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let parsed = YamlLoader::load_from_str("
+    /// host: localhost
+    /// port: 5432
+    /// database: app
+    /// pool: {min: 1, max: 4}
+    /// log_level: info
+    /// ").unwrap();
+    /// let yaml = parsed[0].clone().into();
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.align_values(Some(10));
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---
+    /// host:     localhost
+    /// port:     5432
+    /// database: app
+    /// pool:
+    ///   min: 1
+    ///   max: 4
+    /// log_level: info");
+    /// ```
+    pub fn align_values(&mut self, group: Option<usize>) {
+        self.align_values = group;
+    }
+
+    pub fn get_align_values(&self) -> Option<usize> {
+        self.align_values
+    }
+
+    /// Render strings containing multiple lines in [literal style].
+    ///
+    /// The header of the block gets the chomping indicator that reproduces the
+    /// trailing line breaks of the string: `|-` when there is none, `|` for one and
+    /// `|+` for more. Strings with characters that need escapes stay quoted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlOutput, YamlEmitter, YamlLoader};
+    ///
+    /// let input = r#"{foo: "bar!\nbar!", baz: 42}"#;
+    /// let parsed = YamlLoader::load_from_str(input).unwrap();
+    /// eprintln!("{:?}", parsed);
+    /// let yaml = parsed[0].clone().into();
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.multiline_strings(true);
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output.as_str(), "\
+    /// ---
+    /// foo: |-
+    ///   bar!
+    ///   bar!
+    /// baz: 42");
+    /// ```
+    ///
+    /// [literal style]: https://yaml.org/spec/1.2/spec.html#id2795688
+    pub fn multiline_strings(&mut self, multiline_strings: bool) {
+        self.multiline_strings = multiline_strings
+    }
+
+    /// Determine if this emitter will emit multiline strings when appropriate.
+    pub fn is_multiline_strings(&self) -> bool {
+        self.multiline_strings
+    }
+
+    /// Emit strings containing characters that YAML cannot represent
+    /// unescaped (such as NUL or other control characters) as `!!binary`
+    /// scalars holding their base64 encoded UTF-8 bytes, instead of as
+    /// escaped double-quoted strings.
+    ///
+    /// Some stricter consumers reject documents containing such escapes.
+    /// Note that reloading the document yields the encoded bytes rather than
+    /// the original string.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlOutput, YamlEmitter};
+    ///
+    /// let yaml = YamlOutput::String("bell\x07".to_owned());
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.binary_strings(true);
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\n!!binary YmVsbAc=");
+    /// ```
+    pub fn binary_strings(&mut self, binary_strings: bool) {
+        self.binary_strings = binary_strings;
+    }
+
+    /// Determine if this emitter writes non-printable strings as `!!binary`.
+    pub fn is_binary_strings(&self) -> bool {
+        self.binary_strings
+    }
+
+    /// Render strings wider than `width` columns in [folded style], broken into
+    /// lines of about `width` columns. Only strings made of a single paragraph, with
+    /// at most a final newline, are folded. Off by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlOutput, YamlEmitter};
+    ///
+    /// let yaml = YamlOutput::String("The quick brown fox jumps over the lazy dog".to_owned());
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.fold_width(Some(20));
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\n>-\n  The quick brown fox\n  jumps over the lazy\n  dog");
+    /// ```
+    ///
+    /// [folded style]: https://yaml.org/spec/1.2/spec.html#id2796251
+    pub fn fold_width(&mut self, width: Option<usize>) {
+        self.fold_width = width;
+    }
+
+    pub fn get_fold_width(&self) -> Option<usize> {
+        self.fold_width
+    }
+
+    /// Keep on one line the strings holding a URL or a token, such as a hash or an
+    /// API key, instead of folding them for `fold_width` or breaking them for
+    /// `max_line_width`, so that they can be found and copied from the output as they
+    /// are. Off by default.
+    ///
+    /// Lines are never broken inside a word, a grapheme cluster or a quoted scalar,
+    /// whatever this option.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlOutput};
+    ///
+    /// let yaml = YamlOutput::from("Download it from https://example.com/release.tar.gz today");
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.fold_width(Some(20));
+    /// emitter.conservative_folding(true);
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\n\"Download it from https://example.com/release.tar.gz today\"");
+    /// ```
+    pub fn conservative_folding(&mut self, conservative_folding: bool) {
+        self.conservative_folding = conservative_folding;
+    }
+
+    /// Determine if strings holding URLs or tokens are kept on one line.
+    pub fn is_conservative_folding(&self) -> bool {
+        self.conservative_folding
+    }
+
+    /// Also quote the string keys that a YAML 1.1 reader, such as PyYAML or go-yaml v2,
+    /// would take for something else than a string: `y` and `n`, binary numbers like
+    /// `0b101` and numbers with `_` separators like `1_000`. Off by default.
+    ///
+    /// String keys that this crate would read back as another type, such as `01234` or
+    /// `0x1F`, are always quoted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let parsed = YamlLoader::load_from_str("{'0x1F': a, '1_000': b, 'y': c}").unwrap();
+    /// let yaml = parsed[0].clone().into();
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.quote_yaml11_keys(true);
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\n\"0x1F\": a\n\"1_000\": b\n\"y\": c");
+    /// ```
+    pub fn quote_yaml11_keys(&mut self, quote_yaml11_keys: bool) {
+        self.quote_yaml11_keys = quote_yaml11_keys;
+    }
+
+    /// Determine if this emitter quotes the keys that YAML 1.1 reads as non-strings.
+    pub fn is_quote_yaml11_keys(&self) -> bool {
+        self.quote_yaml11_keys
+    }
+
+    /// Render sequences and mappings in [flow style], like `[1, 2]` or `{a: 1}`, when
+    /// that takes at most `width` columns. Collections holding comments or
+    /// collection keys stay in block style. Off by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let parsed = YamlLoader::load_from_str("{point: {x: 1, y: 2}, tags: [a, b, c, d]}").unwrap();
+    /// let yaml = parsed[0].clone().into();
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.flow_width(Some(12));
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\npoint: {x: 1, y: 2}\ntags: [a, b, c, d]");
+    /// ```
+    ///
+    /// [flow style]: https://yaml.org/spec/1.2/spec.html#id2790088
+    pub fn flow_width(&mut self, width: Option<usize>) {
+        self.flow_width = width;
+    }
+
+    pub fn get_flow_width(&self) -> Option<usize> {
+        self.flow_width
+    }
+
+    /// Render sequences and mappings whose entries are all scalars in flow style,
+    /// whatever their length. A matrix of numbers then takes one line per row.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let parsed = YamlLoader::load_from_str("matrix: [[1, 0], [0, 1]]").unwrap();
+    /// let yaml = parsed[0].clone().into();
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.flow_scalar_collections(true);
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\nmatrix:\n  - [1, 0]\n  - [0, 1]");
+    /// ```
+    pub fn flow_scalar_collections(&mut self, flow_scalar_collections: bool) {
+        self.flow_scalar_collections = flow_scalar_collections;
+    }
+
+    /// Determine if this emitter writes collections of scalars in flow style.
+    pub fn is_flow_scalar_collections(&self) -> bool {
+        self.flow_scalar_collections
+    }
+
+    /// Break long plain scalars and flow collections across lines so that, where
+    /// they have a space to break at, lines stay within `width` columns. The
+    /// continuation lines are indented below their key or indicator, where reading
+    /// them back folds each line break into the space it replaced. Keys, quoted and
+    /// block scalars are never broken. Off by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let parsed = YamlLoader::load_from_str("
+    /// motd: Welcome to the build server please be nice
+    /// users: [alice, bob, carol, dave]
+    /// ").unwrap();
+    /// let yaml = parsed[0].clone().into();
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.flow_scalar_collections(true);
+    /// emitter.max_line_width(Some(24));
+    /// emitter.dump(&yaml).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---
+    /// motd: Welcome to the
+    ///   build server please be
+    ///   nice
+    /// users: [alice, bob,
+    ///   carol, dave]");
+    /// assert_eq!(YamlLoader::load_from_str(&output).unwrap(), parsed);
+    /// ```
+    pub fn max_line_width(&mut self, width: Option<usize>) {
+        self.max_line_width = width;
+    }
+
+    pub fn get_max_line_width(&self) -> Option<usize> {
+        self.max_line_width
+    }
+
+    /// Write documents in canonical form, like `yaml --canonical`: every node has its
+    /// explicit tag, every scalar is double quoted and every collection is in flow
+    /// style, one entry per line. Two documents with the same content are then written
+    /// the same, whatever their style, comments or spelling, which helps comparing
+    /// them byte for byte. Anchors, aliases and custom tags are kept; the other
+    /// options about styles are ignored. Documents written with `stream` are not
+    /// affected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let emit = |source| {
+    ///     let doc = YamlLoader::load_from_str(source).unwrap()[0].clone().into();
+    ///     let mut output = String::new();
+    ///     let mut emitter = YamlEmitter::new(&mut output);
+    ///     emitter.canonical(true);
+    ///     emitter.dump(&doc).unwrap();
+    ///     output
+    /// };
+    ///
+    /// let output = emit("a: 0x1F # mask\nb: [x, ~]\n");
+    /// assert_eq!(output, emit("{'a': 31, \"b\": [\"x\", null]}"));
+    /// assert_eq!(
+    ///     output,
+    ///     r#"---
+    /// !!map {
+    ///   ? !!str "a"
+    ///   : !!int "31",
+    ///   ? !!str "b"
+    ///   : !!seq [
+    ///     !!str "x",
+    ///     !!null "",
+    ///   ],
+    /// }"#
+    /// );
+    /// ```
+    pub fn canonical(&mut self, canonical: bool) {
+        self.canonical = canonical;
+    }
+
+    /// Determine if documents are written in canonical form.
+    pub fn is_canonical(&self) -> bool {
+        self.canonical
+    }
+
+    /// Cut string values longer than `limit` bytes, to preview large documents. A cut
+    /// string ends at a character boundary and is marked by a trailing comment giving
+    /// its full length, unless documents are written in canonical form, which has no
+    /// comments. Keys are never cut, so mappings keep their entries apart.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlOutput};
+    ///
+    /// let doc = YamlOutput::Array(vec![
+    ///     YamlOutput::String("short".to_owned()),
+    ///     YamlOutput::String("x".repeat(1000)),
+    /// ]);
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.truncate_strings(Some(8));
+    /// emitter.dump(&doc).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\n- short\n- xxxxxxxx # truncated, 8 of 1000 bytes");
+    /// ```
+    pub fn truncate_strings(&mut self, limit: Option<usize>) {
+        self.truncate_strings = limit;
+    }
+
+    pub fn get_truncate_strings(&self) -> Option<usize> {
+        self.truncate_strings
+    }
+
+    /// Write `directives` before every document from now on, followed by `---` even
+    /// without `explicit_start`. Set them again before the next document, or
+    /// [`StreamBuilder::directives`] when writing a stream, to change them. A document
+    /// after the first one of a stream is then ended with `...`, as YAML requires.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::emitter::Directives;
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader, YamlOutput};
+    ///
+    /// let docs = [YamlOutput::Integer(1), YamlOutput::Integer(2)];
+    /// let mut directives = Directives::new();
+    /// directives.yaml_version(Some((1, 2)));
+    /// directives.tag("!e!", "tag:example.com,2000:");
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.explicit_start(false);
+    /// emitter.directives(&directives);
+    /// emitter.dump_all(&docs).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(
+    ///     output,
+    ///     "%YAML 1.2\n%TAG !e! tag:example.com,2000:\n---\n1\n...\n%YAML 1.2\n%TAG !e! tag:example.com,2000:\n---\n2"
+    /// );
+    /// assert_eq!(YamlLoader::load_from_str(&output).unwrap().len(), 2);
+    /// ```
+    pub fn directives(&mut self, directives: &'a Directives) {
+        self.directives = Some(directives);
+    }
+
+    /// Determine if documents are written with directives.
+    fn has_directives(&self) -> bool {
+        self.directives
+            .is_some_and(|directives| !directives.is_empty())
+    }
+
+    /// Write the directives, if any, and the `---` that must follow them unless it is
+    /// written anyway.
+    fn write_directives(&mut self) -> EmitResult {
+        let directives = match self.directives {
+            Some(directives) if !directives.is_empty() => directives,
+            _ => return Ok(()),
+        };
+        if let Some((major, minor)) = directives.yaml_version {
+            writeln!(self.writer, "%YAML {}.{}", major, minor)?;
+        }
+        for (handle, prefix) in &directives.tags {
+            writeln!(self.writer, "%TAG {} {}", handle, prefix)?;
+        }
+        if !self.explicit_start {
+            self.write_marker()?;
+        }
+        Ok(())
+    }
+
+    /// Write the `---` starting a document, leaving its line open for the root.
+    fn write_marker(&mut self) -> EmitResult {
+        write!(self.writer, "---")?;
+        self.open_marker = true;
+        Ok(())
+    }
+
+    /// Start writing `root`, the root of a document or `None` for a collection, after
+    /// the `---` if any. Returns false if the root is left out of an empty document.
+    fn start_root(&mut self, root: Option<&YamlOutput>) -> Result<bool, EmitError> {
+        if self.empty_null_documents && root == Some(&YamlOutput::Null) {
+            if !self.open_marker {
+                // without its marker, an empty document is no document at all
+                self.write_marker()?;
+            }
+            self.open_marker = false;
+            return Ok(false);
+        }
+        if self.open_marker {
+            if self.inline_root_scalars && root.is_some_and(is_scalar) {
+                write!(self.writer, " ")?;
+            } else {
+                writeln!(self.writer)?;
+            }
+            self.open_marker = false;
+        }
+        Ok(true)
+    }
+
+    /// Separate the next document of a stream from the one before.
+    fn write_document_separator(&mut self) -> EmitResult {
+        if !self.trailing_newline {
+            writeln!(self.writer)?;
+        }
+        if self.has_directives() {
+            // directives may only follow a document that is ended
+            if !self.explicit_end {
+                writeln!(self.writer, "...")?;
+            }
+        } else if !self.explicit_start {
+            // documents after the first one always need their marker
+            self.write_marker()?;
+        }
+        Ok(())
+    }
+
+    /// Start every document with a `---` line, the default. Without it a single
+    /// document is written as a bare fragment; the documents after the first one of a
+    /// stream still start with `---`, which separates them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlOutput};
+    ///
+    /// let docs = [YamlOutput::Integer(1), YamlOutput::Integer(2)];
+    /// let emit = |explicit_start, explicit_end| {
+    ///     let mut output = String::new();
+    ///     let mut emitter = YamlEmitter::new(&mut output);
+    ///     emitter.explicit_start(explicit_start);
+    ///     emitter.explicit_end(explicit_end);
+    ///     emitter.dump_all(&docs).unwrap();
+    ///     output
+    /// };
+    ///
+    /// assert_eq!(emit(true, false), "---\n1\n---\n2");
+    /// assert_eq!(emit(false, false), "1\n---\n2");
+    /// assert_eq!(emit(true, true), "---\n1\n...\n---\n2\n...");
+    /// ```
+    pub fn explicit_start(&mut self, explicit_start: bool) {
+        self.explicit_start = explicit_start;
+    }
+
+    /// Determine if this emitter starts documents with `---`.
+    pub fn is_explicit_start(&self) -> bool {
+        self.explicit_start
+    }
+
+    /// End every document with a `...` line.
+    pub fn explicit_end(&mut self, explicit_end: bool) {
+        self.explicit_end = explicit_end;
+    }
+
+    /// Determine if this emitter ends documents with `...`.
+    pub fn is_explicit_end(&self) -> bool {
+        self.explicit_end
+    }
+
+    /// Write a document whose root is a scalar on the line of its `---`, like
+    /// `--- value` or `--- |`, rather than on the next line. A document written without
+    /// `---` is not changed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlOutput};
+    ///
+    /// let docs = [
+    ///     YamlOutput::String("value".to_owned()),
+    ///     YamlOutput::Null,
+    ///     YamlOutput::Array(vec![YamlOutput::Integer(1)]),
+    /// ];
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.inline_root_scalars(true);
+    /// emitter.dump_all(&docs).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "--- value\n--- ~\n---\n- 1");
+    /// ```
+    pub fn inline_root_scalars(&mut self, inline_root_scalars: bool) {
+        self.inline_root_scalars = inline_root_scalars;
+    }
+
+    /// Determine if scalar roots are written on the line of their `---`.
+    pub fn is_inline_root_scalars(&self) -> bool {
+        self.inline_root_scalars
+    }
+
+    /// Write a document whose root is a null as an empty document, a bare `---`,
+    /// rather than with the text of the null. Such a document always starts with
+    /// `---`, even without `explicit_start`, since there would be no document without it.
+    /// A null with an anchor, a tag or comments is still written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader, YamlOutput};
+    ///
+    /// let docs = [YamlOutput::Null, YamlOutput::Integer(1), YamlOutput::Null];
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.explicit_start(false);
+    /// emitter.empty_null_documents(true);
+    /// emitter.dump_all(&docs).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\n---\n1\n---");
+    /// let loaded = YamlLoader::load_from_str(&output).unwrap();
+    /// assert_eq!(loaded.into_iter().map(YamlOutput::from).collect::<Vec<_>>(), docs);
+    /// ```
+    pub fn empty_null_documents(&mut self, empty_null_documents: bool) {
+        self.empty_null_documents = empty_null_documents;
+    }
+
+    /// Determine if documents whose root is a null are written empty.
+    pub fn is_empty_null_documents(&self) -> bool {
+        self.empty_null_documents
+    }
+
+    /// Write `doc`. When this fails, the error is an `EmitError::Node` telling which
+    /// node was being written and how much of the document was written already.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fmt;
+    /// use yaml_rust_formatter::{EmitError, YamlEmitter, YamlLoader, YamlOutput};
+    ///
+    /// // a writer with room for 16 bytes
+    /// struct Limited(String);
+    ///
+    /// impl fmt::Write for Limited {
+    ///     fn write_str(&mut self, s: &str) -> fmt::Result {
+    ///         if self.0.len() + s.len() > 16 {
+    ///             return Err(fmt::Error);
+    ///         }
+    ///         self.0.push_str(s);
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let doc = YamlLoader::load_from_str("spec:\n  ports: [80, 443]\n  name: web").unwrap();
+    /// let mut output = Limited(String::new());
+    /// let err = YamlEmitter::new(&mut output)
+    ///     .dump(&doc[0].clone().into())
+    ///     .unwrap_err();
+    /// match err {
+    ///     EmitError::Node(ref err) => {
+    ///         assert_eq!(err.path().to_string(), "spec.ports");
+    ///         assert_eq!(err.written(), output.0.len());
+    ///     }
+    ///     _ => unreachable!(),
+    /// }
+    /// assert!(err.to_string().ends_with("at spec.ports, after 12 bytes"));
+    /// ```
+    pub fn dump(&mut self, doc: &YamlOutput) -> EmitResult {
+        let start = self.writer.written;
+        self.path = Path::new();
+        match self.dump_document(doc) {
+            Ok(()) => {
+                metrics::emitted(self.writer.written - start);
+                Ok(())
+            }
+            Err(error) => {
+                metrics::failed("emit");
+                Err(EmitError::Node(Box::new(NodeError {
+                    path: std::mem::take(&mut self.path),
+                    written: self.writer.written - start,
+                    error,
+                })))
+            }
+        }
+    }
+
+    fn dump_document(&mut self, doc: &YamlOutput) -> EmitResult {
+        self.write_directives()?;
+        if self.explicit_start {
+            // write DocumentStart
+            self.write_marker()?;
+        }
+        self.indent = -1;
+        self.start_anchor_names();
+        let doc = self.rename_anchors(doc, &mut HashMap::new());
+        let doc = self.format_numbers(&doc, None);
+        let doc = match self.truncate_strings {
+            Some(limit) => Cow::Owned(truncate_strings(&doc, limit)),
+            None => doc,
+        };
+        let doc = match self.key_comments {
+            Some(comments) => Cow::Owned(document_keys(&doc, comments, &mut Vec::new())),
+            None => doc,
+        };
+        if self.ascii_only {
+            self.check_ascii(&doc)?;
+        }
+        if self.start_root(Some(&doc))? {
+            if self.canonical {
+                self.emit_canonical(&doc, 0, false)?;
+            } else {
+                self.emit_node(&doc)?;
+            }
+        }
+        if self.open_literal && !self.trailing_newline {
+            // the last line break of a literal is part of its text
+            writeln!(self.writer)?;
+        }
+        self.open_literal = false;
+        if self.explicit_end {
+            write!(self.writer, "\n...")?;
+        }
+        if self.trailing_newline {
+            writeln!(self.writer)?;
+        }
+        Ok(())
+    }
+
+    /// Write a stream of documents, such as the ones returned by
+    /// `YamlLoader::load_from_str`, each starting with `---`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader, YamlOutput};
+    ///
+    /// let docs: Vec<YamlOutput> = YamlLoader::load_from_str("a: 1\n---\n[b]")
+    ///     .unwrap()
+    ///     .into_iter()
+    ///     .map(YamlOutput::from)
+    ///     .collect();
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.explicit_end(true);
+    /// emitter.dump_all(&docs).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\na: 1\n...\n---\n- b\n...");
+    /// ```
+    pub fn dump_all(&mut self, docs: &[YamlOutput]) -> EmitResult {
+        for (i, doc) in docs.iter().enumerate() {
+            if i > 0 {
+                self.write_document_separator()?;
+            }
+            self.dump(doc)?;
+        }
+        Ok(())
+    }
+
+    /// Write documents as they are described, node by node, instead of building their
+    /// `YamlOutput` first, see [`StreamBuilder`].
+    #[cfg(feature = "stream")]
+    pub fn stream(&mut self) -> StreamBuilder<'_, 'a> {
+        StreamBuilder {
+            emitter: self,
+            stack: Vec::new(),
+            anchor_names: HashMap::new(),
+            documents: 0,
+            root: None,
+            last_key: None,
+        }
+    }
+
+    /// Choose the names of the anchors with `namer`, see [`AnchorNamer`]. They are
+    /// written as they are named in the document otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::emitter::NumberedAnchors;
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader, YamlOutput};
+    ///
+    /// let docs = YamlLoader::load_from_str("a: &base x\nb: &other y\nc: *base").unwrap();
+    /// let doc: YamlOutput = docs[0].clone().into();
+    /// let mut output = String::new();
+    /// let mut namer = NumberedAnchors::new("anchor_");
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.anchor_namer(&mut namer);
+    /// emitter.dump(&doc).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\na: &anchor_1 x\nb: &anchor_2 y\nc: *anchor_1");
+    /// ```
+    pub fn anchor_namer(&mut self, namer: &'a mut dyn AnchorNamer) {
+        self.anchor_namer = Some(namer);
+    }
+
+    /// Write integers and reals with `formatter`, see [`NumberFormatter`]. Mapping
+    /// keys are written as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::emitter::NumberFormatter;
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader, YamlOutput};
+    ///
+    /// struct Invoice;
+    ///
+    /// impl NumberFormatter for Invoice {
+    ///     fn integer(&mut self, value: i64, key: Option<&YamlOutput>) -> Option<String> {
+    ///         match key {
+    ///             Some(YamlOutput::String(key)) if key == "id" => Some(format!("{:06}", value)),
+    ///             _ => None,
+    ///         }
+    ///     }
+    ///
+    ///     fn real(&mut self, text: &str, _key: Option<&YamlOutput>) -> Option<String> {
+    ///         Some(format!("{:.2}", text.parse::<f64>().ok()?))
+    ///     }
+    /// }
+    ///
+    /// let docs = YamlLoader::load_from_str("id: 42\nlines: 3\ntotal: 12.5").unwrap();
+    /// let doc: YamlOutput = docs[0].clone().into();
+    /// let mut output = String::new();
+    /// let mut invoice = Invoice;
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.number_formatter(&mut invoice);
+    /// emitter.dump(&doc).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output, "---\nid: 000042\nlines: 3\ntotal: 12.50");
+    /// let reloaded = &YamlLoader::load_from_str(&output).unwrap()[0];
+    /// assert_eq!(reloaded["id"].as_i64(), Some(42));
+    /// assert_eq!(reloaded["total"].as_f64(), Some(12.5));
+    /// ```
+    pub fn number_formatter(&mut self, formatter: &'a mut dyn NumberFormatter) {
+        self.number_formatter = Some(formatter);
+    }
+
+    /// Write the comments of `comments` before the entries they document, after the
+    /// comments the entries already have. Documents written with `stream` are not
+    /// affected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_formatter::emitter::KeyComments;
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader, YamlOutput};
+    ///
+    /// let docs = YamlLoader::load_from_str("server: {port: 80}\nworkers: [{name: a}, {name: b}]").unwrap();
+    /// let doc: YamlOutput = docs[0].clone().into();
+    /// let mut comments = KeyComments::new();
+    /// comments.insert("server.port", "Port to listen on.\nRequires root below 1024.");
+    /// comments.insert("workers.name", "Shown in the logs.");
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.key_comments(&comments);
+    /// emitter.dump(&doc).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(
+    ///     output,
+    ///     "---\nserver:\n  # Port to listen on.\n  # Requires root below 1024.\n  port: 80\n\
+    ///      workers:\n  - # Shown in the logs.\n    name: a\n  - # Shown in the logs.\n    name: b"
+    /// );
+    /// ```
+    pub fn key_comments(&mut self, comments: &'a KeyComments) {
+        self.key_comments = Some(comments);
+    }
+
+    /// Tell the `AnchorNamer`, if any, that a document starts.
+    fn start_anchor_names(&mut self) {
+        if let Some(ref mut namer) = self.anchor_namer {
+            namer.start_document();
+        }
+    }
+
+    /// How `tag` is written, see `ascii_only`.
+    fn tag_text<'t>(&self, tag: &'t str) -> Cow<'t, str> {
+        if self.ascii_only {
+            ascii_tag(tag)
+        } else {
+            Cow::Borrowed(tag)
+        }
+    }
+
+    /// Check that the comments, anchors and aliases of `node` are ASCII, as
+    /// `ascii_only` needs, leaving `path` at the node of the first one that is not.
+    fn check_ascii(&mut self, node: &YamlOutput) -> EmitResult {
+        match *node {
+            YamlOutput::Array(ref v) => {
+                for (i, x) in v.iter().enumerate() {
+                    self.path.push(Segment::Index(i));
+                    self.check_ascii(x)?;
+                    self.path.pop();
+                }
+            }
+            YamlOutput::Hash(ref h) => {
+                for (k, v) in h {
+                    self.path.push(Segment::Key(key_name(k)));
+                    self.check_ascii(k)?;
+                    self.check_ascii(v)?;
+                    self.path.pop();
+                }
+            }
+            YamlOutput::Anchored(ref name, ref node) => {
+                check_ascii_text(name)?;
+                self.check_ascii(node)?;
+            }
+            YamlOutput::Alias(ref name) => check_ascii_text(name)?,
+            YamlOutput::Commented(ref node, ref comments) => {
+                let lines = comments.before.iter().chain(&comments.trailing);
+                let lines = lines.chain(&comments.key_trailing).chain(&comments.after);
+                for line in lines {
+                    check_ascii_text(line)?;
+                }
+                self.check_ascii(node)?;
+            }
+            YamlOutput::Tagged(_, ref node)
+            | YamlOutput::Spelled(ref node, _)
+            | YamlOutput::Styled(ref node, _) => self.check_ascii(node)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// `node` with the anchors and aliases named by the `AnchorNamer`, if any, given
+    /// the new names of the anchors before it in `names`.
+    fn rename_anchors<'d>(
+        &mut self,
+        node: &'d YamlOutput,
+        names: &mut HashMap<String, String>,
+    ) -> Cow<'d, YamlOutput> {
+        match self.anchor_namer {
+            Some(ref mut namer) => Cow::Owned(rename_anchors(node, &mut **namer, names)),
+            None => Cow::Borrowed(node),
+        }
+    }
+
+    /// `node`, the value of `key` if any, with its numbers written by the
+    /// `NumberFormatter`, if any.
+    fn format_numbers<'d>(
+        &mut self,
+        node: &'d YamlOutput,
+        key: Option<&YamlOutput>,
+    ) -> Cow<'d, YamlOutput> {
+        match self.number_formatter {
+            Some(ref mut formatter) => Cow::Owned(format_numbers(node, &mut **formatter, key)),
+            None => Cow::Borrowed(node),
+        }
+    }
+
+    fn write_indent(&mut self) -> EmitResult {
+        self.write_indent_at(self.indent)
+    }
+
+    fn write_indent_at(&mut self, indent: isize) -> EmitResult {
+        for _ in 0..indent.max(0) {
+            write!(self.writer, " ")?;
+        }
+        Ok(())
+    }
+
+    /// The column of a block nested `step` columns below the current one.
+    /// Blocks directly below the document root start at column 0.
+    fn child_indent(&self, step: usize) -> isize {
+        if self.indent < 0 {
+            0
+        } else {
+            self.indent + step as isize
+        }
+    }
+
+    /// Write `node` in canonical form, see `canonical`, its lines indented by `indent`.
+    /// A `tagged` node has its tag written already.
+    fn emit_canonical(&mut self, node: &YamlOutput, indent: usize, tagged: bool) -> EmitResult {
+        let tag = match *node {
+            YamlOutput::Anchored(ref name, ref node) => {
+                write!(self.writer, "&{} ", name)?;
+                return self.emit_canonical(node, indent, tagged);
+            }
+            YamlOutput::Tagged(ref tag, ref node) => {
+                write!(self.writer, "{} ", self.tag_text(tag))?;
+                return self.emit_canonical(node, indent, true);
+            }
+            YamlOutput::Commented(ref node, _)
+            | YamlOutput::Spelled(ref node, _)
+            | YamlOutput::Styled(ref node, _) => {
+                return self.emit_canonical(node, indent, tagged);
+            }
+            YamlOutput::Alias(ref name) => {
+                write!(self.writer, "*{}", name)?;
+                return Ok(());
+            }
+            YamlOutput::Array(_) => "!!seq",
+            YamlOutput::Hash(_) => "!!map",
+            YamlOutput::String(_) | YamlOutput::Literal(_) => "!!str",
+            YamlOutput::Integer(_) => "!!int",
+            YamlOutput::Real(_) => "!!float",
+            YamlOutput::Boolean(_) => "!!bool",
+            YamlOutput::Binary(_) => "!!binary",
+            YamlOutput::Null | YamlOutput::BadValue => "!!null",
+        };
+        if !tagged {
+            write!(self.writer, "{} ", self.tag_text(tag))?;
+        }
+        let text = match *node {
+            YamlOutput::Array(ref v) if v.is_empty() => return Ok(write!(self.writer, "[]")?),
+            YamlOutput::Array(ref v) => {
+                writeln!(self.writer, "[")?;
+                for (i, item) in v.iter().enumerate() {
+                    self.path.push(Segment::Index(i));
+                    write!(self.writer, "{:1$}", "", indent + 2)?;
+                    self.emit_canonical(item, indent + 2, false)?;
+                    writeln!(self.writer, ",")?;
+                    self.path.pop();
+                }
+                return Ok(write!(self.writer, "{:1$}]", "", indent)?);
+            }
+            YamlOutput::Hash(ref h) if h.is_empty() => return Ok(write!(self.writer, "{{}}")?),
+            YamlOutput::Hash(ref h) => {
+                writeln!(self.writer, "{{")?;
+                for (k, v) in self.entries(h) {
+                    self.path.push(Segment::Key(key_name(k)));
+                    write!(self.writer, "{:1$}? ", "", indent + 2)?;
+                    self.emit_canonical(k, indent + 2, false)?;
+                    write!(self.writer, "\n{:1$}: ", "", indent + 2)?;
+                    self.emit_canonical(v, indent + 2, false)?;
+                    writeln!(self.writer, ",")?;
+                    self.path.pop();
+                }
+                return Ok(write!(self.writer, "{:1$}}}", "", indent)?);
+            }
+            YamlOutput::String(ref v) | YamlOutput::Literal(ref v) => Cow::Borrowed(v.as_str()),
+            YamlOutput::Integer(v) => Cow::Owned(v.to_string()),
+            YamlOutput::Real(ref v) => {
+                let mut format = FloatFormat::new();
+                format.preserve_source(false);
+                format.text(v)
+            }
+            YamlOutput::Boolean(v) => Cow::Borrowed(if v { "true" } else { "false" }),
+            YamlOutput::Binary(ref bytes) => Cow::Owned(base64::encode(bytes)),
+            _ => Cow::Borrowed(""),
+        };
+        escape_str_with(&mut self.writer, &text, self.ascii_only)?;
+        Ok(())
+    }
+
+    fn emit_node(&mut self, node: &YamlOutput) -> EmitResult {
+        self.open_literal = false;
+        if let Some((flow, points)) = self.flow(node) {
+            return self.emit_wrapped(&flow, &points);
+        }
+        match *node {
+            YamlOutput::Array(ref v) => {
+                let indent = self.child_indent(self.best_indent);
+                self.emit_array_at(indent, v)
+            }
+            YamlOutput::Hash(ref h) => {
+                let indent = self.child_indent(self.best_indent);
+                self.emit_hash_at(indent, h)
+            }
+            YamlOutput::String(ref v) => self.emit_str(v, None),
+            YamlOutput::Literal(ref v) if self.can_literal(v) => self.emit_literal(v, None),
+            YamlOutput::Literal(ref v) => self.emit_str(v, None),
+            YamlOutput::Boolean(v) => {
                 if v {
                     self.writer.write_str("true")?;
                 } else {
-                    self.writer.write_str("false")?;
+                    self.writer.write_str("false")?;
+                }
+                Ok(())
+            }
+            YamlOutput::Integer(v) => {
+                write!(self.writer, "{}", v)?;
+                Ok(())
+            }
+            YamlOutput::Real(ref v) => {
+                write!(self.writer, "{}", self.float_format.text(v))?;
+                Ok(())
+            }
+            YamlOutput::Null | YamlOutput::BadValue => {
+                write!(self.writer, "{}", self.null_style.text())?;
+                Ok(())
+            }
+            YamlOutput::Alias(ref s) => {
+                write!(self.writer, "*{}", s)?;
+                Ok(())
+            }
+            YamlOutput::Binary(ref bytes) => self.emit_binary(bytes),
+            YamlOutput::Anchored(ref s, ref data) => {
+                write!(self.writer, "&{}", s)?;
+                if self.is_empty_null(data) {
+                    // the loader reads an empty node with properties as an empty string
+                    write!(self.writer, " {}", self.null_style.text())?;
+                } else {
+                    self.emit_val(false, data)?;
+                }
+                Ok(())
+            }
+            YamlOutput::Tagged(ref tag, ref data) => {
+                write!(self.writer, "{}", self.tag_text(tag))?;
+                if self.is_empty_null(data) {
+                    // the loader reads an empty node with properties as an empty string
+                    write!(self.writer, " {}", self.null_style.text())?;
+                } else {
+                    self.emit_val(false, data)?;
+                }
+                Ok(())
+            }
+            // a line break would end the scalar written as is, and escapes need quotes
+            YamlOutput::Spelled(ref node, ref spelling)
+                if has_line_break(spelling) || self.is_escaped(spelling) =>
+            {
+                self.emit_node(node)
+            }
+            YamlOutput::Spelled(_, ref spelling) => {
+                write!(self.writer, "{}", spelling)?;
+                Ok(())
+            }
+            YamlOutput::Styled(ref node, style) => self.emit_styled(node, style, None),
+            YamlOutput::Commented(ref node, ref comments) => {
+                self.emit_comment_lines(&comments.before)?;
+                match comments.trailing {
+                    // a block collection starts right here, so its comment gets its own line
+                    Some(ref trailing) if self.is_block(node) => {
+                        self.emit_comment_lines(std::slice::from_ref(trailing))?;
+                        self.emit_node(node)?;
+                    }
+                    Some(ref trailing) => self.emit_trailing(node, trailing)?,
+                    None => self.emit_node(node)?,
+                }
+                self.emit_comments_after(&comments.after)
+            }
+        }
+    }
+
+    /// Determine if `v` is emitted as a literal block scalar, which can hold it only
+    /// if it has a line that is not empty and no characters needing escapes.
+    fn is_literal(&self, v: &str) -> bool {
+        self.multiline_strings && self.can_literal(v)
+    }
+
+    /// Determine if `v` can be written as a literal block scalar, which cannot hold
+    /// escapes.
+    fn can_literal(&self, v: &str) -> bool {
+        can_literal(v) && !self.is_escaped(v)
+    }
+
+    /// Emit a literal block scalar, with `comment` on its header line.
+    ///
+    /// The header carries an indentation indicator when the first line starts with a
+    /// space, and a chomping indicator unless the text ends with exactly one newline:
+    /// `-` to strip the final line break when there is none, `+` to keep the extra ones.
+    fn emit_literal(&mut self, v: &str, comment: Option<&str>) -> EmitResult {
+        let leading_space = v
+            .lines()
+            .find(|line| !line.is_empty())
+            .is_some_and(|line| line.starts_with(' '));
+        let chomping = if !v.ends_with('\n') {
+            "-"
+        } else if v.ends_with("\n\n") {
+            "+"
+        } else {
+            ""
+        };
+        write!(self.writer, "|")?;
+        if leading_space {
+            write!(self.writer, "{}", self.best_indent)?;
+        }
+        write!(self.writer, "{}", chomping)?;
+        if let Some(comment) = comment {
+            write!(self.writer, " #{}", comment)?;
+        }
+        let saved = self.indent;
+        // unlike other blocks, the text of a literal at the root has to be indented
+        self.indent = self.indent.max(0) + self.best_indent as isize;
+        for line in v.lines() {
+            writeln!(self.writer)?;
+            if !line.is_empty() {
+                self.write_indent()?;
+                // It's literal text, so don't escape special chars!
+                write!(self.writer, "{}", line)?;
+            }
+        }
+        self.indent = saved;
+        self.open_literal = v.ends_with('\n');
+        Ok(())
+    }
+
+    /// Emit whole comment lines, each followed by the indentation of the current block.
+    fn emit_comment_lines(&mut self, lines: &[String]) -> EmitResult {
+        for line in lines {
+            writeln!(self.writer, "#{}", line)?;
+            self.write_indent()?;
+        }
+        Ok(())
+    }
+
+    fn emit_comments_after(&mut self, lines: &[String]) -> EmitResult {
+        if !lines.is_empty() {
+            self.open_literal = false;
+        }
+        for line in lines {
+            writeln!(self.writer)?;
+            self.write_indent()?;
+            write!(self.writer, "#{}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Emit a scalar-like node followed by a comment on the same line. The node is not
+    /// broken over lines, which would leave the comment after its last line, where it
+    /// is read as the comment of what follows.
+    fn emit_trailing(&mut self, node: &YamlOutput, trailing: &str) -> EmitResult {
+        let width = self.max_line_width.take();
+        let result = self.emit_trailing_unwrapped(node, trailing);
+        self.max_line_width = width;
+        result
+    }
+
+    fn emit_trailing_unwrapped(&mut self, node: &YamlOutput, trailing: &str) -> EmitResult {
+        match *node {
+            YamlOutput::String(ref v) => {
+                self.open_literal = false;
+                self.emit_str(v, Some(trailing))
+            }
+            YamlOutput::Literal(ref v) if self.can_literal(v) => {
+                self.emit_literal(v, Some(trailing))
+            }
+            YamlOutput::Literal(ref v) => {
+                self.open_literal = false;
+                self.emit_str(v, Some(trailing))
+            }
+            YamlOutput::Styled(ref node, style) => {
+                self.open_literal = false;
+                self.emit_styled(node, style, Some(trailing))
+            }
+            _ => {
+                self.emit_node(node)?;
+                write!(self.writer, " #{}", trailing)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Emit a string in the first style that can hold it, followed by `comment`.
+    fn emit_str(&mut self, v: &str, comment: Option<&str>) -> EmitResult {
+        if self.is_binary(v) {
+            self.emit_binary(v.as_bytes())?;
+        } else if self.is_literal(v) {
+            return self.emit_literal(v, comment);
+        } else if self.is_folded(v) {
+            return self.emit_folded(v, comment);
+        } else if self.is_quoted(v) {
+            self.emit_quoted(v)?;
+        } else if self.conservative_folding && has_unbreakable_word(v) {
+            self.writer.write_str(v)?;
+        } else {
+            let points: Vec<_> = fold_points(v).collect();
+            self.emit_wrapped(v, &points)?;
+        }
+        if let Some(comment) = comment {
+            write!(self.writer, " #{}", comment)?;
+        }
+        Ok(())
+    }
+
+    /// Emit a string in `style` when that style can hold it, and in the first style
+    /// that can otherwise, followed by `comment`.
+    fn emit_styled(
+        &mut self,
+        node: &YamlOutput,
+        style: TScalarStyle,
+        comment: Option<&str>,
+    ) -> EmitResult {
+        let v = match *node {
+            YamlOutput::String(ref v) | YamlOutput::Literal(ref v) if !self.is_binary(v) => v,
+            _ => {
+                return match comment {
+                    Some(comment) => self.emit_trailing(node, comment),
+                    None => self.emit_node(node),
+                }
+            }
+        };
+        match style {
+            TScalarStyle::Literal if self.can_literal(v) => return self.emit_literal(v, comment),
+            TScalarStyle::Foled if self.can_fold(v) => return self.emit_folded(v, comment),
+            TScalarStyle::SingleQuoted if can_single_quote(v) && !self.is_escaped(v) => {
+                single_quote_str(&mut self.writer, v)?
+            }
+            TScalarStyle::DoubleQuoted => escape_str_with(&mut self.writer, v, self.ascii_only)?,
+            _ => return self.emit_str(v, comment),
+        }
+        if let Some(comment) = comment {
+            write!(self.writer, " #{}", comment)?;
+        }
+        Ok(())
+    }
+
+    /// Write `text` from the current column, replacing some of the spaces at `points`
+    /// by line breaks to keep within the maximum line width.
+    fn emit_wrapped(&mut self, text: &str, points: &[usize]) -> EmitResult {
+        let width = match self.max_line_width {
+            Some(width) => width,
+            None => {
+                self.writer.write_str(text)?;
+                return Ok(());
+            }
+        };
+        let indent = self.indent.max(0) as usize + self.best_indent;
+        let mut available = width.saturating_sub(self.writer.column);
+        let (mut start, mut last) = (0, None);
+        for &point in points {
+            if display_width(&text[start..point]) > available {
+                if let Some(last) = last {
+                    self.writer.write_str(&text[start..last])?;
+                    writeln!(self.writer)?;
+                    self.write_indent_at(indent as isize)?;
+                    start = last + 1;
+                    available = width.saturating_sub(indent);
+                }
+            }
+            last = Some(point);
+        }
+        if display_width(&text[start..]) > available {
+            if let Some(last) = last.filter(|&last| last >= start) {
+                self.writer.write_str(&text[start..last])?;
+                writeln!(self.writer)?;
+                self.write_indent_at(indent as isize)?;
+                start = last + 1;
+            }
+        }
+        self.writer.write_str(&text[start..])?;
+        Ok(())
+    }
+
+    fn emit_quoted(&mut self, v: &str) -> EmitResult {
+        let single = self.is_single_quoted(v);
+        quote_str(&mut self.writer, v, single, self.ascii_only)?;
+        Ok(())
+    }
+
+    /// Determine if the string value `v` is quoted.
+    fn is_quoted(&self, v: &str) -> bool {
+        match self.quote_style {
+            QuoteStyle::AlwaysSingle | QuoteStyle::AlwaysDouble => true,
+            _ => {
+                needs_quoting(v, ScalarContext::Value) != QuoteRequirement::Plain
+                    || self.is_escaped(v)
+            }
+        }
+    }
+
+    /// Determine if the string key `k` is quoted.
+    fn is_quoted_key(&self, k: &str) -> bool {
+        quote_requirement(k, self.key_context()) != QuoteRequirement::Plain || self.is_escaped(k)
+    }
+
+    /// Determine if `v` holds characters that `ascii_only` escapes.
+    fn is_escaped(&self, v: &str) -> bool {
+        self.ascii_only && !v.is_ascii()
+    }
+
+    /// The context of the string keys of mappings.
+    fn key_context(&self) -> ScalarContext {
+        if self.quote_yaml11_keys {
+            ScalarContext::Yaml11Key
+        } else {
+            ScalarContext::Key
+        }
+    }
+
+    /// Determine if `v` takes single rather than double quotes when it is quoted.
+    fn is_single_quoted(&self, v: &str) -> bool {
+        needs_quoting(v, ScalarContext::Value) != QuoteRequirement::DoubleQuotes
+            && !self.is_escaped(v)
+            && match self.quote_style {
+                QuoteStyle::Plain => !v.contains('\''),
+                QuoteStyle::SingleWhenNeeded | QuoteStyle::AlwaysSingle => true,
+                QuoteStyle::DoubleWhenNeeded | QuoteStyle::AlwaysDouble => false,
+            }
+    }
+
+    /// Determine if the node is emitted as an indented block below its key or indicator.
+    fn is_block(&self, node: &YamlOutput) -> bool {
+        match *node {
+            YamlOutput::Array(ref v) => !v.is_empty() && self.flow(node).is_none(),
+            YamlOutput::Hash(ref h) => !h.is_empty() && self.flow(node).is_none(),
+            _ => false,
+        }
+    }
+
+    /// The width to pad the key of each of `entries` to, see `align_values`: the width
+    /// of the widest key of its group, if it is in one.
+    fn aligned_key_widths(&self, entries: &[(&YamlOutput, &YamlOutput)]) -> Vec<Option<usize>> {
+        let group = match self.align_values {
+            Some(group) => group.max(1),
+            None => return vec![None; entries.len()],
+        };
+        let mut widths = Vec::with_capacity(entries.len());
+        // the start of the current group and the width of its widest key
+        let mut start = 0;
+        let mut widest = 0;
+        for (i, &(k, v)) in entries.iter().enumerate() {
+            let (v, blank_lines) = match *v {
+                YamlOutput::Commented(ref v, ref comments) => (&**v, comments.blank_lines),
+                _ => (v, 0),
+            };
+            let width = match self.key_text(k) {
+                Some(ref text) if !self.is_block(v) && !is_long_key(text) => {
+                    Some(display_width(text))
+                }
+                _ => None,
+            };
+            if width.is_none() || blank_lines > 0 || i - start >= group {
+                for width in &mut widths[start..i] {
+                    *width = Some(widest);
+                }
+                start = if width.is_none() { i + 1 } else { i };
+                widest = 0;
+            }
+            widest = widest.max(width.unwrap_or(0));
+            widths.push(None);
+        }
+        for width in &mut widths[start..] {
+            *width = Some(widest);
+        }
+        widths
+    }
+
+    /// The text `emit_key` writes for `k`, if it is a scalar written on one line.
+    fn key_text(&self, k: &YamlOutput) -> Option<String> {
+        let mut out = String::new();
+        match *k {
+            YamlOutput::String(ref v) | YamlOutput::Literal(ref v) if !self.is_binary(v) => {
+                if self.is_quoted_key(v) {
+                    quote_str(&mut out, v, self.is_single_quoted(v), self.ascii_only).ok()?;
+                } else {
+                    out.push_str(v);
+                }
+            }
+            YamlOutput::Anchored(ref name, ref k) => {
+                out = format!("&{} {}", name, self.key_text(k)?)
+            }
+            YamlOutput::Tagged(ref tag, ref k) => out = format!("{} {}", tag, self.key_text(k)?),
+            YamlOutput::Styled(ref k, _) => return self.key_text(k),
+            YamlOutput::String(_) | YamlOutput::Literal(_) => return None,
+            _ if is_collection(k) => return None,
+            _ => self.write_flow(&mut out, &mut Vec::new(), k).ok()?,
+        }
+        Some(out)
+    }
+
+    /// Determine if the scalar key `k` is too long to be written before its `:` alone,
+    /// and takes a `?` indicator instead.
+    fn is_explicit_key(&self, k: &YamlOutput) -> bool {
+        !is_collection(k) && self.key_text(k).is_some_and(|text| is_long_key(&text))
+    }
+
+    /// The entries of `h` in the order they are emitted.
+    fn entries<'b>(&self, h: &'b HashOutput) -> Vec<(&'b YamlOutput, &'b YamlOutput)> {
+        let mut entries: Vec<_> = Entries::new(h).collect();
+        match self.key_order {
+            KeyOrder::Input => {}
+            KeyOrder::Ascending => entries.sort_by(|a, b| a.0.cmp(b.0)),
+            KeyOrder::Descending => entries.sort_by(|a, b| b.0.cmp(a.0)),
+            KeyOrder::Custom(compare) => entries.sort_by(|a, b| compare(a.0, b.0)),
+        }
+        entries
+    }
+
+    /// The flow style form of `node`, if it is a collection that the flow options
+    /// select, with the offsets of the spaces after its commas.
+    fn flow(&self, node: &YamlOutput) -> Option<(String, Vec<usize>)> {
+        if self.flow_width.is_none() && !self.flow_scalar_collections {
+            return None;
+        }
+        let children: Vec<_> = match *node {
+            YamlOutput::Array(ref v) if !v.is_empty() => v.iter().collect(),
+            YamlOutput::Hash(ref h) if !h.is_empty() => {
+                Entries::new(h).flat_map(|(k, v)| vec![k, v]).collect()
+            }
+            _ => return None,
+        };
+        let mut out = String::new();
+        let mut points = Vec::new();
+        self.write_flow(&mut out, &mut points, node).ok()?;
+        let fits = self
+            .flow_width
+            .is_some_and(|width| display_width(&out) <= width);
+        let scalars =
+            self.flow_scalar_collections && children.into_iter().all(|child| !is_collection(child));
+        if fits || scalars {
+            Some((out, points))
+        } else {
+            None
+        }
+    }
+
+    /// Write `node` in flow style, failing on the nodes it cannot hold. The offsets of
+    /// the spaces after commas, where lines can be broken, go to `points`.
+    fn write_flow(
+        &self,
+        out: &mut String,
+        points: &mut Vec<usize>,
+        node: &YamlOutput,
+    ) -> fmt::Result {
+        match *node {
+            YamlOutput::Array(ref v) => {
+                out.push('[');
+                for (i, x) in v.iter().enumerate() {
+                    if i > 0 {
+                        points.push(out.len() + 1);
+                        out.push_str(", ");
+                    }
+                    self.write_flow(out, points, x)?;
+                }
+                out.push(']');
+            }
+            YamlOutput::Hash(ref h) => {
+                out.push('{');
+                for (i, (k, v)) in self.entries(h).into_iter().enumerate() {
+                    if i > 0 {
+                        points.push(out.len() + 1);
+                        out.push_str(", ");
+                    }
+                    match *k {
+                        _ if self.is_explicit_key(k) => return Err(fmt::Error),
+                        YamlOutput::String(ref k) | YamlOutput::Literal(ref k)
+                            if self.is_quoted_key(k) =>
+                        {
+                            quote_str(out, k, self.is_single_quoted(k), self.ascii_only)?
+                        }
+                        _ if is_collection(k) => return Err(fmt::Error),
+                        _ => self.write_flow(out, points, k)?,
+                    }
+                    out.push_str(": ");
+                    self.write_flow(out, points, v)?;
+                }
+                out.push('}');
+            }
+            YamlOutput::String(ref v) | YamlOutput::Literal(ref v) => {
+                if self.is_binary(v) {
+                    write_binary(out, v.as_bytes())?;
+                } else if self.is_quoted(v) {
+                    quote_str(out, v, self.is_single_quoted(v), self.ascii_only)?;
+                } else {
+                    out.push_str(v);
+                }
+            }
+            YamlOutput::Boolean(v) => write!(out, "{}", v)?,
+            YamlOutput::Integer(v) => write!(out, "{}", v)?,
+            YamlOutput::Real(ref v) => out.push_str(&self.float_format.text(v)),
+            YamlOutput::Null | YamlOutput::BadValue => out.push_str(self.null_style.text()),
+            YamlOutput::Alias(ref s) => write!(out, "*{}", s)?,
+            YamlOutput::Binary(ref bytes) => write_binary(out, bytes)?,
+            YamlOutput::Anchored(ref s, ref data) => {
+                write!(out, "&{} ", s)?;
+                self.write_flow(out, points, data)?;
+            }
+            YamlOutput::Tagged(ref tag, ref data) => {
+                write!(out, "{} ", self.tag_text(tag))?;
+                self.write_flow(out, points, data)?;
+            }
+            // a plain scalar of block context may hold flow indicators
+            YamlOutput::Spelled(_, ref spelling)
+                if spelling.contains(&[',', '[', ']', '{', '}'][..])
+                    || has_line_break(spelling)
+                    || self.is_escaped(spelling) =>
+            {
+                return Err(fmt::Error)
+            }
+            YamlOutput::Spelled(_, ref spelling) => out.push_str(spelling),
+            YamlOutput::Styled(ref node, style) => match (style, &**node) {
+                (TScalarStyle::SingleQuoted, YamlOutput::String(v))
+                    if can_single_quote(v) && !self.is_escaped(v) && !self.is_binary(v) =>
+                {
+                    single_quote_str(out, v)?
+                }
+                (TScalarStyle::DoubleQuoted, YamlOutput::String(v)) if !self.is_binary(v) => {
+                    escape_str_with(out, v, self.ascii_only)?
+                }
+                _ => self.write_flow(out, points, node)?,
+            },
+            YamlOutput::Commented(..) => return Err(fmt::Error),
+        }
+        Ok(())
+    }
+
+    /// Emit the key of a block mapping entry. String keys never take a block style and
+    /// are quoted whenever they would be read back as another type.
+    fn emit_key(&mut self, k: &YamlOutput) -> EmitResult {
+        self.open_literal = false;
+        match *k {
+            YamlOutput::String(ref v) | YamlOutput::Literal(ref v) => {
+                if self.is_binary(v) {
+                    self.emit_binary(v.as_bytes())
+                } else if self.is_quoted_key(v) {
+                    self.emit_quoted(v)
+                } else {
+                    write!(self.writer, "{}", v)?;
+                    Ok(())
                 }
-                Ok(())
             }
-            YamlOutput::Integer(v) => {
-                write!(self.writer, "{}", v)?;
-                Ok(())
+            YamlOutput::Anchored(ref name, ref k) => {
+                write!(self.writer, "&{} ", name)?;
+                self.emit_key(k)
             }
-            YamlOutput::Real(ref v) => {
-                write!(self.writer, "{}", v)?;
-                Ok(())
+            YamlOutput::Tagged(ref tag, ref k) => {
+                write!(self.writer, "{} ", self.tag_text(tag))?;
+                self.emit_key(k)
             }
-            YamlOutput::Null | YamlOutput::BadValue => {
-                write!(self.writer, "~")?;
-                Ok(())
+            // block scalars cannot be keys
+            YamlOutput::Styled(ref k, _) => self.emit_key(k),
+            _ => self.emit_node(k),
+        }
+    }
+
+    /// Determine if `v` is emitted as a folded block scalar: a single paragraph longer
+    /// than the fold width that the folded style can hold.
+    fn is_folded(&self, v: &str) -> bool {
+        let text = v.strip_suffix('\n').unwrap_or(v);
+        match self.fold_width {
+            Some(width) => display_width(text) > width && self.can_fold(v),
+            None => false,
+        }
+    }
+
+    /// Determine if `v` can be written as a folded block scalar: a single paragraph,
+    /// optionally ending with a newline, that can be broken at a space and has no
+    /// characters needing escapes.
+    fn can_fold(&self, v: &str) -> bool {
+        let text = v.strip_suffix('\n').unwrap_or(v);
+        if self.is_escaped(text) || (self.conservative_folding && has_unbreakable_word(text)) {
+            return false;
+        }
+        !text.starts_with(' ')
+            && !text.ends_with(' ')
+            && fold_points(text).next().is_some()
+            && text
+                .chars()
+                .all(|c| c != '\n' && c != '\r' && c != '\u{85}' && is_printable(c))
+    }
+
+    /// Emit a folded block scalar, with `comment` on its header line. Lines are broken
+    /// at single spaces, which folding turns back into spaces when the text is read.
+    fn emit_folded(&mut self, v: &str, comment: Option<&str>) -> EmitResult {
+        let text = v.strip_suffix('\n').unwrap_or(v);
+        let width = self.fold_width.unwrap_or(STYLED_FOLD_WIDTH);
+        write!(self.writer, ">")?;
+        if !v.ends_with('\n') {
+            write!(self.writer, "-")?;
+        }
+        if let Some(comment) = comment {
+            write!(self.writer, " #{}", comment)?;
+        }
+        let saved = self.indent;
+        self.indent = self.indent.max(0) + self.best_indent as isize;
+        let mut start = 0;
+        let mut last = None;
+        for point in fold_points(text) {
+            // break before the line gets too long, or as soon as possible
+            if display_width(&text[start..point]) > width {
+                if let Some(last) = last {
+                    self.emit_folded_line(&text[start..last])?;
+                    start = last + 1;
+                }
+            }
+            if display_width(&text[start..point]) > width {
+                self.emit_folded_line(&text[start..point])?;
+                start = point + 1;
+                last = None;
+            } else {
+                last = Some(point);
+            }
+        }
+        if display_width(&text[start..]) > width {
+            if let Some(last) = last {
+                self.emit_folded_line(&text[start..last])?;
+                start = last + 1;
+            }
+        }
+        self.emit_folded_line(&text[start..])?;
+        self.indent = saved;
+        self.open_literal = v.ends_with('\n');
+        Ok(())
+    }
+
+    fn emit_folded_line(&mut self, line: &str) -> EmitResult {
+        writeln!(self.writer)?;
+        self.write_indent()?;
+        write!(self.writer, "{}", line)?;
+        Ok(())
+    }
+
+    fn is_binary(&self, v: &str) -> bool {
+        self.binary_strings && !v.chars().all(is_printable)
+    }
+
+    /// Emit bytes as base64, wrapped in a literal block scalar when longer than a
+    /// line of `BINARY_LINE_WIDTH` characters.
+    fn emit_binary(&mut self, bytes: &[u8]) -> EmitResult {
+        let encoded = base64::encode(bytes);
+        if encoded.len() <= BINARY_LINE_WIDTH {
+            write_binary(&mut self.writer, bytes)?;
+            return Ok(());
+        }
+        write!(self.writer, "!!binary |")?;
+        let indent = self.indent.max(0) + self.best_indent as isize;
+        // base64 is ASCII, so every chunk is valid UTF-8
+        for line in encoded.as_bytes().chunks(BINARY_LINE_WIDTH) {
+            writeln!(self.writer)?;
+            self.write_indent_at(indent)?;
+            write!(self.writer, "{}", std::str::from_utf8(line).unwrap())?;
+        }
+        self.open_literal = true;
+        Ok(())
+    }
+
+    fn emit_array_at(&mut self, indent: isize, v: &[YamlOutput]) -> EmitResult {
+        let saved = self.indent;
+        self.indent = indent;
+        let result = self.emit_array(v);
+        self.indent = saved;
+        result
+    }
+
+    fn emit_hash_at(&mut self, indent: isize, h: &HashOutput) -> EmitResult {
+        let saved = self.indent;
+        self.indent = indent;
+        let result = self.emit_hash(h);
+        self.indent = saved;
+        result
+    }
+
+    fn emit_array(&mut self, v: &[YamlOutput]) -> EmitResult {
+        if v.is_empty() {
+            write!(self.writer, "[]")?;
+        } else {
+            for (cnt, x) in v.iter().enumerate() {
+                self.path.push(Segment::Index(cnt));
+                if cnt > 0 {
+                    writeln!(self.writer)?;
+                    if let YamlOutput::Commented(_, ref comments) = *x {
+                        for _ in 0..comments.blank_lines {
+                            writeln!(self.writer)?;
+                        }
+                    }
+                    self.write_indent()?;
+                }
+                match *x {
+                    YamlOutput::Commented(ref x, ref comments) => {
+                        self.emit_comment_lines(&comments.before)?;
+                        write!(self.writer, "-")?;
+                        self.emit_commented_val(true, x, comments)?;
+                    }
+                    _ => {
+                        write!(self.writer, "-")?;
+                        self.emit_val(true, x)?;
+                    }
+                }
+                self.path.pop();
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_hash(&mut self, h: &HashOutput) -> EmitResult {
+        if h.is_empty() {
+            self.writer.write_str("{}")?;
+        } else {
+            let entries = self.entries(h);
+            let key_widths = self.aligned_key_widths(&entries);
+            for (cnt, (k, v)) in entries.into_iter().enumerate() {
+                self.path.push(Segment::Key(key_name(k)));
+                let (v, comments) = match *v {
+                    YamlOutput::Commented(ref v, ref comments) => (&**v, Some(comments)),
+                    _ => (v, None),
+                };
+                let key_comment = comments.and_then(|comments| comments.key_trailing.as_ref());
+                let complex_key = matches!(*k, YamlOutput::Hash(_) | YamlOutput::Array(_))
+                    || key_comment.is_some()
+                    || self.is_explicit_key(k);
+                if cnt > 0 {
+                    writeln!(self.writer)?;
+                    let spacing = if self.path.segments().len() == 1 {
+                        self.key_spacing
+                    } else {
+                        0
+                    };
+                    let blank_lines = comments.map_or(0, |comments| comments.blank_lines);
+                    for _ in 0..spacing.max(blank_lines) {
+                        writeln!(self.writer)?;
+                    }
+                    self.write_indent()?;
+                }
+                if let Some(comments) = comments {
+                    self.emit_comment_lines(&comments.before)?;
+                }
+                if complex_key {
+                    write!(self.writer, "?")?;
+                    match key_comment {
+                        // the comment ends the line of the indicator, the key starts below it
+                        Some(comment) => {
+                            write!(self.writer, " #{}", comment)?;
+                            if self.is_block(k) {
+                                self.emit_val_with(true, false, k)?;
+                            } else {
+                                writeln!(self.writer)?;
+                                let indent = self.child_indent(self.best_indent);
+                                self.write_indent_at(indent)?;
+                                self.emit_key(k)?;
+                            }
+                        }
+                        None => self.emit_val(true, k)?,
+                    }
+                    writeln!(self.writer)?;
+                    self.write_indent()?;
+                    write!(self.writer, ":")?;
+                } else {
+                    let key_column = self.writer.column;
+                    self.emit_key(k)?;
+                    write!(self.writer, ":")?;
+                    if let Some(width) = key_widths[cnt] {
+                        if !self.is_empty_null(v) {
+                            let column = key_column + width + 1;
+                            for _ in self.writer.column..column {
+                                write!(self.writer, " ")?;
+                            }
+                        }
+                    }
+                }
+                match comments {
+                    Some(comments) => self.emit_commented_val(complex_key, v, comments)?,
+                    None => self.emit_val(complex_key, v)?,
+                }
+                self.path.pop();
+            }
+        }
+        Ok(())
+    }
+
+    /// Emit a yaml as a hash or array value: i.e., which should appear
+    /// following a ":" or "-", either after a space, or on a new line.
+    /// If `inline` is true, then the preceding characters are distinct
+    /// and short enough to respect the compact flag.
+    fn emit_val(&mut self, inline: bool, val: &YamlOutput) -> EmitResult {
+        self.emit_val_with(inline, self.compact, val)
+    }
+
+    /// Emit the value of an entry whose comments before it are already written.
+    fn emit_commented_val(
+        &mut self,
+        inline: bool,
+        val: &YamlOutput,
+        comments: &Comments,
+    ) -> EmitResult {
+        match comments.trailing {
+            None => self.emit_val(inline, val)?,
+            Some(ref trailing) => match *val {
+                // the comment ends the line of the indicator, the block starts below it
+                _ if self.is_block(val) => {
+                    write!(self.writer, " #{}", trailing)?;
+                    self.emit_val_with(inline, false, val)?;
+                }
+                YamlOutput::Anchored(ref name, ref data) if self.is_block(data) => {
+                    write!(self.writer, " &{} #{}", name, trailing)?;
+                    self.emit_val_with(false, false, data)?;
+                }
+                YamlOutput::Tagged(ref tag, ref data) if self.is_block(data) => {
+                    write!(self.writer, " {} #{}", self.tag_text(tag), trailing)?;
+                    self.emit_val_with(false, false, data)?;
+                }
+                _ if self.is_empty_null(val) => write!(self.writer, " #{}", trailing)?,
+                _ => {
+                    write!(self.writer, " ")?;
+                    self.emit_trailing(val, trailing)?;
+                }
+            },
+        }
+        self.emit_comments_after(&comments.after)
+    }
+
+    fn emit_val_with(&mut self, inline: bool, compact: bool, val: &YamlOutput) -> EmitResult {
+        if let Some((flow, points)) = self.flow(val) {
+            write!(self.writer, " ")?;
+            return self.emit_wrapped(&flow, &points);
+        }
+        match *val {
+            YamlOutput::Array(ref v) => {
+                let compact = compact && self.can_compact(v.first());
+                let indent = if (inline && compact) || v.is_empty() {
+                    write!(self.writer, " ")?;
+                    // the entries line up after the "- " or "? " indicator
+                    self.indent + 2
+                } else {
+                    writeln!(self.writer)?;
+                    let step = if inline || self.indent_sequences {
+                        self.best_indent
+                    } else {
+                        0
+                    };
+                    let indent = self.child_indent(step);
+                    self.write_indent_at(indent)?;
+                    indent
+                };
+                self.emit_array_at(indent, v)
+            }
+            YamlOutput::Hash(ref h) => {
+                let first = self.entries(h).first().map(|&(_, v)| v);
+                let compact = compact && self.can_compact(first);
+                let indent = if (inline && compact) || h.is_empty() {
+                    write!(self.writer, " ")?;
+                    self.indent + 2
+                } else {
+                    writeln!(self.writer)?;
+                    let indent = self.child_indent(self.best_indent);
+                    self.write_indent_at(indent)?;
+                    indent
+                };
+                self.emit_hash_at(indent, h)
+            }
+            _ if self.is_empty_null(val) => Ok(()),
+            _ => {
+                write!(self.writer, " ")?;
+                self.emit_node(val)
+            }
+        }
+    }
+
+    /// Determine if a collection whose first entry is `first` can follow its indicator
+    /// on the same line. Comment lines before that entry are then read back as the
+    /// comment of the collection, written as a block at the indent of children, so
+    /// the compact form is kept only when that is the same column.
+    fn can_compact(&self, first: Option<&YamlOutput>) -> bool {
+        !first.is_some_and(has_comment_lines)
+            || self.child_indent(self.best_indent) == self.indent + 2
+    }
+
+    /// Check if `val` is a null written as nothing after its key or indicator.
+    fn is_empty_null(&self, val: &YamlOutput) -> bool {
+        self.null_style == NullStyle::Empty
+            && matches!(*val, YamlOutput::Null | YamlOutput::BadValue)
+    }
+}
+
+/// Writes documents through a [`YamlEmitter`] as they are described, keeping only
+/// the open collections in memory, for generated documents too large to build as a
+/// `YamlOutput`. The output is the one `dump_all` writes for the same documents,
+/// except that the collections started here are written in block style and keep
+/// their entries in the order given. The nodes given whole follow every option of
+/// the emitter.
+///
+/// # Errors
+///
+/// The methods return `EmitError::OutOfOrder` when called out of order, like a
+/// mapping value without its key or a node outside of a document, before writing
+/// anything, so the stream can go on with the right call.
+///
+/// # Examples
+///
+/// ```rust
+/// use yaml_rust_formatter::{YamlEmitter, YamlOutput};
+///
+/// let mut output = String::new();
+/// {
+///     let mut emitter = YamlEmitter::new(&mut output);
+///     let mut stream = emitter.stream();
+///     stream.start_document().unwrap();
+///     stream.start_sequence().unwrap();
+///     for id in 0..2 {
+///         stream.start_mapping().unwrap();
+///         stream.key(&YamlOutput::String("id".to_owned())).unwrap();
+///         stream.node(&YamlOutput::Integer(id)).unwrap();
+///         stream.end_mapping().unwrap();
+///     }
+///     stream.end_sequence().unwrap();
+///     stream.end_document().unwrap();
+/// }
+/// assert_eq!(output, "---\n- id: 0\n- id: 1");
+/// ```
+#[cfg(feature = "stream")]
+pub struct StreamBuilder<'e, 'a> {
+    emitter: &'e mut YamlEmitter<'a>,
+    stack: Vec<OpenCollection>,
+    // the names written for the anchors of the current document
+    anchor_names: HashMap<String, String>,
+    documents: usize,
+    // whether the root of the current document is written, `None` outside of documents
+    root: Option<bool>,
+    // the key written last, for the `NumberFormatter` of the emitter
+    last_key: Option<YamlOutput>,
+}
+
+/// A collection started on a `StreamBuilder` and not yet ended.
+#[cfg(feature = "stream")]
+struct OpenCollection {
+    mapping: bool,
+    // column of the entries, once the first one is written
+    indent: isize,
+    // column of the entries of the parent, -1 at the document root
+    parent_indent: isize,
+    // whether the collection follows a `-` or `?` indicator
+    inline: bool,
+    len: usize,
+    // in a mapping, the key waiting for its value, and whether it is a complex key
+    key: Option<bool>,
+}
+
+/// Where the next node of a `StreamBuilder` goes.
+#[cfg(feature = "stream")]
+enum StreamPosition {
+    Root,
+    Item,
+    Value { complex_key: bool },
+}
+
+#[cfg(feature = "stream")]
+impl<'e, 'a> StreamBuilder<'e, 'a> {
+    /// Start a document, with `---` unless the emitter leaves it out.
+    pub fn start_document(&mut self) -> EmitResult {
+        if self.root.is_some() {
+            return Err(EmitError::OutOfOrder("the previous document is not ended"));
+        }
+        let emitter = &mut *self.emitter;
+        if self.documents > 0 {
+            emitter.write_document_separator()?;
+        }
+        emitter.write_directives()?;
+        if emitter.explicit_start {
+            emitter.write_marker()?;
+        }
+        emitter.indent = -1;
+        emitter.start_anchor_names();
+        self.anchor_names.clear();
+        self.root = Some(false);
+        Ok(())
+    }
+
+    /// Write `directives` before the documents started from now on, see
+    /// [`YamlEmitter::directives`].
+    pub fn directives(&mut self, directives: &'a Directives) {
+        self.emitter.directives = Some(directives);
+    }
+
+    /// End the current document, once its root node is complete.
+    pub fn end_document(&mut self) -> EmitResult {
+        if !self.stack.is_empty() || self.root != Some(true) {
+            return Err(EmitError::OutOfOrder(
+                "the document has no complete root node",
+            ));
+        }
+        let emitter = &mut *self.emitter;
+        if emitter.open_literal && !emitter.trailing_newline {
+            // the last line break of a literal is part of its text
+            writeln!(emitter.writer)?;
+        }
+        emitter.open_literal = false;
+        if emitter.explicit_end {
+            write!(emitter.writer, "\n...")?;
+        }
+        if emitter.trailing_newline {
+            writeln!(emitter.writer)?;
+        }
+        self.root = None;
+        self.documents += 1;
+        Ok(())
+    }
+
+    /// Write `node` whole, as the root of the document, the next item of a sequence
+    /// or the value of the last key of a mapping.
+    pub fn node(&mut self, node: &YamlOutput) -> EmitResult {
+        if self.emitter.ascii_only {
+            self.emitter.check_ascii(node)?;
+        }
+        let position = self.enter()?;
+        let node = self.emitter.rename_anchors(node, &mut self.anchor_names);
+        let key = match position {
+            StreamPosition::Value { .. } => self.last_key.take(),
+            _ => None,
+        };
+        let node = self.emitter.format_numbers(&node, key.as_ref());
+        let node = &*match self.emitter.truncate_strings {
+            Some(limit) => Cow::Owned(truncate_strings(&node, limit)),
+            None => node,
+        };
+        match position {
+            StreamPosition::Root if !self.emitter.start_root(Some(node))? => Ok(()),
+            StreamPosition::Root => self.emitter.emit_node(node),
+            StreamPosition::Item => self.emitter.emit_val(true, node),
+            StreamPosition::Value { complex_key } => self.emitter.emit_val(complex_key, node),
+        }
+    }
+
+    /// Write the key of the next entry of the innermost mapping, whose value is the
+    /// next node.
+    pub fn key(&mut self, key: &YamlOutput) -> EmitResult {
+        match self.stack.last() {
+            Some(open) if open.mapping && open.key.is_none() => {}
+            _ => {
+                return Err(EmitError::OutOfOrder(
+                    "a key must be written in a mapping, before its value",
+                ))
+            }
+        }
+        if self.emitter.ascii_only {
+            self.emitter.check_ascii(key)?;
+        }
+        self.next_entry()?;
+        let emitter = &mut *self.emitter;
+        let key = &*emitter.rename_anchors(key, &mut self.anchor_names);
+        let complex_key = matches!(*key, YamlOutput::Hash(_) | YamlOutput::Array(_))
+            || emitter.is_explicit_key(key);
+        if complex_key {
+            write!(emitter.writer, "?")?;
+            emitter.emit_val(true, key)?;
+            writeln!(emitter.writer)?;
+            emitter.write_indent()?;
+        } else {
+            emitter.emit_key(key)?;
+        }
+        write!(emitter.writer, ":")?;
+        if emitter.number_formatter.is_some() {
+            self.last_key = Some(key.clone());
+        }
+        if let Some(open) = self.stack.last_mut() {
+            open.key = Some(complex_key);
+        }
+        Ok(())
+    }
+
+    /// Start a sequence, whose items are the nodes written until `end_sequence`.
+    pub fn start_sequence(&mut self) -> EmitResult {
+        self.start_collection(false)
+    }
+
+    pub fn end_sequence(&mut self) -> EmitResult {
+        self.end_collection(false)
+    }
+
+    /// Start a mapping, whose entries are the keys and nodes written until
+    /// `end_mapping`.
+    pub fn start_mapping(&mut self) -> EmitResult {
+        self.start_collection(true)
+    }
+
+    pub fn end_mapping(&mut self) -> EmitResult {
+        self.end_collection(true)
+    }
+
+    fn start_collection(&mut self, mapping: bool) -> EmitResult {
+        let (parent_indent, inline) = match self.enter()? {
+            StreamPosition::Root => {
+                self.emitter.start_root(None)?;
+                (-1, false)
+            }
+            StreamPosition::Item => (self.emitter.indent, true),
+            StreamPosition::Value { complex_key } => (self.emitter.indent, complex_key),
+        };
+        self.stack.push(OpenCollection {
+            mapping,
+            indent: parent_indent,
+            parent_indent,
+            inline,
+            len: 0,
+            key: None,
+        });
+        Ok(())
+    }
+
+    fn end_collection(&mut self, mapping: bool) -> EmitResult {
+        let open = match self.stack.pop() {
+            Some(open) if open.mapping == mapping && open.key.is_none() => open,
+            open => {
+                self.stack.extend(open);
+                return Err(EmitError::OutOfOrder(if mapping {
+                    "no mapping to end, or its last key has no value"
+                } else {
+                    "no sequence to end"
+                }));
+            }
+        };
+        let emitter = &mut *self.emitter;
+        if open.len == 0 {
+            if open.parent_indent >= 0 || open.inline {
+                write!(emitter.writer, " ")?;
+            }
+            write!(emitter.writer, "{}", if mapping { "{}" } else { "[]" })?;
+        }
+        emitter.indent = self.stack.last().map_or(-1, |parent| parent.indent);
+        Ok(())
+    }
+
+    /// Prepare for the next node, writing the `-` of a sequence item.
+    fn enter(&mut self) -> Result<StreamPosition, EmitError> {
+        let open = match self.stack.last_mut() {
+            Some(open) => open,
+            None => {
+                if self.root != Some(false) {
+                    return Err(EmitError::OutOfOrder(
+                        "a node must be written in a document, as its only root",
+                    ));
+                }
+                self.root = Some(true);
+                self.emitter.indent = -1;
+                return Ok(StreamPosition::Root);
+            }
+        };
+        if open.mapping {
+            return match open.key.take() {
+                Some(complex_key) => Ok(StreamPosition::Value { complex_key }),
+                None => Err(EmitError::OutOfOrder("a mapping value needs its key first")),
+            };
+        }
+        self.next_entry()?;
+        write!(self.emitter.writer, "-")?;
+        Ok(StreamPosition::Item)
+    }
+
+    /// Start the next entry of the innermost collection on its own line, after the
+    /// indicator or key of the collection itself for the first one.
+    fn next_entry(&mut self) -> EmitResult {
+        let open = match self.stack.last_mut() {
+            Some(open) => open,
+            None => return Ok(()),
+        };
+        let emitter = &mut *self.emitter;
+        if open.len > 0 {
+            writeln!(emitter.writer)?;
+            emitter.write_indent_at(open.indent)?;
+        } else if open.parent_indent < 0 && !open.inline {
+            // at the document root
+            open.indent = 0;
+        } else if open.inline && emitter.compact {
+            write!(emitter.writer, " ")?;
+            // the entries line up after the "- " or "? " indicator
+            open.indent = open.parent_indent + 2;
+        } else {
+            writeln!(emitter.writer)?;
+            let step = if open.mapping || open.inline || emitter.indent_sequences {
+                emitter.best_indent
+            } else {
+                0
+            };
+            open.indent = if open.parent_indent < 0 {
+                0
+            } else {
+                open.parent_indent + step as isize
+            };
+            emitter.write_indent_at(open.indent)?;
+        }
+        open.len += 1;
+        emitter.indent = open.indent;
+        Ok(())
+    }
+}
+
+/// A copy of `node` with its anchors named by `namer`, and its aliases named like the
+/// last anchor of their name complete before them, whose new names are in `names`.
+fn rename_anchors(
+    node: &YamlOutput,
+    namer: &mut dyn AnchorNamer,
+    names: &mut HashMap<String, String>,
+) -> YamlOutput {
+    match *node {
+        YamlOutput::Anchored(ref name, ref node) => {
+            let new_name = namer.name(name);
+            let node = rename_anchors(node, namer, names);
+            names.insert(name.clone(), new_name.clone());
+            YamlOutput::Anchored(new_name, Box::new(node))
+        }
+        YamlOutput::Alias(ref name) => YamlOutput::Alias(names.get(name).unwrap_or(name).clone()),
+        YamlOutput::Array(ref v) => {
+            YamlOutput::Array(v.iter().map(|x| rename_anchors(x, namer, names)).collect())
+        }
+        YamlOutput::Hash(ref h) => YamlOutput::Hash(
+            h.iter()
+                .map(|(k, v)| {
+                    let k = rename_anchors(k, namer, names);
+                    (k, rename_anchors(v, namer, names))
+                })
+                .collect(),
+        ),
+        YamlOutput::Tagged(ref tag, ref node) => {
+            YamlOutput::Tagged(tag.clone(), Box::new(rename_anchors(node, namer, names)))
+        }
+        YamlOutput::Commented(ref node, ref comments) => YamlOutput::Commented(
+            Box::new(rename_anchors(node, namer, names)),
+            comments.clone(),
+        ),
+        YamlOutput::Spelled(ref node, ref text) => {
+            YamlOutput::Spelled(Box::new(rename_anchors(node, namer, names)), text.clone())
+        }
+        ref node => node.clone(),
+    }
+}
+
+/// A copy of `node`, the value of `key` if any, with its integers and reals spelled
+/// by `formatter`.
+fn format_numbers(
+    node: &YamlOutput,
+    formatter: &mut dyn NumberFormatter,
+    key: Option<&YamlOutput>,
+) -> YamlOutput {
+    let spelling = match *node {
+        YamlOutput::Integer(v) => formatter.integer(v, key),
+        YamlOutput::Real(ref v) => formatter.real(v, key),
+        _ => None,
+    };
+    if let Some(spelling) = spelling {
+        return YamlOutput::Spelled(Box::new(node.clone()), spelling);
+    }
+    match *node {
+        YamlOutput::Array(ref v) => YamlOutput::Array(
+            v.iter()
+                .map(|x| format_numbers(x, formatter, None))
+                .collect(),
+        ),
+        YamlOutput::Hash(ref h) => YamlOutput::Hash(
+            h.iter()
+                .map(|(k, v)| (k.clone(), format_numbers(v, formatter, Some(k))))
+                .collect(),
+        ),
+        YamlOutput::Anchored(ref name, ref node) => {
+            YamlOutput::Anchored(name.clone(), Box::new(format_numbers(node, formatter, key)))
+        }
+        YamlOutput::Tagged(ref tag, ref node) => {
+            YamlOutput::Tagged(tag.clone(), Box::new(format_numbers(node, formatter, key)))
+        }
+        YamlOutput::Commented(ref node, ref comments) => YamlOutput::Commented(
+            Box::new(format_numbers(node, formatter, key)),
+            comments.clone(),
+        ),
+        ref node => node.clone(),
+    }
+}
+
+/// Determine if `node` is a scalar, maybe with an anchor or a tag.
+fn is_scalar(node: &YamlOutput) -> bool {
+    match *node {
+        YamlOutput::Anchored(_, ref node)
+        | YamlOutput::Tagged(_, ref node)
+        | YamlOutput::Spelled(ref node, _)
+        | YamlOutput::Styled(ref node, _) => is_scalar(node),
+        YamlOutput::Array(_) | YamlOutput::Hash(_) | YamlOutput::Commented(..) => false,
+        _ => true,
+    }
+}
+
+/// A copy of `node` with its string values cut to `limit` bytes, each marked by a
+/// trailing comment.
+fn truncate_strings(node: &YamlOutput, limit: usize) -> YamlOutput {
+    match *node {
+        YamlOutput::String(ref v) if v.len() > limit => {
+            let mut end = limit;
+            while !v.is_char_boundary(end) {
+                end -= 1;
+            }
+            YamlOutput::Commented(
+                Box::new(YamlOutput::String(v[..end].to_owned())),
+                Comments {
+                    trailing: Some(format!(" truncated, {} of {} bytes", end, v.len())),
+                    ..Comments::default()
+                },
+            )
+        }
+        YamlOutput::Array(ref v) => {
+            YamlOutput::Array(v.iter().map(|x| truncate_strings(x, limit)).collect())
+        }
+        YamlOutput::Hash(ref h) => YamlOutput::Hash(
+            h.iter()
+                .map(|(k, v)| (k.clone(), truncate_strings(v, limit)))
+                .collect(),
+        ),
+        YamlOutput::Anchored(ref name, ref node) => {
+            YamlOutput::Anchored(name.clone(), Box::new(truncate_strings(node, limit)))
+        }
+        YamlOutput::Tagged(ref tag, ref node) => {
+            YamlOutput::Tagged(tag.clone(), Box::new(truncate_strings(node, limit)))
+        }
+        YamlOutput::Styled(ref node, style) => {
+            YamlOutput::Styled(Box::new(truncate_strings(node, limit)), style)
+        }
+        YamlOutput::Commented(ref node, ref existing) => {
+            match (&**node, truncate_strings(node, limit)) {
+                (YamlOutput::String(_), YamlOutput::Commented(node, mark)) => {
+                    let mark = mark.trailing.unwrap_or_default();
+                    let trailing = match existing.trailing {
+                        // one line has room for a single comment
+                        Some(ref comment) => format!("{};{}", comment, mark),
+                        None => mark,
+                    };
+                    YamlOutput::Commented(
+                        node,
+                        Comments {
+                            trailing: Some(trailing),
+                            ..existing.clone()
+                        },
+                    )
+                }
+                (_, node) => YamlOutput::Commented(Box::new(node), existing.clone()),
+            }
+        }
+        ref node => node.clone(),
+    }
+}
+
+/// A copy of `node`, at `path`, with the entries of its mappings documented by
+/// `comments`.
+fn document_keys(node: &YamlOutput, comments: &KeyComments, path: &mut Vec<String>) -> YamlOutput {
+    match *node {
+        YamlOutput::Array(ref v) => {
+            YamlOutput::Array(v.iter().map(|x| document_keys(x, comments, path)).collect())
+        }
+        YamlOutput::Hash(ref h) => YamlOutput::Hash(
+            h.iter()
+                .map(|(k, v)| {
+                    path.push(key_name(k));
+                    let v = document_keys(v, comments, path);
+                    let v = match comments.comments.get(path) {
+                        Some(comment) => {
+                            let lines = comment.lines().map(|line| format!(" {}", line));
+                            match v {
+                                YamlOutput::Commented(node, mut existing) => {
+                                    existing.before.extend(lines);
+                                    YamlOutput::Commented(node, existing)
+                                }
+                                v => YamlOutput::Commented(
+                                    Box::new(v),
+                                    Comments {
+                                        before: lines.collect(),
+                                        ..Comments::default()
+                                    },
+                                ),
+                            }
+                        }
+                        None => v,
+                    };
+                    path.pop();
+                    (k.clone(), v)
+                })
+                .collect(),
+        ),
+        YamlOutput::Anchored(ref name, ref node) => {
+            YamlOutput::Anchored(name.clone(), Box::new(document_keys(node, comments, path)))
+        }
+        YamlOutput::Tagged(ref tag, ref node) => {
+            YamlOutput::Tagged(tag.clone(), Box::new(document_keys(node, comments, path)))
+        }
+        YamlOutput::Commented(ref node, ref existing) => YamlOutput::Commented(
+            Box::new(document_keys(node, comments, path)),
+            existing.clone(),
+        ),
+        ref node => node.clone(),
+    }
+}
+
+/// Whether `node`, the first entry of a collection, has comment lines before it,
+/// which need the entry on a line of its own.
+fn has_comment_lines(node: &YamlOutput) -> bool {
+    matches!(*node, YamlOutput::Commented(_, ref comments) if !comments.before.is_empty())
+}
+
+/// The name of `key` in a path, as `diff::Path` writes it.
+pub(crate) fn key_name(key: &YamlOutput) -> String {
+    match *key.inner() {
+        YamlOutput::String(ref v) | YamlOutput::Literal(ref v) | YamlOutput::Real(ref v) => {
+            v.clone()
+        }
+        YamlOutput::Integer(v) => v.to_string(),
+        YamlOutput::Boolean(v) => v.to_string(),
+        YamlOutput::Null => "~".to_owned(),
+        ref key => format!("{:?}", key),
+    }
+}
+
+/// Check if the node is a sequence or a mapping, under its anchor, tag or comments.
+fn is_collection(node: &YamlOutput) -> bool {
+    matches!(*node.inner(), YamlOutput::Array(_) | YamlOutput::Hash(_))
+}
+
+/// Check if a literal block scalar can hold `v`: it must have several lines, one of
+/// them not empty, and no characters needing escapes.
+fn can_literal(v: &str) -> bool {
+    v.contains('\n')
+        && v.lines().any(|line| !line.is_empty())
+        && v.chars()
+            .all(|c| c != '\r' && c != '\u{85}' && is_printable(c))
+}
+
+/// The byte offsets of the spaces where `text` can be broken in a folded scalar: the
+/// ones between two characters other than spaces, so that no line starts or ends with
+/// a space and gets kept as written. A space followed by a character extending it,
+/// such as a combining accent, starts a grapheme cluster and is not broken.
+fn fold_points(text: &str) -> impl Iterator<Item = usize> + '_ {
+    let bytes = text.as_bytes();
+    (1..bytes.len().saturating_sub(1)).filter(move |&i| {
+        bytes[i] == b' '
+            && bytes[i - 1] != b' '
+            && bytes[i + 1] != b' '
+            && !text[i + 1..].chars().next().is_some_and(extends_grapheme)
+    })
+}
+
+/// Determine if `c` belongs to the grapheme cluster of the character before it,
+/// following the `Extend` and `ZWJ` classes of Unicode Standard Annex #29.
+fn extends_grapheme(c: char) -> bool {
+    is_combining_mark(c)
+        || matches!(c,
+            '\u{200c}' | '\u{200d}'
+            // emoji skin tone modifiers and tag characters
+            | '\u{1f3fb}'..='\u{1f3ff}'
+            | '\u{e0020}'..='\u{e007f}'
+        )
+}
+
+#[cfg(feature = "unicode")]
+fn is_combining_mark(c: char) -> bool {
+    unicode_normalization::char::is_combining_mark(c)
+}
+
+/// Determine if `c` is one of the combining marks of the blocks made of them, which
+/// covers the accents of the Latin, Greek and Cyrillic scripts.
+#[cfg(not(feature = "unicode"))]
+fn is_combining_mark(c: char) -> bool {
+    matches!(c,
+        '\u{300}'..='\u{36f}'
+        | '\u{1ab0}'..='\u{1aff}'
+        | '\u{1dc0}'..='\u{1dff}'
+        | '\u{20d0}'..='\u{20ff}'
+        | '\u{fe20}'..='\u{fe2f}'
+    )
+}
+
+/// Determine if a word of `text` is a URL or looks like a token: a long run of
+/// letters and digits mixed, as in a hash or a key.
+fn has_unbreakable_word(text: &str) -> bool {
+    text.split(' ').any(|word| {
+        word.contains("://")
+            || word.starts_with("mailto:")
+            || (word.chars().count() >= 16
+                && word.chars().any(|c| c.is_ascii_digit())
+                && word.chars().any(char::is_alphabetic))
+    })
+}
+
+/// Check if the character belongs to the printable set of the YAML spec, see
+/// [5.1. Character Set](https://yaml.org/spec/1.2/spec.html#id2770814).
+pub(crate) fn is_printable(character: char) -> bool {
+    matches!(character,
+        '\t'
+        | '\n'
+        | '\r'
+        | '\x20'..='\x7e'
+        | '\u{85}'
+        | '\u{a0}'..='\u{d7ff}'
+        | '\u{e000}'..='\u{fffd}'
+        | '\u{10000}'..='\u{10ffff}'
+    )
+}
+
+/// Check if the string can be written in single quotes, which cannot express escapes.
+fn can_single_quote(string: &str) -> bool {
+    !string.contains(|character: char| {
+        character.is_control() || matches!(character, '\u{2028}' | '\u{2029}')
+    })
+}
+
+pub(crate) fn write_binary(wr: &mut dyn fmt::Write, bytes: &[u8]) -> fmt::Result {
+    if bytes.is_empty() {
+        write!(wr, "!!binary \"\"")
+    } else {
+        write!(wr, "!!binary {}", base64::encode(bytes))
+    }
+}
+
+fn quote_str(
+    wr: &mut dyn fmt::Write,
+    v: &str,
+    single_quotes: bool,
+    ascii_only: bool,
+) -> fmt::Result {
+    if single_quotes && can_single_quote(v) {
+        single_quote_str(wr, v)
+    } else {
+        escape_str_with(wr, v, ascii_only)
+    }
+}
+
+fn single_quote_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
+    wr.write_str("'")?;
+    wr.write_str(&v.replace('\'', "''"))?;
+    wr.write_str("'")?;
+    Ok(())
+}
+
+/// Check if the string requires quoting.
+/// Strings starting with any of the following characters must be quoted.
+/// :, &, *, ?, |, -, <, >, =, !, %, @
+/// Strings containing any of the following characters must be quoted.
+/// {, }, [, ], ,, #, `
+///
+/// If the string contains any of the following control characters, it must be escaped with double quotes:
+/// \0, \x01, \x02, \x03, \x04, \x05, \x06, \a, \b, \t, \n, \v, \f, \r, \x0e, \x0f, \x10, \x11, \x12, \x13, \x14, \x15, \x16, \x17, \x18, \x19, \x1a, \e, \x1c, \x1d, \x1e, \x1f, \N, \_, \L, \P
+///
+/// Finally, there are other cases when the strings must be quoted, no matter if you're using single or double quotes:
+/// * When the string is true or false (otherwise, it would be treated as a boolean value);
+/// * When the string is null or ~ (otherwise, it would be considered as a null value);
+/// * When the string looks like a number, such as integers (e.g. 2, 14, etc.), floats (e.g. 2.6, 14.9) and exponential numbers (e.g. 12e7, etc.) (otherwise, it would be treated as a numeric value);
+/// * When the string looks like a date (e.g. 2014-12-31) (otherwise it would be automatically converted into a Unix timestamp).
+pub(crate) fn need_quotes(string: &str) -> bool {
+    fn need_quotes_spaces(string: &str) -> bool {
+        string.starts_with(' ') || string.ends_with(' ')
+    }
+
+    string.is_empty()
+        || need_quotes_spaces(string)
+        || string.starts_with(|character: char| {
+            matches!(
+                character,
+                '&' | '*' | '?' | '|' | '-' | '<' | '>' | '=' | '!' | '%' | '@'
+            )
+        })
+        || string.contains(|character: char| {
+            matches!(character, ':'
+                | '{'
+                | '}'
+                | '['
+                | ']'
+                | ','
+                | '#'
+                | '`'
+                | '\"'
+                | '\''
+                | '\\'
+                | '\0'..='\x06'
+                | '\t'
+                | '\n'
+                | '\r'
+                | '\x0e'..='\x1a'
+                | '\x1c'..='\x1f'
+                | '\u{85}'
+                | '\u{2028}'
+                | '\u{2029}'
+                | '\u{feff}'
+            )
+        })
+        || !string.chars().all(is_printable)
+        || [
+            // http://yaml.org/type/bool.html
+            // Note: 'y', 'Y', 'n', 'N', is not quoted deliberately, as in libyaml. PyYAML also parse
+            // them as string, not booleans, although it is violating the YAML 1.1 specification.
+            // See https://github.com/dtolnay/serde-yaml/pull/83#discussion_r152628088.
+            "yes", "Yes", "YES", "no", "No", "NO", "True", "TRUE", "true", "False", "FALSE",
+            "false", "on", "On", "ON", "off", "Off", "OFF",
+            // http://yaml.org/type/null.html
+            "null", "Null", "NULL", "~",
+        ]
+        .contains(&string)
+        || string.starts_with('.')
+        || string.starts_with("0x")
+        || string.parse::<i64>().is_ok()
+        || string.parse::<f64>().is_ok()
+        // anything else the loader resolves, like `0o17` or `+.inf`
+        || !matches!(YamlInput::from_str(string), YamlInput::String(_))
+}
+
+/// Tell how the emitter writes `scalar` in `context`, where it takes a plain style
+/// whenever it can. The emitter also quotes strings that do not need it with
+/// [`QuoteStyle::AlwaysSingle`] and [`QuoteStyle::AlwaysDouble`].
+///
+/// A plain key cannot start with `?` or hold a `:`, which would be read as the
+/// indicators of an explicit key and of its value; values follow the same rules. A key
+/// longer than 1024 characters, as written in the quotes the emitter picks for it by
+/// default, is [`QuoteRequirement::ExplicitKey`], and a value never is.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::emitter::{needs_quoting, QuoteRequirement, ScalarContext};
+///
+/// assert_eq!(needs_quoting("web", ScalarContext::Value), QuoteRequirement::Plain);
+/// assert_eq!(needs_quoting("true", ScalarContext::Value), QuoteRequirement::Quotes);
+/// assert_eq!(needs_quoting("a\nb", ScalarContext::Key), QuoteRequirement::DoubleQuotes);
+/// assert_eq!(needs_quoting("y", ScalarContext::Key), QuoteRequirement::Plain);
+/// assert_eq!(needs_quoting("y", ScalarContext::Yaml11Key), QuoteRequirement::Quotes);
+/// assert_eq!(needs_quoting("?x", ScalarContext::Key), QuoteRequirement::Quotes);
+///
+/// let long = "a".repeat(1025);
+/// assert_eq!(needs_quoting(&long, ScalarContext::Value), QuoteRequirement::Plain);
+/// assert_eq!(needs_quoting(&long, ScalarContext::Key), QuoteRequirement::ExplicitKey);
+/// ```
+pub fn needs_quoting(scalar: &str, context: ScalarContext) -> QuoteRequirement {
+    let requirement = quote_requirement(scalar, context);
+    if context == ScalarContext::Value {
+        return requirement;
+    }
+    let text = match requirement {
+        QuoteRequirement::Plain => Cow::Borrowed(scalar),
+        QuoteRequirement::Quotes if !scalar.contains('\'') => Cow::Owned(format!("'{}'", scalar)),
+        _ => Cow::Owned(escape_double_quoted(scalar)),
+    };
+    if is_long_key(&text) {
+        QuoteRequirement::ExplicitKey
+    } else {
+        requirement
+    }
+}
+
+/// How `scalar` is quoted in `context`, whatever its length.
+fn quote_requirement(scalar: &str, context: ScalarContext) -> QuoteRequirement {
+    if !can_single_quote(scalar) {
+        QuoteRequirement::DoubleQuotes
+    } else if need_quotes(scalar)
+        || (context == ScalarContext::Yaml11Key && is_yaml11_scalar(scalar))
+    {
+        QuoteRequirement::Quotes
+    } else {
+        QuoteRequirement::Plain
+    }
+}
+
+/// Check if the written key `text` is longer than a key without a `?` indicator can
+/// be, which a reader looks for its `:` in.
+fn is_long_key(text: &str) -> bool {
+    text.chars().count() > 1024
+}
+
+/// Write `scalar` in double quotes, escaped as the emitter does.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::emitter::escape_double_quoted;
+///
+/// assert_eq!(escape_double_quoted("say \"hi\"\n"), r#""say \"hi\"\n""#);
+/// ```
+pub fn escape_double_quoted(scalar: &str) -> String {
+    let mut out = String::with_capacity(scalar.len() + 2);
+    // writing to a string cannot fail
+    escape_str(&mut out, scalar).unwrap();
+    out
+}
+
+/// Check if a YAML 1.1 reader resolves the plain scalar `string` to something else than a
+/// string, where the core schema used by this crate does not.
+fn is_yaml11_scalar(string: &str) -> bool {
+    let digits = string
+        .strip_prefix(|c| c == '+' || c == '-')
+        .unwrap_or(string);
+    ["y", "Y", "n", "N"].contains(&string)
+        || digits.strip_prefix("0b").is_some_and(|bits| {
+            !bits.is_empty() && bits.chars().all(|c| matches!(c, '0' | '1' | '_'))
+        })
+        || (digits.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+            && digits.contains('_')
+            && digits.replace('_', "").parse::<f64>().is_ok())
+}
+
+#[cfg(test)]
+#[allow(clippy::needless_borrow)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_emit_simple() {
+        let s = "
+# comment
+a0 bb: val
+a1:
+    b1: 4
+    b2: d
+a2: 4 # i'm comment
+a3: [1, 2, 3]
+a4:
+    - [a1, a2]
+    - 2
+";
+
+        let docs = YamlLoader::load_from_str(&s).unwrap();
+        let doc = docs[0].clone().into();
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(&doc).unwrap();
+        }
+        println!("original:\n{}", s);
+        println!("emitted:\n{}", writer);
+        let docs_new = match YamlLoader::load_from_str(&writer) {
+            Ok(y) => y,
+            Err(e) => panic!("{}", e),
+        };
+        let doc_new = &docs_new[0];
+
+        assert_eq!(&docs[0], doc_new);
+    }
+
+    #[test]
+    fn test_emit_complex() {
+        let s = r#"
+cataloge:
+  product1: &coffee   { name: Coffee,    price: 2.5  ,  unit: 1l  }
+  product2: &cookies  { name: Cookies!,  price: 3.40 ,  unit: 400g}
+
+products:
+  *coffee:
+    amount: 4
+  *cookies:
+    amount: 4
+  [1,2,3,4]:
+    array key
+  2.4:
+    real key
+  true:
+    bool key
+  {}:
+    empty hash key
+            "#;
+        let docs = YamlLoader::load_from_str(&s).unwrap();
+        let doc = docs[0].clone().into();
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(&doc).unwrap();
+        }
+        let docs_new = match YamlLoader::load_from_str(&writer) {
+            Ok(y) => y,
+            Err(e) => panic!("{}", e),
+        };
+        let doc_new = &docs_new[0];
+        assert_eq!(&docs[0], doc_new);
+    }
+
+    #[test]
+    fn test_emit_avoid_quotes() {
+        let s = r#"---
+a7: 你好
+boolean: "true"
+boolean2: "false"
+date: 2014-12-31
+empty_string: ""
+empty_string1: " "
+empty_string2: "    a"
+empty_string3: "    a "
+exp: "12e7"
+field: ":"
+field2: "{"
+field3: "\\"
+field4: "\n"
+field5: "can't avoid quote"
+float: "2.6"
+int: "4"
+nullable: "null"
+nullable2: "~"
+products:
+  "*coffee":
+    amount: 4
+  "*cookies":
+    amount: 4
+  ".milk":
+    amount: 1
+  "2.4": real key
+  "[1,2,3,4]": array key
+  "true": bool key
+  "{}": empty hash key
+x: test
+y: avoid quoting here
+z: string with spaces"#;
+
+        let docs = YamlLoader::load_from_str(&s).unwrap();
+        let doc = docs[0].clone().into();
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(&doc).unwrap();
+        }
+
+        assert_eq!(s, writer, "actual:\n\n{}\n", writer);
+    }
+
+    #[test]
+    fn emit_quoted_bools() {
+        let input = r#"---
+string0: yes
+string1: no
+string2: "true"
+string3: "false"
+string4: "~"
+null0: ~
+[true, false]: real_bools
+[True, TRUE, False, FALSE, y,Y,yes,Yes,YES,n,N,no,No,NO,on,On,ON,off,Off,OFF]: false_bools
+bool0: true
+bool1: false"#;
+        let expected = r#"---
+string0: "yes"
+string1: "no"
+string2: "true"
+string3: "false"
+string4: "~"
+null0: ~
+? - true
+  - false
+: real_bools
+? - "True"
+  - "TRUE"
+  - "False"
+  - "FALSE"
+  - y
+  - Y
+  - "yes"
+  - "Yes"
+  - "YES"
+  - n
+  - N
+  - "no"
+  - "No"
+  - "NO"
+  - "on"
+  - "On"
+  - "ON"
+  - "off"
+  - "Off"
+  - "OFF"
+: false_bools
+bool0: true
+bool1: false"#;
+
+        let docs = YamlLoader::load_from_str(&input).unwrap();
+        let doc = docs[0].clone().into();
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(&doc).unwrap();
+        }
+
+        assert_eq!(
+            expected, writer,
+            "expected:\n{}\nactual:\n{}\n",
+            expected, writer
+        );
+    }
+
+    #[test]
+    fn test_empty_and_nested() {
+        test_empty_and_nested_flag(false)
+    }
+
+    #[test]
+    fn test_empty_and_nested_compact() {
+        test_empty_and_nested_flag(true)
+    }
+
+    fn test_empty_and_nested_flag(compact: bool) {
+        let s = if compact {
+            r#"---
+a:
+  b:
+    c: hello
+  d: {}
+e:
+  - f
+  - g
+  - h: []"#
+        } else {
+            r#"---
+a:
+  b:
+    c: hello
+  d: {}
+e:
+  - f
+  - g
+  -
+    h: []"#
+        };
+
+        let docs = YamlLoader::load_from_str(&s).unwrap();
+        let doc = docs[0].clone().into();
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.compact(compact);
+            emitter.dump(&doc).unwrap();
+        }
+
+        assert_eq!(s, writer);
+    }
+
+    #[test]
+    fn test_nested_arrays() {
+        let s = r#"---
+a:
+  - b
+  - - c
+    - d
+    - - e
+      - f"#;
+
+        let docs = YamlLoader::load_from_str(&s).unwrap();
+        let doc = docs[0].clone().into();
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(&doc).unwrap();
+        }
+        println!("original:\n{}", s);
+        println!("emitted:\n{}", writer);
+
+        assert_eq!(s, writer);
+    }
+
+    #[test]
+    fn test_deeply_nested_arrays() {
+        let s = r#"---
+a:
+  - b
+  - - c
+    - d
+    - - e
+      - - f
+      - - e"#;
+
+        let docs = YamlLoader::load_from_str(&s).unwrap();
+        let doc = docs[0].clone().into();
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(&doc).unwrap();
+        }
+        println!("original:\n{}", s);
+        println!("emitted:\n{}", writer);
+
+        assert_eq!(s, writer);
+    }
+
+    #[test]
+    fn test_emit_binary() {
+        let mut hash = HashOutput::new();
+        hash.insert(
+            YamlOutput::String("bytes".to_owned()),
+            YamlOutput::Binary(vec![0, 1, 2, 0xff]),
+        );
+        hash.insert(
+            YamlOutput::String("empty".to_owned()),
+            YamlOutput::Binary(vec![]),
+        );
+        hash.insert(
+            YamlOutput::String("text".to_owned()),
+            YamlOutput::String("nul\0".to_owned()),
+        );
+        hash.insert(
+            YamlOutput::String("tab".to_owned()),
+            YamlOutput::String("a\tb".to_owned()),
+        );
+        let doc = YamlOutput::Hash(hash);
+
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(&doc).unwrap();
+        }
+        assert_eq!(
+            writer,
+            "---\nbytes: !!binary AAEC/w==\nempty: !!binary \"\"\ntext: \"nul\\u0000\"\ntab: \"a\\tb\""
+        );
+
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.binary_strings(true);
+            emitter.dump(&doc).unwrap();
+        }
+        assert_eq!(
+            writer,
+            "---\nbytes: !!binary AAEC/w==\nempty: !!binary \"\"\ntext: !!binary bnVsAA==\ntab: \"a\\tb\""
+        );
+        YamlLoader::load_from_str(&writer).unwrap();
+    }
+
+    #[test]
+    fn test_nested_hashes() {
+        let s = r#"---
+a:
+  b:
+    c:
+      d:
+        e: f"#;
+
+        let docs = YamlLoader::load_from_str(&s).unwrap();
+        let doc = docs[0].clone().into();
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.dump(&doc).unwrap();
+        }
+        println!("original:\n{}", s);
+        println!("emitted:\n{}", writer);
+
+        assert_eq!(s, writer);
+    }
+
+    #[test]
+    fn test_key_type_stability() {
+        let keys = [
+            "01234",
+            "0x1F",
+            "0o17",
+            "+12",
+            "-3",
+            "1e3",
+            ".5",
+            "+.inf",
+            "-.Inf",
+            ".NaN",
+            "~",
+            "null",
+            "true",
+            "True",
+            "y",
+            "n",
+            "0b101",
+            "1_000",
+            "a\nb",
+            "a long key with words",
+        ];
+        let mut h = HashOutput::new();
+        for (i, k) in keys.iter().enumerate() {
+            h.insert(
+                YamlOutput::String((*k).to_owned()),
+                YamlOutput::Integer(i as i64),
+            );
+        }
+        h.insert(YamlOutput::Integer(7), YamlOutput::Null);
+        h.insert(YamlOutput::Real("1.5".to_owned()), YamlOutput::Null);
+        let doc = YamlOutput::Hash(h);
+
+        for &yaml11 in &[false, true] {
+            let mut out = String::new();
+            {
+                let mut emitter = YamlEmitter::new(&mut out);
+                emitter.multiline_strings(true);
+                emitter.fold_width(Some(5));
+                emitter.quote_yaml11_keys(yaml11);
+                emitter.dump(&doc).unwrap();
             }
-            YamlOutput::Alias(ref s) => {
-                write!(self.writer, "*{}", s)?;
-                Ok(())
+            let reloaded = YamlLoader::load_from_str(&out).unwrap().remove(0);
+            assert_eq!(YamlOutput::from(reloaded), doc, "{}", out);
+            let quoted = |k: &str| out.contains(&format!("\n\"{}\":", k));
+            for k in &[
+                "01234", "0x1F", "0o17", "+12", "+.inf", "-.Inf", ".NaN", "True",
+            ] {
+                assert!(quoted(k), "{} in {}", k, out);
             }
-            YamlOutput::Anchored(ref s, ref data) => {
-                write!(self.writer, "&{}", s)?;
-                self.emit_val(false, &*data)?;
-                Ok(())
+            for k in &["y", "n", "0b101", "1_000"] {
+                assert_eq!(quoted(k), yaml11, "{} in {}", k, out);
             }
+            assert!(out.contains("\n\"a\\nb\": 18\na long key with words: 19\n7: ~\n1.5: ~"));
         }
     }
 
-    fn emit_array(&mut self, v: &[YamlOutput]) -> EmitResult {
-        if v.is_empty() {
-            write!(self.writer, "[]")?;
-        } else {
-            self.level += 1;
-            for (cnt, x) in v.iter().enumerate() {
-                if cnt > 0 {
-                    writeln!(self.writer)?;
-                    self.write_indent()?;
-                }
-                write!(self.writer, "-")?;
-                self.emit_val(true, x)?;
+    #[test]
+    fn test_flow_style() {
+        let s = "---
+matrix:
+  - - 1
+    - 0.5
+  - - -2
+    - 1
+point:
+  x: 1
+  \"y, z\": \"a: b\"
+nested:
+  - a
+  - - b
+    - c
+empty: []
+anchored: &p
+  - 1
+  - 2
+alias: *p
+long:
+  - one
+  - two
+  - three";
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let doc: YamlOutput = docs[0].clone().into();
+        let emit = |width: Option<usize>, scalars: bool| {
+            let mut out = String::new();
+            {
+                let mut emitter = YamlEmitter::new(&mut out);
+                emitter.flow_width(width);
+                emitter.flow_scalar_collections(scalars);
+                emitter.dump(&doc).unwrap();
             }
-            self.level -= 1;
+            assert_eq!(YamlLoader::load_from_str(&out).unwrap(), docs, "{}", out);
+            out
+        };
+        assert_eq!(emit(None, false), s);
+        assert_eq!(
+            emit(Some(16), false),
+            "---
+matrix:
+  - [1, 0.5]
+  - [-2, 1]
+point:
+  x: 1
+  \"y, z\": \"a: b\"
+nested: [a, [b, c]]
+empty: []
+anchored: &p [1, 2]
+alias: *p
+long:
+  - one
+  - two
+  - three"
+        );
+        assert_eq!(
+            emit(None, true),
+            "---
+matrix:
+  - [1, 0.5]
+  - [-2, 1]
+point: {x: 1, \"y, z\": \"a: b\"}
+nested:
+  - a
+  - [b, c]
+empty: []
+anchored: &p [1, 2]
+alias: *p
+long: [one, two, three]"
+        );
+
+        // collections with comments stay in block style
+        let s = "---\na: # about a\n  - 1 # one\n  - 2\nb: [1, 2] # about b";
+        let mut load_options = crate::yaml::LoadOptions::new();
+        load_options.keep_comments(true);
+        let doc: YamlOutput = YamlLoader::load_from_str_with_options(s, &load_options)
+            .unwrap()
+            .remove(0)
+            .into();
+        let mut out = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.flow_scalar_collections(true);
+            emitter.dump(&doc).unwrap();
         }
-        Ok(())
+        assert_eq!(out, s);
     }
 
-    fn emit_hash(&mut self, h: &HashOutput) -> EmitResult {
-        if h.is_empty() {
-            self.writer.write_str("{}")?;
-        } else {
-            self.level += 1;
-            for (cnt, (k, v)) in h.iter().enumerate() {
-                let complex_key = matches!(*k, YamlOutput::Hash(_) | YamlOutput::Array(_));
-                if cnt > 0 {
-                    writeln!(self.writer)?;
-                    self.write_indent()?;
-                }
-                if complex_key {
-                    write!(self.writer, "?")?;
-                    self.emit_val(true, k)?;
-                    writeln!(self.writer)?;
-                    self.write_indent()?;
-                    write!(self.writer, ":")?;
-                    self.emit_val(true, v)?;
-                } else {
-                    self.emit_node(k)?;
-                    write!(self.writer, ":")?;
-                    self.emit_val(false, v)?;
-                }
+    #[test]
+    fn test_max_line_width() {
+        let s = "
+description: a plain scalar long enough to be wrapped more than once
+nested:
+  - name: another long value that needs wrapping
+    ids: [1000, 2000, 3000, 4000, 5000, 6000]
+  - averyveryveryverylongwordthatcannotbebroken and more
+quoted: 'a quoted string: with words that stay on one line'
+key with a long name that is not wrapped at all: x
+";
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let doc: YamlOutput = docs[0].clone().into();
+        let mut out = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.flow_scalar_collections(true);
+            emitter.max_line_width(Some(30));
+            emitter.dump(&doc).unwrap();
+        }
+        assert_eq!(
+            out,
+            "---
+description: a plain scalar
+  long enough to be wrapped
+  more than once
+nested:
+  - name: another long value
+      that needs wrapping
+    ids: [1000, 2000, 3000,
+      4000, 5000, 6000]
+  - averyveryveryverylongwordthatcannotbebroken
+    and more
+quoted: \"a quoted string: with words that stay on one line\"
+key with a long name that is not wrapped at all: x"
+        );
+        assert_eq!(YamlLoader::load_from_str(&out).unwrap(), docs);
+    }
+
+    #[test]
+    #[cfg(feature = "unicode")]
+    fn test_max_line_width_display_width() {
+        // wide characters take two columns, combining marks none
+        let s = "a: 你好 你好 你好 你好 你好\nb: cafe\u{301} cafe\u{301} cafe\u{301}\n";
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let doc: YamlOutput = docs[0].clone().into();
+        let mut out = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.max_line_width(Some(12));
+            emitter.dump(&doc).unwrap();
+        }
+        assert_eq!(
+            out,
+            "---\na: 你好 你好\n  你好 你好\n  你好\nb: cafe\u{301} cafe\u{301}\n  cafe\u{301}"
+        );
+        assert_eq!(YamlLoader::load_from_str(&out).unwrap(), docs);
+    }
+
+    #[test]
+    fn test_fold_grapheme_clusters() {
+        // the spaces before a combining accent, a zero width joiner and a skin tone
+        // modifier start a grapheme cluster
+        let s = "aaaa \u{301}bbbb cccc \u{200d}dddd eeee \u{1f3fd}ffff gggg hhhh";
+        let mut out = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.fold_width(Some(4));
+            emitter.dump(&YamlOutput::from(s)).unwrap();
+        }
+        assert_eq!(
+            out,
+            "---\n>-\n  aaaa \u{301}bbbb\n  cccc \u{200d}dddd\n  eeee \u{1f3fd}ffff\n  gggg\n  hhhh"
+        );
+        let docs = YamlLoader::load_from_str(&out).unwrap();
+        assert_eq!(docs[0].as_str(), Some(s));
+    }
+
+    #[test]
+    fn test_conservative_folding() {
+        let strings = [
+            "see https://example.com for the details of this",
+            "the commit is 3f2a9c1e8b7d6a5f4e3d2c1b0a9f8e7d if you ask",
+            "only words of prose here 1234567890123456789 digits",
+        ];
+        let doc = YamlOutput::from(strings.to_vec());
+        let emit = |conservative: bool| {
+            let mut out = String::new();
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.fold_width(Some(30));
+            emitter.max_line_width(Some(30));
+            emitter.conservative_folding(conservative);
+            emitter.dump(&doc).unwrap();
+            out
+        };
+        assert_eq!(
+            emit(true),
+            "---
+- \"see https://example.com for the details of this\"
+- the commit is 3f2a9c1e8b7d6a5f4e3d2c1b0a9f8e7d if you ask
+- >-
+  only words of prose here
+  1234567890123456789 digits"
+        );
+        assert!(emit(false).starts_with("---\n- >-\n  see https://example.com for\n"));
+    }
+
+    #[test]
+    fn test_needs_quoting() {
+        use QuoteRequirement::*;
+        let contexts = [
+            ScalarContext::Value,
+            ScalarContext::Key,
+            ScalarContext::Yaml11Key,
+        ];
+        let cases: &[(&str, [QuoteRequirement; 3])] = &[
+            ("web", [Plain, Plain, Plain]),
+            ("?x", [Quotes, Quotes, Quotes]),
+            ("? x", [Quotes, Quotes, Quotes]),
+            ("a:b", [Quotes, Quotes, Quotes]),
+            ("a: b", [Quotes, Quotes, Quotes]),
+            ("a:", [Quotes, Quotes, Quotes]),
+            ("on", [Quotes, Quotes, Quotes]),
+            ("y", [Plain, Plain, Quotes]),
+            ("a\tb", [DoubleQuotes, DoubleQuotes, DoubleQuotes]),
+        ];
+        for &(scalar, ref expected) in cases {
+            for (context, expected) in contexts.iter().zip(expected) {
+                assert_eq!(needs_quoting(scalar, *context), *expected, "{:?}", scalar);
             }
-            self.level -= 1;
         }
-        Ok(())
+        // the limit counts the quotes and escapes written with the key
+        let plain = "a".repeat(1024);
+        let quoted = format!("{}:", "a".repeat(1021));
+        let escaped = format!("{}\t", "a".repeat(1020));
+        for &(ref scalar, value, key) in &[
+            (plain.clone(), Plain, Plain),
+            (plain + "a", Plain, ExplicitKey),
+            (quoted.clone(), Quotes, Quotes),
+            (quoted + "a", Quotes, ExplicitKey),
+            (escaped.clone(), DoubleQuotes, DoubleQuotes),
+            (escaped + "a", DoubleQuotes, ExplicitKey),
+        ] {
+            assert_eq!(needs_quoting(scalar, ScalarContext::Value), value);
+            assert_eq!(needs_quoting(scalar, ScalarContext::Key), key);
+            assert_eq!(needs_quoting(scalar, ScalarContext::Yaml11Key), key);
+        }
     }
 
-    /// Emit a yaml as a hash or array value: i.e., which should appear
-    /// following a ":" or "-", either after a space, or on a new line.
-    /// If `inline` is true, then the preceding characters are distinct
-    /// and short enough to respect the compact flag.
-    fn emit_val(&mut self, inline: bool, val: &YamlOutput) -> EmitResult {
-        match *val {
-            YamlOutput::Array(ref v) => {
-                if (inline && self.compact) || v.is_empty() {
-                    write!(self.writer, " ")?;
-                } else {
-                    writeln!(self.writer)?;
-                    self.level += 1;
-                    self.write_indent()?;
-                    self.level -= 1;
-                }
-                self.emit_array(v)
-            }
-            YamlOutput::Hash(ref h) => {
-                if (inline && self.compact) || h.is_empty() {
-                    write!(self.writer, " ")?;
-                } else {
-                    writeln!(self.writer)?;
-                    self.level += 1;
-                    self.write_indent()?;
-                    self.level -= 1;
+    #[test]
+    fn test_long_keys() {
+        let long = "x y".repeat(400);
+        let mut h = HashOutput::new();
+        h.insert(YamlOutput::String(long.clone()), YamlOutput::Integer(1));
+        h.insert(
+            YamlOutput::String("short".to_owned()),
+            YamlOutput::Integer(2),
+        );
+        let doc = YamlOutput::Hash(h);
+        let emit = |setup: &dyn Fn(&mut YamlEmitter)| {
+            let mut out = String::new();
+            let mut emitter = YamlEmitter::new(&mut out);
+            setup(&mut emitter);
+            emitter.dump(&doc).unwrap();
+            out
+        };
+        let expected = format!("---\n? {}\n: 1\nshort: 2", long);
+        assert_eq!(emit(&|_| {}), expected);
+        // too long for an implicit key in flow style too
+        assert_eq!(
+            emit(&|emitter: &mut YamlEmitter| {
+                emitter.flow_width(Some(usize::MAX));
+            }),
+            expected
+        );
+        assert_eq!(
+            emit(&|emitter: &mut YamlEmitter| {
+                emitter.align_values(Some(2));
+            }),
+            expected
+        );
+        let loaded = YamlLoader::load_from_str(&expected).unwrap();
+        assert_eq!(loaded[0][&*long].as_i64(), Some(1));
+        #[cfg(feature = "stream")]
+        {
+            let mut out = String::new();
+            {
+                let mut emitter = YamlEmitter::new(&mut out);
+                let mut stream = emitter.stream();
+                stream.start_document().unwrap();
+                stream.start_mapping().unwrap();
+                for &(ref k, v) in &[(long.clone(), 1), ("short".to_owned(), 2)] {
+                    stream.key(&YamlOutput::String(k.clone())).unwrap();
+                    stream.node(&YamlOutput::Integer(v)).unwrap();
                 }
-                self.emit_hash(h)
-            }
-            _ => {
-                write!(self.writer, " ")?;
-                self.emit_node(val)
+                stream.end_mapping().unwrap();
+                stream.end_document().unwrap();
             }
+            assert_eq!(out, expected);
         }
     }
-}
 
-/// Check if the string requires quoting.
-/// Strings starting with any of the following characters must be quoted.
-/// :, &, *, ?, |, -, <, >, =, !, %, @
-/// Strings containing any of the following characters must be quoted.
-/// {, }, [, ], ,, #, `
-///
-/// If the string contains any of the following control characters, it must be escaped with double quotes:
-/// \0, \x01, \x02, \x03, \x04, \x05, \x06, \a, \b, \t, \n, \v, \f, \r, \x0e, \x0f, \x10, \x11, \x12, \x13, \x14, \x15, \x16, \x17, \x18, \x19, \x1a, \e, \x1c, \x1d, \x1e, \x1f, \N, \_, \L, \P
-///
-/// Finally, there are other cases when the strings must be quoted, no matter if you're using single or double quotes:
-/// * When the string is true or false (otherwise, it would be treated as a boolean value);
-/// * When the string is null or ~ (otherwise, it would be considered as a null value);
-/// * When the string looks like a number, such as integers (e.g. 2, 14, etc.), floats (e.g. 2.6, 14.9) and exponential numbers (e.g. 12e7, etc.) (otherwise, it would be treated as a numeric value);
-/// * When the string looks like a date (e.g. 2014-12-31) (otherwise it would be automatically converted into a Unix timestamp).
-fn need_quotes(string: &str) -> bool {
-    fn need_quotes_spaces(string: &str) -> bool {
-        string.starts_with(' ') || string.ends_with(' ')
+    #[test]
+    fn test_quote_style() {
+        let strings = [
+            "plain",
+            "it's",
+            "012",
+            "null",
+            "%x",
+            "a: b",
+            "tab\there",
+            "\u{85}",
+            " lead",
+            "",
+        ];
+        let doc = YamlOutput::Array(
+            strings
+                .iter()
+                .map(|v| YamlOutput::String((*v).to_owned()))
+                .collect(),
+        );
+        let emit = |style| {
+            let mut out = String::new();
+            {
+                let mut emitter = YamlEmitter::new(&mut out);
+                emitter.quote_style(style);
+                emitter.dump(&doc).unwrap();
+            }
+            let reloaded = YamlLoader::load_from_str(&out).unwrap().remove(0);
+            assert_eq!(YamlOutput::from(reloaded), doc, "{}", out);
+            out[6..]
+                .split("\n- ")
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            emit(QuoteStyle::Plain),
+            [
+                "plain",
+                "\"it's\"",
+                "'012'",
+                "'null'",
+                "'%x'",
+                "'a: b'",
+                "\"tab\\there\"",
+                "\"\u{85}\"",
+                "' lead'",
+                "''"
+            ]
+        );
+        assert_eq!(
+            emit(QuoteStyle::SingleWhenNeeded),
+            [
+                "plain",
+                "'it''s'",
+                "'012'",
+                "'null'",
+                "'%x'",
+                "'a: b'",
+                "\"tab\\there\"",
+                "\"\u{85}\"",
+                "' lead'",
+                "''"
+            ]
+        );
+        assert_eq!(
+            emit(QuoteStyle::DoubleWhenNeeded),
+            [
+                "plain",
+                "\"it's\"",
+                "\"012\"",
+                "\"null\"",
+                "\"%x\"",
+                "\"a: b\"",
+                "\"tab\\there\"",
+                "\"\u{85}\"",
+                "\" lead\"",
+                "\"\""
+            ]
+        );
+        assert_eq!(
+            emit(QuoteStyle::AlwaysSingle),
+            [
+                "'plain'",
+                "'it''s'",
+                "'012'",
+                "'null'",
+                "'%x'",
+                "'a: b'",
+                "\"tab\\there\"",
+                "\"\u{85}\"",
+                "' lead'",
+                "''"
+            ]
+        );
+        assert_eq!(
+            emit(QuoteStyle::AlwaysDouble),
+            [
+                "\"plain\"",
+                "\"it's\"",
+                "\"012\"",
+                "\"null\"",
+                "\"%x\"",
+                "\"a: b\"",
+                "\"tab\\there\"",
+                "\"\u{85}\"",
+                "\" lead\"",
+                "\"\""
+            ]
+        );
     }
 
-    string.is_empty()
-        || need_quotes_spaces(string)
-        || string.starts_with(|character: char| {
-            matches!(
-                character,
-                '&' | '*' | '?' | '|' | '-' | '<' | '>' | '=' | '!' | '%' | '@'
-            )
-        })
-        || string.contains(|character: char| {
-            matches!(character, ':'
-                | '{'
-                | '}'
-                | '['
-                | ']'
-                | ','
-                | '#'
-                | '`'
-                | '\"'
-                | '\''
-                | '\\'
-                | '\0'..='\x06'
-                | '\t'
-                | '\n'
-                | '\r'
-                | '\x0e'..='\x1a'
-                | '\x1c'..='\x1f'
-            )
-        })
-        || [
-            // http://yaml.org/type/bool.html
-            // Note: 'y', 'Y', 'n', 'N', is not quoted deliberately, as in libyaml. PyYAML also parse
-            // them as string, not booleans, although it is violating the YAML 1.1 specification.
-            // See https://github.com/dtolnay/serde-yaml/pull/83#discussion_r152628088.
-            "yes", "Yes", "YES", "no", "No", "NO", "True", "TRUE", "true", "False", "FALSE",
-            "false", "on", "On", "ON", "off", "Off", "OFF",
-            // http://yaml.org/type/null.html
-            "null", "Null", "NULL", "~",
-        ]
-        .contains(&string)
-        || string.starts_with('.')
-        || string.starts_with("0x")
-        || string.parse::<i64>().is_ok()
-        || string.parse::<f64>().is_ok()
-}
+    #[test]
+    fn test_preserve_input_order() {
+        let s = "---\nzeta: 1\nalpha:\n  m: 1\n  b: 2\nmid: 3";
+        let doc = YamlLoader::load_from_str(s).unwrap()[0].clone().into();
+        let emit = |preserve: Option<bool>| {
+            let mut writer = String::new();
+            let mut emitter = YamlEmitter::new(&mut writer);
+            if let Some(preserve) = preserve {
+                emitter.preserve_input_order(preserve);
+            }
+            assert_eq!(emitter.is_preserve_input_order(), preserve.unwrap_or(true));
+            emitter.dump(&doc).unwrap();
+            writer
+        };
+        assert_eq!(emit(None), s);
+        assert_eq!(emit(Some(true)), s);
+        assert_eq!(
+            emit(Some(false)),
+            "---\nalpha:\n  b: 2\n  m: 1\nmid: 3\nzeta: 1"
+        );
+    }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::YamlLoader;
+    #[test]
+    fn test_sort_keys() {
+        let s = "{b: {y: 1, x: 2}, 2: two, a: [1, {d: 3, c: 4}], 10: ten}";
+        let doc = YamlLoader::load_from_str(s).unwrap()[0].clone().into();
+        let emit = |order: KeyOrder, flow: bool| {
+            let mut writer = String::new();
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.sort_keys(order);
+            if flow {
+                emitter.flow_width(Some(80));
+            }
+            emitter.dump(&doc).unwrap();
+            writer
+        };
+        assert_eq!(
+            emit(KeyOrder::Ascending, false),
+            "---\n2: two\n10: ten\na:\n  - 1\n  - c: 4\n    d: 3\nb:\n  x: 2\n  y: 1"
+        );
+        assert_eq!(
+            emit(KeyOrder::Descending, true),
+            "---\n{b: {y: 1, x: 2}, a: [1, {d: 3, c: 4}], 10: ten, 2: two}"
+        );
+        // equal keys keep their input order
+        let strings_last = |a: &YamlOutput, b: &YamlOutput| {
+            matches!(*a, YamlOutput::String(_)).cmp(&matches!(*b, YamlOutput::String(_)))
+        };
+        assert_eq!(
+            emit(KeyOrder::Custom(strings_last), true),
+            "---\n{2: two, 10: ten, b: {y: 1, x: 2}, a: [1, {d: 3, c: 4}]}"
+        );
+    }
 
     #[test]
-    fn test_emit_simple() {
-        let s = "
-# comment
-a0 bb: val
-a1:
-    b1: 4
-    b2: d
-a2: 4 # i'm comment
-a3: [1, 2, 3]
-a4:
-    - [a1, a2]
-    - 2
-";
+    fn test_emit_tags() {
+        let s = "---
+bucket: !Ref MyBucket
+name: !Sub
+  - a
+  - b
+attr: !GetAtt
+  a: b
+empty: !t []
+anchored: &a !t x
+!k key: 1
+long: !<tag:x> v";
+        let doc = YamlLoader::load_from_str(s).unwrap()[0].clone().into();
+        let mut writer = String::new();
+        YamlEmitter::new(&mut writer).dump(&doc).unwrap();
+        assert_eq!(s, writer);
 
-        let docs = YamlLoader::load_from_str(&s).unwrap();
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.flow_width(Some(80));
+            emitter.dump(&doc).unwrap();
+        }
+        assert!(writer.contains("name: !Sub [a, b]\n"), "{}", writer);
+        assert!(writer.contains("attr: !GetAtt {a: b}\n"), "{}", writer);
+    }
+
+    #[test]
+    fn test_emit_comments() {
+        let s = "---
+# top
+name: web # the name
+# about env
+env: # env vars
+  A: 1
+  # before B
+  B: |
+    x
+    y
+list:
+  - 1 # one
+  # two next
+  - 2
+  - a: 1 # in map
+    b: 2
+empty: [] # e
+base: &b # anchored
+  x: 1
+# the end";
+        let mut options = crate::LoadOptions::new();
+        options.keep_comments(true);
+        let docs = YamlLoader::load_from_str_with_options(s, &options).unwrap();
         let doc = docs[0].clone().into();
         let mut writer = String::new();
         {
             let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.multiline_strings(true);
             emitter.dump(&doc).unwrap();
         }
-        println!("original:\n{}", s);
-        println!("emitted:\n{}", writer);
-        let docs_new = match YamlLoader::load_from_str(&writer) {
-            Ok(y) => y,
-            Err(e) => panic!("{}", e),
+        assert_eq!(s, writer);
+    }
+
+    #[test]
+    fn test_emit_blank_lines() {
+        let commented = |node: YamlOutput, before: &[&str], blank_lines: usize| {
+            YamlOutput::Commented(
+                Box::new(node),
+                Comments {
+                    before: before.iter().map(|line| line.to_string()).collect(),
+                    blank_lines,
+                    ..Comments::default()
+                },
+            )
         };
-        let doc_new = &docs_new[0];
+        let mut h = HashOutput::new();
+        h.insert(
+            YamlOutput::String("first".to_owned()),
+            commented(YamlOutput::Integer(1), &[], 2),
+        );
+        h.insert(
+            YamlOutput::String("list".to_owned()),
+            commented(
+                YamlOutput::Array(vec![
+                    YamlOutput::Integer(1),
+                    commented(YamlOutput::Integer(2), &[" two"], 1),
+                ]),
+                &[" the list"],
+                1,
+            ),
+        );
+        let doc = YamlOutput::Hash(h);
 
-        assert_eq!(&docs[0], doc_new);
+        let mut writer = String::new();
+        YamlEmitter::new(&mut writer).dump(&doc).unwrap();
+        assert_eq!(
+            writer,
+            "---\nfirst: 1\n\n# the list\nlist:\n  - 1\n\n  # two\n  - 2"
+        );
+
+        let mut writer = String::new();
+        let mut emitter = YamlEmitter::new(&mut writer);
+        emitter.indent_sequences(false);
+        emitter.key_spacing(2);
+        emitter.dump(&doc).unwrap();
+        assert_eq!(
+            writer,
+            "---\nfirst: 1\n\n\n# the list\nlist:\n- 1\n\n# two\n- 2"
+        );
     }
 
     #[test]
-    fn test_emit_complex() {
-        let s = r#"
-cataloge:
-  product1: &coffee   { name: Coffee,    price: 2.5  ,  unit: 1l  }
-  product2: &cookies  { name: Cookies!,  price: 3.40 ,  unit: 400g}
+    fn test_align_values() {
+        let s = "a: 1\nlonger: two # note\nempty:\n'quoted key': 3\nnested:\n  x: 1\n  xyz: [1]\nb: 4\ncc: 5\nddd: 6\ne: 7\n";
+        let mut options = crate::LoadOptions::new();
+        options.keep_comments(true);
+        let doc: YamlOutput = YamlLoader::load_from_str_with_options(s, &options).unwrap()[0]
+            .clone()
+            .into();
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.align_values(Some(3));
+        emitter.null_style(NullStyle::Empty);
+        emitter.flow_scalar_collections(true);
+        emitter.dump(&doc).unwrap();
+        assert_eq!(
+            output,
+            "---
+a:      1
+longer: two # note
+empty:
+quoted key: 3
+nested:
+  x:   1
+  xyz: [1]
+b:   4
+cc:  5
+ddd: 6
+e: 7"
+        );
+        assert_eq!(
+            YamlLoader::load_from_str(&output).unwrap(),
+            YamlLoader::load_from_str(s).unwrap()
+        );
+    }
 
-products:
-  *coffee:
-    amount: 4
-  *cookies:
-    amount: 4
-  [1,2,3,4]:
-    array key
-  2.4:
-    real key
-  true:
-    bool key
-  {}:
-    empty hash key
-            "#;
-        let docs = YamlLoader::load_from_str(&s).unwrap();
-        let doc = docs[0].clone().into();
-        let mut writer = String::new();
+    #[test]
+    fn test_ascii_only() {
+        let s = "name: Zoë\nlines: \"één\\ntwee\\n\"\nflow: {clé: [ß, x]}\nsingle: it's\n";
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let doc: YamlOutput = docs[0].clone().into();
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.ascii_only(true);
+        emitter.multiline_strings(true);
+        emitter.flow_scalar_collections(true);
+        emitter.quote_style(QuoteStyle::AlwaysSingle);
+        emitter.dump(&doc).unwrap();
+        assert_eq!(
+            output,
+            "---
+name: \"Zo\\u00eb\"
+lines: \"\\u00e9\\u00e9n\\ntwee\\n\"
+flow:
+  \"cl\\u00e9\": [\"\\u00df\", 'x']
+single: 'it''s'"
+        );
+        assert!(output.is_ascii());
+        assert_eq!(YamlLoader::load_from_str(&output).unwrap(), docs);
+
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.ascii_only(true);
+        emitter.canonical(true);
+        emitter.dump(&doc).unwrap();
+        assert!(output.is_ascii());
+        assert_eq!(YamlLoader::load_from_str(&output).unwrap(), docs);
+    }
+
+    #[test]
+    fn test_ascii_only_properties() {
+        let emit = |s: &str| {
+            let mut options = crate::LoadOptions::new();
+            options.keep_comments(true);
+            let docs = YamlLoader::load_from_str_with_options(s, &options).unwrap();
+            let mut output = String::new();
+            let mut emitter = YamlEmitter::new(&mut output);
+            emitter.ascii_only(true);
+            emitter.dump(&docs[0].clone().into()).map(|()| output)
+        };
+        let not_ascii = |result: Result<String, EmitError>| match result {
+            Err(EmitError::Node(err)) => match *err.error() {
+                EmitError::NotAscii(c) => (err.path().to_string(), c),
+                ref error => panic!("unexpected error {}", error),
+            },
+            result => panic!("unexpected {:?}", result),
+        };
+
+        // tags are percent-encoded, which reads back as the same tag
+        let doc = YamlOutput::Array(vec![YamlOutput::Tagged(
+            "!tomató".to_owned(),
+            Box::new(YamlOutput::String("x".to_owned())),
+        )]);
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.ascii_only(true);
+        emitter.dump(&doc).unwrap();
+        assert_eq!(output, "---\n- !tomat%C3%B3 x");
+        let docs = YamlLoader::load_from_str(&output).unwrap();
+        assert_eq!(
+            docs[0][0],
+            YamlInput::Tagged(
+                "!tomató".to_owned(),
+                Box::new(YamlInput::String("x".to_owned()))
+            )
+        );
+
+        // comments and anchors have no escapes
+        assert_eq!(
+            not_ascii(emit("a: 1\nb: 2 # déjà vu\n")),
+            ("b".to_owned(), 'é')
+        );
+        assert_eq!(not_ascii(emit("# über\na: [1, 2]\n")), ("".to_owned(), 'ü'));
+        let mut h = HashOutput::new();
+        h.insert(
+            YamlOutput::String("a".to_owned()),
+            YamlOutput::Array(vec![
+                YamlOutput::Anchored("ñ".to_owned(), Box::new(YamlOutput::Integer(1))),
+                YamlOutput::Alias("ñ".to_owned()),
+            ]),
+        );
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.ascii_only(true);
+        let result = emitter.dump(&YamlOutput::Hash(h)).map(|()| output);
+        assert_eq!(not_ascii(result), ("a[0]".to_owned(), 'ñ'));
+        assert!(emit("a: &n x # plain\nb: *n\n").is_ok());
+    }
+
+    #[test]
+    fn test_scalar_styles() {
+        let s = "a: 'x'\nb: \"y\"\nc: |\n  one\n  two\nd: >-\n  folded\n  text\ne: 'it''s\n\n  two'\nf: [\"p\", 'q']\n'g': \"\\t\"\n";
+        let mut options = crate::LoadOptions::new();
+        options.keep_scalar_style(true);
+        let docs = YamlLoader::load_from_str_with_options(s, &options).unwrap();
+        let doc: YamlOutput = docs[0].clone().into();
+        let mut output = String::new();
+        YamlEmitter::new(&mut output).dump(&doc).unwrap();
+        assert_eq!(
+            output,
+            "---
+a: 'x'
+b: \"y\"
+c: |
+  one
+  two
+d: >-
+  folded text
+e: \"it's\\ntwo\"
+f:
+  - \"p\"
+  - 'q'
+g: \"\\t\""
+        );
+        assert_eq!(
+            YamlLoader::load_from_str(&output).unwrap(),
+            YamlLoader::load_from_str(s).unwrap()
+        );
+
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.flow_scalar_collections(true);
+        emitter.ascii_only(true);
+        let doc = YamlOutput::Array(vec![
+            YamlOutput::Styled(
+                Box::new(YamlOutput::String("é".to_owned())),
+                TScalarStyle::SingleQuoted,
+            ),
+            YamlOutput::Styled(
+                Box::new(YamlOutput::String("q".to_owned())),
+                TScalarStyle::SingleQuoted,
+            ),
+        ]);
+        emitter.dump(&doc).unwrap();
+        assert_eq!(output, "---\n[\"\\u00e9\", 'q']");
+    }
+
+    /// Describe `node` on `stream`, streaming its sequences and mappings.
+    #[cfg(feature = "stream")]
+    fn replay(stream: &mut StreamBuilder, node: &YamlOutput) {
+        match *node {
+            YamlOutput::Array(ref v) => {
+                stream.start_sequence().unwrap();
+                for x in v {
+                    replay(stream, x);
+                }
+                stream.end_sequence().unwrap();
+            }
+            YamlOutput::Hash(ref h) => {
+                stream.start_mapping().unwrap();
+                for (k, v) in h {
+                    stream.key(k).unwrap();
+                    replay(stream, v);
+                }
+                stream.end_mapping().unwrap();
+            }
+            _ => stream.node(node).unwrap(),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "stream")]
+    fn test_stream_matches_dump() {
+        let s = "
+a: 1
+b:
+  - x
+  - [1, [2, 3], []]
+  - c: {}
+    d: |
+      text
+  - - e
+    - f: [g]
+? [h, i]
+: j
+k: {l: m}
+---
+- 1
+---
+[]
+";
+        let docs: Vec<YamlOutput> = YamlLoader::load_from_str(s)
+            .unwrap()
+            .into_iter()
+            .map(YamlOutput::from)
+            .collect();
+        let settings: Vec<fn(&mut YamlEmitter)> = vec![
+            |_| {},
+            |emitter| emitter.profile(EmitProfile::GoYamlV3),
+            |emitter| emitter.profile(EmitProfile::SerdeYaml),
+            |emitter| {
+                emitter.compact(false);
+                emitter.multiline_strings(true);
+                emitter.explicit_end(true);
+            },
+        ];
+        for setup in settings {
+            let mut dumped = String::new();
+            {
+                let mut emitter = YamlEmitter::new(&mut dumped);
+                setup(&mut emitter);
+                emitter.dump_all(&docs).unwrap();
+            }
+            let mut streamed = String::new();
+            {
+                let mut emitter = YamlEmitter::new(&mut streamed);
+                setup(&mut emitter);
+                let mut stream = emitter.stream();
+                for doc in &docs {
+                    stream.start_document().unwrap();
+                    replay(&mut stream, doc);
+                    stream.end_document().unwrap();
+                }
+            }
+            assert_eq!(dumped, streamed);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "stream")]
+    fn test_stream_out_of_order() {
+        fn out_of_order(result: EmitResult) -> bool {
+            matches!(result, Err(EmitError::OutOfOrder(_)))
+        }
+        let one = YamlOutput::Integer(1);
+        let mut output = String::new();
         {
-            let mut emitter = YamlEmitter::new(&mut writer);
-            emitter.dump(&doc).unwrap();
+            let mut emitter = YamlEmitter::new(&mut output);
+            let mut stream = emitter.stream();
+            assert!(out_of_order(stream.node(&one)));
+            assert!(out_of_order(stream.end_document()));
+            stream.start_document().unwrap();
+            assert!(out_of_order(stream.start_document()));
+            assert!(out_of_order(stream.key(&one)));
+            assert!(out_of_order(stream.end_document()));
+            stream.start_mapping().unwrap();
+            assert!(out_of_order(stream.node(&one)));
+            assert!(out_of_order(stream.end_sequence()));
+            stream.key(&one).unwrap();
+            assert!(out_of_order(stream.key(&one)));
+            assert!(out_of_order(stream.end_mapping()));
+            stream.node(&one).unwrap();
+            assert!(out_of_order(stream.node(&one)));
+            stream.end_mapping().unwrap();
+            assert!(out_of_order(stream.node(&one)));
+            stream.end_document().unwrap();
         }
-        let docs_new = match YamlLoader::load_from_str(&writer) {
-            Ok(y) => y,
-            Err(e) => panic!("{}", e),
-        };
-        let doc_new = &docs_new[0];
-        assert_eq!(&docs[0], doc_new);
+        // the calls out of order write nothing
+        assert_eq!(output, "---\n1: 1");
     }
 
     #[test]
-    fn test_emit_avoid_quotes() {
-        let s = r#"---
-a7: 你好
-boolean: "true"
-boolean2: "false"
-date: 2014-12-31
-empty_string: ""
-empty_string1: " "
-empty_string2: "    a"
-empty_string3: "    a "
-exp: "12e7"
-field: ":"
-field2: "{"
-field3: "\\"
-field4: "\n"
-field5: "can't avoid quote"
-float: "2.6"
-int: "4"
-nullable: "null"
-nullable2: "~"
-products:
-  "*coffee":
-    amount: 4
-  "*cookies":
-    amount: 4
-  ".milk":
-    amount: 1
-  "2.4": real key
-  "[1,2,3,4]": array key
-  "true": bool key
-  "{}": empty hash key
-x: test
-y: avoid quoting here
-z: string with spaces"#;
+    fn test_anchor_namers() {
+        let load =
+            |s: &str| -> YamlOutput { YamlLoader::load_from_str(s).unwrap()[0].clone().into() };
+        // two documents merged into one, both using the anchor `base`
+        let mut h = HashOutput::new();
+        h.insert(
+            YamlOutput::String("first".to_owned()),
+            load("{x: &base 1, y: *base}"),
+        );
+        h.insert(
+            YamlOutput::String("second".to_owned()),
+            load("{x: &base 2, y: *base}"),
+        );
+        h.insert(
+            YamlOutput::String("third".to_owned()),
+            YamlOutput::Anchored("my anchor".to_owned(), Box::new(YamlOutput::Integer(3))),
+        );
+        let doc = YamlOutput::Hash(h);
 
-        let docs = YamlLoader::load_from_str(&s).unwrap();
-        let doc = docs[0].clone().into();
-        let mut writer = String::new();
+        let mut output = String::new();
         {
-            let mut emitter = YamlEmitter::new(&mut writer);
+            let mut namer = UniqueAnchors::new();
+            let mut emitter = YamlEmitter::new(&mut output);
+            emitter.anchor_namer(&mut namer);
             emitter.dump(&doc).unwrap();
         }
+        assert_eq!(
+            output,
+            "---
+first:
+  x: &base 1
+  y: *base
+second:
+  x: &base_2 2
+  y: *base_2
+third: &my_anchor 3"
+        );
+        assert_eq!(
+            YamlLoader::load_from_str(&output).unwrap()[0]["second"]["y"].as_i64(),
+            Some(2)
+        );
 
-        assert_eq!(s, writer, "actual:\n\n{}\n", writer);
+        // numbering starts again with each document
+        let docs = vec![load("[&a 1, &b 2, *a]"), load("[&c 3, *c]")];
+        let mut output = String::new();
+        {
+            let mut namer = NumberedAnchors::new("n");
+            let mut emitter = YamlEmitter::new(&mut output);
+            emitter.anchor_namer(&mut namer);
+            emitter.dump_all(&docs).unwrap();
+        }
+        assert_eq!(output, "---\n- &n1 1\n- &n2 2\n- *n1\n---\n- &n1 3\n- *n1");
     }
 
     #[test]
-    fn emit_quoted_bools() {
-        let input = r#"---
-string0: yes
-string1: no
-string2: "true"
-string3: "false"
-string4: "~"
-null0: ~
-[true, false]: real_bools
-[True, TRUE, False, FALSE, y,Y,yes,Yes,YES,n,N,no,No,NO,on,On,ON,off,Off,OFF]: false_bools
-bool0: true
-bool1: false"#;
-        let expected = r#"---
-string0: "yes"
-string1: "no"
-string2: "true"
-string3: "false"
-string4: "~"
-null0: ~
-? - true
-  - false
-: real_bools
-? - "True"
-  - "TRUE"
-  - "False"
-  - "FALSE"
-  - y
-  - Y
-  - "yes"
-  - "Yes"
-  - "YES"
-  - n
-  - N
-  - "no"
-  - "No"
-  - "NO"
-  - "on"
-  - "On"
-  - "ON"
-  - "off"
-  - "Off"
-  - "OFF"
-: false_bools
-bool0: true
-bool1: false"#;
-
-        let docs = YamlLoader::load_from_str(&input).unwrap();
-        let doc = docs[0].clone().into();
-        let mut writer = String::new();
+    fn test_null_style_empty() {
+        let s = "a: ~ # none\nb: &n ~\nc: [~, 1]\nd:\n  - ~\n  - *n\n? [k]\n: ~\n";
+        let mut options = crate::LoadOptions::new();
+        options.keep_comments(true);
+        let docs = YamlLoader::load_from_str_with_options(s, &options).unwrap();
+        let mut output = String::new();
         {
-            let mut emitter = YamlEmitter::new(&mut writer);
-            emitter.dump(&doc).unwrap();
+            let mut emitter = YamlEmitter::new(&mut output);
+            emitter.null_style(NullStyle::Empty);
+            emitter.flow_scalar_collections(true);
+            emitter.dump(&docs[0].clone().into()).unwrap();
+            emitter.dump(&YamlOutput::Null).unwrap();
         }
-
         assert_eq!(
-            expected, writer,
-            "expected:\n{}\nactual:\n{}\n",
-            expected, writer
+            output,
+            "---\na: # none\nb: &n null\nc: [null, 1]\nd: [null, *n]\n? [k]\n:---\nnull"
         );
+        let reloaded = YamlLoader::load_from_str(&output[..output.len() - 8]).unwrap();
+        assert_eq!(reloaded[0], YamlLoader::load_from_str(s).unwrap()[0]);
     }
 
     #[test]
-    fn test_empty_and_nested() {
-        test_empty_and_nested_flag(false)
+    fn test_float_format_reads_back() {
+        let values = [
+            0.0,
+            -0.0,
+            1.0,
+            -2.5,
+            1e-7,
+            123456789.0,
+            1e300,
+            0.1 + 0.2,
+            7.0,
+        ];
+        let doc = YamlOutput::Array(
+            values
+                .iter()
+                .map(|v| YamlOutput::Real(v.to_string()))
+                .collect(),
+        );
+        let thresholds = [None, Some(0), Some(3)];
+        let precisions = [None, Some(0), Some(2)];
+        for &exponent_threshold in &thresholds {
+            for &precision in &precisions {
+                let mut format = FloatFormat::new();
+                format.preserve_source(false);
+                format.exponent_threshold(exponent_threshold);
+                format.precision(precision);
+                let mut output = String::new();
+                {
+                    let mut emitter = YamlEmitter::new(&mut output);
+                    emitter.float_format(format);
+                    emitter.dump(&doc).unwrap();
+                }
+                let reloaded = &YamlLoader::load_from_str(&output).unwrap()[0];
+                for (i, &value) in values.iter().enumerate() {
+                    // every real stays a real, and without a precision keeps its value
+                    let real = reloaded[i].as_f64().unwrap_or_else(|| panic!("{}", output));
+                    if precision.is_none() {
+                        assert_eq!(real, value, "{}", output);
+                    }
+                }
+            }
+        }
     }
 
     #[test]
-    fn test_empty_and_nested_compact() {
-        test_empty_and_nested_flag(true)
+    fn test_bare_documents_read_back() {
+        let s = "--- &a\nk: v\n--- !t [1]\n--- !!binary aGVsbG8=\n--- |\n  text\n";
+        let docs: Vec<YamlOutput> = YamlLoader::load_from_str(s)
+            .unwrap()
+            .into_iter()
+            .map(YamlOutput::from)
+            .collect();
+        for &explicit_end in &[false, true] {
+            let mut output = String::new();
+            {
+                let mut emitter = YamlEmitter::new(&mut output);
+                emitter.explicit_start(false);
+                emitter.explicit_end(explicit_end);
+                emitter.multiline_strings(true);
+                emitter.dump_all(&docs).unwrap();
+            }
+            assert!(output.starts_with("&a\nk: v\n"), "{}", output);
+            let reloaded: Vec<YamlOutput> = YamlLoader::load_from_str(&output)
+                .unwrap()
+                .into_iter()
+                .map(YamlOutput::from)
+                .collect();
+            assert_eq!(reloaded, docs);
+        }
     }
 
-    fn test_empty_and_nested_flag(compact: bool) {
-        let s = if compact {
-            r#"---
-a:
-  b:
-    c: hello
-  d: {}
-e:
-  - f
-  - g
-  - h: []"#
-        } else {
-            r#"---
-a:
-  b:
-    c: hello
-  d: {}
-e:
-  - f
-  - g
-  -
-    h: []"#
-        };
+    #[test]
+    #[cfg(feature = "stream")]
+    fn test_directives_per_document() {
+        let mut version = Directives::new();
+        version.yaml_version(Some((1, 2)));
+        let mut tags = Directives::new();
+        tags.tag("!e!", "tag:example.com,2000:");
+        tags.tag("!!", "tag:yaml.org,2002:");
+        let none = Directives::new();
+        let mut output = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut output);
+            emitter.explicit_start(false);
+            let mut stream = emitter.stream();
+            stream.directives(&version);
+            stream.start_document().unwrap();
+            stream.node(&YamlOutput::Integer(1)).unwrap();
+            stream.end_document().unwrap();
+            stream.directives(&tags);
+            stream.start_document().unwrap();
+            stream.start_sequence().unwrap();
+            stream.node(&YamlOutput::String("a".to_owned())).unwrap();
+            stream.end_sequence().unwrap();
+            stream.end_document().unwrap();
+            stream.directives(&none);
+            stream.start_document().unwrap();
+            stream.node(&YamlOutput::Integer(3)).unwrap();
+            stream.end_document().unwrap();
+        }
+        assert_eq!(
+            output,
+            "%YAML 1.2\n---\n1\n...\n%TAG !e! tag:example.com,2000:\n%TAG !! tag:yaml.org,2002:\n---\n- a\n---\n3"
+        );
+        let docs = YamlLoader::load_from_str(&output).unwrap();
+        assert_eq!(docs.len(), 3);
+        assert_eq!(docs[1][0].as_str(), Some("a"));
 
-        let docs = YamlLoader::load_from_str(&s).unwrap();
-        let doc = docs[0].clone().into();
-        let mut writer = String::new();
+        // an explicit end already closes the document before the directives
+        let mut output = String::new();
         {
-            let mut emitter = YamlEmitter::new(&mut writer);
-            emitter.compact(compact);
-            emitter.dump(&doc).unwrap();
+            let mut emitter = YamlEmitter::new(&mut output);
+            emitter.explicit_end(true);
+            emitter.directives(&version);
+            emitter
+                .dump_all(&[YamlOutput::Integer(1), YamlOutput::Integer(2)])
+                .unwrap();
         }
+        assert_eq!(output, "%YAML 1.2\n---\n1\n...\n%YAML 1.2\n---\n2\n...");
+        assert!(!version.is_empty() && none.is_empty());
+    }
 
-        assert_eq!(s, writer);
+    #[test]
+    #[cfg(feature = "stream")]
+    fn test_scalar_and_empty_documents() {
+        let docs = vec![
+            YamlOutput::Null,
+            YamlOutput::Integer(1),
+            YamlOutput::String("two\nlines\n".to_owned()),
+            YamlOutput::Anchored("a".to_owned(), Box::new(YamlOutput::Null)),
+            YamlOutput::Null,
+            YamlOutput::Tagged(
+                "!t".to_owned(),
+                Box::new(YamlOutput::Real("1.5".to_owned())),
+            ),
+            YamlOutput::Array(vec![]),
+            YamlOutput::Hash(
+                vec![(YamlOutput::Null, YamlOutput::Null)]
+                    .into_iter()
+                    .collect(),
+            ),
+            YamlOutput::Null,
+        ];
+        let mut outputs = Vec::new();
+        for &explicit_start in &[false, true] {
+            for &explicit_end in &[false, true] {
+                for &inline in &[false, true] {
+                    for &empty in &[false, true] {
+                        let mut output = String::new();
+                        let mut streamed = String::new();
+                        let configure = |emitter: &mut YamlEmitter| {
+                            emitter.explicit_start(explicit_start);
+                            emitter.explicit_end(explicit_end);
+                            emitter.inline_root_scalars(inline);
+                            emitter.empty_null_documents(empty);
+                            emitter.multiline_strings(true);
+                        };
+                        {
+                            let mut emitter = YamlEmitter::new(&mut output);
+                            configure(&mut emitter);
+                            emitter.dump_all(&docs).unwrap();
+                        }
+                        {
+                            let mut emitter = YamlEmitter::new(&mut streamed);
+                            configure(&mut emitter);
+                            let mut stream = emitter.stream();
+                            for doc in &docs {
+                                stream.start_document().unwrap();
+                                match *doc {
+                                    YamlOutput::Hash(ref h) => {
+                                        stream.start_mapping().unwrap();
+                                        for (k, v) in h {
+                                            stream.key(k).unwrap();
+                                            stream.node(v).unwrap();
+                                        }
+                                        stream.end_mapping().unwrap();
+                                    }
+                                    ref doc => stream.node(doc).unwrap(),
+                                }
+                                stream.end_document().unwrap();
+                            }
+                        }
+                        assert_eq!(streamed, output);
+                        let reloaded: Vec<YamlOutput> = YamlLoader::load_from_str(&output)
+                            .unwrap()
+                            .into_iter()
+                            .map(YamlOutput::from)
+                            .collect();
+                        assert_eq!(reloaded, docs, "{}", output);
+                        outputs.push(output);
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            outputs[0],
+            "~\n---\n1\n---\n|\n  two\n  lines\n\n---\n&a ~\n---\n~\n---\n!t 1.5\n---\n[]\n---\n~: ~\n---\n~"
+        );
+        assert_eq!(
+            outputs[15],
+            "---\n...\n--- 1\n...\n--- |\n  two\n  lines\n\n...\n--- &a ~\n...\n---\n...\n--- !t 1.5\n...\n---\n[]\n...\n---\n~: ~\n...\n---\n..."
+        );
     }
 
     #[test]
-    fn test_nested_arrays() {
-        let s = r#"---
-a:
-  - b
-  - - c
-    - d
-    - - e
-      - f"#;
+    #[cfg(feature = "stream")]
+    fn test_number_formatter() {
+        struct Padded;
 
-        let docs = YamlLoader::load_from_str(&s).unwrap();
-        let doc = docs[0].clone().into();
-        let mut writer = String::new();
+        impl NumberFormatter for Padded {
+            fn integer(&mut self, value: i64, key: Option<&YamlOutput>) -> Option<String> {
+                let key = match key {
+                    Some(YamlOutput::String(key)) => key.as_str(),
+                    _ => "-",
+                };
+                Some(format!("{:03} # {}", value, key))
+            }
+        }
+
+        let s = "a: &x 1\nb: [2, !n 3]\nc: *x\nd: {4: 5}\ne: 1.5\n";
+        let doc: YamlOutput = YamlLoader::load_from_str(s).unwrap()[0].clone().into();
+        let mut dumped = String::new();
+        let mut formatter = Padded;
         {
-            let mut emitter = YamlEmitter::new(&mut writer);
+            let mut emitter = YamlEmitter::new(&mut dumped);
+            emitter.number_formatter(&mut formatter);
             emitter.dump(&doc).unwrap();
         }
-        println!("original:\n{}", s);
-        println!("emitted:\n{}", writer);
+        assert_eq!(
+            dumped,
+            "---\na: &x 001 # a\nb:\n  - 002 # -\n  - !n 003 # -\nc: *x\nd:\n  4: 005 # -\ne: 1.5"
+        );
 
-        assert_eq!(s, writer);
+        let mut streamed = String::new();
+        let mut formatter = Padded;
+        {
+            let mut emitter = YamlEmitter::new(&mut streamed);
+            emitter.number_formatter(&mut formatter);
+            let mut stream = emitter.stream();
+            stream.start_document().unwrap();
+            stream.start_mapping().unwrap();
+            if let YamlOutput::Hash(ref h) = doc {
+                for (k, v) in h {
+                    stream.key(k).unwrap();
+                    stream.node(v).unwrap();
+                }
+            }
+            stream.end_mapping().unwrap();
+            stream.end_document().unwrap();
+        }
+        assert_eq!(streamed, dumped);
     }
 
     #[test]
-    fn test_deeply_nested_arrays() {
-        let s = r#"---
-a:
-  - b
-  - - c
-    - d
-    - - e
-      - - f
-      - - e"#;
-
-        let docs = YamlLoader::load_from_str(&s).unwrap();
-        let doc = docs[0].clone().into();
-        let mut writer = String::new();
+    fn test_canonical_reads_back() {
+        let s = "
+base: &b {name: x, tags: [], meta: {}}
+copy: *b
+custom: !point [1, 2]
+bin: !!binary aGVsbG8=
+special: [.inf, -.Inf, 1.50, '1', \"line\\nbreak\", yes, ~, '']
+? [complex, key]
+: !!str 1
+";
+        let doc: YamlOutput = YamlLoader::load_from_str(s).unwrap()[0].clone().into();
+        let mut output = String::new();
         {
-            let mut emitter = YamlEmitter::new(&mut writer);
+            let mut emitter = YamlEmitter::new(&mut output);
+            emitter.canonical(true);
             emitter.dump(&doc).unwrap();
         }
-        println!("original:\n{}", s);
-        println!("emitted:\n{}", writer);
-
-        assert_eq!(s, writer);
+        assert!(output.contains("\n  : &b !!map {\n"), "{}", output);
+        assert!(output.contains("\n  : !point [\n"), "{}", output);
+        assert!(output.contains("!!float \"1.5\""), "{}", output);
+        let reloaded: YamlOutput = YamlLoader::load_from_str(&output).unwrap()[0]
+            .clone()
+            .into();
+        let expected: YamlOutput =
+            YamlLoader::load_from_str(&s.replace("1.50", "1.5").replace(".Inf", ".inf")).unwrap()
+                [0]
+            .clone()
+            .into();
+        assert_eq!(reloaded, expected);
     }
 
     #[test]
-    fn test_nested_hashes() {
-        let s = r#"---
-a:
-  b:
-    c:
-      d:
-        e: f"#;
+    fn test_truncate_strings() {
+        let long = "line one\nline: two\n".repeat(4);
+        let doc = YamlOutput::Hash(
+            vec![
+                (
+                    YamlOutput::String("x".repeat(20)),
+                    YamlOutput::String("é".repeat(10)),
+                ),
+                (
+                    YamlOutput::String("text".to_owned()),
+                    YamlOutput::String(long.clone()),
+                ),
+                (
+                    YamlOutput::String("tags".to_owned()),
+                    YamlOutput::Array(vec![
+                        YamlOutput::String("#hash: and more".to_owned()),
+                        YamlOutput::Integer(12345678901),
+                    ]),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        for &(multiline, flow) in &[(false, false), (true, false), (false, true)] {
+            let mut output = String::new();
+            {
+                let mut emitter = YamlEmitter::new(&mut output);
+                emitter.truncate_strings(Some(9));
+                emitter.multiline_strings(multiline);
+                emitter.flow_scalar_collections(flow);
+                emitter.dump(&doc).unwrap();
+            }
+            assert!(output.contains("# truncated, 8 of 20 bytes"), "{}", output);
+            assert!(output.contains("# truncated, 9 of 76 bytes"), "{}", output);
+            assert!(output.contains("# truncated, 9 of 15 bytes"), "{}", output);
+            let loaded = &YamlLoader::load_from_str(&output).unwrap()[0];
+            // a two byte character is never split
+            assert_eq!(loaded[&*"x".repeat(20)].as_str(), Some("éééé"));
+            assert_eq!(loaded["text"].as_str(), Some(&long[..9]));
+            assert_eq!(loaded["tags"][0].as_str(), Some("#hash: an"));
+            assert_eq!(loaded["tags"][1].as_i64(), Some(12345678901));
+        }
 
-        let docs = YamlLoader::load_from_str(&s).unwrap();
-        let doc = docs[0].clone().into();
-        let mut writer = String::new();
+        let mut options = crate::LoadOptions::new();
+        options.keep_comments(true);
+        let docs =
+            YamlLoader::load_from_str_with_options("# kept\nname: a long name # note\n", &options)
+                .unwrap();
+        let mut output = String::new();
         {
-            let mut emitter = YamlEmitter::new(&mut writer);
+            let mut emitter = YamlEmitter::new(&mut output);
+            emitter.truncate_strings(Some(6));
+            emitter.dump(&docs[0].clone().into()).unwrap();
+        }
+        assert_eq!(
+            output,
+            "---\n# kept\nname: a long # note; truncated, 6 of 11 bytes"
+        );
+    }
+
+    #[test]
+    fn test_key_comments() {
+        let s = "# kept\nname: web # trailing\nports: [80, 443]\n1: one\n";
+        let mut options = crate::LoadOptions::new();
+        options.keep_comments(true);
+        let doc: YamlOutput = YamlLoader::load_from_str_with_options(s, &options).unwrap()[0]
+            .clone()
+            .into();
+        let mut comments = KeyComments::new();
+        comments.insert("name", "The service name.");
+        comments.insert("ports", "Ports to expose.");
+        comments.insert("1", "A number key.");
+        comments.insert("missing", "Not written.");
+        let mut output = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut output);
+            emitter.key_comments(&comments);
+            emitter.flow_scalar_collections(true);
             emitter.dump(&doc).unwrap();
         }
-        println!("original:\n{}", s);
-        println!("emitted:\n{}", writer);
+        assert_eq!(
+            output,
+            "---\n# kept\n# The service name.\nname: web # trailing\n# Ports to expose.\nports: [80, 443]\n# A number key.\n1: one"
+        );
+        let reloaded = YamlLoader::load_from_str(&output).unwrap();
+        assert_eq!(reloaded, YamlLoader::load_from_str(s).unwrap());
+    }
 
-        assert_eq!(s, writer);
+    /// A writer failing once it holds `limit` bytes.
+    struct Limited {
+        out: String,
+        limit: usize,
+    }
+
+    impl fmt::Write for Limited {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            if self.out.len() + s.len() > self.limit {
+                return Err(fmt::Error);
+            }
+            self.out.push_str(s);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_error_path() {
+        let s = "a: 1\nb:\n  - x\n  - {c: [1, 2]}\n  - d: long text here\n";
+        let doc: YamlOutput = YamlLoader::load_from_str(s).unwrap().remove(0).into();
+        let fail_at = |limit: usize, canonical: bool| {
+            let mut writer = Limited {
+                out: String::new(),
+                limit,
+            };
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.canonical(canonical);
+            let err = emitter.dump(&doc).unwrap_err();
+            match err {
+                EmitError::Node(err) => {
+                    assert_eq!(err.written(), writer.out.len());
+                    assert!(matches!(err.error(), EmitError::FmtError(_)));
+                    err.path().to_string()
+                }
+                err => panic!("{:?}", err),
+            }
+        };
+        // ---\na: 1\nb:\n  - x\n  - c:\n      - 1\n      - 2\n  - d: long text here
+        assert_eq!(fail_at(0, false), "");
+        assert_eq!(fail_at(6, false), "a");
+        assert_eq!(fail_at(8, false), "b");
+        assert_eq!(fail_at(17, false), "b[1]");
+        assert_eq!(fail_at(32, false), "b[1].c[0]");
+        assert_eq!(fail_at(50, false), "b[2].d");
+        assert_eq!(fail_at(20, true), "a");
+        assert_eq!(fail_at(130, true), "b[1].c[0]");
+
+        // the path does not leak into the next document
+        let mut writer = Limited {
+            out: String::new(),
+            limit: 11,
+        };
+        let mut emitter = YamlEmitter::new(&mut writer);
+        emitter.dump(&YamlOutput::from("first")).unwrap();
+        let err = emitter.dump(&YamlOutput::from("second")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "an error occurred when formatting an argument at the root, after 0 bytes"
+        );
+        assert!(err.source().is_some());
     }
 }
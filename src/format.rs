@@ -0,0 +1,2663 @@
+use crate::cst::{Cst, SyntaxKind, SyntaxNode};
+use crate::diff::{self, Path, Segment};
+use crate::emitter::{EmitError, QuoteStyle, YamlEmitter};
+use crate::line_ending::{self, LineEnding};
+use crate::scanner::{Marker, ScanError, TScalarStyle};
+use crate::yaml::{self, LoadOptions, Marks, YamlInput, YamlLoader, YamlOutput};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A set of style options matching the conventions of a kind of YAML file, see
+/// `FormatOptions::profile`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum StyleProfile {
+    /// The style of `FormatOptions::new()`: two spaces, indented sequences, double
+    /// quotes where needed and no line width.
+    #[default]
+    Default,
+    /// Google's style: two spaces, indented sequences, double quotes where needed and
+    /// lines of 80 columns.
+    GoogleStyle,
+    /// The style `kubectl` writes: two spaces, sequences flush with their key and
+    /// double quotes where needed.
+    KubernetesManifest,
+    /// The style of the GitHub Actions documentation: two spaces, indented sequences
+    /// and single quotes where needed.
+    GithubActions,
+    /// The style `ansible-lint` expects: two spaces, indented sequences, double quotes
+    /// where needed and lines of 160 columns.
+    AnsiblePlaybook,
+}
+
+/// Options controlling how `format_str` rewrites a source.
+#[derive(Clone, Debug)]
+pub struct FormatOptions {
+    normalize_scalars: bool,
+    line_ending: Option<LineEnding>,
+    indent: Option<usize>,
+    indent_sequences: bool,
+    quote_style: Option<QuoteStyle>,
+    keep_scalar_style: bool,
+    ascii_only: bool,
+    line_width: Option<usize>,
+    key_spacing: usize,
+    blank_lines: usize,
+    align_values: Option<usize>,
+    inline_anchors: Option<usize>,
+    embedded_yaml: Vec<String>,
+    check: bool,
+    threads: usize,
+    passes: Vec<Arc<dyn FormatPass>>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            normalize_scalars: false,
+            line_ending: None,
+            indent: None,
+            indent_sequences: true,
+            quote_style: None,
+            keep_scalar_style: true,
+            ascii_only: false,
+            line_width: None,
+            key_spacing: 0,
+            blank_lines: 0,
+            align_values: None,
+            inline_anchors: None,
+            embedded_yaml: Vec::new(),
+            check: false,
+            threads: 1,
+            passes: Vec::new(),
+        }
+    }
+}
+
+impl FormatOptions {
+    pub fn new() -> FormatOptions {
+        FormatOptions::default()
+    }
+
+    /// Set the indentation, the indentation of sequences, the quotes and the line width
+    /// to the ones of `profile`. Options set afterwards override them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::format::{self, FormatOptions, StyleProfile};
+    ///
+    /// let source = "spec:\n  containers: [{name: web, args: [--port, '80']}]\n";
+    /// let mut options = FormatOptions::new();
+    /// options.profile(StyleProfile::KubernetesManifest);
+    /// assert_eq!(
+    ///     format::format_str(source, &options).unwrap(),
+    ///     "---\nspec:\n  containers:\n  - name: web\n    args:\n    - --port\n    - \"80\"\n"
+    /// );
+    /// ```
+    pub fn profile(&mut self, profile: StyleProfile) {
+        let (indent, indent_sequences, quote_style, line_width) = match profile {
+            StyleProfile::Default => (None, true, None, None),
+            StyleProfile::GoogleStyle => {
+                (Some(2), true, Some(QuoteStyle::DoubleWhenNeeded), Some(80))
+            }
+            StyleProfile::KubernetesManifest => {
+                (Some(2), false, Some(QuoteStyle::DoubleWhenNeeded), None)
+            }
+            StyleProfile::GithubActions => {
+                (Some(2), true, Some(QuoteStyle::SingleWhenNeeded), None)
+            }
+            StyleProfile::AnsiblePlaybook => {
+                (Some(2), true, Some(QuoteStyle::DoubleWhenNeeded), Some(160))
+            }
+        };
+        self.indent = indent;
+        self.indent_sequences = indent_sequences;
+        self.quote_style = quote_style;
+        self.line_width = line_width;
+    }
+
+    /// Write every null as `~` and every boolean as `true` or `false`, instead of keeping
+    /// the spelling of the source such as `null`. Capitalized spellings such as `NULL`
+    /// or `True` are strings, which are kept as written.
+    pub fn normalize_scalars(&mut self, normalize_scalars: bool) {
+        self.normalize_scalars = normalize_scalars;
+    }
+
+    /// Determine if nulls and booleans are written in their standard spelling.
+    pub fn is_normalize_scalars(&self) -> bool {
+        self.normalize_scalars
+    }
+
+    /// End every line of the output with `line_ending`. By default the output uses
+    /// the ending of the first line of the source, or LF for a single line.
+    pub fn line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = Some(line_ending);
+    }
+
+    pub fn get_line_ending(&self) -> Option<LineEnding> {
+        self.line_ending
+    }
+
+    /// Indent nested block collections by `indent` spaces instead of 2, see
+    /// `YamlEmitter::indent`.
+    ///
+    /// # Panics
+    ///
+    /// When `indent` is not between 1 and 9.
+    pub fn indent(&mut self, indent: usize) {
+        assert!(
+            (1..=9).contains(&indent),
+            "indent {} is not between 1 and 9",
+            indent
+        );
+        self.indent = Some(indent);
+    }
+
+    pub fn get_indent(&self) -> Option<usize> {
+        self.indent
+    }
+
+    /// Indent the block sequences held by mappings under their key, `true` by default,
+    /// see `YamlEmitter::indent_sequences`.
+    pub fn indent_sequences(&mut self, indent_sequences: bool) {
+        self.indent_sequences = indent_sequences;
+    }
+
+    pub fn is_indent_sequences(&self) -> bool {
+        self.indent_sequences
+    }
+
+    /// Quote strings as `quote_style` says, whatever quotes they have in the source.
+    /// By default the quotes of the source are kept, see `keep_scalar_style`, and the
+    /// other strings are quoted as `QuoteStyle::DoubleWhenNeeded` says.
+    pub fn quote_style(&mut self, quote_style: QuoteStyle) {
+        self.quote_style = Some(quote_style);
+    }
+
+    pub fn get_quote_style(&self) -> QuoteStyle {
+        self.quote_style.unwrap_or_default()
+    }
+
+    /// Write the quoted strings and the block scalars of the source in the style they
+    /// have there whenever it can hold them, `true` by default, so that formatting does
+    /// not change their quotes. A `quote_style` set explicitly replaces the quotes, and
+    /// only the block scalars keep their style.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::format::{self, FormatOptions};
+    ///
+    /// let source = "name:   'web'\nimage: \"nginx\"\ncmd: >\n  make\n  install\n";
+    /// let mut options = FormatOptions::new();
+    /// assert_eq!(
+    ///     format::format_str(source, &options).unwrap(),
+    ///     "---\nname: 'web'\nimage: \"nginx\"\ncmd: >\n  make install\n"
+    /// );
+    /// options.keep_scalar_style(false);
+    /// assert_eq!(
+    ///     format::format_str(source, &options).unwrap(),
+    ///     "---\nname: web\nimage: nginx\ncmd: \"make install\\n\"\n"
+    /// );
+    /// ```
+    pub fn keep_scalar_style(&mut self, keep_scalar_style: bool) {
+        self.keep_scalar_style = keep_scalar_style;
+    }
+
+    pub fn is_keep_scalar_style(&self) -> bool {
+        self.keep_scalar_style
+    }
+
+    /// Escape the characters other than ASCII in double-quoted strings, see
+    /// `YamlEmitter::ascii_only`.
+    pub fn ascii_only(&mut self, ascii_only: bool) {
+        self.ascii_only = ascii_only;
+    }
+
+    pub fn is_ascii_only(&self) -> bool {
+        self.ascii_only
+    }
+
+    /// Break long plain scalars and flow collections to keep lines within `width`
+    /// columns where they can be, see `YamlEmitter::max_line_width`.
+    pub fn line_width(&mut self, width: Option<usize>) {
+        self.line_width = width;
+    }
+
+    pub fn get_line_width(&self) -> Option<usize> {
+        self.line_width
+    }
+
+    /// Separate the entries of the mappings at the root of the documents with `lines`
+    /// blank lines, see `YamlEmitter::key_spacing`.
+    pub fn key_spacing(&mut self, lines: usize) {
+        self.key_spacing = lines;
+    }
+
+    pub fn get_key_spacing(&self) -> usize {
+        self.key_spacing
+    }
+
+    /// Keep up to `max` of the blank lines found before the entries of the block
+    /// collections of the source, and before their comments, instead of removing them.
+    /// `0`, the default, removes them all and `usize::MAX` keeps them all. The blank
+    /// lines before the first entry of a collection are always removed, and the
+    /// spacing of `key_spacing` is kept where it is larger.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::format::{self, FormatOptions};
+    ///
+    /// let source = "name: web\n\n\n\n# the ports\nports:\n- 80\n\n- 443\n";
+    /// let mut options = FormatOptions::new();
+    /// options.keep_blank_lines(2);
+    /// assert_eq!(
+    ///     format::format_str(source, &options).unwrap(),
+    ///     "---\nname: web\n\n\n# the ports\nports:\n  - 80\n\n  - 443\n"
+    /// );
+    /// ```
+    pub fn keep_blank_lines(&mut self, max: usize) {
+        self.blank_lines = max;
+    }
+
+    pub fn get_keep_blank_lines(&self) -> usize {
+        self.blank_lines
+    }
+
+    /// Pad the values of block mappings after their `:` to a common column, by groups
+    /// of at most `group` entries in a row, see `YamlEmitter::align_values`.
+    pub fn align_values(&mut self, group: usize) {
+        self.align_values = Some(group);
+    }
+
+    pub fn get_align_values(&self) -> Option<usize> {
+        self.align_values
+    }
+
+    /// Inline the anchors aliased at most `max_aliases` times: each alias is replaced by
+    /// a copy of the anchored node, which loses its anchor. With `1`, anchors used only
+    /// once or never are dropped while the ones reused several times stay.
+    pub fn inline_anchors(&mut self, max_aliases: usize) {
+        self.inline_anchors = Some(max_aliases);
+    }
+
+    pub fn get_inline_anchors(&self) -> Option<usize> {
+        self.inline_anchors
+    }
+
+    /// Also format the YAML documents embedded in the strings found at the paths
+    /// matching `pattern`, writing them back as literal block scalars. Paths are
+    /// written like `spec.containers[0].image` and `*` matches any run of characters,
+    /// so `data.*.yaml` selects the `.yaml` entries of a Kubernetes ConfigMap.
+    ///
+    /// Strings that do not parse, or hold a single scalar, are left as they are.
+    pub fn embedded_yaml(&mut self, pattern: &str) {
+        self.embedded_yaml.push(pattern.to_owned());
+    }
+
+    pub fn embedded_yaml_patterns(&self) -> &[String] {
+        &self.embedded_yaml
+    }
+
+    /// Run `pass` on every document after the other options, and after the passes
+    /// added before it. Passes are not run on the documents found by `embedded_yaml`.
+    pub fn pass<P: FormatPass + 'static>(&mut self, pass: P) {
+        self.passes.push(Arc::new(pass));
+    }
+
+    pub fn passes(&self) -> &[Arc<dyn FormatPass>] {
+        &self.passes
+    }
+
+    /// Only report the files `format_files` would rewrite, leaving them untouched.
+    pub fn check(&mut self, check: bool) {
+        self.check = check;
+    }
+
+    /// Determine if `format_files` leaves the files untouched.
+    pub fn is_check(&self) -> bool {
+        self.check
+    }
+
+    /// Format up to `threads` files at once in `format_tree`, or as many as the machine
+    /// runs in parallel with `0`. One by default.
+    pub fn threads(&mut self, threads: usize) {
+        self.threads = threads;
+    }
+
+    pub fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    /// A hash of the options changing the output. Passes are told apart by their name
+    /// and by what they feed to `FormatPass::hash_config`.
+    fn output_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.normalize_scalars.hash(&mut hasher);
+        self.line_ending.hash(&mut hasher);
+        self.indent.hash(&mut hasher);
+        self.indent_sequences.hash(&mut hasher);
+        self.quote_style.hash(&mut hasher);
+        self.keep_scalar_style.hash(&mut hasher);
+        self.ascii_only.hash(&mut hasher);
+        self.line_width.hash(&mut hasher);
+        self.key_spacing.hash(&mut hasher);
+        self.blank_lines.hash(&mut hasher);
+        self.align_values.hash(&mut hasher);
+        self.inline_anchors.hash(&mut hasher);
+        self.embedded_yaml.hash(&mut hasher);
+        for pass in &self.passes {
+            pass.name().hash(&mut hasher);
+            pass.hash_config(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+/// A rule of its own that `format_str` applies to every document between loading and
+/// emitting it, such as a house convention for labels, see `FormatOptions::pass`.
+/// Passes are `Send + Sync`, so that options can be shared with other threads.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::format::{self, FormatOptions, FormatPass};
+/// use yaml_rust_formatter::YamlOutput;
+///
+/// struct NoLatest;
+///
+/// impl FormatPass for NoLatest {
+///     fn name(&self) -> &str {
+///         "no-latest"
+///     }
+///
+///     fn run(&self, doc: &mut YamlOutput) -> Result<(), String> {
+///         if let YamlOutput::Hash(ref h) = *doc {
+///             let tag = h.get(&YamlOutput::String("tag".to_owned()));
+///             if tag == Some(&YamlOutput::String("latest".to_owned())) {
+///                 return Err("the image tag is not pinned".to_owned());
+///             }
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut options = FormatOptions::new();
+/// options.pass(NoLatest);
+/// assert!(format::format_str("image: web\ntag: 1.2.0", &options).is_ok());
+/// let err = format::format_str("image: web\ntag: latest", &options).unwrap_err();
+/// assert_eq!(err.to_string(), "no-latest: the image tag is not pinned");
+/// ```
+pub trait FormatPass: Send + Sync {
+    /// The name of the pass, prefixed to its errors.
+    fn name(&self) -> &str;
+
+    /// Inspect or change `doc`. An error stops the formatting of the source.
+    fn run(&self, doc: &mut YamlOutput) -> Result<(), String>;
+
+    /// Feed `state` the settings of the pass that change its output, so that a
+    /// `FormatCache` formats the files again when they change. Passes without
+    /// settings, which always change a document the same way, keep the default that
+    /// feeds nothing.
+    fn hash_config(&self, _state: &mut dyn Hasher) {}
+}
+
+impl fmt::Debug for dyn FormatPass {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "FormatPass({})", self.name())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum FormatError {
+    Scan(ScanError),
+    Emit(EmitError),
+    /// A `FormatPass` refused a document: the name of the pass and its message.
+    Pass(String, String),
+    /// `format_range` could not reformat the bytes of the source in the range without
+    /// changing the documents it loads to or its comments.
+    Range(Range<usize>),
+}
+
+impl Error for FormatError {
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            FormatError::Scan(ref err) => Some(err),
+            FormatError::Emit(ref err) => Some(err),
+            FormatError::Pass(..) | FormatError::Range(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FormatError::Scan(ref err) => fmt::Display::fmt(err, formatter),
+            FormatError::Emit(ref err) => fmt::Display::fmt(err, formatter),
+            FormatError::Pass(ref name, ref message) => write!(formatter, "{}: {}", name, message),
+            FormatError::Range(ref range) => write!(
+                formatter,
+                "formatting bytes {}..{} alone changes the document",
+                range.start, range.end
+            ),
+        }
+    }
+}
+
+impl From<ScanError> for FormatError {
+    fn from(err: ScanError) -> FormatError {
+        FormatError::Scan(err)
+    }
+}
+
+impl From<EmitError> for FormatError {
+    fn from(err: EmitError) -> FormatError {
+        FormatError::Emit(err)
+    }
+}
+
+/// The reason `format_files` could not format a file.
+#[derive(Debug)]
+pub enum FileError {
+    Io(io::Error),
+    Format(FormatError),
+}
+
+impl Error for FileError {
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            FileError::Io(ref err) => Some(err),
+            FileError::Format(ref err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FileError::Io(ref err) => fmt::Display::fmt(err, formatter),
+            FileError::Format(ref err) => fmt::Display::fmt(err, formatter),
+        }
+    }
+}
+
+impl From<io::Error> for FileError {
+    fn from(err: io::Error) -> FileError {
+        FileError::Io(err)
+    }
+}
+
+impl From<FormatError> for FileError {
+    fn from(err: FormatError) -> FileError {
+        FileError::Format(err)
+    }
+}
+
+/// Receives the progress of `format_files` as it goes, one file at a time. Both
+/// methods do nothing by default, and `()` is a receiver ignoring everything.
+pub trait FormatProgress {
+    /// Called before `path` is read.
+    fn start(&mut self, _path: &path::Path) {}
+
+    /// Called once `path` is done, with the time it took and either whether its
+    /// formatting changed or why it could not be formatted.
+    fn finish(
+        &mut self,
+        _path: &path::Path,
+        _duration: Duration,
+        _result: &Result<bool, FileError>,
+    ) {
+    }
+}
+
+impl FormatProgress for () {}
+
+/// The number of files `format_files` found in each state, and the time spent in
+/// each pass.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FormatSummary {
+    changed: usize,
+    unchanged: usize,
+    cached: usize,
+    failed: usize,
+    pass_durations: Vec<(String, Duration)>,
+}
+
+impl FormatSummary {
+    /// The files whose formatting changed, rewritten unless `FormatOptions::is_check()`.
+    pub fn changed(&self) -> usize {
+        self.changed
+    }
+
+    pub fn unchanged(&self) -> usize {
+        self.unchanged
+    }
+
+    /// The unchanged files skipped because a `FormatCache` knew them to be formatted.
+    pub fn cached(&self) -> usize {
+        self.cached
+    }
+
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+
+    /// The name of every pass of `FormatOptions::passes()` with the time it took over
+    /// all the files, in the order the passes run.
+    pub fn pass_durations(&self) -> &[(String, Duration)] {
+        &self.pass_durations
+    }
+}
+
+/// Remembers the sources `format_files_cached` found formatted, and the options they
+/// were formatted with, so that later runs skip them while they stay the same.
+#[derive(Clone, Debug, Default)]
+pub struct FormatCache {
+    formatted: HashSet<(u64, u64)>,
+}
+
+impl FormatCache {
+    pub fn new() -> FormatCache {
+        FormatCache::default()
+    }
+
+    /// The number of formatted sources remembered.
+    pub fn len(&self) -> usize {
+        self.formatted.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formatted.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.formatted.clear();
+    }
+}
+
+fn content_hash(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Format the files at `paths` in place, one after the other, telling `progress`
+/// when each file starts and finishes. A file that cannot be read, parsed or written
+/// is reported and skipped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use std::time::Duration;
+/// use yaml_rust_formatter::format::{self, FileError, FormatOptions, FormatProgress};
+///
+/// struct Log;
+///
+/// impl FormatProgress for Log {
+///     fn finish(&mut self, path: &Path, duration: Duration, result: &Result<bool, FileError>) {
+///         match *result {
+///             Ok(changed) => println!("{} {} ({:?})", path.display(), changed, duration),
+///             Err(ref err) => eprintln!("{}: {}", path.display(), err),
+///         }
+///     }
+/// }
+///
+/// let mut options = FormatOptions::new();
+/// options.check(true);
+/// let summary = format::format_files(&["a.yaml", "b.yaml"], &options, &mut Log);
+/// if summary.changed() + summary.failed() > 0 {
+///     std::process::exit(1);
+/// }
+/// ```
+pub fn format_files<P: AsRef<path::Path>>(
+    paths: &[P],
+    options: &FormatOptions,
+    progress: &mut dyn FormatProgress,
+) -> FormatSummary {
+    format_files_with(paths, options, None, progress)
+}
+
+/// Like `format_files`, but skip the files `cache` knows to be formatted with
+/// `options`, and remember the ones found or left formatted. Keeping the cache
+/// between runs, as a watch mode does, only formats the files changed since.
+///
+/// # Examples
+///
+/// ```no_run
+/// use yaml_rust_formatter::format::{self, FormatCache, FormatOptions};
+///
+/// let options = FormatOptions::new();
+/// let mut cache = FormatCache::new();
+/// format::format_files_cached(&["a.yaml", "b.yaml"], &options, &mut cache, &mut ());
+/// // unless a file was edited in the meantime, nothing is formatted again
+/// let summary = format::format_files_cached(&["a.yaml", "b.yaml"], &options, &mut cache, &mut ());
+/// println!("{} of 2 files were skipped", summary.cached());
+/// ```
+pub fn format_files_cached<P: AsRef<path::Path>>(
+    paths: &[P],
+    options: &FormatOptions,
+    cache: &mut FormatCache,
+    progress: &mut dyn FormatProgress,
+) -> FormatSummary {
+    format_files_with(paths, options, Some(cache), progress)
+}
+
+fn format_files_with<P: AsRef<path::Path>>(
+    paths: &[P],
+    options: &FormatOptions,
+    mut cache: Option<&mut FormatCache>,
+    progress: &mut dyn FormatProgress,
+) -> FormatSummary {
+    let mut summary = FormatSummary {
+        pass_durations: options
+            .passes
+            .iter()
+            .map(|pass| (pass.name().to_owned(), Duration::default()))
+            .collect(),
+        ..FormatSummary::default()
+    };
+    let options_hash = options.output_hash();
+    for path in paths {
+        let path = path.as_ref();
+        progress.start(path);
+        let started = Instant::now();
+        let cache = cache.as_deref_mut().map(|cache| (cache, options_hash));
+        let result = format_file_with(path, options, cache, &mut summary);
+        match result {
+            Ok(true) => summary.changed += 1,
+            Ok(false) => summary.unchanged += 1,
+            Err(_) => summary.failed += 1,
+        }
+        progress.finish(path, started.elapsed(), &result);
+    }
+    summary
+}
+
+/// What `format_tree` did with a file.
+#[derive(Debug)]
+pub struct FileReport {
+    path: path::PathBuf,
+    result: Result<bool, FileError>,
+}
+
+impl FileReport {
+    pub fn path(&self) -> &path::Path {
+        &self.path
+    }
+
+    /// Whether the formatting of the file changed, or why it could not be formatted.
+    pub fn result(&self) -> &Result<bool, FileError> {
+        &self.result
+    }
+
+    pub fn is_changed(&self) -> bool {
+        matches!(self.result, Ok(true))
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.result.is_err()
+    }
+}
+
+/// Format in place the files below the directory `root` matching one of the `include`
+/// patterns and none of the `exclude` ones, `*.yml` and `*.yaml` when `include` is
+/// empty, with `FormatOptions::threads` at once. The reports are sorted by path.
+///
+/// The patterns are matched against the path of a file relative to `root` written
+/// with `/`, or against its name for the patterns without `/`, `*` standing for any
+/// run of characters. A directory matching an `exclude` pattern is not entered.
+/// Symbolic links to directories are not followed. A directory below `root` that
+/// cannot be read is reported like a file that cannot.
+///
+/// # Examples
+///
+/// ```no_run
+/// use yaml_rust_formatter::format::{self, FormatOptions};
+///
+/// let mut options = FormatOptions::new();
+/// options.threads(0);
+/// let reports = format::format_tree("deploy", &[], &["vendor", "*.generated.yaml"], &options)
+///     .unwrap();
+/// for report in reports.iter().filter(|report| report.is_changed()) {
+///     println!("formatted {}", report.path().display());
+/// }
+/// ```
+pub fn format_tree<P: AsRef<path::Path>>(
+    root: P,
+    include: &[&str],
+    exclude: &[&str],
+    options: &FormatOptions,
+) -> io::Result<Vec<FileReport>> {
+    let include: &[&str] = if include.is_empty() {
+        &["*.yml", "*.yaml"]
+    } else {
+        include
+    };
+    let mut files = Vec::new();
+    let mut reports = Vec::new();
+    walk_tree(
+        root.as_ref(),
+        "",
+        include,
+        exclude,
+        &mut files,
+        &mut reports,
+    )?;
+    let threads = match options.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        threads => threads,
+    }
+    .min(files.len());
+    if threads <= 1 {
+        reports.extend(files.into_iter().map(|path| FileReport {
+            result: format_file(&path, options),
+            path,
+        }));
+    } else {
+        let next = AtomicUsize::new(0);
+        let done = Mutex::new(Vec::with_capacity(files.len()));
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    while let Some(path) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let result = format_file(path, options);
+                        done.lock()
+                            .unwrap_or_else(|err| err.into_inner())
+                            .push(FileReport {
+                                path: path.clone(),
+                                result,
+                            });
+                    }
+                });
+            }
+        });
+        reports.extend(done.into_inner().unwrap_or_else(|err| err.into_inner()));
+    }
+    reports.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(reports)
+}
+
+/// Add the files below `dir`, at `relative` from the root, to `files`, and the
+/// directories that cannot be read to `reports`.
+fn walk_tree(
+    dir: &path::Path,
+    relative: &str,
+    include: &[&str],
+    exclude: &[&str],
+    files: &mut Vec<path::PathBuf>,
+    reports: &mut Vec<FileReport>,
+) -> io::Result<()> {
+    let matches = |patterns: &[&str], relative: &str, name: &str| {
+        patterns.iter().any(|pattern| {
+            glob_match(
+                pattern,
+                if pattern.contains('/') {
+                    relative
+                } else {
+                    name
+                },
+            )
+        })
+    };
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let relative = if relative.is_empty() {
+            name.clone()
+        } else {
+            format!("{}/{}", relative, name)
+        };
+        if matches(exclude, &relative, &name) {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if let Err(err) = walk_tree(&path, &relative, include, exclude, files, reports) {
+                reports.push(FileReport {
+                    path,
+                    result: Err(FileError::Io(err)),
+                });
+            }
+        } else if (file_type.is_file() || path.is_file()) && matches(include, &relative, &name) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Format the file at `path` in place, returning whether its formatting changed. The
+/// file is only written when it changes, and not at all with `FormatOptions::check`.
+///
+/// The formatted text is written to a temporary file next to it, which then replaces
+/// it: a reader sees either the old or the new text, never a part of it, and a failure
+/// leaves the file as it was. The file keeps its permissions, and a symbolic link is
+/// followed, the file it links to being replaced.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use yaml_rust_formatter::format::{self, FormatOptions};
+///
+/// if format::format_file(Path::new("config.yaml"), &FormatOptions::new()).unwrap() {
+///     println!("config.yaml was reformatted");
+/// }
+/// ```
+pub fn format_file(path: &path::Path, options: &FormatOptions) -> Result<bool, FileError> {
+    format_file_with(path, options, None, &mut FormatSummary::default())
+}
+
+/// Format the file at `path`, returning whether its formatting changed, unless
+/// `cache` knows it to be formatted with the options of the given hash.
+fn format_file_with(
+    path: &path::Path,
+    options: &FormatOptions,
+    cache: Option<(&mut FormatCache, u64)>,
+    summary: &mut FormatSummary,
+) -> Result<bool, FileError> {
+    let source = fs::read_to_string(path)?;
+    if let Some((ref cache, options_hash)) = cache {
+        if cache
+            .formatted
+            .contains(&(content_hash(&source), options_hash))
+        {
+            summary.cached += 1;
+            return Ok(false);
+        }
+    }
+    let mut durations = vec![Duration::default(); options.passes.len()];
+    let formatted = format_timed(&source, options, &mut durations)?;
+    for (total, duration) in summary.pass_durations.iter_mut().zip(durations) {
+        total.1 += duration;
+    }
+    let changed = formatted != source;
+    if changed {
+        if options.check {
+            return Ok(true);
+        }
+        write_atomic(path, &formatted)?;
+    }
+    if let Some((cache, options_hash)) = cache {
+        cache
+            .formatted
+            .insert((content_hash(&formatted), options_hash));
+    }
+    Ok(changed)
+}
+
+/// Replace the file at `path`, or the file it links to, by one holding `contents`
+/// with the same permissions, through a temporary file in the same directory.
+fn write_atomic(path: &path::Path, contents: &str) -> io::Result<()> {
+    static TEMPORARY_FILES: AtomicUsize = AtomicUsize::new(0);
+    let path = fs::canonicalize(path)?;
+    let permissions = fs::metadata(&path)?.permissions();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        name,
+        process::id(),
+        TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temporary)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.set_permissions(permissions)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temporary, &path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Replace every spelled null or boolean of `node` by its plain value.
+fn normalize_scalars(node: YamlOutput) -> YamlOutput {
+    match node {
+        YamlOutput::Spelled(node, _)
+            if matches!(*node, YamlOutput::Null | YamlOutput::Boolean(_)) =>
+        {
+            *node
+        }
+        YamlOutput::Array(v) => YamlOutput::Array(v.into_iter().map(normalize_scalars).collect()),
+        YamlOutput::Hash(h) => YamlOutput::Hash(
+            h.into_iter()
+                .map(|(k, v)| (normalize_scalars(k), normalize_scalars(v)))
+                .collect(),
+        ),
+        YamlOutput::Anchored(name, node) => {
+            YamlOutput::Anchored(name, Box::new(normalize_scalars(*node)))
+        }
+        YamlOutput::Tagged(tag, node) => {
+            YamlOutput::Tagged(tag, Box::new(normalize_scalars(*node)))
+        }
+        YamlOutput::Commented(node, comments) => {
+            YamlOutput::Commented(Box::new(normalize_scalars(*node)), comments)
+        }
+        node => node,
+    }
+}
+
+/// Drop the quotes kept from the source on every string of `node`, keeping the style
+/// of its block scalars.
+fn drop_quotes(node: YamlOutput) -> YamlOutput {
+    match node {
+        YamlOutput::Styled(node, TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted) => *node,
+        YamlOutput::Array(v) => YamlOutput::Array(v.into_iter().map(drop_quotes).collect()),
+        YamlOutput::Hash(h) => YamlOutput::Hash(
+            h.into_iter()
+                .map(|(k, v)| (drop_quotes(k), drop_quotes(v)))
+                .collect(),
+        ),
+        YamlOutput::Anchored(name, node) => {
+            YamlOutput::Anchored(name, Box::new(drop_quotes(*node)))
+        }
+        YamlOutput::Tagged(tag, node) => YamlOutput::Tagged(tag, Box::new(drop_quotes(*node))),
+        YamlOutput::Commented(node, comments) => {
+            YamlOutput::Commented(Box::new(drop_quotes(*node)), comments)
+        }
+        node => node,
+    }
+}
+
+/// Count the aliases of every anchor of `node`, numbered in the order they appear.
+/// An alias refers to the last anchor of its name that is complete before it.
+fn count_aliases(node: &YamlOutput, names: &mut HashMap<String, usize>, counts: &mut Vec<usize>) {
+    match *node {
+        YamlOutput::Anchored(ref name, ref node) => {
+            let id = counts.len();
+            counts.push(0);
+            count_aliases(node, names, counts);
+            names.insert(name.clone(), id);
+        }
+        YamlOutput::Alias(ref name) => {
+            if let Some(&id) = names.get(name) {
+                counts[id] += 1;
+            }
+        }
+        YamlOutput::Array(ref v) => {
+            for x in v {
+                count_aliases(x, names, counts);
+            }
+        }
+        YamlOutput::Hash(ref h) => {
+            for (k, v) in h {
+                count_aliases(k, names, counts);
+                count_aliases(v, names, counts);
+            }
+        }
+        YamlOutput::Tagged(_, ref node)
+        | YamlOutput::Commented(ref node, _)
+        | YamlOutput::Spelled(ref node, _)
+        | YamlOutput::Styled(ref node, _) => count_aliases(node, names, counts),
+        _ => {}
+    }
+}
+
+/// Replaces the aliases of the anchors with few enough of them by the anchored nodes,
+/// walking a document in the same order as `count_aliases`.
+struct AnchorInliner {
+    counts: Vec<usize>,
+    max_aliases: usize,
+    next: usize,
+    names: HashMap<String, usize>,
+    inlined: HashMap<usize, YamlOutput>,
+}
+
+impl AnchorInliner {
+    fn new(doc: &YamlOutput, max_aliases: usize) -> AnchorInliner {
+        let mut counts = Vec::new();
+        count_aliases(doc, &mut HashMap::new(), &mut counts);
+        AnchorInliner {
+            counts,
+            max_aliases,
+            next: 0,
+            names: HashMap::new(),
+            inlined: HashMap::new(),
+        }
+    }
+
+    fn inline(&mut self, node: YamlOutput) -> YamlOutput {
+        match node {
+            YamlOutput::Anchored(name, node) => {
+                let id = self.next;
+                self.next += 1;
+                let node = self.inline(*node);
+                self.names.insert(name.clone(), id);
+                if self.counts[id] <= self.max_aliases {
+                    self.inlined.insert(id, node.clone());
+                    node
+                } else {
+                    YamlOutput::Anchored(name, Box::new(node))
+                }
+            }
+            YamlOutput::Alias(name) => {
+                match self.names.get(&name).and_then(|id| self.inlined.get(id)) {
+                    Some(node) => node.clone(),
+                    None => YamlOutput::Alias(name),
+                }
+            }
+            YamlOutput::Array(v) => {
+                YamlOutput::Array(v.into_iter().map(|x| self.inline(x)).collect())
+            }
+            YamlOutput::Hash(h) => YamlOutput::Hash(
+                h.into_iter()
+                    .map(|(k, v)| (self.inline(k), self.inline(v)))
+                    .collect(),
+            ),
+            YamlOutput::Commented(node, comments) => {
+                YamlOutput::Commented(Box::new(self.inline(*node)), comments)
+            }
+            YamlOutput::Spelled(node, text) => {
+                YamlOutput::Spelled(Box::new(self.inline(*node)), text)
+            }
+            YamlOutput::Styled(node, style) => {
+                YamlOutput::Styled(Box::new(self.inline(*node)), style)
+            }
+            YamlOutput::Tagged(tag, node) => YamlOutput::Tagged(tag, Box::new(self.inline(*node))),
+            node => node,
+        }
+    }
+}
+
+/// Check if `text` matches `pattern`, where `*` stands for any run of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    match pattern.find('*') {
+        None => pattern == text,
+        Some(star) => {
+            let (prefix, rest) = (&pattern[..star], &pattern[star + 1..]);
+            text.starts_with(prefix)
+                && (prefix.len()..=text.len())
+                    .filter(|&i| text.is_char_boundary(i))
+                    .any(|i| glob_match(rest, &text[i..]))
+        }
+    }
+}
+
+fn key_segment(key: &YamlOutput) -> Segment {
+    Segment::Key(match *key {
+        YamlOutput::String(ref v) | YamlOutput::Real(ref v) | YamlOutput::Literal(ref v) => {
+            v.clone()
+        }
+        YamlOutput::Spelled(_, ref v) => v.clone(),
+        YamlOutput::Integer(v) => v.to_string(),
+        YamlOutput::Boolean(v) => v.to_string(),
+        YamlOutput::Null => "~".to_owned(),
+        YamlOutput::Anchored(_, ref key)
+        | YamlOutput::Tagged(_, ref key)
+        | YamlOutput::Commented(ref key, _)
+        | YamlOutput::Styled(ref key, _) => return key_segment(key),
+        ref key => format!("{:?}", key),
+    })
+}
+
+/// Format `v` if it holds YAML documents with at least one collection.
+fn format_embedded(v: &str, options: &FormatOptions) -> Option<String> {
+    let docs = YamlLoader::load_from_str(v).ok()?;
+    if !docs
+        .iter()
+        .any(|doc| doc.as_hash().is_some() || doc.as_vec().is_some())
+    {
+        return None;
+    }
+    let mut options = options.clone();
+    options.embedded_yaml.clear();
+    options.passes.clear();
+    options.line_ending = Some(LineEnding::Lf);
+    let out = format_str(v, &options).ok()?;
+    // a lone document keeps going without a start marker if it had none
+    match out.strip_prefix("---\n") {
+        Some(body) if docs.len() == 1 && !v.trim_start().starts_with("---") => {
+            Some(body.to_owned())
+        }
+        _ => Some(out),
+    }
+}
+
+fn format_embedded_nodes(node: &mut YamlOutput, path: &Path, options: &FormatOptions) {
+    match *node {
+        YamlOutput::String(ref v)
+            if options
+                .embedded_yaml
+                .iter()
+                .any(|pattern| glob_match(pattern, &path.to_string())) =>
+        {
+            if let Some(formatted) = format_embedded(v, options) {
+                *node = YamlOutput::Literal(formatted);
+            }
+        }
+        YamlOutput::Anchored(_, ref mut node)
+        | YamlOutput::Tagged(_, ref mut node)
+        | YamlOutput::Commented(ref mut node, _) => format_embedded_nodes(node, path, options),
+        YamlOutput::Styled(ref mut inner, _) => {
+            format_embedded_nodes(inner, path, options);
+            // the formatted document replaces the style of the string
+            if let YamlOutput::Literal(_) = **inner {
+                *node = (**inner).clone();
+            }
+        }
+        YamlOutput::Array(ref mut v) => {
+            for (i, x) in v.iter_mut().enumerate() {
+                format_embedded_nodes(x, &path.child(Segment::Index(i)), options);
+            }
+        }
+        YamlOutput::Hash(ref mut h) => {
+            for (k, v) in h.iter_mut() {
+                format_embedded_nodes(v, &path.child(key_segment(k)), options);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Reformat every document of `source`, keeping its comments, the text of its plain
+/// scalars such as `0x1F` or `1.50` and, unless `options.is_normalize_scalars()`, the
+/// spelling of its nulls and booleans. Sources
+/// with mixed line endings are accepted, see `FormatOptions::line_ending` for the output.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::format::{self, FormatOptions};
+///
+/// let source = "debug:   true   # for now\nproxy: null\n";
+/// let mut options = FormatOptions::new();
+/// assert_eq!(
+///     format::format_str(source, &options).unwrap(),
+///     "---\ndebug: true # for now\nproxy: null\n"
+/// );
+/// options.normalize_scalars(true);
+/// assert_eq!(
+///     format::format_str(source, &options).unwrap(),
+///     "---\ndebug: true # for now\nproxy: ~\n"
+/// );
+/// ```
+pub fn format_str(source: &str, options: &FormatOptions) -> Result<String, FormatError> {
+    format_timed(
+        source,
+        options,
+        &mut vec![Duration::default(); options.passes.len()],
+    )
+}
+
+/// Parse `source`, apply the rules of `options` and write it again: the one call that
+/// formats a YAML text. The style of the output is chosen with `FormatOptions::indent`,
+/// `quote_style`, `line_width` and `key_spacing`; everything else is kept as
+/// `format_str` keeps it, which this is the same as.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::format::{self, FormatOptions};
+/// use yaml_rust_formatter::QuoteStyle;
+///
+/// let source = "name:   'web'   # the service\nspec: {replicas: 2, ports: [80, 443]}\n";
+/// let mut options = FormatOptions::new();
+/// options.indent(4);
+/// options.quote_style(QuoteStyle::AlwaysSingle);
+/// options.key_spacing(1);
+/// assert_eq!(
+///     format::reformat(source, &options).unwrap(),
+///     "---\nname: 'web' # the service\n\nspec:\n    replicas: 2\n    ports:\n        - 80\n        - 443\n"
+/// );
+/// ```
+pub fn reformat(source: &str, options: &FormatOptions) -> Result<String, FormatError> {
+    format_str(source, options)
+}
+
+/// Like `format_str`, adding the time each pass takes to `durations`.
+fn format_timed(
+    source: &str,
+    options: &FormatOptions,
+    durations: &mut [Duration],
+) -> Result<String, FormatError> {
+    let mut load_options = LoadOptions::new();
+    load_options.keep_comments(true);
+    load_options.keep_spelling(true);
+    load_options.raw_scalars(true);
+    load_options.keep_scalar_style(options.keep_scalar_style);
+    let docs = if options.blank_lines > 0 {
+        let lines: Vec<&str> = source.lines().collect();
+        YamlLoader::load_from_str_with_marks(source, &load_options)?
+            .into_iter()
+            .map(|(mut doc, marks)| {
+                keep_blank_lines(&mut doc, &Path::new(), &marks, &lines, options.blank_lines);
+                doc
+            })
+            .collect()
+    } else {
+        YamlLoader::load_from_str_with_options(source, &load_options)?
+    };
+    let docs: Vec<YamlOutput> = docs
+        .into_iter()
+        .map(YamlOutput::from)
+        .map(|doc| {
+            let mut doc = if options.normalize_scalars {
+                normalize_scalars(doc)
+            } else {
+                doc
+            };
+            if options.quote_style.is_some() {
+                doc = drop_quotes(doc);
+            }
+            if let Some(max_aliases) = options.inline_anchors {
+                doc = AnchorInliner::new(&doc, max_aliases).inline(doc);
+            }
+            if !options.embedded_yaml.is_empty() {
+                format_embedded_nodes(&mut doc, &Path::new(), options);
+            }
+            for (pass, duration) in options.passes.iter().zip(durations.iter_mut()) {
+                let started = Instant::now();
+                let result = pass.run(&mut doc);
+                *duration += started.elapsed();
+                result.map_err(|message| FormatError::Pass(pass.name().to_owned(), message))?;
+            }
+            Ok(doc)
+        })
+        .collect::<Result<_, FormatError>>()?;
+    let mut out = String::new();
+    let mut emitter = YamlEmitter::new(&mut out);
+    if let Some(indent) = options.indent {
+        emitter.indent(indent);
+    }
+    emitter.indent_sequences(options.indent_sequences);
+    emitter.quote_style(options.get_quote_style());
+    emitter.ascii_only(options.ascii_only);
+    emitter.max_line_width(options.line_width);
+    emitter.key_spacing(options.key_spacing);
+    emitter.align_values(options.align_values);
+    emitter.dump_all(&docs)?;
+    // a block scalar ending the output already wrote its last line break
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    let line_ending = options
+        .line_ending
+        .or_else(|| line_ending::detect(source))
+        .unwrap_or(LineEnding::Lf);
+    if line_ending != LineEnding::Lf {
+        out = line_ending::convert(&out, line_ending);
+    }
+    Ok(out)
+}
+
+/// Record on the entries of the block collections in `node`, found at `path`, the
+/// blank lines before them in the `lines` of the source, up to `max`.
+fn keep_blank_lines(node: &mut YamlInput, path: &Path, marks: &Marks, lines: &[&str], max: usize) {
+    // the blank lines ending a block scalar kept with `|+` are its own
+    let ends_with_break = |node: &YamlInput| node.as_str().is_some_and(|v| v.ends_with('\n'));
+    match *node.inner_mut() {
+        YamlInput::Hash(ref mut h) => {
+            let mut after_break = true;
+            for (k, v) in h.iter_mut() {
+                let child = path.child(diff::key_segment(k));
+                if !after_break {
+                    let blank = marks
+                        .key(&child)
+                        .map_or(0, |mark| blank_lines_before(lines, mark.line()));
+                    if blank > 0 {
+                        yaml::comments_mut(v).blank_lines = blank.min(max);
+                    }
+                }
+                after_break = ends_with_break(v);
+                keep_blank_lines(v, &child, marks, lines, max);
+            }
+        }
+        YamlInput::Array(ref mut v) => {
+            let mut after_break = true;
+            for (i, x) in v.iter_mut().enumerate() {
+                let child = path.child(Segment::Index(i));
+                if !after_break {
+                    let blank = marks
+                        .get(&child)
+                        .map_or(0, |mark| blank_lines_before(lines, mark.line()));
+                    if blank > 0 {
+                        yaml::comments_mut(x).blank_lines = blank.min(max);
+                    }
+                }
+                after_break = ends_with_break(x);
+                keep_blank_lines(x, &child, marks, lines, max);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The blank lines above the line `line`, counted from 1, and the comment lines
+/// right above it.
+fn blank_lines_before(lines: &[&str], line: usize) -> usize {
+    let mut blank = 0;
+    for text in lines[..line.saturating_sub(1).min(lines.len())]
+        .iter()
+        .rev()
+    {
+        match text.trim() {
+            "" => blank += 1,
+            text if text.starts_with('#') => {}
+            _ => break,
+        }
+    }
+    blank
+}
+
+/// The replacement of a part of a source, see `format_to_patch`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    /// The byte offsets of the replaced text in the source.
+    pub range: Range<usize>,
+    pub new_text: String,
+}
+
+/// The largest number of pairs of lines `kept_lines` compares to find the lines kept;
+/// larger changes are replaced whole.
+const MAX_DIFF_CELLS: usize = 1 << 22;
+
+/// Reformat `source` like `format_str`, returning the edits that turn it into its
+/// formatted text rather than the text itself, so that an editor can apply them
+/// without moving the cursor out of the unchanged parts. The edits are sorted and do
+/// not overlap: applied from the last to the first, their ranges stay valid. Only
+/// the bytes that differ are replaced, and nothing when the source is formatted.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::format::{self, FormatOptions, TextEdit};
+///
+/// let source = "---\na:   1\nb: 2\nc:    3\n";
+/// let edits = format::format_to_patch(source, &FormatOptions::new()).unwrap();
+/// assert_eq!(
+///     edits,
+///     vec![
+///         TextEdit { range: 7..9, new_text: String::new() },
+///         TextEdit { range: 19..22, new_text: String::new() },
+///     ]
+/// );
+/// ```
+pub fn format_to_patch(
+    source: &str,
+    options: &FormatOptions,
+) -> Result<Vec<TextEdit>, FormatError> {
+    Ok(text_edits(source, &format_str(source, options)?))
+}
+
+/// The number of unchanged lines `format_to_diff` shows around each change.
+const DIFF_CONTEXT: usize = 3;
+
+/// Reformat `source` like `format_str`, returning a unified diff from it to its
+/// formatted text, with the headers naming both sides `name` and three lines of
+/// context around the changes. The diff is empty when the source is formatted.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::format::{self, FormatOptions};
+///
+/// let options = FormatOptions::new();
+/// let diff = format::format_to_diff("a:   1\nb: 2\n", "app.yaml", &options).unwrap();
+/// assert_eq!(
+///     diff,
+///     "--- app.yaml\n+++ app.yaml\n@@ -1,2 +1,3 @@\n-a:   1\n+---\n+a: 1\n b: 2\n"
+/// );
+/// assert_eq!(format::format_to_diff("---\na: 1\n", "app.yaml", &options).unwrap(), "");
+/// ```
+pub fn format_to_diff(
+    source: &str,
+    name: &str,
+    options: &FormatOptions,
+) -> Result<String, FormatError> {
+    Ok(unified_diff(source, &format_str(source, options)?, name))
+}
+
+/// The unified diff from `old` to `new`, both named `name`.
+fn unified_diff(old: &str, new: &str, name: &str) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let mut kept = kept_lines(&old_lines, &new_lines);
+    kept.push((old_lines.len(), new_lines.len()));
+
+    // every line of both sides with its sign, and the lines of each side before it
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in kept {
+        lines.extend((i..next_i).map(|k| ('-', old_lines[k], k, j)));
+        lines.extend((j..next_j).map(|k| ('+', new_lines[k], next_i, k)));
+        if next_i < old_lines.len() {
+            lines.push((' ', old_lines[next_i], next_i, next_j));
+        }
+        i = next_i + 1;
+        j = next_j + 1;
+    }
+    let changes: Vec<usize> = (0..lines.len()).filter(|&k| lines[k].0 != ' ').collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // the ranges of lines shown, merging the changes whose contexts touch
+    let mut hunks: Vec<Range<usize>> = Vec::new();
+    for &change in &changes {
+        let start = change.saturating_sub(DIFF_CONTEXT);
+        let end = (change + DIFF_CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.end => hunk.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+
+    let header = |start: usize, count: usize| match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    };
+    let mut out = format!("--- {}\n+++ {}\n", name, name);
+    for hunk in hunks {
+        let shown = &lines[hunk];
+        let removed = shown.iter().filter(|line| line.0 != '+').count();
+        let added = shown.iter().filter(|line| line.0 != '-').count();
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            header(shown[0].2, removed),
+            header(shown[0].3, added)
+        ));
+        for &(sign, line, _, _) in shown {
+            out.push(sign);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// A part of a source that differs from its formatted text, see `check`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    mark: Marker,
+    found: String,
+    expected: String,
+}
+
+impl Violation {
+    /// Where the part starts. The line starts from 1, the column from 0.
+    pub fn marker(&self) -> &Marker {
+        &self.mark
+    }
+
+    /// The text of the source, empty when the formatted text has more.
+    pub fn found(&self) -> &str {
+        &self.found
+    }
+
+    /// The text of the formatted source, empty when the source has too much.
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+}
+
+impl fmt::Display for Violation {
+    // col starts from 0
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.found.is_empty() {
+            write!(formatter, "missing {:?}", self.expected)?;
+        } else if self.expected.is_empty() {
+            write!(formatter, "unexpected {:?}", self.found)?;
+        } else {
+            write!(formatter, "{:?} instead of {:?}", self.found, self.expected)?;
+        }
+        write!(
+            formatter,
+            " at line {} column {}",
+            self.mark.line(),
+            self.mark.col() + 1
+        )
+    }
+}
+
+/// What `check` found in a source.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheckResult {
+    violations: Vec<Violation>,
+}
+
+impl CheckResult {
+    pub fn is_formatted(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// The parts differing from the formatted text, in the order of the source.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+}
+
+/// Tell whether `source` is formatted as `format_str` would format it and where it is
+/// not, for a CI job to fail with the places to fix. The places are the ones
+/// `format_to_patch` would edit.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::format::{self, FormatOptions};
+///
+/// let options = FormatOptions::new();
+/// assert!(format::check("---\na: 1\n", &options).unwrap().is_formatted());
+///
+/// let result = format::check("---\na: 1\nb:    [2]\n", &options).unwrap();
+/// let messages: Vec<String> = result.violations().iter().map(|v| v.to_string()).collect();
+/// assert_eq!(
+///     messages,
+///     vec![r#""    [2]" instead of "\n  - 2" at line 3 column 3"#]
+/// );
+/// ```
+pub fn check(source: &str, options: &FormatOptions) -> Result<CheckResult, FormatError> {
+    let formatted = format_str(source, options)?;
+    let violations = text_edits(source, &formatted)
+        .into_iter()
+        .map(|edit| Violation {
+            mark: marker_at(source, edit.range.start),
+            found: source[edit.range].to_owned(),
+            expected: edit.new_text,
+        })
+        .collect();
+    Ok(CheckResult { violations })
+}
+
+/// Reformat like `format_str` only the part of `source` around the bytes in `range`,
+/// for an editor to format a selection, keeping the rest of the text byte for byte.
+/// The part reformatted is made of the entries at the root of the documents that
+/// `range` touches, or of the YAML nodes of the documents when they hold no block
+/// collection, each replaced by its text in the output of `format_str`. An entry
+/// runs up to the next one, with the comments between them, and goes with the next
+/// one when the formatting moves its comments there.
+///
+/// # Errors
+///
+/// Besides the errors of `format_str`, `FormatError::Range` when the text with the
+/// part replaced would not load to the documents of `source`, or would not keep its
+/// comments.
+///
+/// # Panics
+///
+/// When `range` does not lie on character boundaries of `source`.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::format::{self, FormatOptions};
+///
+/// let source = "---\na:   1\nb: {x: 1}\nc:    3\n";
+/// let start = source.find("b:").unwrap();
+/// assert_eq!(
+///     format::format_range(source, start..start + 1, &FormatOptions::new()).unwrap(),
+///     "---\na:   1\nb:\n  x: 1\nc:    3\n"
+/// );
+/// ```
+pub fn format_range(
+    source: &str,
+    range: Range<usize>,
+    options: &FormatOptions,
+) -> Result<String, FormatError> {
+    assert!(
+        source.get(range.clone()).is_some(),
+        "{:?} is not a range of the source",
+        range
+    );
+    let formatted = format_str(source, options)?;
+    let (old, new) = (Cst::parse(source)?, Cst::parse(&formatted)?);
+    let (old_comments, new_comments) = (comment_starts(&old), comment_starts(&new));
+    let count = |comments: &[usize], part: &Range<usize>| {
+        comments
+            .iter()
+            .filter(|&&start| part.contains(&start))
+            .count()
+    };
+    // a part whose comments the formatting moves into the next one goes with it
+    let mut parts: Vec<(Range<usize>, Range<usize>, Range<usize>)> = Vec::new();
+    for (around, part, text) in range_parts(&old, &new) {
+        match parts.last_mut() {
+            Some(last) if count(&old_comments, &last.1) != count(&new_comments, &last.2) => {
+                last.0.end = around.end;
+                last.1.end = part.end;
+                last.2.end = text.end;
+            }
+            _ => parts.push((around, part, text)),
+        }
+    }
+
+    // an empty range touches the parts it is in or at an end of
+    let touches = |part: &Range<usize>| {
+        if range.is_empty() {
+            part.start <= range.start && range.start <= part.end
+        } else {
+            part.start < range.end && range.start < part.end
+        }
+    };
+    parts.retain(|(around, _, _)| touches(around));
+    let (first, last) = match (parts.first(), parts.last()) {
+        (Some(first), Some(last)) => (first.1.start, last.1.end),
+        _ => return Ok(source.to_owned()),
+    };
+
+    let mut out = String::with_capacity(source.len());
+    let mut end = 0;
+    for (_, part, text) in &parts {
+        if count(&old_comments, part) != count(&new_comments, text) {
+            return Err(FormatError::Range(first..last));
+        }
+        out.push_str(&source[end..part.start]);
+        out.push_str(&formatted[text.clone()]);
+        end = part.end;
+    }
+    out.push_str(&source[end..]);
+    match YamlLoader::load_from_str(&out) {
+        Ok(ref docs) if *docs == YamlLoader::load_from_str(source)? => Ok(out),
+        _ => Err(FormatError::Range(first..last)),
+    }
+}
+
+/// The parts of the source of `old` that `format_range` reformats, in order, each with
+/// the bytes a range touches to select it and the part of `new`, its formatted text,
+/// that replaces it. Documents whose entries do not pair up are replaced by their YAML
+/// node, selected by the whole document, and the whole source is replaced when the
+/// documents themselves do not pair up.
+fn range_parts(old: &Cst, new: &Cst) -> Vec<(Range<usize>, Range<usize>, Range<usize>)> {
+    let old_docs: Vec<SyntaxNode> = old.documents().collect();
+    let new_docs: Vec<SyntaxNode> = new.documents().collect();
+    if old_docs.len() != new_docs.len() {
+        let all = 0..old.text().len();
+        return vec![(all.clone(), all, 0..new.text().len())];
+    }
+    let mut parts = Vec::new();
+    for (old_doc, new_doc) in old_docs.iter().zip(&new_docs) {
+        let around = old_doc.text_range();
+        match (entry_parts(old_doc), entry_parts(new_doc)) {
+            (Some(a), Some(b)) if a.len() == b.len() => {
+                parts.extend(a.into_iter().zip(b).map(|(a, b)| (a.clone(), a, b)))
+            }
+            _ => match (old_doc.content(), new_doc.content()) {
+                (Some(a), Some(b)) => parts.push((around, a.text_range(), b.text_range())),
+                (None, None) => {}
+                _ => parts.push((around.clone(), around, new_doc.text_range())),
+            },
+        }
+    }
+    parts
+}
+
+/// The entries at the root of `doc` if it holds a block collection, each running up to
+/// the next one.
+fn entry_parts(doc: &SyntaxNode) -> Option<Vec<Range<usize>>> {
+    let content = doc.content().filter(|content| {
+        content.kind() == SyntaxKind::BlockMapping || content.kind() == SyntaxKind::BlockSequence
+    })?;
+    let starts: Vec<usize> = content
+        .children()
+        .iter()
+        .map(|entry| entry.text_range().start)
+        .collect();
+    let end = content.text_range().end;
+    Some(
+        starts
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| start..starts.get(i + 1).copied().unwrap_or(end))
+            .collect(),
+    )
+}
+
+/// The offsets of the comments of `cst`.
+fn comment_starts(cst: &Cst) -> Vec<usize> {
+    cst.root()
+        .descendants()
+        .iter()
+        .flat_map(|node| node.tokens())
+        .filter(|token| token.kind() == SyntaxKind::Comment)
+        .map(|token| token.text_range().start)
+        .collect()
+}
+
+/// The marker of the byte `offset` of `source`.
+fn marker_at(source: &str, offset: usize) -> Marker {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Marker::new(
+        before.chars().count(),
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count(),
+    )
+}
+
+/// The edits turning `old` into `new`: the runs of lines of `old` that are not kept
+/// in `new`, trimmed to the bytes that differ.
+fn text_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let mut kept = kept_lines(&old_lines, &new_lines);
+    kept.push((old_lines.len(), new_lines.len()));
+
+    let mut offset = 0;
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in kept {
+        if next_i > i || next_j > j {
+            let removed: String = old_lines[i..next_i].concat();
+            let added: String = new_lines[j..next_j].concat();
+            edits.extend(trimmed_edit(offset, &removed, &added));
+            offset += removed.len();
+        }
+        if next_i < old_lines.len() {
+            offset += old_lines[next_i].len();
+        }
+        i = next_i + 1;
+        j = next_j + 1;
+    }
+    edits
+}
+
+/// The pairs of indices of the lines of `old` kept in `new`: the lines they start and
+/// end with, and a longest common subsequence of the others when it is small enough
+/// to compute.
+fn kept_lines(old: &[&str], new: &[&str]) -> Vec<(usize, usize)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut kept: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    if old_middle.len() * new_middle.len() <= MAX_DIFF_CELLS {
+        kept.extend(
+            common_subsequence(old_middle, new_middle)
+                .into_iter()
+                .map(|(i, j)| (prefix + i, prefix + j)),
+        );
+    }
+    kept.extend((0..suffix).map(|k| (old.len() - suffix + k, new.len() - suffix + k)));
+    kept
+}
+
+/// The pairs of indices of a longest common subsequence of `old` and `new`.
+fn common_subsequence<T: PartialEq>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+    let width = new.len() + 1;
+    // lengths[i * width + j]: the longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// The edit replacing `removed`, found at `offset`, by `added`, without the text they
+/// start or end with in common.
+fn trimmed_edit(offset: usize, removed: &str, added: &str) -> Option<TextEdit> {
+    let prefix: usize = removed
+        .chars()
+        .zip(added.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    let (removed, added) = (&removed[prefix..], &added[prefix..]);
+    let suffix: usize = removed
+        .chars()
+        .rev()
+        .zip(added.chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    let (removed, added) = (
+        &removed[..removed.len() - suffix],
+        &added[..added.len() - suffix],
+    );
+    if removed.is_empty() && added.is_empty() {
+        return None;
+    }
+    Some(TextEdit {
+        range: offset + prefix..offset + prefix + removed.len(),
+        new_text: added.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl FormatProgress for Events {
+        fn start(&mut self, path: &path::Path) {
+            self.0.push(format!(
+                "start {}",
+                path.file_name().unwrap().to_string_lossy()
+            ));
+        }
+
+        fn finish(
+            &mut self,
+            path: &path::Path,
+            _duration: Duration,
+            result: &Result<bool, FileError>,
+        ) {
+            let name = path.file_name().unwrap().to_string_lossy();
+            self.0.push(match *result {
+                Ok(changed) => format!("finish {} {}", name, changed),
+                Err(FileError::Io(_)) => format!("finish {} io error", name),
+                Err(FileError::Format(ref err)) => format!("finish {} {}", name, err),
+            });
+        }
+    }
+
+    #[test]
+    fn test_format_files() {
+        let dir = std::env::temp_dir().join(format!("yaml-format-files-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths: Vec<_> = ["messy.yaml", "clean.yaml", "broken.yaml", "missing.yaml"]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect();
+        fs::write(&paths[0], "a:   [1,2]\n").unwrap();
+        fs::write(&paths[1], "---\na: 1\n").unwrap();
+        fs::write(&paths[2], "a: [\n").unwrap();
+
+        let mut options = FormatOptions::new();
+        options.check(true);
+        let mut events = Events::default();
+        let summary = format_files(&paths, &options, &mut events);
+        assert_eq!(
+            (summary.changed(), summary.unchanged(), summary.failed()),
+            (1, 1, 2)
+        );
+        assert_eq!(
+            events.0,
+            vec![
+                "start messy.yaml",
+                "finish messy.yaml true",
+                "start clean.yaml",
+                "finish clean.yaml false",
+                "start broken.yaml",
+                "finish broken.yaml while parsing a node, did not find expected node content at line 2 column 1",
+                "start missing.yaml",
+                "finish missing.yaml io error",
+            ]
+        );
+        assert_eq!(fs::read_to_string(&paths[0]).unwrap(), "a:   [1,2]\n");
+
+        options.check(false);
+        let summary = format_files(&paths[..2], &options, &mut ());
+        assert_eq!(summary.changed(), 1);
+        assert_eq!(
+            fs::read_to_string(&paths[0]).unwrap(),
+            "---\na:\n  - 1\n  - 2\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format_files_cached() {
+        let dir = std::env::temp_dir().join(format!("yaml-format-cached-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths: Vec<_> = ["messy.yaml", "clean.yaml"]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect();
+        fs::write(&paths[0], "a:   [1,2]\n").unwrap();
+        fs::write(&paths[1], "---\na: 1\n").unwrap();
+
+        let mut options = FormatOptions::new();
+        options.pass(LowercaseLabels);
+        let mut cache = FormatCache::new();
+        let summary = format_files_cached(&paths, &options, &mut cache, &mut ());
+        assert_eq!(
+            (summary.changed(), summary.unchanged(), summary.cached()),
+            (1, 1, 0)
+        );
+        assert_eq!(summary.pass_durations().len(), 1);
+        assert_eq!(summary.pass_durations()[0].0, "lowercase-labels");
+        assert_eq!(cache.len(), 2);
+
+        let summary = format_files_cached(&paths, &options, &mut cache, &mut ());
+        assert_eq!(
+            (summary.changed(), summary.unchanged(), summary.cached()),
+            (0, 2, 2)
+        );
+        assert_eq!(summary.pass_durations()[0].1, Duration::default());
+
+        // an edited file, or other options, are formatted again
+        fs::write(&paths[1], "a:  2\n").unwrap();
+        let summary = format_files_cached(&paths, &options, &mut cache, &mut ());
+        assert_eq!((summary.changed(), summary.cached()), (1, 1));
+        options.normalize_scalars(true);
+        let summary = format_files_cached(&paths, &options, &mut cache, &mut ());
+        assert_eq!((summary.unchanged(), summary.cached()), (2, 0));
+        assert_eq!(cache.len(), 5);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    struct LowercaseLabels;
+
+    impl FormatPass for LowercaseLabels {
+        fn name(&self) -> &str {
+            "lowercase-labels"
+        }
+
+        fn run(&self, doc: &mut YamlOutput) -> Result<(), String> {
+            let key = YamlOutput::String("labels".to_owned());
+            if let YamlOutput::Hash(ref mut h) = *doc {
+                let labels = match h.get_mut(&key) {
+                    Some(YamlOutput::Commented(node, _)) => &mut **node,
+                    Some(node) => node,
+                    None => return Ok(()),
+                };
+                if let YamlOutput::Hash(labels) = labels {
+                    let entries = std::mem::take(labels);
+                    for (k, v) in entries {
+                        let k = match k {
+                            YamlOutput::String(k) => YamlOutput::String(k.to_lowercase()),
+                            k => k,
+                        };
+                        labels.insert(k, v);
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    struct RequireLabels;
+
+    impl FormatPass for RequireLabels {
+        fn name(&self) -> &str {
+            "require-labels"
+        }
+
+        fn run(&self, doc: &mut YamlOutput) -> Result<(), String> {
+            match *doc {
+                YamlOutput::Hash(ref h)
+                    if h.contains_key(&YamlOutput::String("labels".to_owned())) =>
+                {
+                    Ok(())
+                }
+                _ => Err("missing labels".to_owned()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_passes() {
+        let mut options = FormatOptions::new();
+        options.pass(LowercaseLabels);
+        options.pass(RequireLabels);
+        assert_eq!(
+            format!("{:?}", options.passes()[1]),
+            "FormatPass(require-labels)"
+        );
+        assert_eq!(
+            format_str("labels: {App: web, Tier: front} # kept\n", &options).unwrap(),
+            "---\nlabels: # kept\n  app: web\n  tier: front\n"
+        );
+        let err = format_str("labels: {}\n---\nname: x\n", &options).unwrap_err();
+        assert_eq!(err.to_string(), "require-labels: missing labels");
+    }
+
+    /// Sets the `team` label to its configured value.
+    struct TeamLabel(&'static str);
+
+    impl FormatPass for TeamLabel {
+        fn name(&self) -> &str {
+            "team-label"
+        }
+
+        fn run(&self, doc: &mut YamlOutput) -> Result<(), String> {
+            if let YamlOutput::Hash(ref mut h) = *doc {
+                h.insert(
+                    YamlOutput::String("team".to_owned()),
+                    YamlOutput::String(self.0.to_owned()),
+                );
+            }
+            Ok(())
+        }
+
+        fn hash_config(&self, state: &mut dyn Hasher) {
+            state.write(self.0.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_pass_config_hash() {
+        fn hash<P: FormatPass + 'static>(pass: P) -> u64 {
+            let mut options = FormatOptions::new();
+            options.pass(pass);
+            options.output_hash()
+        }
+        assert_ne!(hash(TeamLabel("web")), hash(TeamLabel("db")));
+        assert_eq!(hash(TeamLabel("web")), hash(TeamLabel("web")));
+        // passes without settings are told apart by their name
+        assert_ne!(hash(LowercaseLabels), hash(RequireLabels));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("data.*.yaml", "data.config.yaml"));
+        assert!(glob_match("data.*.yaml", "data.app.config.yaml"));
+        assert!(!glob_match("data.*.yaml", "data.config.json"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "a\u{e9}bxc"));
+        assert!(!glob_match("a", "ab"));
+    }
+
+    #[test]
+    fn test_format_embedded_yaml() {
+        let source = "apiVersion: v1
+kind: ConfigMap
+data:
+  app.yaml: \"server:   {port: 80,  hosts: [a, b]}  # main\\n\"
+  multi.yaml: \"a: 1\\n---\\nb: 2\\n\"
+  notes.yaml: just a sentence
+  broken.yaml: \"a: [\"
+  app.json: \"{\\\"a\\\": 1}\"
+";
+        let mut options = FormatOptions::new();
+        options.embedded_yaml("data.*.yaml");
+        assert_eq!(options.embedded_yaml_patterns(), ["data.*.yaml"]);
+        let out = format_str(source, &options).unwrap();
+        assert_eq!(
+            out,
+            "---
+apiVersion: v1
+kind: ConfigMap
+data:
+  app.yaml: |
+    server: # main
+      port: 80
+      hosts:
+        - a
+        - b
+  multi.yaml: |
+    ---
+    a: 1
+    ---
+    b: 2
+  notes.yaml: just a sentence
+  broken.yaml: \"a: [\"
+  app.json: \"{\\\"a\\\": 1}\"
+"
+        );
+        assert_eq!(format_str(&out, &options).unwrap(), out);
+    }
+
+    #[test]
+    fn test_format_str_keeps_scalar_text() {
+        let source = "{mask: 0x1F, mode: 0o644, version: 1.50, n: +1, on: yes, e: 1e3, TRUE: x}";
+        let mut options = FormatOptions::new();
+        options.normalize_scalars(true);
+        assert_eq!(
+            format_str(source, &options).unwrap(),
+            "---\nmask: 0x1F\nmode: 0o644\nversion: 1.50\nn: +1\non: yes\ne: 1e3\nTRUE: x\n"
+        );
+    }
+
+    #[test]
+    fn test_format_str_folded_plain_scalar() {
+        let source = "a: 1st\n\n  2nd\nb: one\n  two\n";
+        let options = FormatOptions::new();
+        let formatted = format_str(source, &options).unwrap();
+        assert_eq!(formatted, "---\na: \"1st\\n2nd\"\nb: one two\n");
+        assert_eq!(format_str(&formatted, &options).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_inline_anchors() {
+        let source = "\
+unused: &unused 1
+base: &base {image: web}
+single: &single [a, b]
+x: *base
+y: *base
+z: *single
+redefined: &single c
+w: *single
+";
+        let mut options = FormatOptions::new();
+        options.inline_anchors(1);
+        let out = format_str(source, &options).unwrap();
+        assert_eq!(
+            out,
+            "---\nunused: 1\nbase: &base\n  image: web\nsingle:\n  - a\n  - b\nx: *base\ny: *base\nz:\n  - a\n  - b\nredefined: c\nw: c\n"
+        );
+        options.inline_anchors(0);
+        let out = format_str("a: &a 1\nb: *a\n", &options).unwrap();
+        assert_eq!(out, "---\na: &a 1\nb: *a\n");
+        let out = format_str("a: &a [&b 1]\nb: *a\nc: *a\n", &options).unwrap();
+        assert_eq!(out, "---\na: &a\n  - 1\nb: *a\nc: *a\n");
+    }
+
+    #[test]
+    fn test_format_str_line_ending() {
+        let source = "a: 1\r\nb: |\r  x\n  y\n";
+        let out = format_str(source, &FormatOptions::new()).unwrap();
+        assert_eq!(out, "---\r\na: 1\r\nb: |\r\n  x\r\n  y\r\n");
+
+        let mut options = FormatOptions::new();
+        options.line_ending(LineEnding::Lf);
+        let out = format_str(source, &options).unwrap();
+        assert_eq!(out, "---\na: 1\nb: |\n  x\n  y\n");
+        assert_eq!(
+            format_str("[a, b]", &FormatOptions::new()).unwrap(),
+            "---\n- a\n- b\n"
+        );
+    }
+
+    #[test]
+    fn test_format_str_spelling() {
+        let source = "
+a: ~
+b: null
+c: Null
+d: NULL
+e: [true, True, TRUE, false, False, FALSE]
+f: 'True'
+g: !!str NULL
+";
+        let out = format_str(source, &FormatOptions::new()).unwrap();
+        assert_eq!(
+            out,
+            "---
+a: ~
+b: null
+c: Null
+d: NULL
+e:
+  - true
+  - True
+  - TRUE
+  - false
+  - False
+  - FALSE
+f: 'True'
+g: \"NULL\"
+"
+        );
+
+        let mut options = FormatOptions::new();
+        options.normalize_scalars(true);
+        let out = format_str(source, &options).unwrap();
+        assert_eq!(
+            out,
+            "---
+a: ~
+b: ~
+c: Null
+d: NULL
+e:
+  - true
+  - True
+  - TRUE
+  - false
+  - False
+  - FALSE
+f: 'True'
+g: \"NULL\"
+"
+        );
+    }
+
+    /// Apply `edits` to `source`, from the last one to the first.
+    fn apply(source: &str, edits: &[TextEdit]) -> String {
+        let mut text = source.to_owned();
+        for edit in edits.iter().rev() {
+            text.replace_range(edit.range.clone(), &edit.new_text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_format_to_patch() {
+        let options = FormatOptions::new();
+        let sources = [
+            "---\na: 1\n",
+            "a:   1\n",
+            "# head\nlist:\n- 1\n-   2\nmap: {x: 1,   y: [a,b]}\nlast: z\n",
+            "a: 1\r\nb:\r\n    c: 2\r\n",
+            "k: caf\u{e9}   # \u{e9}t\u{e9}\nk2: v\n",
+            "a: 1\n---\nb:   2\n",
+        ];
+        for source in &sources {
+            let formatted = format_str(source, &options).unwrap();
+            let edits = format_to_patch(source, &options).unwrap();
+            assert_eq!(apply(source, &edits), formatted, "{:?}", source);
+            assert!(edits.windows(2).all(|w| w[0].range.end <= w[1].range.start));
+        }
+        assert!(format_to_patch("---\na: 1\n", &options).unwrap().is_empty());
+
+        // a change in the middle of a long source stays small
+        let mut source: String = (0..100).map(|i| format!("k{}: {}\n", i, i)).collect();
+        source.insert_str(0, "---\n");
+        let line = source.find("k50:").unwrap();
+        source.insert_str(line + 4, "   ");
+        let edits = format_to_patch(&source, &options).unwrap();
+        assert_eq!(
+            edits,
+            vec![TextEdit {
+                range: line + 5..line + 8,
+                new_text: String::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_reformat_style() {
+        let source = "a: {b: [1, 2], c: 'some words to wrap here'}\n# d\nd: plain words to wrap here\n---\n- x\n- {y: 1, z: 2}\n";
+        let mut options = FormatOptions::new();
+        let plain = options.output_hash();
+        options.indent(3);
+        options.quote_style(QuoteStyle::SingleWhenNeeded);
+        options.line_width(Some(20));
+        options.key_spacing(2);
+        assert_ne!(options.output_hash(), plain);
+        let out = reformat(source, &options).unwrap();
+        assert_eq!(
+            out,
+            "---
+a:
+   b:
+      - 1
+      - 2
+   c: some words to
+      wrap here
+
+
+# d
+d: plain words to
+   wrap here
+---
+- x
+- y: 1
+  z: 2
+"
+        );
+        assert_eq!(reformat(&out, &options).unwrap(), out);
+    }
+
+    #[test]
+    fn test_keep_scalar_style() {
+        let source = "a:   'x'\nb: [\"y\",   'z']\nc: |-\n  one\n  two\n";
+        let mut options = FormatOptions::new();
+        let formatted = format_str(source, &options).unwrap();
+        assert_eq!(
+            formatted,
+            "---\na: 'x'\nb:\n  - \"y\"\n  - 'z'\nc: |-\n  one\n  two\n"
+        );
+        assert_eq!(format_str(&formatted, &options).unwrap(), formatted);
+
+        options.quote_style(QuoteStyle::AlwaysDouble);
+        assert_eq!(
+            format_str(source, &options).unwrap(),
+            "---\na: \"x\"\nb:\n  - \"y\"\n  - \"z\"\nc: |-\n  one\n  two\n"
+        );
+    }
+
+    #[test]
+    fn test_keep_blank_lines() {
+        let source = "\n\nname: web\n\n\n\nspec:\n\n  a: 1\n\n  # b next\n\n  b: |+\n    x\n\n  c: 3\nsteps:\n  - run: a\n\n  - run: b\n";
+        let mut options = FormatOptions::new();
+        assert_eq!(
+            format_str(source, &options).unwrap(),
+            "---\nname: web\nspec:\n  a: 1\n  # b next\n  b: |+\n    x\n\n  c: 3\nsteps:\n  - run: a\n  - run: b\n"
+        );
+        options.keep_blank_lines(2);
+        let formatted = format_str(source, &options).unwrap();
+        assert_eq!(
+            formatted,
+            "---\nname: web\n\n\nspec:\n  a: 1\n\n\n  # b next\n  b: |+\n    x\n\n  c: 3\nsteps:\n  - run: a\n\n  - run: b\n"
+        );
+        assert_eq!(format_str(&formatted, &options).unwrap(), formatted);
+
+        options.keep_blank_lines(usize::MAX);
+        options.key_spacing(1);
+        let source = "a: 1\r\nb: 2\r\n\r\n\r\n\r\nc: 3\r\n";
+        assert_eq!(
+            format_str(source, &options).unwrap(),
+            "---\r\na: 1\r\n\r\nb: 2\r\n\r\n\r\n\r\nc: 3\r\n"
+        );
+    }
+
+    #[test]
+    fn test_align_values() {
+        let source = "DB_HOST: db\nDB_PORT:   5432\n\nAPP_NAME: web # shown\nLOG: info\n";
+        let mut options = FormatOptions::new();
+        options.align_values(10);
+        options.keep_blank_lines(1);
+        let formatted = format_str(source, &options).unwrap();
+        assert_eq!(
+            formatted,
+            "---\nDB_HOST: db\nDB_PORT: 5432\n\nAPP_NAME: web # shown\nLOG:      info\n"
+        );
+        assert_eq!(format_str(&formatted, &options).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_to_diff() {
+        let options = FormatOptions::new();
+        let mut source: String = (0..12).map(|i| format!("k{}: {}\n", i, i)).collect();
+        source.insert_str(0, "---\n");
+        let source = source
+            .replace("k1: 1", "k1:   1")
+            .replace("k10: 10", "k10:  10");
+        assert_eq!(
+            format_to_diff(&source, "keys.yaml", &options).unwrap(),
+            "--- keys.yaml\n+++ keys.yaml\n\
+             @@ -1,6 +1,6 @@\n ---\n k0: 0\n-k1:   1\n+k1: 1\n k2: 2\n k3: 3\n k4: 4\n\
+             @@ -9,5 +9,5 @@\n k7: 7\n k8: 8\n k9: 9\n-k10:  10\n+k10: 10\n k11: 11\n"
+        );
+
+        // changes three lines apart share a hunk
+        let source = "---\na:  1\nb: 2\nc: 3\nd: 4\ne:  5\n";
+        assert_eq!(
+            format_to_diff(source, "x", &options).unwrap(),
+            "--- x\n+++ x\n@@ -1,6 +1,6 @@\n ---\n-a:  1\n+a: 1\n b: 2\n c: 3\n d: 4\n-e:  5\n+e: 5\n"
+        );
+
+        assert_eq!(
+            format_to_diff("---\na: 1", "x", &options).unwrap(),
+            "--- x\n+++ x\n@@ -1,2 +1,2 @@\n ---\n-a: 1\n\\ No newline at end of file\n+a: 1\n"
+        );
+        assert_eq!(format_to_diff("", "x", &options).unwrap(), "");
+        assert!(format_to_diff("a: [", "x", &options).is_err());
+    }
+
+    #[test]
+    fn test_format_range() {
+        let options = FormatOptions::new();
+        let source = "---\nname:   web\nspec:\n    ports: [80,  443]\n    host:   a\nlast:    z\n";
+        let at = |text: &str| source.find(text).unwrap();
+
+        // a nested line reformats the whole entry at the root holding it
+        let start = at("host");
+        assert_eq!(
+            format_range(source, start..start, &options).unwrap(),
+            "---\nname:   web\nspec:\n  ports:\n    - 80\n    - 443\n  host: a\nlast:    z\n"
+        );
+        assert_eq!(
+            format_range(source, at("web")..at("last"), &options).unwrap(),
+            "---\nname: web\nspec:\n  ports:\n    - 80\n    - 443\n  host: a\nlast:    z\n"
+        );
+        assert_eq!(
+            format_range(source, 0..source.len(), &options).unwrap(),
+            format_str(source, &options).unwrap()
+        );
+        let formatted = format_str(source, &options).unwrap();
+        assert_eq!(format_range(&formatted, 0..4, &options).unwrap(), formatted);
+
+        // documents holding a scalar are reformatted whole
+        let source = "---\n\"\\x61\"\n---\nb:    1\n";
+        let start = source.find('b').unwrap();
+        assert_eq!(
+            format_range(source, 0..1, &options).unwrap(),
+            "---\n\"a\"\n---\nb:    1\n"
+        );
+        assert_eq!(
+            format_range(source, start..start + 1, &options).unwrap(),
+            "---\n\"\\x61\"\n---\nb: 1\n"
+        );
+        assert!(format_range("a: [", 0..1, &options).is_err());
+
+        // whole entries are replaced, with their comments and values
+        assert_eq!(
+            format_range("b: [1, 2] # flow\n", 0..1, &options).unwrap(),
+            "b: # flow\n  - 1\n  - 2\n"
+        );
+        assert_eq!(
+            format_range("a: 1\nb: [1, 2] # c\n", 5..6, &options).unwrap(),
+            "a: 1\nb: # c\n  - 1\n  - 2\n"
+        );
+        assert_eq!(
+            format_range("a: !!str 1", 10..10, &options).unwrap(),
+            "a: \"1\"\n"
+        );
+        assert_eq!(format_range("? a", 3..3, &options).unwrap(), "a: ~\n");
+        let source = "%TAG !e! tag:example.com,2000:\n---\na: !e!x 1";
+        assert_eq!(format_range(source, 0..0, &options).unwrap(), source);
+        let source = "%TAG !e! tag:example.com,2000:\n---\n!e!x  1\n";
+        assert_eq!(
+            format_range(source, 0..0, &options).unwrap(),
+            "%TAG !e! tag:example.com,2000:\n---\n!<tag:example.com,2000:x> 1\n"
+        );
+
+        // a part whose formatting changes the document is refused
+        let mut options = FormatOptions::new();
+        options.pass(LowercaseLabels);
+        let source = "labels: {A: 1}\nb: 2\n";
+        assert_eq!(format_range(source, 17..17, &options).unwrap(), source);
+        let err = format_range(source, 0..1, &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "formatting bytes 0..15 alone changes the document"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a range of the source")]
+    fn test_format_range_out_of_source() {
+        format_range("a: 1\n", 2..10, &FormatOptions::new()).unwrap();
+    }
+
+    #[test]
+    fn test_check() {
+        let mut options = FormatOptions::new();
+        options.keep_scalar_style(false);
+        let source = "---\nnom:   \"é\"\nlast: 3\nlist: [1,  2]\n";
+        let result = check(source, &options).unwrap();
+        assert!(!result.is_formatted());
+        let places: Vec<(usize, usize, usize)> = result
+            .violations()
+            .iter()
+            .map(|v| (v.marker().index(), v.marker().line(), v.marker().col()))
+            .collect();
+        assert_eq!(places, vec![(9, 2, 5), (28, 4, 5)]);
+        assert_eq!(result.violations()[0].found(), "  \"é\"");
+        assert_eq!(result.violations()[0].expected(), "é");
+        assert_eq!(
+            result.violations()[1].to_string(),
+            r#"" [1,  2]" instead of "\n  - 1\n  - 2" at line 4 column 6"#
+        );
+
+        let formatted = format_str(source, &options).unwrap();
+        assert_eq!(check(&formatted, &options).unwrap(), CheckResult::default());
+        assert!(check("a: [", &options).is_err());
+    }
+
+    #[test]
+    fn test_style_profiles() {
+        let profiles = [
+            StyleProfile::Default,
+            StyleProfile::GoogleStyle,
+            StyleProfile::KubernetesManifest,
+            StyleProfile::GithubActions,
+            StyleProfile::AnsiblePlaybook,
+        ];
+        let mut hashes = HashSet::new();
+        for &profile in profiles.iter() {
+            let mut options = FormatOptions::new();
+            options.profile(profile);
+            hashes.insert(options.output_hash());
+        }
+        assert_eq!(hashes.len(), profiles.len());
+        assert!(hashes.contains(&FormatOptions::new().output_hash()));
+
+        let source = "on: [push]\njobs: {test: {steps: [{run: 'echo \"a: b\"'}]}}\n";
+        let mut options = FormatOptions::new();
+        options.profile(StyleProfile::GithubActions);
+        assert_eq!(
+            format_str(source, &options).unwrap(),
+            "---\non:\n  - push\njobs:\n  test:\n    steps:\n      - run: 'echo \"a: b\"'\n"
+        );
+        options.indent(4);
+        assert_eq!(options.get_line_width(), None);
+        assert!(format_str(source, &options)
+            .unwrap()
+            .contains("\n    test:\n        steps:\n"));
+
+        options.profile(StyleProfile::GoogleStyle);
+        assert_eq!(options.get_indent(), Some(2));
+        let long = format!("text: {}\n", vec!["word"; 30].join(" "));
+        let out = format_str(&long, &options).unwrap();
+        assert!(out.lines().all(|line| line.len() <= 80), "{}", out);
+    }
+
+    #[test]
+    fn test_format_file() {
+        let dir = std::env::temp_dir().join(format!("yaml-format-file-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.yaml");
+        fs::write(&path, "a:   [1]\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        let mut options = FormatOptions::new();
+        options.check(true);
+        assert!(format_file(&path, &options).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a:   [1]\n");
+
+        let options = FormatOptions::new();
+        assert!(format_file(&path, &options).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "---\na:\n  - 1\n");
+        assert!(!format_file(&path, &options).unwrap());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+
+            let link = dir.join("link.yaml");
+            std::os::unix::fs::symlink(&path, &link).unwrap();
+            fs::write(&path, "b: 2").unwrap();
+            assert!(format_file(&link, &options).unwrap());
+            assert!(fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink());
+            assert_eq!(fs::read_to_string(&path).unwrap(), "---\nb: 2\n");
+        }
+
+        fs::write(&path, "a: [").unwrap();
+        assert!(format_file(&path, &options).is_err());
+        assert!(format_file(&dir.join("missing.yaml"), &options).is_err());
+        // no temporary file is left behind
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert!(names
+            .iter()
+            .all(|name| !name.to_string_lossy().ends_with(".tmp")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format_tree() {
+        let dir = std::env::temp_dir().join(format!("yaml-format-tree-{}", std::process::id()));
+        for sub in ["sub", "vendor", "sub/deep"].iter() {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        let files = [
+            ("a.yaml", "a:   1\n"),
+            ("b.yml", "---\nb: 2\n"),
+            ("c.txt", "c:   3\n"),
+            ("vendor/d.yaml", "d:   4\n"),
+            ("sub/e.yaml", "e: [\n"),
+            ("sub/deep/f.yaml", "f:   6\n"),
+            ("sub/deep/g.generated.yaml", "g:   7\n"),
+        ];
+        for &(name, source) in files.iter() {
+            fs::write(dir.join(name), source).unwrap();
+        }
+        let status = |reports: &[FileReport]| -> Vec<(String, Option<bool>)> {
+            reports
+                .iter()
+                .map(|report| {
+                    let path = report.path().strip_prefix(&dir).unwrap();
+                    let path = path.to_string_lossy().replace('\\', "/");
+                    (path, report.result().as_ref().ok().cloned())
+                })
+                .collect()
+        };
+
+        let mut options = FormatOptions::new();
+        options.check(true);
+        let reports = format_tree(&dir, &[], &["vendor", "*.generated.yaml"], &options).unwrap();
+        let expected = vec![
+            ("a.yaml".to_owned(), Some(true)),
+            ("b.yml".to_owned(), Some(false)),
+            ("sub/deep/f.yaml".to_owned(), Some(true)),
+            ("sub/e.yaml".to_owned(), None),
+        ];
+        assert_eq!(status(&reports), expected);
+        assert_eq!(fs::read_to_string(dir.join("a.yaml")).unwrap(), "a:   1\n");
+
+        options.check(false);
+        options.threads(3);
+        let reports = format_tree(&dir, &["sub/*", "a.yaml"], &["e.yaml"], &options).unwrap();
+        let expected = vec![
+            ("a.yaml".to_owned(), Some(true)),
+            ("sub/deep/f.yaml".to_owned(), Some(true)),
+            ("sub/deep/g.generated.yaml".to_owned(), Some(true)),
+        ];
+        assert_eq!(status(&reports), expected);
+        assert_eq!(
+            fs::read_to_string(dir.join("sub/deep/f.yaml")).unwrap(),
+            "---\nf: 6\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("vendor/d.yaml")).unwrap(),
+            "d:   4\n"
+        );
+
+        assert!(format_tree(dir.join("missing"), &[], &[], &options).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
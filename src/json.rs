@@ -0,0 +1,287 @@
+//! Convert documents to and from `serde_json::Value`, to hand them to the tools that
+//! work on JSON, such as JSON Schema validators, see `to_json`.
+
+use crate::base64;
+use crate::diff::{Path, Segment};
+use crate::yaml::{parse_f64, YamlInput};
+use serde_json::{Map, Number, Value};
+use std::error::Error;
+use std::fmt;
+
+/// What `to_json` does with the keys of a mapping that are not strings.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum KeyPolicy {
+    /// A key is written as its JSON text without quotes, like `80`, `true` or `null`,
+    /// so keys with the same text end up as one: the later one wins.
+    #[default]
+    Stringify,
+    /// A key that is not a string is an error.
+    Error,
+}
+
+/// Options controlling how `to_json` converts a document.
+#[derive(Clone, Debug)]
+pub struct JsonOptions {
+    keys: KeyPolicy,
+    expand_aliases: bool,
+}
+
+impl Default for JsonOptions {
+    fn default() -> JsonOptions {
+        JsonOptions {
+            keys: KeyPolicy::default(),
+            expand_aliases: true,
+        }
+    }
+}
+
+impl JsonOptions {
+    pub fn new() -> JsonOptions {
+        JsonOptions::default()
+    }
+
+    pub fn keys(&mut self, keys: KeyPolicy) {
+        self.keys = keys;
+    }
+
+    pub fn get_keys(&self) -> KeyPolicy {
+        self.keys
+    }
+
+    /// Whether an alias is replaced by a copy of its anchored node, `true` by default.
+    /// Otherwise any alias is an error, so a document cannot grow while converted.
+    pub fn expand_aliases(&mut self, expand_aliases: bool) {
+        self.expand_aliases = expand_aliases;
+    }
+
+    pub fn is_expand_aliases(&self) -> bool {
+        self.expand_aliases
+    }
+}
+
+/// A node `to_json` cannot convert.
+#[derive(Clone, PartialEq, Debug)]
+pub struct JsonError {
+    path: Path,
+    info: String,
+}
+
+impl JsonError {
+    /// The path of the offending node, or of the mapping holding the offending key.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+}
+
+impl Error for JsonError {}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.segments().is_empty() {
+            write!(formatter, "{} at the root", self.info)
+        } else {
+            write!(formatter, "{} at {}", self.info, self.path)
+        }
+    }
+}
+
+/// Convert `doc` to JSON, keeping the order of the keys. Anchors, tags, comments and
+/// spellings are dropped, `!!binary` bytes are written as base64 strings, and integers
+/// beyond `i64` as numbers as long as they fit `u64`.
+///
+/// An alias to an anchor that is not defined, and a real that is not finite such as
+/// `.inf`, cannot be written in JSON and are errors. `serde_json::Value::from` writes
+/// them as `null` instead, with the default options.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::json::{self, JsonOptions, KeyPolicy};
+/// use yaml_rust_formatter::YamlLoader;
+///
+/// let doc = &YamlLoader::load_from_str("base: &b {image: web}\nspec: *b\nports: {80: http}")
+///     .unwrap()[0];
+/// let value = json::to_json(doc, &JsonOptions::new()).unwrap();
+/// assert_eq!(
+///     value.to_string(),
+///     r#"{"base":{"image":"web"},"spec":{"image":"web"},"ports":{"80":"http"}}"#
+/// );
+///
+/// let mut options = JsonOptions::new();
+/// options.keys(KeyPolicy::Error);
+/// let err = json::to_json(doc, &options).unwrap_err();
+/// assert_eq!(err.to_string(), "key 80 is not a string at ports");
+///
+/// options.keys(KeyPolicy::Stringify);
+/// options.expand_aliases(false);
+/// let err = json::to_json(doc, &options).unwrap_err();
+/// assert_eq!(err.to_string(), "alias *b is refused at spec");
+/// ```
+pub fn to_json(doc: &YamlInput, options: &JsonOptions) -> Result<Value, JsonError> {
+    Converter {
+        options,
+        path: Path::new(),
+        lossy: false,
+    }
+    .convert(doc)
+}
+
+struct Converter<'o> {
+    options: &'o JsonOptions,
+    path: Path,
+    // whether the nodes JSON cannot hold are written as null rather than refused
+    lossy: bool,
+}
+
+impl<'o> Converter<'o> {
+    fn error(&self, info: String) -> Result<Value, JsonError> {
+        if self.lossy {
+            return Ok(Value::Null);
+        }
+        Err(JsonError {
+            path: self.path.clone(),
+            info,
+        })
+    }
+
+    fn convert(&mut self, node: &YamlInput) -> Result<Value, JsonError> {
+        Ok(match *node.inner() {
+            YamlInput::Aliased(ref name, _) if !self.options.expand_aliases => {
+                self.error(format!("alias *{} is refused", name))?
+            }
+            YamlInput::Aliased(_, Some(ref node)) => self.convert(node)?,
+            YamlInput::Aliased(ref name, None) => self.error(format!("unknown anchor {}", name))?,
+            YamlInput::Raw(ref text) => self.convert(&YamlInput::from_str(text))?,
+            // integers beyond `i64` are loaded as reals
+            YamlInput::Real(ref v) => match v.parse::<u64>() {
+                Ok(u) => Value::from(u),
+                Err(_) => match parse_f64(v).and_then(Number::from_f64) {
+                    Some(n) => Value::Number(n),
+                    None => self.error(format!("{} is not a finite number", v))?,
+                },
+            },
+            YamlInput::Integer(v) => Value::from(v),
+            YamlInput::String(ref v) => Value::String(v.clone()),
+            YamlInput::Boolean(v) => Value::Bool(v),
+            YamlInput::Binary(ref v) => Value::String(base64::encode(v)),
+            YamlInput::Array(ref v) => {
+                let mut array = Vec::with_capacity(v.len());
+                for (i, item) in v.iter().enumerate() {
+                    self.path.push(Segment::Index(i));
+                    array.push(self.convert(item)?);
+                    self.path.pop();
+                }
+                Value::Array(array)
+            }
+            YamlInput::Hash(ref h) => {
+                let mut map = Map::new();
+                for (k, v) in h {
+                    let key = match self.convert(k)? {
+                        Value::String(key) => key,
+                        key if self.options.keys == KeyPolicy::Error => {
+                            return self.error(format!("key {} is not a string", key));
+                        }
+                        key => key.to_string(),
+                    };
+                    self.path.push(Segment::Key(key.clone()));
+                    let value = self.convert(v)?;
+                    self.path.pop();
+                    map.insert(key, value);
+                }
+                Value::Object(map)
+            }
+            // nulls and bad values, `inner` leaves no wrapper
+            _ => Value::Null,
+        })
+    }
+}
+
+/// Converts with the default `JsonOptions`, writing as `null` what `to_json` refuses.
+impl From<YamlInput> for Value {
+    fn from(doc: YamlInput) -> Value {
+        let options = JsonOptions::default();
+        let mut converter = Converter {
+            options: &options,
+            path: Path::new(),
+            lossy: true,
+        };
+        converter.convert(&doc).unwrap_or(Value::Null)
+    }
+}
+
+/// Numbers that are not integers fitting `i64` become reals, written as JSON writes
+/// them.
+impl From<Value> for YamlInput {
+    fn from(value: Value) -> YamlInput {
+        match value {
+            Value::Null => YamlInput::Null,
+            Value::Bool(v) => YamlInput::Boolean(v),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => YamlInput::Integer(i),
+                None => YamlInput::Real(n.to_string()),
+            },
+            Value::String(v) => YamlInput::String(v),
+            Value::Array(v) => YamlInput::Array(v.into_iter().map(YamlInput::from).collect()),
+            Value::Object(map) => YamlInput::Hash(
+                map.into_iter()
+                    .map(|(k, v)| (YamlInput::String(k), YamlInput::from(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::yaml::YamlLoader;
+
+    #[test]
+    fn test_to_json() {
+        let s = "
+a: !!binary aGk=
+b: [1, 1.5, 18446744073709551615, 'x', ~, true]
+c: !tag &c {1: one, [x]: list, ~: none}
+d: *c
+";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        let value = to_json(doc, &JsonOptions::new()).unwrap();
+        assert_eq!(
+            value.to_string(),
+            r#"{"a":"aGk=","b":[1,1.5,18446744073709551615,"x",null,true],"c":{"1":"one","[\"x\"]":"list","null":"none"},"d":{"1":"one","[\"x\"]":"list","null":"none"}}"#
+        );
+        assert_eq!(Value::from(doc.clone()), value);
+
+        let mut options = JsonOptions::new();
+        options.keys(KeyPolicy::Error);
+        let err = to_json(doc, &options).unwrap_err();
+        assert_eq!(err.path().to_string(), "c");
+        assert_eq!(err.info(), "key 1 is not a string");
+
+        let doc = &YamlLoader::load_from_str("- [.inf]\n- *x").unwrap()[0];
+        let err = to_json(doc, &JsonOptions::new()).unwrap_err();
+        assert_eq!(err.to_string(), ".inf is not a finite number at [0][0]");
+        assert_eq!(Value::from(doc.clone()).to_string(), "[[null],null]");
+        let err = to_json(&doc[1], &JsonOptions::new()).unwrap_err();
+        assert_eq!(err.to_string(), "unknown anchor x at the root");
+    }
+
+    #[test]
+    fn test_from_json() {
+        let value: Value = serde_json::from_str(
+            r#"{"a": [1, -2, 2.5, 1e300, 18446744073709551615], "b": {"c": null, "d": false}}"#,
+        )
+        .unwrap();
+        let doc = YamlInput::from(value.clone());
+        let expected = &YamlLoader::load_from_str(
+            "a: [1, -2, 2.5, 1e+300, 18446744073709551615]\nb: {c: ~, d: false}",
+        )
+        .unwrap()[0];
+        assert_eq!(doc, *expected);
+        assert_eq!(to_json(&doc, &JsonOptions::new()).unwrap(), value);
+    }
+}
@@ -11,14 +11,15 @@
 //!
 //! ```toml
 //! [dependencies]
-//! yaml_rust_formatter = { version = "0.5" }
+//! yaml_rust_formatter = { version = "0.6" }
+//! ```
 //!
 //! Parse a string into `Vec<Yaml>` and then serialize it as a YAML string.
 //!
 //! # Examples
 //!
 //! ```
-//! use yaml_rust_formatter::{YamlLoader, YamlEmitter};
+//! use yaml_rust_formatter::{YamlEmitter, YamlLoader, YamlOutput};
 //!
 //! let docs = YamlLoader::load_from_str("[1, 2, 3]").unwrap();
 //! let doc = &docs[0]; // select the first document
@@ -26,30 +27,109 @@
 //!
 //! let mut out_str = String::new();
 //! let mut emitter = YamlEmitter::new(&mut out_str);
-//! emitter.dump(doc).unwrap(); // dump the YAML object to a String
+//! let doc: YamlOutput = doc.clone().into();
+//! emitter.dump(&doc).unwrap(); // dump the YAML object to a String
+//! assert_eq!(out_str, "---\n- 1\n- 2\n- 3");
+//! ```
+//!
+//! # Features
+//!
+//! Loading and emitting are always built, with `diff::Path` to point at nodes. The
+//! other parts of the crate are behind features, none enabled by default:
+//!
+//! - `formatter`: the `format`, `config`, `convert`, `cst`, `report` and `testing`
+//!   modules. It enables `diff` and `unicode`.
+//! - `lint`: the `lint` and `schema` modules, and `line_ending::check`. `lint::fix`
+//!   also needs `formatter`. It enables `unicode`.
+//! - `query`: the `replace` and `mask` modules, and the `regex` dependency.
+//! - `stream`: `YamlLoader::iter_documents` and `YamlEmitter::stream`.
+//! - `diff`: comparing and patching documents with the `diff` module.
+//! - `template`: the `template` module and `LoadOptions::expand_templates`.
+//! - `borrowed`: the `borrowed` module and `YamlLoader::load_from_str_borrowed`.
+//! - `unicode`: `LoadOptions::normalize_keys`, and the display widths and grapheme
+//!   clusters of the Unicode tables where line widths are counted. Without it, every
+//!   character but the common combining marks takes one column.
+//! - `serde`: `from_str` and `to_string`.
+//! - `json`: the `json` module, converting documents to and from
+//!   `serde_json::Value`.
+//! - `toml`: reading `yamlfmt.toml` configurations in the `config` module.
+//! - `metrics`: the `metrics` module, counters recorded through the `metrics`
+//!   facade.
+//!
+//! A formatting tool picks the parts it uses:
 //!
+//! ```toml
+//! [dependencies]
+//! yaml_rust_formatter = { version = "0.6", features = ["formatter", "lint"] }
 //! ```
 
-#![doc(html_root_url = "https://docs.rs/yaml-rust-davvid/0.5.1")]
-#![cfg_attr(feature = "cargo-clippy", allow(renamed_and_removed_lints))]
-#![cfg_attr(feature = "cargo-clippy", warn(cognitive_complexity))]
-#![cfg_attr(
-    feature = "cargo-clippy",
-    allow(match_same_arms, should_implement_trait)
-)]
+#![doc(html_root_url = "https://docs.rs/yaml-rust-davvid/0.6.0")]
+#![allow(clippy::match_same_arms, clippy::should_implement_trait)]
 
 extern crate linked_hash_map;
+#[cfg(feature = "query")]
+extern crate regex;
+#[cfg(feature = "unicode")]
+extern crate unicode_normalization;
 
+mod base64;
+#[cfg(feature = "borrowed")]
+pub mod borrowed;
+#[cfg(feature = "formatter")]
+pub mod config;
+#[cfg(feature = "formatter")]
+pub mod convert;
+#[cfg(feature = "formatter")]
+pub mod cst;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod diff;
 pub mod emitter;
+#[cfg(feature = "formatter")]
+pub mod format;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod line_ending;
+#[cfg(feature = "lint")]
+pub mod lint;
+#[cfg(feature = "query")]
+pub mod mask;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(not(feature = "metrics"))]
+mod metrics;
 pub mod parser;
+#[cfg(feature = "query")]
+pub mod replace;
+#[cfg(feature = "formatter")]
+pub mod report;
 pub mod scanner;
+#[cfg(feature = "lint")]
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "template")]
+pub mod template;
+#[cfg(feature = "formatter")]
+pub mod testing;
 pub mod yaml;
 
 // reexport key APIs
-pub use crate::emitter::{EmitError, YamlEmitter};
+#[cfg(feature = "serde")]
+pub use crate::de::from_str;
+#[cfg(feature = "stream")]
+pub use crate::emitter::StreamBuilder;
+pub use crate::emitter::{EmitError, EmitProfile, NodeError, QuoteStyle, YamlEmitter};
 pub use crate::parser::Event;
 pub use crate::scanner::ScanError;
-pub use crate::yaml::{YamlInput, YamlLoader, YamlOutput};
+#[cfg(feature = "serde")]
+pub use crate::ser::to_string;
+#[cfg(feature = "stream")]
+pub use crate::yaml::Documents;
+pub use crate::yaml::{
+    AliasPolicy, AnchorError, DocumentBuilder, DuplicateKeyPolicy, LoadError, LoadOptions, Marks,
+    MergeStrategy, ResolutionScheme, YamlInput, YamlLoader, YamlOutput,
+};
 
 #[cfg(test)]
 mod tests {
@@ -112,4 +192,45 @@ key1:a2
         assert!(YamlLoader::load_from_str(s).is_err());
         assert!(try_fail(s).is_err());
     }
+
+    #[test]
+    fn test_send_sync() {
+        // documents, options and errors can be moved to and shared with other threads
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<YamlInput>();
+        send_sync::<YamlOutput>();
+        send_sync::<YamlLoader>();
+        send_sync::<LoadOptions>();
+        send_sync::<LoadError>();
+        send_sync::<ScanError>();
+        send_sync::<EmitError>();
+        send_sync::<Event>();
+        #[cfg(feature = "stream")]
+        send_sync::<Documents>();
+        send_sync::<parser::Events<parser::OwnedChars>>();
+        send_sync::<emitter::Directives>();
+        send_sync::<emitter::KeyComments>();
+        #[cfg(feature = "formatter")]
+        send_sync::<format::FormatOptions>();
+        #[cfg(feature = "formatter")]
+        send_sync::<format::FormatError>();
+        #[cfg(feature = "formatter")]
+        send_sync::<format::FormatCache>();
+        #[cfg(feature = "lint")]
+        send_sync::<schema::Schema>();
+        #[cfg(feature = "lint")]
+        send_sync::<schema::SchemaError>();
+        #[cfg(feature = "lint")]
+        send_sync::<lint::Diagnostic>();
+        #[cfg(feature = "query")]
+        send_sync::<mask::MaskRules>();
+        #[cfg(feature = "query")]
+        send_sync::<replace::ReplaceOptions>();
+        #[cfg(feature = "diff")]
+        send_sync::<diff::Change>();
+        #[cfg(feature = "formatter")]
+        send_sync::<convert::ConversionNote>();
+        #[cfg(feature = "formatter")]
+        send_sync::<report::RunSummary>();
+    }
 }
@@ -0,0 +1,236 @@
+#[cfg(feature = "lint")]
+use crate::lint::Diagnostic;
+use crate::scanner::Marker;
+use std::fmt;
+
+/// The characters ending a line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LineEnding {
+    /// `\n`, as written on Unix.
+    Lf,
+    /// `\r\n`, as written on Windows.
+    CrLf,
+    /// A lone `\r`, as written by classic Mac OS and some old exports.
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(match *self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CR LF",
+            LineEnding::Cr => "CR",
+        })
+    }
+}
+
+/// The line endings a source may use, see `LoadOptions::line_endings`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum LineEndingPolicy {
+    /// Every line ending, even mixed in one source.
+    #[default]
+    Any,
+    /// Every line ending, as long as all the lines of a source end the same way.
+    Consistent,
+    /// Only this line ending.
+    Only(LineEnding),
+}
+
+impl LineEndingPolicy {
+    /// The ending this policy requires once a source is seen to start with `first`.
+    pub(crate) fn expected(self, first: LineEnding) -> Option<LineEnding> {
+        match self {
+            LineEndingPolicy::Any => None,
+            LineEndingPolicy::Consistent => Some(first),
+            LineEndingPolicy::Only(ending) => Some(ending),
+        }
+    }
+}
+
+/// Finds the line endings of a source read one character at a time.
+pub(crate) struct Tracker {
+    index: usize,
+    line: usize,
+    col: usize,
+    cr: Option<Marker>,
+}
+
+impl Tracker {
+    pub(crate) fn new() -> Tracker {
+        Tracker {
+            index: 0,
+            line: 1,
+            col: 0,
+            cr: None,
+        }
+    }
+
+    /// Read `c`, returning the line ending it completes with the position where the
+    /// ending starts. A CR is only known to be lone once the next character is read.
+    pub(crate) fn push(&mut self, c: char) -> Option<(LineEnding, Marker)> {
+        let mut found = None;
+        if let Some(mark) = self.cr.take() {
+            self.line += 1;
+            self.col = 0;
+            if c == '\n' {
+                self.index += 1;
+                return Some((LineEnding::CrLf, mark));
+            }
+            found = Some((LineEnding::Cr, mark));
+        }
+        let mark = Marker::new(self.index, self.line, self.col);
+        self.index += 1;
+        match c {
+            '\r' => self.cr = Some(mark),
+            '\n' => {
+                self.line += 1;
+                self.col = 0;
+                return Some((LineEnding::Lf, mark));
+            }
+            _ => self.col += 1,
+        }
+        found
+    }
+
+    /// The lone CR ending the source, if any.
+    pub(crate) fn finish(&mut self) -> Option<(LineEnding, Marker)> {
+        self.cr.take().map(|mark| (LineEnding::Cr, mark))
+    }
+}
+
+/// Every line ending of `source` with the position where it starts.
+fn line_endings(source: &str) -> Vec<(LineEnding, Marker)> {
+    let mut tracker = Tracker::new();
+    let mut endings: Vec<_> = source.chars().filter_map(|c| tracker.push(c)).collect();
+    endings.extend(tracker.finish());
+    endings
+}
+
+/// The line ending of the first line of `source`, if it has more than one line.
+pub fn detect(source: &str) -> Option<LineEnding> {
+    line_endings(source).first().map(|&(ending, _)| ending)
+}
+
+/// The message for a line ending refused in favor of `expected`.
+pub(crate) fn refusal(ending: LineEnding, expected: LineEnding) -> String {
+    format!("line ends with {} instead of {}", ending, expected)
+}
+
+/// Report every line ending of `source` that `policy` refuses, under the
+/// `line-endings` rule.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::line_ending::{self, LineEndingPolicy};
+///
+/// let diagnostics = line_ending::check("a: 1\r\nb: 2\nc: 3\r\n", LineEndingPolicy::Consistent);
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(diagnostics[0].marker().line(), 2);
+/// assert_eq!(diagnostics[0].message(), "line ends with LF instead of CR LF");
+/// ```
+#[cfg(feature = "lint")]
+pub fn check(source: &str, policy: LineEndingPolicy) -> Vec<Diagnostic> {
+    refused(source, policy)
+        .into_iter()
+        .map(|(mark, message)| Diagnostic::new("line-endings", mark, &message))
+        .collect()
+}
+
+/// Where `policy` refuses a line ending of `source`, with the message saying why.
+pub(crate) fn refused(source: &str, policy: LineEndingPolicy) -> Vec<(Marker, String)> {
+    let endings = line_endings(source);
+    let expected = match endings
+        .first()
+        .and_then(|&(first, _)| policy.expected(first))
+    {
+        Some(expected) => expected,
+        None => return Vec::new(),
+    };
+    endings
+        .into_iter()
+        .filter(|&(ending, _)| ending != expected)
+        .map(|(ending, mark)| (mark, refusal(ending, expected)))
+        .collect()
+}
+
+/// Rewrite every line ending of `text` as `ending`.
+pub fn convert(text: &str, ending: LineEnding) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' if chars.peek() == Some(&'\n') => {
+                chars.next();
+                out.push_str(ending.as_str());
+            }
+            '\r' | '\n' => out.push_str(ending.as_str()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::yaml::{LoadOptions, YamlLoader};
+
+    #[test]
+    fn test_load_line_endings() {
+        let lf = "a:\n  b: |\n    x\n    y\n  c: 'p\n    q'\nd:\n- 1\n- 2\n";
+        let expected = YamlLoader::load_from_str(lf).unwrap();
+        for &ending in &[LineEnding::CrLf, LineEnding::Cr] {
+            let source = convert(lf, ending);
+            assert_eq!(YamlLoader::load_from_str(&source).unwrap(), expected);
+        }
+        let mixed = "a:\r  b: |\r\n    x\n    y\r  c: 'p\n    q'\r\nd:\r- 1\n- 2";
+        assert_eq!(YamlLoader::load_from_str(mixed).unwrap(), expected);
+
+        // lines are counted the same for every ending
+        let err = YamlLoader::load_from_str("a: 1\rb: [\r").unwrap_err();
+        assert_eq!(err.marker().line(), 3);
+    }
+
+    #[test]
+    fn test_line_ending_policy() {
+        let mixed = "a: 1\r\nb: 2\rc: 3\n";
+        let mut options = LoadOptions::new();
+        assert!(YamlLoader::load_from_str_with_options(mixed, &options).is_ok());
+
+        options.line_endings(LineEndingPolicy::Consistent);
+        let err = YamlLoader::load_from_str_with_options(mixed, &options).unwrap_err();
+        assert_eq!(err.info(), "line ends with CR instead of CR LF");
+        assert_eq!((err.marker().line(), err.marker().col()), (2, 4));
+        assert_eq!(err.marker().index(), 10);
+        assert!(YamlLoader::load_from_str_with_options("a: 1\rb: 2\r", &options).is_ok());
+
+        options.line_endings(LineEndingPolicy::Only(LineEnding::Lf));
+        let refused = refused(mixed, options.get_line_endings());
+        let lines: Vec<_> = refused.iter().map(|(mark, _)| mark.line()).collect();
+        assert_eq!(lines, vec![1, 2]);
+        assert!(YamlLoader::load_from_str_with_options("a: 1\nb: 2", &options).is_ok());
+    }
+
+    #[test]
+    fn test_detect_and_convert() {
+        assert_eq!(detect("a: 1"), None);
+        assert_eq!(detect("a: 1\r\nb: 2\n"), Some(LineEnding::CrLf));
+        assert_eq!(detect("a: 1\rb: 2\n"), Some(LineEnding::Cr));
+        assert_eq!(
+            convert("a\r\nb\rc\nd", LineEnding::CrLf),
+            "a\r\nb\r\nc\r\nd"
+        );
+        assert_eq!(convert("a\r\nb\rc\nd", LineEnding::Lf), "a\nb\nc\nd");
+    }
+}
@@ -0,0 +1,1084 @@
+#[cfg(feature = "formatter")]
+use crate::cst::Cst;
+use crate::diff::Path;
+#[cfg(feature = "formatter")]
+use crate::emitter::YamlEmitter;
+use crate::line_ending::{self, LineEndingPolicy};
+use crate::parser::{Event, MarkedEventReceiver, Parser};
+use crate::scanner::{Marker, ScanError, TScalarStyle};
+use crate::yaml::{
+    core_schema_scalar, yaml_1_1_scalar, LoadOptions, Marks, YamlInput, YamlLoader, YamlOutput,
+};
+use std::fmt;
+use std::ops::Range;
+use unicode_normalization::UnicodeNormalization;
+
+/// A problem found in a YAML source by one of the lint rules.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct Diagnostic {
+    rule: &'static str,
+    mark: Marker,
+    message: String,
+    fix: Option<Fix>,
+}
+
+impl Diagnostic {
+    pub fn new(rule: &'static str, mark: Marker, message: &str) -> Diagnostic {
+        Diagnostic {
+            rule,
+            mark,
+            message: message.to_owned(),
+            fix: None,
+        }
+    }
+
+    /// The diagnostic with `fix`, the change that `fix` applies to fix it.
+    pub fn with_fix(mut self, fix: Fix) -> Diagnostic {
+        self.fix = Some(fix);
+        self
+    }
+
+    /// The identifier of the rule that reported this diagnostic, e.g. `key-normalization`.
+    pub fn rule(&self) -> &'static str {
+        self.rule
+    }
+
+    pub fn marker(&self) -> &Marker {
+        &self.mark
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn fix(&self) -> Option<&Fix> {
+        self.fix.as_ref()
+    }
+}
+
+/// A change that fixes a diagnostic without asking, see `Diagnostic::with_fix`.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum Fix {
+    /// Replace the bytes of the source in the range by the text.
+    Edit(Range<usize>, String),
+    /// Replace the node at the path of the document, counted from 0, by a node written
+    /// in flow style.
+    Node(usize, Path, YamlOutput),
+}
+
+impl fmt::Display for Diagnostic {
+    // col starts from 0
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} at line {} column {} [{}]",
+            self.message,
+            self.mark.line(),
+            self.mark.col() + 1,
+            self.rule
+        )
+    }
+}
+
+/// Run all lint rules over every document in `source`. The diagnostics of every rule
+/// are listed together, in the order of their positions.
+///
+/// Comments can suppress the diagnostics of some rules, named after `disable=` and
+/// separated by commas, or of every rule when no name is given:
+///
+/// - `# yaml-lint: disable=rule-id` after the content of a line suppresses them on
+///   that line;
+/// - on a line of its own, it suppresses them until a `# yaml-lint: enable=rule-id`
+///   line naming one of its rules, or `# yaml-lint: enable`, or the end of the source.
+///
+/// A suppression that suppresses nothing is reported, by the `unused-suppression`
+/// rule.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::lint;
+///
+/// let diagnostics = lint::lint_str("caf\u{e9}: 1\ncafe\u{301}: 2").unwrap();
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(diagnostics[0].rule(), "key-normalization");
+///
+/// let source = "caf\u{e9}: 1\ncafe\u{301}: 2 # yaml-lint: disable=key-normalization";
+/// assert!(lint::lint_str(source).unwrap().is_empty());
+/// ```
+pub fn lint_str(source: &str) -> Result<Vec<Diagnostic>, ScanError> {
+    lint_str_with_rules(source, &LintRules::new())
+}
+
+/// Run the built-in lint rules and `rules` over every document in `source`, see
+/// `lint_str`. The comments suppressing diagnostics apply to every rule.
+pub fn lint_str_with_rules(source: &str, rules: &LintRules) -> Result<Vec<Diagnostic>, ScanError> {
+    let mut linter = Linter::new(source);
+    let mut parser = Parser::new(source.chars());
+    parser.keep_comments(true);
+    parser.load(&mut linter, true)?;
+    let mut diagnostics = line_ending::check(source, LineEndingPolicy::Consistent);
+    diagnostics.append(&mut linter.key_normalization.diagnostics);
+    diagnostics.append(&mut linter.diagnostics);
+    if !rules.rules.is_empty() {
+        let docs = YamlLoader::load_from_str_with_marks(source, &LoadOptions::new())?;
+        for (index, (document, marks)) in docs.iter().enumerate() {
+            for (rule, config) in &rules.rules {
+                let context = LintContext {
+                    source,
+                    index,
+                    document,
+                    marks,
+                    config,
+                };
+                diagnostics.extend(rule.check(&context));
+            }
+        }
+    }
+    let mut suppressions = linter.suppressions;
+    diagnostics.retain(|d| !suppressions.iter_mut().any(|s| s.suppress(d)));
+    diagnostics.extend(suppressions.iter().flat_map(Suppression::unused));
+    diagnostics.sort_by_key(|d| d.marker().index());
+    Ok(diagnostics)
+}
+
+/// How `fix_truthy` rewrites the scalars reported by the `truthy` rule.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TruthyFix {
+    /// Quote them, keeping the string YAML 1.2 reads: `on` becomes `"on"`.
+    Quote,
+    /// Write the boolean YAML 1.1 reads instead: `on` becomes `true`.
+    Canonicalize,
+}
+
+/// Rewrite the plain `yes`, `no`, `on`, `off`, `y` and `n` scalars of `source` that
+/// the `truthy` rule reports, in any case, so that YAML 1.1 and YAML 1.2 readers agree
+/// on them. Every other byte of the source is kept, and so are the scalars whose
+/// diagnostics are suppressed.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::lint::{self, TruthyFix};
+///
+/// let source = "on:\n  push: {branches: [main]}\ndebug: yes  # for now\n";
+/// assert_eq!(
+///     lint::fix_truthy(source, TruthyFix::Quote).unwrap(),
+///     "\"on\":\n  push: {branches: [main]}\ndebug: \"yes\"  # for now\n"
+/// );
+/// assert_eq!(
+///     lint::fix_truthy(source, TruthyFix::Canonicalize).unwrap(),
+///     "true:\n  push: {branches: [main]}\ndebug: true  # for now\n"
+/// );
+/// ```
+pub fn fix_truthy(source: &str, fix: TruthyFix) -> Result<String, ScanError> {
+    fix_plain_scalars(source, TRUTHY, |word| match (fix, yaml_1_1_scalar(word)) {
+        (TruthyFix::Canonicalize, Some(YamlInput::Boolean(v))) => v.to_string(),
+        _ => format!("\"{}\"", word),
+    })
+}
+
+/// How `fix_octal` rewrites the scalars reported by the `octal` rule.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OctalFix {
+    /// Quote them, keeping their text: `0755` becomes `"0755"`, as fits a zip code or
+    /// an identifier.
+    Quote,
+    /// Write the octal YAML 1.1 reads in the `0o` form of YAML 1.2: `0755` becomes
+    /// `0o755`, as fits a file mode. The ones YAML 1.1 does not read as octal, like
+    /// `089`, or with a sign are quoted.
+    Explicit,
+}
+
+/// Rewrite the plain integers of `source` written with a leading zero, like `0755`,
+/// that the `octal` rule reports, so that YAML 1.1 and YAML 1.2 readers agree on
+/// them. Every other byte of the source is kept, and so are the scalars whose
+/// diagnostics are suppressed.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::lint::{self, OctalFix};
+///
+/// let source = "mode: 0755\nzip: 01234  # Boston\n";
+/// assert_eq!(
+///     lint::fix_octal(source, OctalFix::Quote).unwrap(),
+///     "mode: \"0755\"\nzip: \"01234\"  # Boston\n"
+/// );
+/// assert_eq!(
+///     lint::fix_octal(source, OctalFix::Explicit).unwrap(),
+///     "mode: 0o755\nzip: 0o1234  # Boston\n"
+/// );
+/// ```
+pub fn fix_octal(source: &str, fix: OctalFix) -> Result<String, ScanError> {
+    fix_plain_scalars(source, OCTAL, |number| {
+        match (fix, yaml_1_1_octal(number)) {
+            (OctalFix::Explicit, Some(_)) if number.starts_with('0') => {
+                format!("0o{}", &number[1..])
+            }
+            _ => format!("\"{}\"", number),
+        }
+    })
+}
+
+/// Replace the plain scalars of `source` reported by the rule `rule`, unless suppressed,
+/// by `replace` of their text.
+fn fix_plain_scalars<F>(source: &str, rule: &str, replace: F) -> Result<String, ScanError>
+where
+    F: Fn(&str) -> String,
+{
+    let mut out = String::with_capacity(source.len());
+    let mut copied = 0;
+    for diagnostic in lint_str(source)?.iter().filter(|d| d.rule() == rule) {
+        if let Some(Fix::Edit(ref range, _)) = diagnostic.fix {
+            out.push_str(&source[copied..range.start]);
+            out.push_str(&replace(&source[range.clone()]));
+            copied = range.end;
+        }
+    }
+    out.push_str(&source[copied..]);
+    Ok(out)
+}
+
+/// Lint `source` like `lint_str_with_rules` and apply the fixes of the diagnostics,
+/// keeping every other byte of the source. A fix overlapping the range of an earlier
+/// one is left out, and so is a fix that would not leave valid YAML or whose range or
+/// node is not in the source; `Fixed::skipped` lists them with the reason, and the
+/// result may still have diagnostics. The built-in `truthy` and `octal` rules quote
+/// the scalars they report.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::diff::Path;
+/// use yaml_rust_formatter::lint::{self, Diagnostic, Fix, LintContext, LintRule, LintRules};
+/// use yaml_rust_formatter::YamlOutput;
+///
+/// /// Reports unsorted tags, sorting them.
+/// struct SortedTags;
+///
+/// impl LintRule for SortedTags {
+///     fn id(&self) -> &'static str {
+///         "sorted-tags"
+///     }
+///
+///     fn check(&self, context: &LintContext<'_>) -> Vec<Diagnostic> {
+///         let path = Path::parse("tags");
+///         let tags = match context.document().get_at(&path).and_then(|v| v.as_vec()) {
+///             Some(tags) => tags,
+///             None => return vec![],
+///         };
+///         let mut sorted: Vec<_> = tags.iter().filter_map(|tag| tag.as_str()).collect();
+///         sorted.sort_unstable();
+///         if tags.iter().map(|tag| tag.as_str()).eq(sorted.iter().map(|&tag| Some(tag))) {
+///             return vec![];
+///         }
+///         let node = YamlOutput::Array(sorted.into_iter().map(YamlOutput::from).collect());
+///         let mark = *context.marks().get(&path).unwrap();
+///         vec![Diagnostic::new(self.id(), mark, "the tags are not sorted")
+///             .with_fix(Fix::Node(context.document_index(), path, node))]
+///     }
+/// }
+///
+/// let mut rules = LintRules::new();
+/// rules.register(Box::new(SortedTags));
+/// let source = "# the service\ntags: [web, api]   # public\nsecure: off\n";
+/// assert_eq!(
+///     lint::fix(source, &rules).unwrap().text(),
+///     "# the service\ntags: [api, web]   # public\nsecure: \"off\"\n"
+/// );
+/// ```
+#[cfg(feature = "formatter")]
+pub fn fix(source: &str, rules: &LintRules) -> Result<Fixed, ScanError> {
+    let diagnostics = lint_str_with_rules(source, rules)?;
+    let mut cst = Cst::parse(source)?;
+    let documents: Vec<_> = cst.documents().collect();
+    let mut skipped = Vec::new();
+    let mut edits: Vec<(Range<usize>, String, &Diagnostic)> = Vec::new();
+    for diagnostic in &diagnostics {
+        let edit = match diagnostic.fix {
+            None => continue,
+            Some(Fix::Edit(ref range, ref text)) => Some((range.clone(), text.clone())),
+            Some(Fix::Node(index, ref path, ref node)) => documents
+                .get(index)
+                .and_then(|document| document.lookup(path))
+                .and_then(|found| Some((found.text_range(), flow_text(node)?))),
+        };
+        match edit {
+            Some((range, text))
+                if range.start <= range.end
+                    && source.is_char_boundary(range.start)
+                    && source.is_char_boundary(range.end) =>
+            {
+                edits.push((range, text, diagnostic));
+            }
+            _ => skipped.push((diagnostic.clone(), FixSkipped::NotFound)),
+        }
+    }
+    edits.sort_by_key(|(range, _, _)| range.start);
+    let mut applied: Vec<(Range<usize>, String, &Diagnostic)> = Vec::with_capacity(edits.len());
+    for (range, text, diagnostic) in edits {
+        let overlaps = applied
+            .last()
+            .is_some_and(|(last, _, _)| range.start < last.end || range.start == last.start);
+        if overlaps {
+            skipped.push((diagnostic.clone(), FixSkipped::Overlaps));
+        } else {
+            applied.push((range, text, diagnostic));
+        }
+    }
+    // from the last to the first, so that the ranges before stay valid
+    for (range, text, diagnostic) in applied.into_iter().rev() {
+        match cst.edit(range, &text) {
+            Ok(edited) => cst = edited,
+            Err(err) => skipped.push((diagnostic.clone(), FixSkipped::Invalid(err))),
+        }
+    }
+    skipped.sort_by_key(|(diagnostic, _)| diagnostic.mark.index());
+    Ok(Fixed {
+        text: cst.text().to_owned(),
+        skipped,
+    })
+}
+
+/// The source fixed by `fix`, with the fixes it left out.
+#[cfg(feature = "formatter")]
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct Fixed {
+    text: String,
+    skipped: Vec<(Diagnostic, FixSkipped)>,
+}
+
+#[cfg(feature = "formatter")]
+impl Fixed {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The diagnostics whose fix was not applied, in the order of their positions.
+    pub fn skipped(&self) -> &[(Diagnostic, FixSkipped)] {
+        &self.skipped
+    }
+
+    pub fn into_text(self) -> String {
+        self.text
+    }
+}
+
+/// Why `fix` left out the fix of a diagnostic, see `Fixed::skipped`.
+#[cfg(feature = "formatter")]
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum FixSkipped {
+    /// The range of the fix overlaps the one of a fix applied before it.
+    Overlaps,
+    /// The range is not one of the source, or the node is not in its document.
+    NotFound,
+    /// The source would no longer be valid YAML after the fix.
+    Invalid(ScanError),
+}
+
+/// The text of `node` in flow style, for a `Fix::Node`.
+#[cfg(feature = "formatter")]
+fn flow_text(node: &YamlOutput) -> Option<String> {
+    let mut text = String::new();
+    let mut emitter = YamlEmitter::new(&mut text);
+    emitter.explicit_start(false);
+    emitter.flow_width(Some(usize::MAX));
+    emitter.dump(node).ok()?;
+    Some(text)
+}
+
+/// The version of the interface between the lint runner and the rules, see
+/// `LintContext::api_version`. It grows when the context gains something a rule may
+/// want to check for; what a version offers is never removed.
+pub const LINT_API_VERSION: u32 = 1;
+
+/// A lint rule defined outside of this crate, such as a company specific policy,
+/// checked by `lint_str_with_rules` next to the built-in rules.
+///
+/// Rules only see the documents through a `LintContext`, whose accessors stay
+/// available in later versions, so a rule keeps working as the context grows.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::diff::Path;
+/// use yaml_rust_formatter::lint::{self, Diagnostic, LintContext, LintRule, LintRules};
+///
+/// /// Reports images without a pinned tag.
+/// struct PinnedImage;
+///
+/// impl LintRule for PinnedImage {
+///     fn id(&self) -> &'static str {
+///         "pinned-image"
+///     }
+///
+///     fn check(&self, context: &LintContext<'_>) -> Vec<Diagnostic> {
+///         let path = Path::parse("image");
+///         match context.document().get_at(&path).and_then(|v| v.as_str()) {
+///             Some(image) if !image.contains(':') || image.ends_with(":latest") => {
+///                 let mark = *context.marks().get(&path).unwrap();
+///                 vec![Diagnostic::new(self.id(), mark, "the image tag is not pinned")]
+///             }
+///             _ => vec![],
+///         }
+///     }
+/// }
+///
+/// let mut rules = LintRules::new();
+/// rules.register(Box::new(PinnedImage));
+/// let diagnostics = lint::lint_str_with_rules("image: web\n---\nimage: web:1.2\n", &rules)
+///     .unwrap();
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(
+///     diagnostics[0].to_string(),
+///     "the image tag is not pinned at line 1 column 8 [pinned-image]"
+/// );
+/// ```
+pub trait LintRule: Send + Sync {
+    /// The identifier of the rule, reported with its diagnostics and named by the
+    /// suppression comments, e.g. `pinned-image`.
+    fn id(&self) -> &'static str;
+
+    /// Check one document.
+    fn check(&self, context: &LintContext<'_>) -> Vec<Diagnostic>;
+}
+
+/// What a `LintRule` is given to check a document.
+pub struct LintContext<'a> {
+    source: &'a str,
+    index: usize,
+    document: &'a YamlInput,
+    marks: &'a Marks,
+    config: &'a YamlInput,
+}
+
+impl<'a> LintContext<'a> {
+    /// The version of the context, `LINT_API_VERSION` for the crate running the rule.
+    pub fn api_version(&self) -> u32 {
+        LINT_API_VERSION
+    }
+
+    /// The whole source being linted.
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// The position of the document in the source, from 0.
+    pub fn document_index(&self) -> usize {
+        self.index
+    }
+
+    pub fn document(&self) -> &'a YamlInput {
+        self.document
+    }
+
+    /// Where the nodes of the document start in the source.
+    pub fn marks(&self) -> &'a Marks {
+        self.marks
+    }
+
+    /// The configuration the rule was registered with, `Null` when it has none.
+    pub fn config(&self) -> &'a YamlInput {
+        self.config
+    }
+}
+
+/// The rules `lint_str_with_rules` runs besides the built-in ones.
+#[derive(Default)]
+pub struct LintRules {
+    rules: Vec<(Box<dyn LintRule>, YamlInput)>,
+}
+
+impl LintRules {
+    pub fn new() -> LintRules {
+        LintRules::default()
+    }
+
+    pub fn register(&mut self, rule: Box<dyn LintRule>) {
+        self.register_with_config(rule, YamlInput::Null);
+    }
+
+    /// Register a rule with its configuration, given to it as `LintContext::config`.
+    pub fn register_with_config(&mut self, rule: Box<dyn LintRule>, config: YamlInput) {
+        self.rules.push((rule, config));
+    }
+
+    /// The identifiers of the registered rules, in registration order.
+    pub fn ids(&self) -> Vec<&'static str> {
+        self.rules.iter().map(|(rule, _)| rule.id()).collect()
+    }
+}
+
+/// Runs the rules over the events of a source, and reads its suppression comments.
+struct Linter {
+    /// The byte offset of every character of the source, and the length of the source.
+    offsets: Vec<usize>,
+    key_normalization: KeyNormalization,
+    /// The diagnostics of the rules checking one scalar at a time.
+    diagnostics: Vec<Diagnostic>,
+    suppressions: Vec<Suppression>,
+}
+
+impl Linter {
+    fn new(source: &str) -> Linter {
+        let mut offsets: Vec<usize> = source.char_indices().map(|(i, _)| i).collect();
+        offsets.push(source.len());
+        Linter {
+            offsets,
+            key_normalization: KeyNormalization::default(),
+            diagnostics: Vec::new(),
+            suppressions: Vec::new(),
+        }
+    }
+}
+
+impl MarkedEventReceiver for Linter {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Comment(ref text, trailing) => {
+                let directive = match text.trim().strip_prefix("yaml-lint:") {
+                    Some(directive) => directive.trim(),
+                    None => return,
+                };
+                let (action, rules) = match directive.split_once('=') {
+                    Some((action, rules)) => (
+                        action.trim(),
+                        rules
+                            .split(',')
+                            .map(|rule| (rule.trim().to_owned(), false))
+                            .filter(|(rule, _)| !rule.is_empty())
+                            .collect(),
+                    ),
+                    None => (directive, Vec::new()),
+                };
+                match action {
+                    "disable" => self.suppressions.push(Suppression {
+                        mark,
+                        rules,
+                        used: false,
+                        lines: if trailing {
+                            (mark.line(), mark.line())
+                        } else {
+                            (mark.line(), usize::MAX)
+                        },
+                    }),
+                    "enable" => {
+                        for s in &mut self.suppressions {
+                            let named = rules.is_empty()
+                                || s.rules.is_empty()
+                                || s.rules.iter().any(|rule| rules.contains(rule));
+                            if s.lines.1 == usize::MAX && named {
+                                s.lines.1 = mark.line();
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            ev => {
+                if let Event::Scalar(ref v, TScalarStyle::Plain, _, None) = ev {
+                    // the scalars the rules report are single words, written as they read
+                    let start = self.offsets[mark.index().min(self.offsets.len() - 1)];
+                    let range = start..start + v.len();
+                    self.diagnostics.extend(truthy(v, mark, &range));
+                    self.diagnostics.extend(octal(v, mark, &range));
+                }
+                self.key_normalization.on_event(ev, mark);
+            }
+        }
+    }
+}
+
+/// A `# yaml-lint: disable` comment.
+struct Suppression {
+    mark: Marker,
+    /// The rules suppressed, with whether they suppressed a diagnostic. Every rule
+    /// when empty.
+    rules: Vec<(String, bool)>,
+    /// Whether it suppressed a diagnostic, when it suppresses every rule.
+    used: bool,
+    /// The first and last lines where the rules are suppressed.
+    lines: (usize, usize),
+}
+
+impl Suppression {
+    /// Check if `diagnostic` is suppressed, noting that this suppression is used.
+    fn suppress(&mut self, diagnostic: &Diagnostic) -> bool {
+        let line = diagnostic.marker().line();
+        if line < self.lines.0 || line > self.lines.1 {
+            return false;
+        }
+        if self.rules.is_empty() {
+            self.used = true;
+            return true;
+        }
+        match self
+            .rules
+            .iter_mut()
+            .find(|(rule, _)| rule == diagnostic.rule())
+        {
+            Some((_, used)) => {
+                *used = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn unused(&self) -> Vec<Diagnostic> {
+        let unused = |rule: &str| {
+            Diagnostic::new(
+                "unused-suppression",
+                self.mark,
+                &format!("no {} diagnostic to suppress", rule),
+            )
+        };
+        if self.rules.is_empty() {
+            if self.used {
+                vec![]
+            } else {
+                vec![unused("lint")]
+            }
+        } else {
+            self.rules
+                .iter()
+                .filter(|(_, used)| !used)
+                .map(|(rule, _)| unused(rule))
+                .collect()
+        }
+    }
+}
+
+enum Frame {
+    Sequence,
+    /// Every key seen so far with its NFC form, and whether the next node is a value.
+    Mapping(Vec<(String, String)>, bool),
+}
+
+/// Reports mapping keys that differ from an earlier key of the same mapping
+/// only by their Unicode normalization form.
+#[derive(Default)]
+struct KeyNormalization {
+    stack: Vec<Frame>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl KeyNormalization {
+    /// Returns the key list of the enclosing mapping if the next node is a key.
+    fn enter_node(&mut self) -> Option<&mut Vec<(String, String)>> {
+        match self.stack.last_mut() {
+            Some(Frame::Mapping(keys, next_is_value)) => {
+                *next_is_value = !*next_is_value;
+                if *next_is_value {
+                    Some(keys)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl MarkedEventReceiver for KeyNormalization {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(ref v, ..) => {
+                if let Some(keys) = self.enter_node() {
+                    let normalized: String = v.nfc().collect();
+                    let clash = keys
+                        .iter()
+                        .any(|(key, other)| *other == normalized && key != v);
+                    keys.push((v.clone(), normalized));
+                    if clash {
+                        self.diagnostics.push(Diagnostic::new(
+                            "key-normalization",
+                            mark,
+                            &format!(
+                                "key {:?} differs from an earlier key only by Unicode normalization",
+                                v
+                            ),
+                        ));
+                    }
+                }
+            }
+            Event::Alias(_) => {
+                self.enter_node();
+            }
+            Event::SequenceStart(..) => {
+                self.enter_node();
+                self.stack.push(Frame::Sequence);
+            }
+            Event::MappingStart(..) => {
+                self.enter_node();
+                self.stack.push(Frame::Mapping(Vec::new(), false));
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The identifier of the rule `truthy` checks for.
+const TRUTHY: &str = "truthy";
+
+/// Report the plain scalar without a tag `v`, at `range` of the source, if YAML 1.1
+/// reads it as a boolean and YAML 1.2 as a string, like `yes`, `off` or `n`.
+fn truthy(v: &str, mark: Marker, range: &Range<usize>) -> Option<Diagnostic> {
+    let truthy = matches!(yaml_1_1_scalar(v), Some(YamlInput::Boolean(_)))
+        && core_schema_scalar(v).is_none();
+    if !truthy {
+        return None;
+    }
+    let message = format!("{} is a boolean in YAML 1.1 but a string in YAML 1.2", v);
+    Some(
+        Diagnostic::new(TRUTHY, mark, &message)
+            .with_fix(Fix::Edit(range.clone(), format!("\"{}\"", v))),
+    )
+}
+
+/// The identifier of the rule `octal` checks for.
+const OCTAL: &str = "octal";
+
+/// The value YAML 1.1 reads in the plain scalar `v` if it is an octal integer written
+/// with a leading zero, like `0755`.
+fn yaml_1_1_octal(v: &str) -> Option<i64> {
+    let (negative, digits) = match v.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, v.strip_prefix('+').unwrap_or(v)),
+    };
+    let digits = digits
+        .strip_prefix('0')
+        .filter(|digits| !digits.is_empty())?;
+    let value = i64::from_str_radix(digits, 8).ok()?;
+    Some(if negative { -value } else { value })
+}
+
+/// Report the plain scalar without a tag `v`, at `range` of the source, if it is an
+/// integer written with a leading zero, like `0755`, which YAML 1.1 reads as octal and
+/// YAML 1.2 as decimal.
+fn octal(v: &str, mark: Marker, range: &Range<usize>) -> Option<Diagnostic> {
+    let digits = v.strip_prefix(&['-', '+'][..]).unwrap_or(v);
+    if digits.len() < 2 || !digits.starts_with('0') || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let decimal = match YamlInput::from_str(v) {
+        YamlInput::Integer(decimal) => decimal,
+        _ => return None,
+    };
+    let message = match yaml_1_1_octal(v) {
+        // `07` means 7 either way
+        Some(octal) if octal == decimal => return None,
+        Some(octal) => format!(
+            "{} is the octal {} in YAML 1.1 but the decimal {} in YAML 1.2",
+            v, octal, decimal
+        ),
+        None => format!(
+            "{} is a string in YAML 1.1 but the decimal {} in YAML 1.2",
+            v, decimal
+        ),
+    };
+    Some(
+        Diagnostic::new(OCTAL, mark, &message)
+            .with_fix(Fix::Edit(range.clone(), format!("\"{}\"", v))),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_key_normalization() {
+        let s = "
+a:
+  caf\u{e9}: 1
+  b: [caf\u{e9}, cafe\u{301}]
+  cafe\u{301}: 2
+caf\u{e9}: 3
+";
+        let diagnostics = lint_str(s).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].marker().line(), 5);
+        assert_eq!(diagnostics[0].marker().col(), 2);
+    }
+
+    #[test]
+    fn test_key_normalization_exact_duplicate() {
+        // exact duplicates are not a normalization problem
+        let diagnostics = lint_str("{a: 1, a: 2}").unwrap();
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_mixed_line_endings() {
+        let diagnostics = lint_str("a: 1\r\nb: 2\rc: 3\r\n").unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule(), "line-endings");
+        assert_eq!(diagnostics[0].marker().line(), 2);
+        assert!(lint_str("a: 1\rb: 2\r").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_diagnostics_in_source_order() {
+        let diagnostics = lint_str("caf\u{e9}: 1\r\ncafe\u{301}: 2\nb: 3\r\n").unwrap();
+        let rules: Vec<_> = diagnostics.iter().map(|d| d.rule()).collect();
+        assert_eq!(rules, vec!["key-normalization", "line-endings"]);
+    }
+
+    #[test]
+    fn test_truthy() {
+        let s = "
+on: [push]
+a: [yes, No, OFF, y, True, 'yes', !!bool yes, yess]
+b: {n: 1}
+";
+        let diagnostics = lint_str(s).unwrap();
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.rule(), d.marker().line(), d.marker().col()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("truthy", 2, 0),
+                ("truthy", 3, 4),
+                ("truthy", 3, 9),
+                ("truthy", 3, 13),
+                ("truthy", 3, 18),
+                ("truthy", 4, 4),
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "on is a boolean in YAML 1.1 but a string in YAML 1.2 at line 2 column 1 [truthy]"
+        );
+    }
+
+    #[test]
+    fn test_fix_truthy() {
+        let s = "caf\u{e9}: [yes, 'no', No]\nb: off # yaml-lint: disable=truthy\nc: n\n";
+        assert_eq!(
+            fix_truthy(s, TruthyFix::Quote).unwrap(),
+            "caf\u{e9}: [\"yes\", 'no', \"No\"]\nb: off # yaml-lint: disable=truthy\nc: \"n\"\n"
+        );
+        assert_eq!(
+            fix_truthy(s, TruthyFix::Canonicalize).unwrap(),
+            "caf\u{e9}: [true, 'no', false]\nb: off # yaml-lint: disable=truthy\nc: false\n"
+        );
+        let fixed = fix_truthy(s, TruthyFix::Quote).unwrap();
+        assert_eq!(fix_truthy(&fixed, TruthyFix::Quote).unwrap(), fixed);
+        assert!(fix_truthy("a: [", TruthyFix::Quote).is_err());
+    }
+
+    #[test]
+    fn test_octal() {
+        let s =
+            "[0755, 012345, -010, +017, 089, 07, 0, 0o755, 755, '0755', !!int 0755, 0x10, 0755x]";
+        let found: Vec<_> = lint_str(s).unwrap().iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "0755 is the octal 493 in YAML 1.1 but the decimal 755 in YAML 1.2 at line 1 column 2 [octal]",
+                "012345 is the octal 5349 in YAML 1.1 but the decimal 12345 in YAML 1.2 at line 1 column 8 [octal]",
+                "-010 is the octal -8 in YAML 1.1 but the decimal -10 in YAML 1.2 at line 1 column 16 [octal]",
+                "+017 is the octal 15 in YAML 1.1 but the decimal 17 in YAML 1.2 at line 1 column 22 [octal]",
+                "089 is a string in YAML 1.1 but the decimal 89 in YAML 1.2 at line 1 column 28 [octal]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fix_octal() {
+        let s = "a: [0755, -010, 089, 07]\nb: 0644 # yaml-lint: disable=octal\nc: 010\n";
+        assert_eq!(
+            fix_octal(s, OctalFix::Quote).unwrap(),
+            "a: [\"0755\", \"-010\", \"089\", 07]\nb: 0644 # yaml-lint: disable=octal\nc: \"010\"\n"
+        );
+        let fixed = fix_octal(s, OctalFix::Explicit).unwrap();
+        assert_eq!(
+            fixed,
+            "a: [0o755, \"-010\", \"089\", 07]\nb: 0644 # yaml-lint: disable=octal\nc: 0o10\n"
+        );
+        assert_eq!(fix_octal(&fixed, OctalFix::Explicit).unwrap(), fixed);
+        let doc = &YamlLoader::load_from_str(&fixed).unwrap()[0];
+        assert_eq!(doc["a"][0].as_i64(), Some(0o755));
+        assert_eq!(doc["c"].as_i64(), Some(8));
+    }
+
+    #[test]
+    fn test_suppressions() {
+        let s = "
+a: 1
+# yaml-lint: disable=key-normalization, line-endings
+caf\u{e9}: 1\r
+cafe\u{301}: 2\r
+# yaml-lint: enable=key-normalization
+b:
+  caf\u{e9}: 1
+  cafe\u{301}: 2 # yaml-lint: disable
+  c: 3 # yaml-lint: disable=key-normalization
+  cafe\u{301}: 4
+";
+        let diagnostics = lint_str(s).unwrap();
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.rule(), d.marker().line()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("unused-suppression", 10), ("key-normalization", 11),]
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "no key-normalization diagnostic to suppress at line 10 column 8 [unused-suppression]"
+        );
+        assert!(lint_str("a: 1 # yaml-lint: enable\n# yaml-lint: other\n")
+            .unwrap()
+            .is_empty());
+    }
+
+    /// Reports top-level keys not in the configured list.
+    struct AllowedKeys;
+
+    impl LintRule for AllowedKeys {
+        fn id(&self) -> &'static str {
+            "allowed-keys"
+        }
+
+        fn check(&self, context: &LintContext<'_>) -> Vec<Diagnostic> {
+            assert_eq!(context.api_version(), LINT_API_VERSION);
+            let allowed = context.config().as_vec().unwrap();
+            context
+                .document()
+                .entries()
+                .filter(|(k, _)| !allowed.contains(k))
+                .map(|(k, _)| {
+                    let path = Path::new().key(k.as_str().unwrap());
+                    Diagnostic::new(
+                        self.id(),
+                        *context.marks().key(&path).unwrap(),
+                        &format!(
+                            "key {} is not allowed in document {}",
+                            k.as_str().unwrap(),
+                            context.document_index()
+                        ),
+                    )
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let mut rules = LintRules::new();
+        let config = YamlLoader::load_from_str("[a, b]").unwrap().remove(0);
+        rules.register_with_config(Box::new(AllowedKeys), config);
+        assert_eq!(rules.ids(), vec!["allowed-keys"]);
+        let s = "
+a: 1
+c: 2
+---
+b: 3
+d: 4 # yaml-lint: disable=allowed-keys
+caf\u{e9}: 1
+cafe\u{301}: 2
+";
+        let diagnostics = lint_str_with_rules(s, &rules).unwrap();
+        let found: Vec<_> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "key c is not allowed in document 0 at line 3 column 1 [allowed-keys]",
+                "key caf\u{e9} is not allowed in document 1 at line 7 column 1 [allowed-keys]",
+                "key \"cafe\\u{301}\" differs from an earlier key only by Unicode normalization at line 8 column 1 [key-normalization]",
+                "key cafe\u{301} is not allowed in document 1 at line 8 column 1 [allowed-keys]",
+            ]
+        );
+    }
+
+    /// Reports the first document with the configured fixes.
+    #[cfg(feature = "formatter")]
+    struct Fixes(Vec<Fix>);
+
+    #[cfg(feature = "formatter")]
+    impl LintRule for Fixes {
+        fn id(&self) -> &'static str {
+            "fixes"
+        }
+
+        fn check(&self, context: &LintContext<'_>) -> Vec<Diagnostic> {
+            if context.document_index() > 0 {
+                return vec![];
+            }
+            self.0
+                .iter()
+                .map(|fix| {
+                    Diagnostic::new(self.id(), Marker::new(0, 1, 0), "fix").with_fix(fix.clone())
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "formatter")]
+    fn test_fix() {
+        let s = "a: yes   # on\nb: [1, 2]\nc: 0755\n---\nd: off\n";
+        let mut rules = LintRules::new();
+        rules.register(Box::new(Fixes(vec![
+            // overlaps the fix of `yes`
+            Fix::Edit(4..8, "x".to_owned()),
+            Fix::Node(1, Path::parse("d"), YamlOutput::Boolean(false)),
+            Fix::Node(0, Path::parse("b"), YamlOutput::Hash(Default::default())),
+            Fix::Node(0, Path::parse("missing"), YamlOutput::Null),
+            // would break the source
+            Fix::Edit(32..32, "[".to_owned()),
+            Fix::Edit(100..101, "z".to_owned()),
+        ])));
+        let fixed = fix(s, &rules).unwrap();
+        assert_eq!(
+            fixed.text(),
+            "a: \"yes\"   # on\nb: {}\nc: \"0755\"\n---\nd: false\n"
+        );
+        let skipped: Vec<_> = fixed
+            .skipped()
+            .iter()
+            .map(|(diagnostic, skip)| match *skip {
+                FixSkipped::Invalid(_) => (diagnostic.fix().cloned(), None),
+                ref skip => (diagnostic.fix().cloned(), Some(skip.clone())),
+            })
+            .collect();
+        assert_eq!(skipped.len(), 5);
+        for (fix, skip) in &[
+            (Fix::Edit(4..8, "x".to_owned()), Some(FixSkipped::Overlaps)),
+            // the `truthy` fix of `off`, replaced by the node
+            (
+                Fix::Edit(39..42, "\"off\"".to_owned()),
+                Some(FixSkipped::Overlaps),
+            ),
+            (
+                Fix::Node(0, Path::parse("missing"), YamlOutput::Null),
+                Some(FixSkipped::NotFound),
+            ),
+            (Fix::Edit(32..32, "[".to_owned()), None),
+            (
+                Fix::Edit(100..101, "z".to_owned()),
+                Some(FixSkipped::NotFound),
+            ),
+        ] {
+            assert!(skipped.contains(&(Some(fix.clone()), skip.clone())));
+        }
+        let fixed = fix(s, &LintRules::new()).unwrap();
+        assert!(fixed.skipped().is_empty());
+        let fixed = fixed.into_text();
+        assert_eq!(
+            fixed,
+            "a: \"yes\"   # on\nb: [1, 2]\nc: \"0755\"\n---\nd: \"off\"\n"
+        );
+        assert_eq!(fix(&fixed, &LintRules::new()).unwrap().text(), fixed);
+        assert!(fix("a: [", &rules).is_err());
+    }
+}
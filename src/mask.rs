@@ -0,0 +1,268 @@
+use crate::emitter::{escape_str, is_printable, need_quotes, write_binary};
+use crate::yaml::{Entries, YamlInput};
+use std::fmt;
+
+/// Controls which values `to_log_string` hides and how long its output may get.
+#[derive(Clone, Debug)]
+pub struct MaskRules {
+    keys: Vec<String>,
+    mask: String,
+    max_len: usize,
+}
+
+impl Default for MaskRules {
+    fn default() -> MaskRules {
+        MaskRules {
+            keys: [
+                "password",
+                "passwd",
+                "secret",
+                "token",
+                "apikey",
+                "privatekey",
+                "credential",
+            ]
+            .iter()
+            .map(|key| (*key).to_owned())
+            .collect(),
+            mask: "***".to_owned(),
+            max_len: 1024,
+        }
+    }
+}
+
+/// Lower case `key` and drop its `-` and `_`, so `API_KEY` and `api-key` compare equal.
+fn fold_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl MaskRules {
+    /// The default rules mask the values of keys containing `password`, `passwd`,
+    /// `secret`, `token`, `apikey`, `privatekey` or `credential`.
+    pub fn new() -> MaskRules {
+        MaskRules::default()
+    }
+
+    /// Also mask the values of keys containing `key`, ignoring case, `-` and `_`.
+    pub fn mask_key(&mut self, key: &str) {
+        self.keys.push(fold_key(key));
+    }
+
+    /// Forget every masked key, including the default ones.
+    pub fn clear_keys(&mut self) {
+        self.keys.clear();
+    }
+
+    pub fn masked_keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// The text written in place of a masked value, `***` by default.
+    pub fn mask(&mut self, mask: &str) {
+        self.mask = mask.to_owned();
+    }
+
+    pub fn get_mask(&self) -> &str {
+        &self.mask
+    }
+
+    /// The maximum length of the output in bytes, 1024 by default. Longer renderings are
+    /// cut and end with `...`, or with as many of its dots as fit in a shorter maximum.
+    pub fn max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+    }
+
+    pub fn get_max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Determine if the value of `key` is masked.
+    pub fn is_masked(&self, key: &str) -> bool {
+        let key = fold_key(key);
+        self.keys.iter().any(|masked| key.contains(masked.as_str()))
+    }
+}
+
+const ELLIPSIS: &str = "...";
+
+/// A writer that refuses to grow past `cap` bytes.
+struct CappedWriter {
+    out: String,
+    cap: usize,
+}
+
+impl fmt::Write for CappedWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.out.len() + s.len() <= self.cap {
+            self.out.push_str(s);
+            return Ok(());
+        }
+        let mut end = self.cap - self.out.len();
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.out.push_str(&s[..end]);
+        Err(fmt::Error)
+    }
+}
+
+/// Render `doc` on a single line in flow style, replacing the values of secret-looking
+/// keys and cutting the output at `rules.get_max_len()` bytes, for use in logs.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::mask::{self, MaskRules};
+/// use yaml_rust_formatter::YamlLoader;
+///
+/// let docs = YamlLoader::load_from_str("
+/// user: admin
+/// db_password: hunter2
+/// hosts: [a, b]
+/// ").unwrap();
+/// assert_eq!(
+///     mask::to_log_string(&docs[0], &MaskRules::new()),
+///     "{user: admin, db_password: ***, hosts: [a, b]}"
+/// );
+/// ```
+pub fn to_log_string(doc: &YamlInput, rules: &MaskRules) -> String {
+    let mut writer = CappedWriter {
+        out: String::new(),
+        cap: rules.max_len,
+    };
+    if write_node(&mut writer, doc, rules).is_err() {
+        let mut end = rules.max_len.saturating_sub(ELLIPSIS.len());
+        while !writer.out.is_char_boundary(end) {
+            end -= 1;
+        }
+        writer.out.truncate(end);
+        writer
+            .out
+            .push_str(&ELLIPSIS[..ELLIPSIS.len().min(rules.max_len)]);
+    }
+    writer.out
+}
+
+fn write_str(wr: &mut dyn fmt::Write, v: &str) -> fmt::Result {
+    if need_quotes(v) || !v.chars().all(is_printable) {
+        escape_str(wr, v)
+    } else {
+        wr.write_str(v)
+    }
+}
+
+fn write_node(wr: &mut dyn fmt::Write, node: &YamlInput, rules: &MaskRules) -> fmt::Result {
+    match *node {
+        YamlInput::Array(ref v) => {
+            wr.write_str("[")?;
+            for (i, x) in v.iter().enumerate() {
+                if i > 0 {
+                    wr.write_str(", ")?;
+                }
+                write_node(wr, x, rules)?;
+            }
+            wr.write_str("]")
+        }
+        YamlInput::Hash(ref h) => {
+            wr.write_str("{")?;
+            for (i, (k, v)) in Entries::new(h).enumerate() {
+                if i > 0 {
+                    wr.write_str(", ")?;
+                }
+                write_node(wr, k, rules)?;
+                wr.write_str(": ")?;
+                if k.as_str().is_some_and(|k| rules.is_masked(k)) {
+                    wr.write_str(&rules.mask)?;
+                } else {
+                    write_node(wr, v, rules)?;
+                }
+            }
+            wr.write_str("}")
+        }
+        YamlInput::String(ref v) => write_str(wr, v),
+        YamlInput::Raw(ref v) => match YamlInput::from_str(v) {
+            YamlInput::String(_) => write_str(wr, v),
+            _ => wr.write_str(v),
+        },
+        YamlInput::Real(ref v) => wr.write_str(v),
+        YamlInput::Integer(v) => write!(wr, "{}", v),
+        YamlInput::Boolean(v) => write!(wr, "{}", v),
+        YamlInput::Binary(ref bytes) => write_binary(wr, bytes),
+        YamlInput::Null | YamlInput::BadValue => wr.write_str("~"),
+        YamlInput::Anchored(ref name, ref v) => {
+            write!(wr, "&{} ", name)?;
+            write_node(wr, v, rules)
+        }
+        YamlInput::Aliased(ref name, _) => write!(wr, "*{}", name),
+        YamlInput::Tagged(ref tag, ref v) => {
+            write!(wr, "{} ", tag)?;
+            write_node(wr, v, rules)
+        }
+        YamlInput::Commented(ref v, _)
+        | YamlInput::Spelled(ref v, _)
+        | YamlInput::Styled(ref v, _) => write_node(wr, v, rules),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::yaml::YamlLoader;
+
+    #[test]
+    fn test_to_log_string() {
+        let s = "
+name: web
+env:
+  API-Key: abc
+  Private_Key: |
+    -----BEGIN-----
+    xyz
+  message: \"multi\\nline\"
+credentials:
+  user: admin
+  pass: hunter2
+base: &base {tokens: [a, b]}
+copy: *base
+";
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(
+            to_log_string(&docs[0], &MaskRules::new()),
+            "{name: web, env: {API-Key: ***, Private_Key: ***, message: \"multi\\nline\"}, \
+             credentials: ***, base: &base {tokens: ***}, copy: *base}"
+        );
+
+        let mut rules = MaskRules::new();
+        rules.clear_keys();
+        rules.mask_key("NAME");
+        rules.mask("<hidden>");
+        assert_eq!(
+            to_log_string(&docs[0], &rules),
+            "{name: <hidden>, env: {API-Key: abc, Private_Key: \"-----BEGIN-----\\nxyz\\n\", \
+             message: \"multi\\nline\"}, credentials: {user: admin, pass: hunter2}, \
+             base: &base {tokens: [a, b]}, copy: *base}"
+        );
+    }
+
+    #[test]
+    fn test_to_log_string_max_len() {
+        let docs = YamlLoader::load_from_str("[caf\u{e9}, 2, 3]").unwrap();
+        let mut rules = MaskRules::new();
+        rules.max_len(13);
+        assert_eq!(to_log_string(&docs[0], &rules), "[caf\u{e9}, 2, 3]");
+        rules.max_len(12);
+        assert_eq!(to_log_string(&docs[0], &rules), "[caf\u{e9}, 2...");
+        rules.max_len(8);
+        // never cut inside a character
+        assert_eq!(to_log_string(&docs[0], &rules), "[caf...");
+        rules.max_len(3);
+        assert_eq!(to_log_string(&docs[0], &rules), "...");
+        rules.max_len(2);
+        assert_eq!(to_log_string(&docs[0], &rules), "..");
+        rules.max_len(0);
+        assert_eq!(to_log_string(&docs[0], &rules), "");
+    }
+}
@@ -0,0 +1,189 @@
+//! Counters and histograms about the documents loaded and written, so that a service
+//! embedding the crate can observe it without wrapping every call.
+//!
+//! They are recorded through the [`metrics`](https://docs.rs/metrics) facade, to
+//! whatever recorder the application installs, and `describe` registers their
+//! descriptions. The module comes with the `metrics` feature; without it, nothing is
+//! recorded and the calls cost nothing.
+//!
+//! Documents are counted as loaded by `YamlLoader::load_from_str`, `load_from_reader`,
+//! `iter_documents` and their variants, and as written by `YamlEmitter::dump`.
+
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+/// Counter of the documents loaded.
+#[cfg(feature = "metrics")]
+pub const DOCUMENTS_LOADED: &str = "yaml_documents_loaded_total";
+/// Histogram of the time taken to load a source, in seconds.
+#[cfg(feature = "metrics")]
+pub const LOAD_DURATION: &str = "yaml_load_duration_seconds";
+/// Histogram of the size of the sources loaded from a string, in bytes.
+#[cfg(feature = "metrics")]
+pub const SOURCE_BYTES: &str = "yaml_source_bytes";
+/// Counter of the documents written.
+#[cfg(feature = "metrics")]
+pub const DOCUMENTS_EMITTED: &str = "yaml_documents_emitted_total";
+/// Counter of the bytes written.
+#[cfg(feature = "metrics")]
+pub const BYTES_EMITTED: &str = "yaml_emitted_bytes_total";
+/// Counter of the failures, labelled `kind` with `parse` for a source that is not valid
+/// YAML, `read` for a reader that failed and `emit` for a document that could not be
+/// written.
+#[cfg(feature = "metrics")]
+pub const ERRORS: &str = "yaml_errors_total";
+
+/// Describe every metric of this crate to the installed recorder.
+#[cfg(feature = "metrics")]
+pub fn describe() {
+    use metrics::Unit;
+
+    metrics::describe_counter!(DOCUMENTS_LOADED, "YAML documents loaded");
+    metrics::describe_histogram!(LOAD_DURATION, Unit::Seconds, "time to load a YAML source");
+    metrics::describe_histogram!(SOURCE_BYTES, Unit::Bytes, "size of the YAML sources loaded");
+    metrics::describe_counter!(DOCUMENTS_EMITTED, "YAML documents written");
+    metrics::describe_counter!(BYTES_EMITTED, Unit::Bytes, "bytes of YAML written");
+    metrics::describe_counter!(ERRORS, "YAML sources or documents that failed");
+}
+
+/// When loading started.
+pub(crate) struct Start {
+    #[cfg(feature = "metrics")]
+    at: Instant,
+}
+
+impl Start {
+    pub(crate) fn now() -> Start {
+        Start {
+            #[cfg(feature = "metrics")]
+            at: Instant::now(),
+        }
+    }
+}
+
+/// Record that `documents` were loaded from a source of `bytes`, when known.
+pub(crate) fn loaded(start: Start, bytes: Option<usize>, documents: usize) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::histogram!(LOAD_DURATION).record(start.at.elapsed().as_secs_f64());
+        if let Some(bytes) = bytes {
+            metrics::histogram!(SOURCE_BYTES).record(bytes as f64);
+        }
+        metrics::counter!(DOCUMENTS_LOADED).increment(documents as u64);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (start, bytes, documents);
+}
+
+/// Record that a document of `bytes` was written.
+pub(crate) fn emitted(bytes: usize) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::counter!(DOCUMENTS_EMITTED).increment(1);
+        metrics::counter!(BYTES_EMITTED).increment(bytes as u64);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = bytes;
+}
+
+/// Record a failure of the given kind, see `ERRORS`.
+pub(crate) fn failed(kind: &'static str) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(ERRORS, "kind" => kind).increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = kind;
+}
+
+#[cfg(all(test, feature = "metrics", feature = "stream"))]
+mod test {
+    use super::*;
+    use crate::{YamlEmitter, YamlLoader, YamlOutput};
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+        SharedString, Unit,
+    };
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// The sum of the values recorded for each key, written `name{label=value}`.
+    #[derive(Default)]
+    struct Totals(Arc<Mutex<HashMap<String, f64>>>);
+
+    struct Handle(Arc<Mutex<HashMap<String, f64>>>, String);
+
+    impl CounterFn for Handle {
+        fn increment(&self, value: u64) {
+            *self.0.lock().unwrap().entry(self.1.clone()).or_default() += value as f64;
+        }
+
+        fn absolute(&self, _: u64) {}
+    }
+
+    impl HistogramFn for Handle {
+        fn record(&self, value: f64) {
+            *self.0.lock().unwrap().entry(self.1.clone()).or_default() += value;
+        }
+    }
+
+    impl Totals {
+        fn handle(&self, key: &Key) -> Arc<Handle> {
+            let labels: Vec<_> = key
+                .labels()
+                .map(|l| format!("{}={}", l.key(), l.value()))
+                .collect();
+            let name = if labels.is_empty() {
+                key.name().to_owned()
+            } else {
+                format!("{}{{{}}}", key.name(), labels.join(","))
+            };
+            Arc::new(Handle(self.0.clone(), name))
+        }
+
+        fn get(&self, name: &str) -> f64 {
+            self.0.lock().unwrap().get(name).copied().unwrap_or(0.0)
+        }
+    }
+
+    impl Recorder for Totals {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.handle(key))
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(self.handle(key))
+        }
+    }
+
+    #[test]
+    fn test_recorded() {
+        let totals = Totals::default();
+        metrics::with_local_recorder(&totals, || {
+            describe();
+            YamlLoader::load_from_str("a: 1\n---\nb: 2\n").unwrap();
+            YamlLoader::load_from_str("a: [").unwrap_err();
+            YamlLoader::load_from_reader("c: 3".as_bytes()).unwrap();
+            YamlLoader::load_from_reader(&b"\xff"[..]).unwrap_err();
+            let docs: Vec<_> = YamlLoader::iter_documents("x\n---\ny").collect();
+            assert_eq!(docs.len(), 2);
+            let mut output = String::new();
+            YamlEmitter::new(&mut output)
+                .dump(&YamlOutput::from("text"))
+                .unwrap();
+        });
+        assert_eq!(totals.get(DOCUMENTS_LOADED), 5.0);
+        assert_eq!(totals.get(SOURCE_BYTES), 14.0);
+        assert!(totals.get(LOAD_DURATION) > 0.0);
+        assert_eq!(totals.get("yaml_errors_total{kind=parse}"), 1.0);
+        assert_eq!(totals.get("yaml_errors_total{kind=read}"), 1.0);
+        assert_eq!(totals.get(DOCUMENTS_EMITTED), 1.0);
+        assert_eq!(totals.get(BYTES_EMITTED), 8.0);
+    }
+}
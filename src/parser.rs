@@ -43,12 +43,15 @@ pub enum Event {
     Alias(String),
     /// Value, style, anchor, tag
     Scalar(String, TScalarStyle, Option<String>, Option<TokenType>),
-    /// Anchor
-    SequenceStart(Option<String>),
+    /// Anchor, tag
+    SequenceStart(Option<String>, Option<TokenType>),
     SequenceEnd,
-    /// Anchor
-    MappingStart(Option<String>),
+    /// Anchor, tag
+    MappingStart(Option<String>, Option<TokenType>),
     MappingEnd,
+    /// Text after the `#`, whether it follows other content on its line.
+    /// Only produced when `Parser::keep_comments` is set.
+    Comment(String, bool),
 }
 
 impl Event {
@@ -67,11 +70,63 @@ pub struct Parser<T> {
     scanner: Scanner<T>,
     states: Vec<State>,
     state: State,
-    marks: Vec<Marker>,
     token: Option<Token>,
     current: Option<(Event, Marker)>,
+    /// An event held back until the comments before it are produced.
+    deferred: Option<(Event, Marker)>,
     anchors: HashMap<String, usize>,
     anchor_id: usize,
+    /// The prefixes of the tag handles declared by `%TAG` for the current document.
+    tag_directives: HashMap<String, String>,
+    /// The span of the last scalar read from a token, see `take_scalar_span`.
+    #[cfg(feature = "borrowed")]
+    scalar_span: Option<usize>,
+}
+
+/// The events of a parser, see `Parser::into_events`. An error ends them.
+#[derive(Debug)]
+pub struct Events<T> {
+    parser: Parser<T>,
+    done: bool,
+}
+
+impl<T: Iterator<Item = char>> Iterator for Events<T> {
+    type Item = Result<(Event, Marker), ScanError>;
+
+    fn next(&mut self) -> Option<Result<(Event, Marker), ScanError>> {
+        if self.done {
+            return None;
+        }
+        let next = self.parser.next();
+        self.done = match next {
+            Ok((Event::StreamEnd, _)) | Err(_) => true,
+            Ok(_) => false,
+        };
+        Some(next)
+    }
+}
+
+/// The characters of an owned string, for a parser that does not borrow its source.
+#[derive(Clone, Debug)]
+pub struct OwnedChars {
+    source: String,
+    offset: usize,
+}
+
+impl From<String> for OwnedChars {
+    fn from(source: String) -> OwnedChars {
+        OwnedChars { source, offset: 0 }
+    }
+}
+
+impl Iterator for OwnedChars {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.source[self.offset..].chars().next()?;
+        self.offset += c.len_utf8();
+        Some(c)
+    }
 }
 
 pub trait EventReceiver {
@@ -96,13 +151,16 @@ impl<T: Iterator<Item = char>> Parser<T> {
             scanner: Scanner::new(src),
             states: Vec::new(),
             state: State::StreamStart,
-            marks: Vec::new(),
             token: None,
             current: None,
+            deferred: None,
 
             anchors: HashMap::new(),
             // valid anchor_id starts from 1
             anchor_id: 1,
+            tag_directives: HashMap::new(),
+            #[cfg(feature = "borrowed")]
+            scalar_span: None,
         }
     }
 
@@ -116,10 +174,97 @@ impl<T: Iterator<Item = char>> Parser<T> {
         }
     }
 
+    /// Produce `Event::Comment`s for the comments of the source, each before the first
+    /// event that follows it.
+    pub fn keep_comments(&mut self, keep_comments: bool) {
+        self.scanner.keep_comments(keep_comments);
+    }
+
+    /// Determine if comment events are produced.
+    pub fn is_keep_comments(&self) -> bool {
+        self.scanner.is_keep_comments()
+    }
+
+    /// Replace the characters YAML does not allow with U+FFFD instead of failing, see
+    /// `Scanner::replace_invalid_characters`.
+    pub fn replace_invalid_characters(&mut self, replace_invalid: bool) {
+        self.scanner.replace_invalid_characters(replace_invalid);
+    }
+
+    /// Determine if characters YAML does not allow are replaced with U+FFFD.
+    pub fn is_replace_invalid_characters(&self) -> bool {
+        self.scanner.is_replace_invalid_characters()
+    }
+
+    /// Leave the text of scalars written as they read in the source out of their
+    /// events, see `Scanner::keep_scalar_spans`.
+    #[cfg(feature = "borrowed")]
+    pub(crate) fn keep_scalar_spans(&mut self, keep_scalar_spans: bool) {
+        self.scanner.keep_scalar_spans(keep_scalar_spans);
+    }
+
+    /// The length in bytes of the source text of the last scalar event, if its text
+    /// was left out, see `Scanner::take_scalar_span`. It is only given once.
+    #[cfg(feature = "borrowed")]
+    pub(crate) fn take_scalar_span(&mut self) -> Option<usize> {
+        self.scalar_span.take()
+    }
+
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> ParseResult {
-        match self.current {
-            None => self.parse(),
-            Some(_) => Ok(self.current.take().unwrap()),
+        if let Some(current) = self.current.take() {
+            return Ok(current);
+        }
+        let (ev, mark) = match self.deferred.take() {
+            Some(deferred) => deferred,
+            None => self.parse()?,
+        };
+        match self.scanner.take_comment_before(&mark) {
+            Some(comment) => {
+                self.deferred = Some((ev, mark));
+                Ok((Event::Comment(comment.text, comment.inline), comment.mark))
+            }
+            None => Ok((ev, mark)),
+        }
+    }
+
+    /// The events of the source, up to and including `Event::StreamEnd`, as an
+    /// iterator that owns the parser. With a source that is owned as well, such as
+    /// `OwnedChars`, the events can be read from another thread or task.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::parser::{Event, OwnedChars, Parser};
+    ///
+    /// let source = String::from("a: 1");
+    /// let events = Parser::new(OwnedChars::from(source)).into_events();
+    /// let scalars = std::thread::spawn(move || {
+    ///     events
+    ///         .filter_map(|event| match event.unwrap().0 {
+    ///             Event::Scalar(v, ..) => Some(v),
+    ///             _ => None,
+    ///         })
+    ///         .collect::<Vec<_>>()
+    /// });
+    /// assert_eq!(scalars.join().unwrap(), ["a", "1"]);
+    /// ```
+    pub fn into_events(self) -> Events<T> {
+        Events {
+            parser: self,
+            done: false,
+        }
+    }
+
+    /// Like `next`, but hands comment events straight to `recv`.
+    fn next_forwarding<R: MarkedEventReceiver>(&mut self, recv: &mut R) -> ParseResult {
+        loop {
+            match self.next()? {
+                (Event::Comment(text, inline), mark) => {
+                    recv.on_event(Event::Comment(text, inline), mark)
+                }
+                next => return Ok(next),
+            }
         }
     }
 
@@ -176,7 +321,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
         multi: bool,
     ) -> Result<(), ScanError> {
         if !self.scanner.stream_started() {
-            let (ev, mark) = self.next()?;
+            let (ev, mark) = self.next_forwarding(recv)?;
             assert_eq!(ev, Event::StreamStart);
             recv.on_event(ev, mark);
         }
@@ -187,7 +332,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
             return Ok(());
         }
         loop {
-            let (ev, mark) = self.next()?;
+            let (ev, mark) = self.next_forwarding(recv)?;
             if ev == Event::StreamEnd {
                 recv.on_event(ev, mark);
                 return Ok(());
@@ -211,11 +356,11 @@ impl<T: Iterator<Item = char>> Parser<T> {
         assert_eq!(first_ev, Event::DocumentStart);
         recv.on_event(first_ev, mark);
 
-        let (ev, mark) = self.next()?;
+        let (ev, mark) = self.next_forwarding(recv)?;
         self.load_node(ev, mark, recv)?;
 
         // DOCUMENT-END is expected.
-        let (ev, mark) = self.next()?;
+        let (ev, mark) = self.next_forwarding(recv)?;
         assert_eq!(ev, Event::DocumentEnd);
         recv.on_event(ev, mark);
 
@@ -233,11 +378,11 @@ impl<T: Iterator<Item = char>> Parser<T> {
                 recv.on_event(first_ev, mark);
                 Ok(())
             }
-            Event::SequenceStart(_) => {
+            Event::SequenceStart(..) => {
                 recv.on_event(first_ev, mark);
                 self.load_sequence(recv)
             }
-            Event::MappingStart(_) => {
+            Event::MappingStart(..) => {
                 recv.on_event(first_ev, mark);
                 self.load_mapping(recv)
             }
@@ -249,17 +394,17 @@ impl<T: Iterator<Item = char>> Parser<T> {
     }
 
     fn load_mapping<R: MarkedEventReceiver>(&mut self, recv: &mut R) -> Result<(), ScanError> {
-        let (mut key_ev, mut key_mark) = self.next()?;
+        let (mut key_ev, mut key_mark) = self.next_forwarding(recv)?;
         while key_ev != Event::MappingEnd {
             // key
             self.load_node(key_ev, key_mark, recv)?;
 
             // value
-            let (ev, mark) = self.next()?;
+            let (ev, mark) = self.next_forwarding(recv)?;
             self.load_node(ev, mark, recv)?;
 
             // next event
-            let (ev, mark) = self.next()?;
+            let (ev, mark) = self.next_forwarding(recv)?;
             key_ev = ev;
             key_mark = mark;
         }
@@ -268,12 +413,12 @@ impl<T: Iterator<Item = char>> Parser<T> {
     }
 
     fn load_sequence<R: MarkedEventReceiver>(&mut self, recv: &mut R) -> Result<(), ScanError> {
-        let (mut ev, mut mark) = self.next()?;
+        let (mut ev, mut mark) = self.next_forwarding(recv)?;
         while ev != Event::SequenceEnd {
             self.load_node(ev, mark, recv)?;
 
             // next event
-            let (next_ev, next_mark) = self.next()?;
+            let (next_ev, next_mark) = self.next_forwarding(recv)?;
             ev = next_ev;
             mark = next_mark;
         }
@@ -365,26 +510,65 @@ impl<T: Iterator<Item = char>> Parser<T> {
     }
 
     fn parser_process_directives(&mut self) -> Result<(), ScanError> {
+        // directives only apply to the document they precede
+        self.tag_directives.clear();
         loop {
-            match self.peek_token()?.1 {
-                TokenType::VersionDirective(_, _) => {
+            match *self.peek_token()? {
+                Token(_, TokenType::VersionDirective(_, _)) => {
                     // XXX parsing with warning according to spec
                     //if major != 1 || minor > 2 {
                     //    return Err(ScanError::new(tok.0,
                     //        "found incompatible YAML document"));
                     //}
                 }
-                TokenType::TagDirective(..) => {
-                    // TODO add tag directive
+                // unknown directives are read as empty tag directives
+                Token(_, TokenType::TagDirective(ref handle, _)) if handle.is_empty() => {}
+                Token(mark, TokenType::TagDirective(ref handle, ref prefix)) => {
+                    let (handle, prefix) = (handle.clone(), prefix.clone());
+                    if self.tag_directives.insert(handle, prefix).is_some() {
+                        return Err(ScanError::new(mark, "found duplicate %TAG directive"));
+                    }
                 }
                 _ => break,
             }
             self.skip();
         }
-        // TODO tag directive
         Ok(())
     }
 
+    /// Resolve the shorthand `tag` against the `%TAG` directives of the document.
+    ///
+    /// A tag under the prefix of the core schema keeps the `!!` handle, and one under a
+    /// local prefix like `!my-` the `!` handle, so that they are read as if written so.
+    /// Any other resolved tag becomes verbatim, like `!<tag:example.com,2000:app/foo>`.
+    fn resolve_tag(&self, mark: Marker, tag: TokenType) -> Result<TokenType, ScanError> {
+        let (handle, suffix) = match tag {
+            TokenType::Tag(handle, suffix) => (handle, suffix),
+            tag => return Ok(tag),
+        };
+        let prefix = match self.tag_directives.get(&handle) {
+            Some(prefix) => prefix,
+            // verbatim and non-specific tags have no handle
+            None if handle.is_empty() || handle == "!" || handle == "!!" => {
+                return Ok(TokenType::Tag(handle, suffix))
+            }
+            None => {
+                return Err(ScanError::new(
+                    mark,
+                    &format!("found undefined tag handle {}", handle),
+                ))
+            }
+        };
+        let tag = format!("{}{}", prefix, suffix);
+        Ok(if let Some(core) = tag.strip_prefix("tag:yaml.org,2002:") {
+            TokenType::Tag("!!".to_owned(), core.to_owned())
+        } else if let Some(local) = tag.strip_prefix('!') {
+            TokenType::Tag("!".to_owned(), local.to_owned())
+        } else {
+            TokenType::Tag(String::new(), tag)
+        })
+    }
+
     fn _explicit_document_start(&mut self) -> ParseResult {
         self.parser_process_directives()?;
         match *self.peek_token()? {
@@ -459,10 +643,10 @@ impl<T: Iterator<Item = char>> Parser<T> {
             Token(_, TokenType::Anchor(_)) => {
                 if let Token(mark, TokenType::Anchor(name)) = self.fetch_token() {
                     anchor = Some(name.clone());
-                    let anchor_id = self.register_anchor(name, &mark)?; // TODO: remove?
+                    self.register_anchor(name, &mark)?;
                     if let TokenType::Tag(..) = self.peek_token()?.1 {
-                        if let tg @ TokenType::Tag(..) = self.fetch_token().1 {
-                            tag = Some(tg);
+                        if let Token(mark, tg @ TokenType::Tag(..)) = self.fetch_token() {
+                            tag = Some(self.resolve_tag(mark, tg)?);
                         } else {
                             unreachable!()
                         }
@@ -472,12 +656,12 @@ impl<T: Iterator<Item = char>> Parser<T> {
                 }
             }
             Token(_, TokenType::Tag(..)) => {
-                if let tg @ TokenType::Tag(..) = self.fetch_token().1 {
-                    tag = Some(tg);
+                if let Token(mark, tg @ TokenType::Tag(..)) = self.fetch_token() {
+                    tag = Some(self.resolve_tag(mark, tg)?);
                     if let TokenType::Anchor(_) = self.peek_token()?.1 {
                         if let Token(mark, TokenType::Anchor(name)) = self.fetch_token() {
                             anchor = Some(name.clone());
-                            let anchor_id = self.register_anchor(name, &mark)?;
+                            self.register_anchor(name, &mark)?;
                         } else {
                             unreachable!()
                         }
@@ -491,11 +675,15 @@ impl<T: Iterator<Item = char>> Parser<T> {
         match *self.peek_token()? {
             Token(mark, TokenType::BlockEntry) if indentless_sequence => {
                 self.state = State::IndentlessSequenceEntry;
-                Ok((Event::SequenceStart(anchor), mark))
+                Ok((Event::SequenceStart(anchor, tag), mark))
             }
             Token(_, TokenType::Scalar(..)) => {
                 self.pop_state();
                 if let Token(mark, TokenType::Scalar(style, v)) = self.fetch_token() {
+                    #[cfg(feature = "borrowed")]
+                    {
+                        self.scalar_span = self.scanner.take_scalar_span();
+                    }
                     Ok((Event::Scalar(v, style, anchor, tag), mark))
                 } else {
                     unreachable!()
@@ -503,19 +691,19 @@ impl<T: Iterator<Item = char>> Parser<T> {
             }
             Token(mark, TokenType::FlowSequenceStart) => {
                 self.state = State::FlowSequenceFirstEntry;
-                Ok((Event::SequenceStart(anchor), mark))
+                Ok((Event::SequenceStart(anchor, tag), mark))
             }
             Token(mark, TokenType::FlowMappingStart) => {
                 self.state = State::FlowMappingFirstKey;
-                Ok((Event::MappingStart(anchor), mark))
+                Ok((Event::MappingStart(anchor, tag), mark))
             }
             Token(mark, TokenType::BlockSequenceStart) if block => {
                 self.state = State::BlockSequenceFirstEntry;
-                Ok((Event::SequenceStart(anchor), mark))
+                Ok((Event::SequenceStart(anchor, tag), mark))
             }
             Token(mark, TokenType::BlockMappingStart) if block => {
                 self.state = State::BlockMappingFirstKey;
-                Ok((Event::MappingStart(anchor), mark))
+                Ok((Event::MappingStart(anchor, tag), mark))
             }
             // ex 7.2, an empty scalar can follow a secondary tag
             Token(mark, _) if tag.is_some() || anchor.is_some() => {
@@ -712,7 +900,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
             Token(mark, TokenType::Key) => {
                 self.state = State::FlowSequenceEntryMappingKey;
                 self.skip();
-                Ok((Event::MappingStart(None), mark))
+                Ok((Event::MappingStart(None, None), mark))
             }
             _ => {
                 self.push_state(State::FlowSequenceEntry);
@@ -849,4 +1037,86 @@ a5: *x
             event.0 != Event::StreamEnd
         } {}
     }
+
+    #[test]
+    fn test_keep_comments() {
+        let s = "# head\na: 1 # one\n";
+        let events = |keep: bool| {
+            let mut p = Parser::new(s.chars());
+            p.keep_comments(keep);
+            let mut events = Vec::new();
+            loop {
+                let (event, _) = p.next().unwrap();
+                if event == Event::StreamEnd {
+                    return events;
+                }
+                events.push(event);
+            }
+        };
+        let comments: Vec<_> = events(true)
+            .into_iter()
+            .filter(|event| matches!(*event, Event::Comment(..)))
+            .collect();
+        assert_eq!(
+            comments,
+            vec![
+                Event::Comment(" head".to_owned(), false),
+                Event::Comment(" one".to_owned(), true),
+            ]
+        );
+        assert!(!events(false)
+            .iter()
+            .any(|event| matches!(*event, Event::Comment(..))));
+    }
+
+    #[test]
+    fn test_tag_directives() {
+        use crate::yaml::{YamlInput, YamlLoader};
+
+        let tagged = |tag: &str, v: &str| {
+            YamlInput::Tagged(tag.to_owned(), Box::new(YamlInput::String(v.to_owned())))
+        };
+        let s =
+            "%TAG !e! tag:example.com,2000:app/\n%TAG !m! !my-\n%TAG !y! tag:yaml.org,2002:\n---\n\
+                 - !e!foo bar\n- !m!light green\n- !y!int 1\n- !local x\n- !!str y\n\
+                 - !e!tag%21 baz\n";
+        assert_eq!(
+            YamlLoader::load_from_str(s).unwrap()[0],
+            YamlInput::Array(vec![
+                tagged("!<tag:example.com,2000:app/foo>", "bar"),
+                tagged("!my-light", "green"),
+                YamlInput::Integer(1),
+                tagged("!local", "x"),
+                YamlInput::String("y".to_owned()),
+                tagged("!<tag:example.com,2000:app/tag!>", "baz"),
+            ])
+        );
+
+        // the handles can be declared again, and the secondary one reassigned
+        let s = "%TAG ! tag:example.com,2000:app/\n--- !foo bar\n...\n\
+                 %TAG !! tag:example.com,2000:app/\n---\n!!int 1 - 3\n...\n--- !foo bar\n";
+        assert_eq!(
+            YamlLoader::load_from_str(s).unwrap(),
+            vec![
+                tagged("!<tag:example.com,2000:app/foo>", "bar"),
+                tagged("!<tag:example.com,2000:app/int>", "1 - 3"),
+                tagged("!foo", "bar"),
+            ]
+        );
+
+        let error = |s: &str| YamlLoader::load_from_str(s).unwrap_err().to_string();
+        assert_eq!(
+            error("%TAG ! !foo\n%TAG ! !foo\nbar\n"),
+            "found duplicate %TAG directive at line 2 column 1"
+        );
+        assert_eq!(
+            error("%TAG !e! tag:example,2000:app/\n---\n- !h!bar baz\n"),
+            "found undefined tag handle !h! at line 3 column 3"
+        );
+        // a directive does not outlive its document
+        assert_eq!(
+            error("%TAG !e! tag:example,2000:\n--- !e!a 1\n...\n--- !e!b 2\n"),
+            "found undefined tag handle !e! at line 4 column 5"
+        );
+    }
 }
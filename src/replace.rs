@@ -0,0 +1,306 @@
+use crate::diff::{key_segment, Path, Segment};
+use crate::yaml::YamlInput;
+use regex::Regex;
+use std::mem;
+use std::ops::Range;
+
+/// Options controlling what `replace_scalars` touches.
+#[derive(Clone, Debug, Default)]
+pub struct ReplaceOptions {
+    keys: bool,
+    dry_run: bool,
+}
+
+impl ReplaceOptions {
+    pub fn new() -> ReplaceOptions {
+        ReplaceOptions::default()
+    }
+
+    /// Also replace inside string keys of mappings.
+    pub fn keys(&mut self, keys: bool) {
+        self.keys = keys;
+    }
+
+    /// Determine if string keys are replaced too.
+    pub fn is_keys(&self) -> bool {
+        self.keys
+    }
+
+    /// Only report the matches, leaving the document untouched.
+    pub fn dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Determine if the document is left untouched.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+}
+
+/// One match of the pattern inside a string scalar.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct ScalarMatch {
+    path: Path,
+    key: bool,
+    span: Range<usize>,
+    text: String,
+    replacement: String,
+}
+
+impl ScalarMatch {
+    /// The path of the scalar, or of the mapping entry for a key.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Determine if the match is in a key rather than a value.
+    pub fn is_key(&self) -> bool {
+        self.key
+    }
+
+    /// The byte range of the match in the original scalar.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+}
+
+/// Replace the matches of `regex` in the string scalars of `doc` by `replacement`, which
+/// may refer to capture groups like `$1`. Numbers, booleans and nulls are never touched,
+/// nor are keys unless `options.is_keys()`.
+///
+/// When `scope` is not empty only the nodes under one of its paths are considered. Aliases
+/// are left alone: they are emitted as references to their anchored node, which is
+/// replaced where it is defined.
+///
+/// Returns every match, in document order.
+///
+/// # Examples
+///
+/// ```
+/// use regex::Regex;
+/// use yaml_rust_formatter::diff::Path;
+/// use yaml_rust_formatter::replace::{self, ReplaceOptions};
+/// use yaml_rust_formatter::YamlLoader;
+///
+/// let mut doc = YamlLoader::load_from_str("
+/// image: registry.old/web:1
+/// sidecar: registry.old/proxy:2
+/// ").unwrap().remove(0);
+/// let regex = Regex::new(r"registry\.old/").unwrap();
+/// let scope = [Path::parse("image")];
+/// let matches = replace::replace_scalars(&mut doc, &regex, "registry.new/", &scope, &ReplaceOptions::new());
+/// assert_eq!(matches.len(), 1);
+/// assert_eq!(matches[0].span(), 0..13);
+/// assert_eq!(doc["image"].as_str(), Some("registry.new/web:1"));
+/// assert_eq!(doc["sidecar"].as_str(), Some("registry.old/proxy:2"));
+/// ```
+pub fn replace_scalars(
+    doc: &mut YamlInput,
+    regex: &Regex,
+    replacement: &str,
+    scope: &[Path],
+    options: &ReplaceOptions,
+) -> Vec<ScalarMatch> {
+    let mut replacer = Replacer {
+        regex,
+        replacement,
+        scope,
+        options,
+        matches: Vec::new(),
+    };
+    replacer.node(doc, &Path::new());
+    replacer.matches
+}
+
+struct Replacer<'a> {
+    regex: &'a Regex,
+    replacement: &'a str,
+    scope: &'a [Path],
+    options: &'a ReplaceOptions,
+    matches: Vec<ScalarMatch>,
+}
+
+impl<'a> Replacer<'a> {
+    fn in_scope(&self, path: &Path) -> bool {
+        self.scope.is_empty() || self.scope.iter().any(|scope| path.starts_with(scope))
+    }
+
+    /// Determine if a node under `path` can be in scope.
+    fn leads_to_scope(&self, path: &Path) -> bool {
+        self.in_scope(path) || self.scope.iter().any(|scope| scope.starts_with(path))
+    }
+
+    /// Replace in `v`, returning the new string if anything matched.
+    fn string(&mut self, v: &str, path: &Path, key: bool) -> Option<String> {
+        let mut out = String::new();
+        let mut last = 0;
+        let mut matched = false;
+        for caps in self.regex.captures_iter(v) {
+            let m = caps.get(0).unwrap();
+            let mut replacement = String::new();
+            caps.expand(self.replacement, &mut replacement);
+            out.push_str(&v[last..m.start()]);
+            out.push_str(&replacement);
+            last = m.end();
+            matched = true;
+            self.matches.push(ScalarMatch {
+                path: path.clone(),
+                key,
+                span: m.range(),
+                text: m.as_str().to_owned(),
+                replacement,
+            });
+        }
+        if !matched {
+            return None;
+        }
+        out.push_str(&v[last..]);
+        Some(out)
+    }
+
+    fn node(&mut self, node: &mut YamlInput, path: &Path) {
+        if !self.leads_to_scope(path) {
+            return;
+        }
+        let node = node.inner_mut();
+        match *node {
+            YamlInput::String(ref mut v) if self.in_scope(path) => {
+                if let Some(new) = self.string(v, path, false) {
+                    if !self.options.dry_run {
+                        *v = new;
+                    }
+                }
+            }
+            YamlInput::Raw(ref v) if self.in_scope(path) && is_string(v) => {
+                if let Some(new) = self.string(v, path, false) {
+                    if !self.options.dry_run {
+                        *node = YamlInput::String(new);
+                    }
+                }
+            }
+            YamlInput::Array(ref mut v) => {
+                for (i, x) in v.iter_mut().enumerate() {
+                    self.node(x, &path.child(Segment::Index(i)));
+                }
+            }
+            YamlInput::Hash(ref mut h) => {
+                let entries = mem::take(h);
+                for (mut k, mut v) in entries {
+                    let child = path.child(key_segment(&k));
+                    if self.options.keys && self.in_scope(&child) {
+                        let new = match k {
+                            YamlInput::String(ref key) => self.string(key, &child, true),
+                            YamlInput::Raw(ref key) if is_string(key) => {
+                                self.string(key, &child, true)
+                            }
+                            _ => None,
+                        };
+                        if let Some(new) = new.filter(|_| !self.options.dry_run) {
+                            k = YamlInput::String(new);
+                        }
+                    }
+                    self.node(&mut v, &child);
+                    h.insert(k, v);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Check if the text of a raw scalar reads as a string. Raw scalars that stand for
+/// numbers, booleans or nulls are left alone like the resolved ones.
+fn is_string(v: &str) -> bool {
+    matches!(YamlInput::from_str(v), YamlInput::String(_))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emitter::YamlEmitter;
+    use crate::yaml::{YamlLoader, YamlOutput};
+
+    fn emit(doc: YamlInput) -> String {
+        let mut out = String::new();
+        YamlEmitter::new(&mut out)
+            .dump(&YamlOutput::from(doc))
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_replace_scalars() {
+        let s = "
+name: foo
+foo: foo-bar
+count: 10
+list: [foo, 'foo: bar', 100]
+base: &base {image: foo}
+copy: *base
+";
+        let mut doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        let regex = Regex::new("foo").unwrap();
+        let matches = replace_scalars(&mut doc, &regex, "a:b", &[], &ReplaceOptions::new());
+        let found: Vec<_> = matches
+            .iter()
+            .map(|m| format!("{} {:?}", m.path(), m.span()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                "name 0..3",
+                "foo 0..3",
+                "list[0] 0..3",
+                "list[1] 0..3",
+                "base.image 0..3",
+            ]
+        );
+        assert_eq!(
+            emit(doc),
+            "---
+name: \"a:b\"
+foo: \"a:b-bar\"
+count: 10
+list:
+  - \"a:b\"
+  - \"a:b: bar\"
+  - 100
+base: &base
+  image: \"a:b\"
+copy: *base"
+        );
+
+        // numbers are not strings
+        let mut doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        let regex = Regex::new("10").unwrap();
+        assert!(replace_scalars(&mut doc, &regex, "x", &[], &ReplaceOptions::new()).is_empty());
+    }
+
+    #[test]
+    fn test_replace_scalars_options() {
+        let s = "{foo: foo, bar: {foo: foo}}";
+        let regex = Regex::new("f(o+)").unwrap();
+        let mut options = ReplaceOptions::new();
+        options.keys(true);
+        options.dry_run(true);
+        let mut doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        let matches = replace_scalars(&mut doc, &regex, "b$1", &[Path::parse("bar")], &options);
+        assert_eq!(matches.len(), 2);
+        assert!(matches[0].is_key());
+        assert_eq!(matches[0].path().to_string(), "bar.foo");
+        assert_eq!(matches[1].replacement(), "boo");
+        assert_eq!(doc, YamlLoader::load_from_str(s).unwrap()[0]);
+
+        options.dry_run(false);
+        replace_scalars(&mut doc, &regex, "b$1", &[], &options);
+        assert_eq!(emit(doc), "---\nboo: boo\nbar:\n  boo: boo");
+    }
+}
@@ -0,0 +1,175 @@
+//! The summary of a run over many files, and the exit code a tool reports it with.
+
+use crate::format::FormatSummary;
+use std::fmt;
+use std::process;
+
+/// The exit status of a tool, following the convention of `diff`: differences or
+/// problems found in the files are not failures of the tool itself.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// Every file is as expected.
+    Success,
+    /// Some files need formatting or have diagnostics.
+    Findings,
+    /// Some files could not be read or parsed.
+    Failure,
+}
+
+impl ExitStatus {
+    /// The process exit code: 0, 1 or 2.
+    pub fn code(self) -> i32 {
+        match self {
+            ExitStatus::Success => 0,
+            ExitStatus::Findings => 1,
+            ExitStatus::Failure => 2,
+        }
+    }
+}
+
+impl From<ExitStatus> for process::ExitCode {
+    fn from(status: ExitStatus) -> process::ExitCode {
+        process::ExitCode::from(status.code() as u8)
+    }
+}
+
+/// What a tool found in the files of a run.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::report::{ExitStatus, RunSummary};
+///
+/// let mut summary = RunSummary::default();
+/// summary.merge(&RunSummary { files_checked: 2, changed: 1, ..RunSummary::default() });
+/// summary.merge(&RunSummary { files_checked: 1, diagnostics: 2, ..RunSummary::default() });
+/// assert_eq!(summary.to_string(), "3 files checked, 1 changed, 0 errors, 2 diagnostics");
+/// assert_eq!(summary.exit_status(true), ExitStatus::Findings);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    /// The files read, including the ones that failed.
+    pub files_checked: usize,
+    /// The files whose formatting changed, or would in check mode.
+    pub changed: usize,
+    /// The files that could not be read or parsed.
+    pub errors: usize,
+    /// The diagnostics reported over all the files.
+    pub diagnostics: usize,
+}
+
+impl RunSummary {
+    pub fn new() -> RunSummary {
+        RunSummary::default()
+    }
+
+    /// Add the counts of `other` to these.
+    pub fn merge(&mut self, other: &RunSummary) {
+        self.files_checked += other.files_checked;
+        self.changed += other.changed;
+        self.errors += other.errors;
+        self.diagnostics += other.diagnostics;
+    }
+
+    /// The exit status of the run. Changed files are findings only in `check` mode:
+    /// otherwise they have been rewritten.
+    pub fn exit_status(&self, check: bool) -> ExitStatus {
+        if self.errors > 0 {
+            ExitStatus::Failure
+        } else if self.diagnostics > 0 || (check && self.changed > 0) {
+            ExitStatus::Findings
+        } else {
+            ExitStatus::Success
+        }
+    }
+}
+
+impl<'a> From<&'a FormatSummary> for RunSummary {
+    fn from(summary: &'a FormatSummary) -> RunSummary {
+        RunSummary {
+            files_checked: summary.changed()
+                + summary.unchanged()
+                + summary.cached()
+                + summary.failed(),
+            changed: summary.changed(),
+            errors: summary.failed(),
+            diagnostics: 0,
+        }
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        write!(
+            formatter,
+            "{} file{} checked, {} changed, {} error{}, {} diagnostic{}",
+            self.files_checked,
+            plural(self.files_checked),
+            self.changed,
+            self.errors,
+            plural(self.errors),
+            self.diagnostics,
+            plural(self.diagnostics)
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::format::{self, FormatOptions};
+    use std::fs;
+
+    #[test]
+    fn test_exit_status() {
+        let clean = RunSummary {
+            files_checked: 3,
+            ..RunSummary::new()
+        };
+        assert_eq!(clean.exit_status(true), ExitStatus::Success);
+        let changed = RunSummary {
+            changed: 1,
+            ..clean
+        };
+        assert_eq!(changed.exit_status(false), ExitStatus::Success);
+        assert_eq!(changed.exit_status(true).code(), 1);
+        let failed = RunSummary {
+            errors: 1,
+            diagnostics: 4,
+            ..changed
+        };
+        assert_eq!(failed.exit_status(false).code(), 2);
+        assert_eq!(
+            failed.to_string(),
+            "3 files checked, 1 changed, 1 error, 4 diagnostics"
+        );
+    }
+
+    #[test]
+    fn test_from_format_summary() {
+        let dir = std::env::temp_dir().join(format!("yaml-report-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths = [
+            dir.join("a.yaml"),
+            dir.join("b.yaml"),
+            dir.join("missing.yaml"),
+        ];
+        fs::write(&paths[0], "a:   1\n").unwrap();
+        fs::write(&paths[1], "---\nb: 1\n").unwrap();
+        let mut options = FormatOptions::new();
+        options.check(true);
+        let summary = RunSummary::from(&format::format_files(&paths, &options, &mut ()));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            summary,
+            RunSummary {
+                files_checked: 3,
+                changed: 1,
+                errors: 1,
+                diagnostics: 0,
+            }
+        );
+        assert_eq!(summary.exit_status(true), ExitStatus::Failure);
+    }
+}
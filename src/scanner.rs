@@ -1,13 +1,13 @@
 use std::collections::VecDeque;
 use std::error::Error;
-use std::{char, fmt};
+use std::{char, fmt, mem};
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
 pub enum TEncoding {
     Utf8,
 }
 
-#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Eq, Ord, Hash)]
 pub enum TScalarStyle {
     Any,
     Plain,
@@ -26,7 +26,7 @@ pub struct Marker {
 }
 
 impl Marker {
-    fn new(index: usize, line: usize, col: usize) -> Marker {
+    pub(crate) fn new(index: usize, line: usize, col: usize) -> Marker {
         Marker { index, line, col }
     }
 
@@ -60,6 +60,10 @@ impl ScanError {
     pub fn marker(&self) -> &Marker {
         &self.mark
     }
+
+    pub fn info(&self) -> &str {
+        &self.info
+    }
 }
 
 impl Error for ScanError {
@@ -117,6 +121,16 @@ pub enum TokenType {
 #[derive(Clone, PartialEq, Debug, Eq)]
 pub struct Token(pub Marker, pub TokenType);
 
+/// A `#` comment, kept when `Scanner::keep_comments` is set.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub(crate) struct Comment {
+    pub mark: Marker,
+    /// The text after the `#`.
+    pub text: String,
+    /// Whether the comment follows other content on its line.
+    pub inline: bool,
+}
+
 #[derive(Clone, PartialEq, Debug, Eq)]
 struct SimpleKey {
     possible: bool,
@@ -139,6 +153,8 @@ impl SimpleKey {
 #[derive(Debug)]
 pub struct Scanner<T> {
     rdr: T,
+    // the character read after a CR, to tell CR LF from a lone CR
+    peeked: Option<char>,
     mark: Marker,
     tokens: VecDeque<Token>,
     buffer: VecDeque<char>,
@@ -154,6 +170,23 @@ pub struct Scanner<T> {
     flow_level: u8,
     tokens_parsed: usize,
     token_available: bool,
+    keep_comments: bool,
+    comments: VecDeque<Comment>,
+    /// The last line where something other than blanks was read.
+    content_line: Option<usize>,
+    replace_invalid: bool,
+    /// The number of characters handed out by `read_char`.
+    read_count: usize,
+    /// The index and value of the first character YAML does not allow, where the
+    /// input is cut off.
+    invalid: Option<(usize, char)>,
+    keep_scalar_spans: bool,
+    /// For each scalar token in the queue, with `keep_scalar_spans`, the length in
+    /// bytes of its text when it is read as it is written in the source.
+    scalar_spans: VecDeque<Option<usize>>,
+    /// The buffer the next scalar is read into, kept from a scalar whose text was
+    /// left in the source.
+    scalar_buffer: String,
 }
 
 impl<T: Iterator<Item = char>> Iterator for Scanner<T> {
@@ -172,6 +205,12 @@ impl<T: Iterator<Item = char>> Iterator for Scanner<T> {
     }
 }
 
+/// A C0 control character other than tab and line breaks, or DEL. Such characters
+/// cannot appear in a YAML stream, and the emitter always escapes them.
+#[inline]
+fn is_control(c: char) -> bool {
+    matches!(c, '\0'..='\x08' | '\x0b' | '\x0c' | '\x0e'..='\x1f' | '\x7f')
+}
 #[inline]
 fn is_z(c: char) -> bool {
     c == '\0'
@@ -194,7 +233,7 @@ fn is_blankz(c: char) -> bool {
 }
 #[inline]
 fn is_digit(c: char) -> bool {
-    ('0'..='9').contains(&c)
+    c.is_ascii_digit()
 }
 #[inline]
 fn is_alpha(c: char) -> bool {
@@ -202,7 +241,7 @@ fn is_alpha(c: char) -> bool {
 }
 #[inline]
 fn is_hex(c: char) -> bool {
-    ('0'..='9').contains(&c) || ('a'..='f').contains(&c) || ('A'..='F').contains(&c)
+    c.is_ascii_hexdigit()
 }
 #[inline]
 fn as_hex(c: char) -> u32 {
@@ -225,6 +264,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     pub fn new(rdr: T) -> Scanner<T> {
         Scanner {
             rdr,
+            peeked: None,
             buffer: VecDeque::new(),
             mark: Marker::new(0, 1, 0),
             tokens: VecDeque::new(),
@@ -240,8 +280,82 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             flow_level: 0,
             tokens_parsed: 0,
             token_available: false,
+            keep_comments: false,
+            comments: VecDeque::new(),
+            content_line: None,
+            replace_invalid: false,
+            read_count: 0,
+            invalid: None,
+            keep_scalar_spans: false,
+            scalar_spans: VecDeque::new(),
+            scalar_buffer: String::new(),
+        }
+    }
+
+    /// Keep the comments met while scanning instead of dropping them.
+    pub fn keep_comments(&mut self, keep_comments: bool) {
+        self.keep_comments = keep_comments;
+    }
+
+    /// Determine if comments are kept while scanning.
+    pub fn is_keep_comments(&self) -> bool {
+        self.keep_comments
+    }
+
+    /// Replace the control characters YAML does not allow, such as NUL, and lone
+    /// surrogates in `\u` escapes with U+FFFD instead of reporting them as errors.
+    pub fn replace_invalid_characters(&mut self, replace_invalid: bool) {
+        self.replace_invalid = replace_invalid;
+    }
+
+    /// Determine if characters YAML does not allow are replaced with U+FFFD.
+    pub fn is_replace_invalid_characters(&self) -> bool {
+        self.replace_invalid
+    }
+
+    /// Leave the text of a plain or quoted scalar read as it is written in the source
+    /// out of its token, which then holds an empty string, and keep the length of that
+    /// text instead, see `take_scalar_span`. The source is the one place the text is,
+    /// so the scalars are read without allocating a string each.
+    #[cfg(feature = "borrowed")]
+    pub(crate) fn keep_scalar_spans(&mut self, keep_scalar_spans: bool) {
+        self.keep_scalar_spans = keep_scalar_spans;
+    }
+
+    /// The length in bytes of the text of the oldest scalar token not asked for yet,
+    /// if it was left in the source, see `keep_scalar_spans`. The text starts at the
+    /// marker of the token, after the quote of a quoted scalar.
+    #[cfg(feature = "borrowed")]
+    pub(crate) fn take_scalar_span(&mut self) -> Option<usize> {
+        self.scalar_spans.pop_front().flatten()
+    }
+
+    /// The text of the scalar read into `string`, empty with `keep_scalar_spans` when
+    /// it is `verbatim`, as written in the source, see `keep_scalar_spans`.
+    fn scalar_text(&mut self, mut string: String, verbatim: bool) -> String {
+        if !self.keep_scalar_spans {
+            return string;
+        }
+        if verbatim && !string.is_empty() && !self.replace_invalid {
+            self.scalar_spans.push_back(Some(string.len()));
+            string.clear();
+            self.scalar_buffer = string;
+            String::new()
+        } else {
+            self.scalar_spans.push_back(None);
+            string
+        }
+    }
+
+    /// Take the oldest kept comment if it starts before `mark`.
+    pub(crate) fn take_comment_before(&mut self, mark: &Marker) -> Option<Comment> {
+        if self.comments.front()?.mark.index < mark.index {
+            self.comments.pop_front()
+        } else {
+            None
         }
     }
+
     #[inline]
     pub fn get_error(&self) -> Option<ScanError> {
         self.error.as_ref().cloned()
@@ -253,13 +367,62 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             return;
         }
         for _ in 0..(count - self.buffer.len()) {
-            self.buffer.push_back(self.rdr.next().unwrap_or('\0'));
+            let c = self.read_char();
+            self.buffer.push_back(c);
+        }
+    }
+
+    /// Read the next character of the input, turning a lone CR into LF so that every
+    /// line ending is either LF or CR LF.
+    ///
+    /// A character YAML does not allow ends the input unless `replace_invalid` is set,
+    /// see `invalid_character`.
+    fn read_char(&mut self) -> char {
+        if self.invalid.is_some() {
+            return '\0';
+        }
+        let index = self.read_count;
+        self.read_count += 1;
+        match self.peeked.take().or_else(|| self.rdr.next()) {
+            Some('\r') => {
+                self.peeked = self.rdr.next();
+                if self.peeked == Some('\n') {
+                    '\r'
+                } else {
+                    '\n'
+                }
+            }
+            Some(c) if !is_control(c) => c,
+            Some(_) if self.replace_invalid => '\u{fffd}',
+            Some(c) => {
+                self.invalid = Some((index, c));
+                '\0'
+            }
+            None => '\0',
+        }
+    }
+
+    /// The error for a character YAML does not allow, once scanning has stopped at it.
+    fn invalid_character(&self) -> Option<ScanError> {
+        match self.invalid {
+            Some((index, c)) if index == self.mark.index => {
+                let info = if c == '\0' {
+                    "found a NUL character".to_owned()
+                } else {
+                    format!("found control character U+{:04X}", c as u32)
+                };
+                Some(ScanError::new(self.mark, &info))
+            }
+            _ => None,
         }
     }
     #[inline]
     fn skip(&mut self) {
         let c = self.buffer.pop_front().unwrap();
 
+        if !is_blankz(c) && !is_break(c) {
+            self.content_line = Some(self.mark.line);
+        }
         self.mark.index += 1;
         if c == '\n' {
             self.mark.line += 1;
@@ -285,6 +448,21 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     fn ch_is(&self, c: char) -> bool {
         self.buffer[0] == c
     }
+    /// The value of a `\uDC00`..`\uDFFF` escape at the head of the buffer.
+    fn low_surrogate_escape(&mut self) -> Option<u32> {
+        self.lookahead(6);
+        if self.buffer[0] != '\\' || self.buffer[1] != 'u' {
+            return None;
+        }
+        let mut value = 0u32;
+        for i in 2..6 {
+            if !is_hex(self.buffer[i]) {
+                return None;
+            }
+            value = (value << 4) + as_hex(self.buffer[i]);
+        }
+        Some(value).filter(|value| (0xdc00..0xe000).contains(value))
+    }
     #[allow(dead_code)]
     #[inline]
     fn eof(&self) -> bool {
@@ -348,6 +526,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.lookahead(4);
 
         if is_z(self.ch()) {
+            if let Some(err) = self.invalid_character() {
+                return Err(err);
+            }
             self.fetch_stream_end()?;
             return Ok(());
         }
@@ -421,7 +602,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         if !self.token_available {
-            self.fetch_more_tokens()?;
+            self.fetch_more_tokens()
+                .map_err(|err| self.invalid_character().unwrap_or(err))?;
         }
         let t = self.tokens.pop_front().unwrap();
         self.token_available = false;
@@ -473,7 +655,26 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         Ok(())
     }
 
+    fn scan_comment(&mut self, inline: bool) {
+        let mark = self.mark;
+        let mut text = String::new();
+        self.skip();
+        self.lookahead(1);
+        while !is_breakz(self.ch()) {
+            if self.keep_comments {
+                text.push(self.ch());
+            }
+            self.skip();
+            self.lookahead(1);
+        }
+        if self.keep_comments {
+            self.comments.push_back(Comment { mark, text, inline });
+        }
+    }
+
     fn skip_to_next_token(&mut self) {
+        // a comment is inline unless only blanks precede it on its line
+        let mut inline = self.content_line == Some(self.mark.line);
         loop {
             self.lookahead(1);
             // TODO(chenyh) BOM
@@ -483,16 +684,12 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 '\n' | '\r' => {
                     self.lookahead(2);
                     self.skip_line();
+                    inline = false;
                     if self.flow_level == 0 {
                         self.allow_simple_key();
                     }
                 }
-                '#' => {
-                    while !is_breakz(self.ch()) {
-                        self.skip();
-                        self.lookahead(1);
-                    }
-                }
+                '#' => self.scan_comment(inline),
                 _ => break,
             }
         }
@@ -687,7 +884,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         let is_secondary = handle == "!!";
-        let prefix = self.scan_tag_uri(true, is_secondary, &String::new(), mark)?;
+        let prefix = self.scan_tag_uri(true, is_secondary, "", mark)?;
 
         self.lookahead(1);
 
@@ -723,7 +920,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             // Eat '!<'
             self.skip();
             self.skip();
-            suffix = self.scan_tag_uri(false, false, &String::new(), &start_mark)?;
+            suffix = self.scan_tag_uri(false, false, "", &start_mark)?;
 
             if self.ch() != '>' {
                 return Err(ScanError::new(
@@ -741,7 +938,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 if handle == "!!" {
                     secondary = true;
                 }
-                suffix = self.scan_tag_uri(false, secondary, &String::new(), &start_mark)?;
+                suffix = self.scan_tag_uri(false, secondary, "", &start_mark)?;
             } else {
                 suffix = self.scan_tag_uri(false, false, &handle, &start_mark)?;
                 handle = "!".to_owned();
@@ -882,7 +1079,13 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                         ));
                     }
                 };
-                code = octet;
+                // the bits of the code point after the length prefix
+                code = match width {
+                    1 => octet,
+                    2 => octet & 0x1F,
+                    3 => octet & 0x0F,
+                    _ => octet & 0x07,
+                };
             } else {
                 if octet & 0xc0 != 0x80 {
                     return Err(ScanError::new(
@@ -890,7 +1093,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                         "while parsing a tag, found an incorrect trailing UTF-8 octet",
                     ));
                 }
-                code = (code << 8) + octet;
+                code = (code << 6) + (octet & 0x3F);
             }
 
             self.skip();
@@ -1126,10 +1329,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         if self.ch() == '#' {
-            while !is_breakz(self.ch()) {
-                self.skip();
-                self.lookahead(1);
-            }
+            self.scan_comment(true);
         }
 
         // Check if we are at the end of the line.
@@ -1202,6 +1402,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             string.push_str(&trailing_breaks);
         }
 
+        let string = self.scalar_text(string, false);
         if literal {
             Ok(Token(
                 start_mark,
@@ -1272,11 +1473,13 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     fn scan_flow_scalar(&mut self, single: bool) -> Result<Token, ScanError> {
         let start_mark = self.mark;
 
-        let mut string = String::new();
+        let mut string = mem::take(&mut self.scalar_buffer);
         let mut leading_break = String::new();
         let mut trailing_breaks = String::new();
         let mut whitespaces = String::new();
         let mut leading_blanks;
+        // whether the text is the one written between the quotes
+        let mut verbatim = true;
 
         /* Eat the left quote. */
         self.skip();
@@ -1315,6 +1518,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     // Check for an escaped single quote.
                     '\'' if self.buffer[1] == '\'' && single => {
                         string.push('\'');
+                        verbatim = false;
                         self.skip();
                         self.skip();
                     }
@@ -1323,6 +1527,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     '"' if !single => break,
                     // Check for an escaped line break.
                     '\\' if !single && is_break(self.buffer[1]) => {
+                        verbatim = false;
                         self.lookahead(3);
                         self.skip();
                         self.skip_line();
@@ -1331,6 +1536,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     }
                     // Check for an escape sequence.
                     '\\' if !single => {
+                        verbatim = false;
+                        let escape_mark = self.mark;
                         let mut code_length = 0usize;
                         match self.buffer[1] {
                             '0' => string.push('\0'),
@@ -1378,18 +1585,35 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                                 value = (value << 4) + as_hex(self.buffer[i]);
                             }
 
+                            for _ in 0..code_length {
+                                self.skip();
+                            }
+
+                            // a UTF-16 surrogate pair, as JSON writes characters
+                            // beyond the Basic Multilingual Plane
+                            if (0xd800..0xdc00).contains(&value) {
+                                if let Some(low) = self.low_surrogate_escape() {
+                                    value = 0x10000 + ((value - 0xd800) << 10) + (low - 0xdc00);
+                                    for _ in 0..6 {
+                                        self.skip();
+                                    }
+                                }
+                            }
+
                             let ch = match char::from_u32(value) {
                                 Some(v) => v,
+                                None if self.replace_invalid => '\u{fffd}',
+                                None if (0xd800..0xe000).contains(&value) => {
+                                    return Err(ScanError::new(escape_mark, &format!(
+                                        "while parsing a quoted scalar, found lone surrogate escape \\u{:04x}",
+                                        value)));
+                                }
                                 None => {
-                                    return Err(ScanError::new(start_mark,
+                                    return Err(ScanError::new(escape_mark,
                                         "while parsing a quoted scalar, found invalid Unicode character escape code"));
                                 }
                             };
                             string.push(ch);
-
-                            for _ in 0..code_length {
-                                self.skip();
-                            }
                         }
                     }
                     c => {
@@ -1430,6 +1654,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             }
             // Join the whitespaces or fold line breaks.
             if leading_blanks {
+                verbatim = false;
                 if leading_break.is_empty() {
                     string.push_str(&leading_break);
                     string.push_str(&trailing_breaks);
@@ -1450,6 +1675,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         // Eat the right quote.
         self.skip();
 
+        let string = self.scalar_text(string, verbatim);
         if single {
             Ok(Token(
                 start_mark,
@@ -1477,11 +1703,13 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let indent = self.indent + 1;
         let start_mark = self.mark;
 
-        let mut string = String::new();
+        let mut string = mem::take(&mut self.scalar_buffer);
         let mut leading_break = String::new();
         let mut trailing_breaks = String::new();
         let mut whitespaces = String::new();
         let mut leading_blanks = false;
+        // whether the text is the one written in the source, on a single line
+        let mut verbatim = true;
 
         loop {
             /* Check for a document indicator. */
@@ -1514,6 +1742,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
                 if leading_blanks || !whitespaces.is_empty() {
                     if leading_blanks {
+                        verbatim = false;
                         if leading_break.is_empty() {
                             string.push_str(&leading_break);
                             string.push_str(&trailing_breaks);
@@ -1580,6 +1809,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.allow_simple_key();
         }
 
+        let string = self.scalar_text(string, verbatim);
         Ok(Token(
             start_mark,
             TokenType::Scalar(TScalarStyle::Plain, string),
@@ -1626,12 +1856,12 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             let tokens_parsed = self.tokens_parsed;
             self.insert_token(sk.token_number - tokens_parsed, tok);
 
-            // Add the BLOCK-MAPPING-START token if needed.
+            // Add the BLOCK-MAPPING-START token if needed, where the mapping starts.
             self.roll_indent(
                 sk.mark.col,
                 Some(sk.token_number),
                 TokenType::BlockMappingStart,
-                start_mark,
+                sk.mark,
             );
 
             self.simple_keys.last_mut().unwrap().possible = false;
@@ -2151,6 +2381,58 @@ key:
         end!(p);
     }
 
+    fn scan_error(source: &str) -> ScanError {
+        let mut p = Scanner::new(source.chars());
+        while p.next().is_some() {}
+        p.get_error().unwrap()
+    }
+
+    #[test]
+    fn test_invalid_characters() {
+        let err = scan_error("a: b\nc: d\0e\nf: g\n");
+        assert_eq!(err.to_string(), "found a NUL character at line 2 column 5");
+        let err = scan_error("key: \"x\0\"\n");
+        assert_eq!(err.to_string(), "found a NUL character at line 1 column 8");
+        let err = scan_error("- \x1b[31m\n");
+        assert_eq!(
+            err.to_string(),
+            "found control character U+001B at line 1 column 3"
+        );
+
+        let mut p = Scanner::new("- a\0b\n- \x7f\n".chars());
+        p.replace_invalid_characters(true);
+        next!(p, StreamStart(..));
+        next!(p, BlockSequenceStart);
+        next!(p, BlockEntry);
+        next_scalar!(p, TScalarStyle::Plain, "a\u{fffd}b");
+        next!(p, BlockEntry);
+        next_scalar!(p, TScalarStyle::Plain, "\u{fffd}");
+        next!(p, BlockEnd);
+        next!(p, StreamEnd);
+        end!(p);
+    }
+
+    #[test]
+    fn test_surrogate_escapes() {
+        let mut p = Scanner::new(r#""\ud83d\ude00 \u00e9\ud800x""#.chars());
+        p.replace_invalid_characters(true);
+        next!(p, StreamStart(..));
+        next_scalar!(p, TScalarStyle::DoubleQuoted, "\u{1f600} \u{e9}\u{fffd}x");
+        next!(p, StreamEnd);
+        end!(p);
+
+        let err = scan_error(r#"a: "ok \udc00 \ud83d\ude00""#);
+        assert_eq!(
+            err.to_string(),
+            "while parsing a quoted scalar, found lone surrogate escape \\udc00 at line 1 column 8"
+        );
+        let err = scan_error(r#"a: "\ud800\u0041""#);
+        assert_eq!(
+            err.to_string(),
+            "while parsing a quoted scalar, found lone surrogate escape \\ud800 at line 1 column 5"
+        );
+    }
+
     #[test]
     fn test_uri() {
         // TODO
@@ -2158,6 +2440,18 @@ key:
 
     #[test]
     fn test_uri_escapes() {
-        // TODO
+        let s = "!a%C3%A9%E2%82%AC%F0%9F%A6%80%21 x";
+        let mut p = Scanner::new(s.chars());
+        next!(p, StreamStart(..));
+        match p.next().unwrap().1 {
+            Tag(ref handle, ref suffix) => {
+                assert_eq!(handle, "!");
+                assert_eq!(suffix, "a\u{e9}\u{20ac}\u{1f980}!");
+            }
+            tok => panic!("unexpected token: {:?}", tok),
+        }
+        next_scalar!(p, TScalarStyle::Plain, "x");
+        next!(p, StreamEnd);
+        end!(p);
     }
 }
@@ -0,0 +1,1530 @@
+use crate::diff::Path;
+use crate::parser::{Event, Parser};
+use crate::scanner::{Marker, ScanError, TScalarStyle};
+use crate::yaml::{
+    resolve, resolve_scalar, Comments, Entries, HashInput, HashOutput, Marks, YamlInput,
+    YamlLoader, YamlOutput,
+};
+use linked_hash_map::LinkedHashMap;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+
+/// The shape a YAML node is expected to have.
+///
+/// Schemas are usually read from a YAML (or JSON) document using the subset of JSON Schema
+/// understood by `Schema::from_yaml`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Schema {
+    /// Accepts every node.
+    Any,
+    Null,
+    Boolean,
+    Integer,
+    /// Accepts both integers and reals.
+    Number,
+    String,
+    /// A sequence whose items all match the boxed schema.
+    Array(Box<Schema>),
+    Hash(HashSchema),
+}
+
+/// The expected shape of a mapping.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct HashSchema {
+    /// Schemas of the known keys.
+    pub properties: LinkedHashMap<String, Schema>,
+    /// Keys that must be present.
+    pub required: Vec<String>,
+    /// Schema of the keys not listed in `properties`, `None` rejects them.
+    pub additional: Option<Box<Schema>>,
+}
+
+static ANY: Schema = Schema::Any;
+
+/// Options controlling how documents are checked against a `Schema`.
+#[derive(Clone, Debug, Default)]
+pub struct ValidateOptions {
+    report_anchors: bool,
+}
+
+impl ValidateOptions {
+    pub fn new() -> ValidateOptions {
+        ValidateOptions::default()
+    }
+
+    /// Also report the anchor of an aliased node that violates the schema.
+    ///
+    /// Violations inside aliased nodes are always reported at the alias site, as a
+    /// node can be valid where it is defined and invalid where it is reused.
+    pub fn report_anchors(&mut self, report_anchors: bool) {
+        self.report_anchors = report_anchors;
+    }
+
+    /// Determine if the anchors of aliased nodes are reported.
+    pub fn is_report_anchors(&self) -> bool {
+        self.report_anchors
+    }
+}
+
+/// A node that does not match its schema, or a schema that could not be read.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct SchemaError {
+    path: String,
+    mark: Option<Marker>,
+    info: String,
+    anchor: Option<Box<(String, Option<Marker>)>>,
+    // whether the error is about the key of the entry at `path` rather than its value
+    at_key: bool,
+}
+
+impl SchemaError {
+    pub fn new(path: &str, mark: Option<Marker>, info: &str) -> SchemaError {
+        SchemaError {
+            path: path.to_owned(),
+            mark,
+            info: info.to_owned(),
+            anchor: None,
+            at_key: false,
+        }
+    }
+
+    /// The path of the offending node, e.g. `spec.containers[0].image`.
+    /// Empty for the document root.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The position of the offending node, when validating from source.
+    pub fn marker(&self) -> Option<&Marker> {
+        self.mark.as_ref()
+    }
+
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+
+    /// The anchor of the aliased node holding the violation, when reported.
+    pub fn anchor(&self) -> Option<&str> {
+        self.anchor.as_ref().map(|anchor| anchor.0.as_str())
+    }
+
+    /// Where the anchor of the aliased node holding the violation was defined, when
+    /// reported while validating from source.
+    pub fn anchor_marker(&self) -> Option<&Marker> {
+        self.anchor.as_ref().and_then(|anchor| anchor.1.as_ref())
+    }
+}
+
+impl Error for SchemaError {
+    fn description(&self) -> &str {
+        self.info.as_ref()
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        None
+    }
+}
+
+impl fmt::Display for SchemaError {
+    // col starts from 0
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if !self.path.is_empty() {
+            write!(formatter, "{}: ", self.path)?;
+        }
+        write!(formatter, "{}", self.info)?;
+        if let Some(ref mark) = self.mark {
+            write!(
+                formatter,
+                " at line {} column {}",
+                mark.line(),
+                mark.col() + 1
+            )?;
+        }
+        match self.anchor.as_deref() {
+            Some((ref name, Some(ref mark))) => write!(
+                formatter,
+                " (through anchor {} defined at line {} column {})",
+                name,
+                mark.line(),
+                mark.col() + 1
+            )?,
+            Some((ref name, None)) => write!(formatter, " (through anchor {})", name)?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+impl From<ScanError> for SchemaError {
+    fn from(e: ScanError) -> SchemaError {
+        SchemaError::new("", Some(*e.marker()), e.info())
+    }
+}
+
+fn key_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn kind(node: &YamlInput) -> &'static str {
+    match *resolve(node) {
+        YamlInput::Null => "null",
+        YamlInput::Boolean(_) => "boolean",
+        YamlInput::Integer(_) => "integer",
+        YamlInput::Real(_) => "number",
+        // binary data is written as a base64 string in JSON
+        YamlInput::String(_) | YamlInput::Binary(_) => "string",
+        YamlInput::Array(_) => "array",
+        YamlInput::Hash(_) => "object",
+        YamlInput::Raw(ref v) => kind(&YamlInput::from_str(v)),
+        _ => "invalid value",
+    }
+}
+
+impl Schema {
+    /// Read a schema written in a subset of JSON Schema: `type` (`null`, `boolean`,
+    /// `integer`, `number`, `string`, `array` or `object`), `items`, `properties`,
+    /// `required` and `additionalProperties`. A missing `type` accepts every node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::schema::Schema;
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let docs = YamlLoader::load_from_str("type: array\nitems: {type: integer}").unwrap();
+    /// let schema = Schema::from_yaml(&docs[0]).unwrap();
+    /// assert_eq!(schema, Schema::Array(Box::new(Schema::Integer)));
+    /// ```
+    pub fn from_yaml(doc: &YamlInput) -> Result<Schema, SchemaError> {
+        Schema::read(doc, "")
+    }
+
+    fn read(doc: &YamlInput, path: &str) -> Result<Schema, SchemaError> {
+        let doc = resolve(doc);
+        let hash = match doc.as_hash() {
+            Some(hash) => hash,
+            None => return Err(SchemaError::new(path, None, "schema must be an object")),
+        };
+        let field = |name: &str| hash.get(&YamlInput::String(name.to_owned()));
+        let ty = match field("type") {
+            None => return Ok(Schema::Any),
+            Some(ty) => ty.as_str().unwrap_or(""),
+        };
+        match ty {
+            "null" => Ok(Schema::Null),
+            "boolean" => Ok(Schema::Boolean),
+            "integer" => Ok(Schema::Integer),
+            "number" => Ok(Schema::Number),
+            "string" => Ok(Schema::String),
+            "array" => match field("items") {
+                Some(items) => Ok(Schema::Array(Box::new(Schema::read(
+                    items,
+                    &key_path(path, "items"),
+                )?))),
+                None => Ok(Schema::Array(Box::new(Schema::Any))),
+            },
+            "object" => {
+                let mut schema = HashSchema {
+                    additional: Some(Box::new(Schema::Any)),
+                    ..HashSchema::default()
+                };
+                if let Some(properties) = field("properties") {
+                    let properties_path = key_path(path, "properties");
+                    let properties = properties.as_hash().ok_or_else(|| {
+                        SchemaError::new(&properties_path, None, "expected an object")
+                    })?;
+                    for (k, v) in Entries::new(properties) {
+                        let k = k.as_str().ok_or_else(|| {
+                            SchemaError::new(
+                                &properties_path,
+                                None,
+                                "property names must be strings",
+                            )
+                        })?;
+                        let v = Schema::read(v, &key_path(&properties_path, k))?;
+                        schema.properties.insert(k.to_owned(), v);
+                    }
+                }
+                if let Some(required) = field("required") {
+                    let required = required.as_vec().and_then(|required| {
+                        required
+                            .iter()
+                            .map(|k| k.as_str().map(str::to_owned))
+                            .collect::<Option<Vec<_>>>()
+                    });
+                    schema.required = required.ok_or_else(|| {
+                        SchemaError::new(
+                            &key_path(path, "required"),
+                            None,
+                            "expected an array of strings",
+                        )
+                    })?;
+                }
+                match field("additionalProperties") {
+                    None => {}
+                    Some(&YamlInput::Boolean(true)) => {}
+                    Some(&YamlInput::Boolean(false)) => schema.additional = None,
+                    Some(additional) => {
+                        schema.additional = Some(Box::new(Schema::read(
+                            additional,
+                            &key_path(path, "additionalProperties"),
+                        )?))
+                    }
+                }
+                Ok(Schema::Hash(schema))
+            }
+            _ => Err(SchemaError::new(
+                &key_path(path, "type"),
+                None,
+                "unknown type",
+            )),
+        }
+    }
+
+    /// The schema of documents shaped like `example`: each mapping has the string keys
+    /// of the example, none of them required, and each sequence has items shaped like
+    /// its first one. Nulls, and the items of empty sequences, accept every node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::schema::Schema;
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let docs = YamlLoader::load_from_str("[1, 2]").unwrap();
+    /// assert_eq!(Schema::from_example(&docs[0]), Schema::Array(Box::new(Schema::Integer)));
+    /// ```
+    pub fn from_example(example: &YamlInput) -> Schema {
+        match *resolve(example) {
+            YamlInput::Raw(ref v) => Schema::from_example(&YamlInput::from_str(v)),
+            YamlInput::Boolean(_) => Schema::Boolean,
+            YamlInput::Integer(_) => Schema::Integer,
+            YamlInput::Real(_) => Schema::Number,
+            YamlInput::String(_) | YamlInput::Binary(_) => Schema::String,
+            YamlInput::Array(ref v) => Schema::Array(Box::new(
+                v.first().map_or(Schema::Any, Schema::from_example),
+            )),
+            YamlInput::Hash(ref h) => Schema::Hash(HashSchema {
+                properties: Entries::new(h)
+                    .filter_map(|(k, v)| Some((k.as_str()?.to_owned(), Schema::from_example(v))))
+                    .collect(),
+                required: Vec::new(),
+                additional: Some(Box::new(Schema::Any)),
+            }),
+            _ => Schema::Any,
+        }
+    }
+
+    /// A skeleton of the documents matching this schema, for a tool to write as the
+    /// starting point of a new file: mappings hold every property, sequences of
+    /// mappings hold one item, and the other values are nulls. Comments after the
+    /// values tell what they expect and which keys are required.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::schema::Schema;
+    /// use yaml_rust_formatter::{YamlEmitter, YamlLoader};
+    ///
+    /// let source = "
+    /// type: object
+    /// required: [name]
+    /// properties:
+    ///   name: {type: string}
+    ///   ports: {type: array, items: {type: integer}}
+    /// ";
+    /// let schema = Schema::from_yaml(&YamlLoader::load_from_str(source).unwrap()[0]).unwrap();
+    /// let mut output = String::new();
+    /// YamlEmitter::new(&mut output).dump(&schema.scaffold()).unwrap();
+    /// assert_eq!(output, "---\nname: ~ # string, required\nports: [] # array of integer");
+    /// ```
+    pub fn scaffold(&self) -> YamlOutput {
+        self.scaffold_value(false)
+    }
+
+    fn scaffold_value(&self, required: bool) -> YamlOutput {
+        let (node, description) = match *self {
+            Schema::Hash(ref schema) => {
+                let mut h = HashOutput::new();
+                for (k, v) in &schema.properties {
+                    let required = schema.required.contains(k);
+                    h.insert(YamlOutput::String(k.clone()), v.scaffold_value(required));
+                }
+                (YamlOutput::Hash(h), None)
+            }
+            Schema::Array(ref items) => match **items {
+                Schema::Hash(_) => (YamlOutput::Array(vec![items.scaffold_value(false)]), None),
+                Schema::Any => (YamlOutput::Array(Vec::new()), Some("array".to_owned())),
+                ref items => (
+                    YamlOutput::Array(Vec::new()),
+                    Some(format!("array of {}", items.describe())),
+                ),
+            },
+            ref schema => (YamlOutput::Null, Some(schema.describe().to_owned())),
+        };
+        let trailing = match (description, required) {
+            (Some(description), true) => Some(format!(" {}, required", description)),
+            (Some(description), false) => Some(format!(" {}", description)),
+            (None, true) => Some(" required".to_owned()),
+            (None, false) => None,
+        };
+        match trailing {
+            Some(trailing) => YamlOutput::Commented(
+                Box::new(node),
+                Comments {
+                    trailing: Some(trailing),
+                    ..Comments::default()
+                },
+            ),
+            None => node,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match *self {
+            Schema::Any => "any value",
+            Schema::Null => "null",
+            Schema::Boolean => "boolean",
+            Schema::Integer => "integer",
+            Schema::Number => "number",
+            Schema::String => "string",
+            Schema::Array(_) => "array",
+            Schema::Hash(_) => "object",
+        }
+    }
+
+    fn mismatch(&self, found: &str, path: &str, mark: Option<Marker>) -> SchemaError {
+        SchemaError::new(
+            path,
+            mark,
+            &format!("expected {}, found {}", self.describe(), found),
+        )
+    }
+
+    /// Check a scalar against this schema.
+    fn check_scalar(
+        &self,
+        node: &YamlInput,
+        path: &str,
+        mark: Option<Marker>,
+    ) -> Result<(), SchemaError> {
+        let ok = match (self, node) {
+            (_, YamlInput::BadValue) => false,
+            (Schema::Any, _) => true,
+            (Schema::Null, YamlInput::Null) => true,
+            (Schema::Boolean, YamlInput::Boolean(_)) => true,
+            (Schema::Integer, YamlInput::Integer(_)) => true,
+            (Schema::Number, YamlInput::Integer(_)) | (Schema::Number, YamlInput::Real(_)) => true,
+            (Schema::String, YamlInput::String(_)) | (Schema::String, YamlInput::Binary(_)) => true,
+            _ => false,
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(self.mismatch(kind(node), path, mark))
+        }
+    }
+
+    /// Check a loaded document against this schema, returning every violation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::schema::Schema;
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let schema = Schema::Array(Box::new(Schema::Integer));
+    /// let docs = YamlLoader::load_from_str("[1, two, 3]").unwrap();
+    /// let errors = schema.validate(&docs[0]);
+    /// assert_eq!(errors.len(), 1);
+    /// assert_eq!(errors[0].to_string(), "[1]: expected integer, found string");
+    /// ```
+    pub fn validate(&self, doc: &YamlInput) -> Vec<SchemaError> {
+        self.validate_with_options(doc, &ValidateOptions::default())
+    }
+
+    pub fn validate_with_options(
+        &self,
+        doc: &YamlInput,
+        options: &ValidateOptions,
+    ) -> Vec<SchemaError> {
+        let mut errors = Vec::new();
+        self.validate_node(doc, "", options, &mut errors);
+        errors
+    }
+
+    /// Check a document loaded by `YamlLoader::load_from_str_with_marks`, pointing
+    /// each violation at the position of the offending node. The violations are
+    /// listed in the order of their positions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::schema::{Schema, ValidateOptions};
+    /// use yaml_rust_formatter::{LoadOptions, YamlLoader};
+    ///
+    /// let schema = Schema::Array(Box::new(Schema::Integer));
+    /// let docs = YamlLoader::load_from_str_with_marks("- 1\n- two\n", &LoadOptions::new())
+    ///     .unwrap();
+    /// let (ref doc, ref marks) = docs[0];
+    /// let errors = schema.validate_with_marks(doc, marks, &ValidateOptions::new());
+    /// assert_eq!(
+    ///     errors[0].to_string(),
+    ///     "[1]: expected integer, found string at line 2 column 3"
+    /// );
+    /// ```
+    pub fn validate_with_marks(
+        &self,
+        doc: &YamlInput,
+        marks: &Marks,
+        options: &ValidateOptions,
+    ) -> Vec<SchemaError> {
+        let mut errors = self.validate_with_options(doc, options);
+        for e in &mut errors {
+            if e.mark.is_none() {
+                let path = Path::parse(&e.path);
+                e.mark = if e.at_key {
+                    marks.key(&path)
+                } else {
+                    marks.get(&path)
+                }
+                .copied();
+            }
+        }
+        errors.sort_by_key(|e| e.mark.map_or(usize::MAX, |mark| mark.index()));
+        errors
+    }
+
+    fn validate_node(
+        &self,
+        node: &YamlInput,
+        path: &str,
+        options: &ValidateOptions,
+        errors: &mut Vec<SchemaError>,
+    ) {
+        match *node.inner() {
+            YamlInput::Raw(ref v) => {
+                self.validate_node(&YamlInput::from_str(v), path, options, errors)
+            }
+            YamlInput::Aliased(ref name, Some(ref node)) => {
+                let first = errors.len();
+                self.validate_node(node, path, options, errors);
+                if options.report_anchors {
+                    in_anchor(&mut errors[first..], name);
+                }
+            }
+            YamlInput::Aliased(ref name, None) => errors.push(SchemaError::new(
+                path,
+                None,
+                &format!("unknown anchor {}", name),
+            )),
+            YamlInput::Array(ref items) => match *self {
+                Schema::Any => {}
+                Schema::Array(ref item) => {
+                    for (i, v) in items.iter().enumerate() {
+                        item.validate_node(v, &format!("{}[{}]", path, i), options, errors);
+                    }
+                }
+                _ => errors.push(self.mismatch("array", path, None)),
+            },
+            YamlInput::Hash(ref hash) => match *self {
+                Schema::Any => {}
+                Schema::Hash(ref schema) => {
+                    let mut seen = Vec::new();
+                    let mut merges = Vec::new();
+                    for (k, v) in Entries::new(hash) {
+                        if k.as_str() == Some(MERGE_KEY) {
+                            merges.push(v);
+                        } else {
+                            schema.validate_entry(k, v, path, options, &mut seen, errors);
+                        }
+                    }
+                    for v in merges {
+                        schema.validate_merge(v, path, options, &mut seen, errors);
+                    }
+                    schema.validate_required(&seen, path, None, errors);
+                }
+                _ => errors.push(self.mismatch("object", path, None)),
+            },
+            _ => {
+                if let Err(e) = self.check_scalar(node, path, None) {
+                    errors.push(e);
+                }
+            }
+        }
+    }
+}
+
+/// The key that merges the entries of other mappings into its own, see
+/// <https://yaml.org/type/merge.html>.
+const MERGE_KEY: &str = "<<";
+
+/// Mark `errors` as found inside the node anchored by `name`.
+fn in_anchor(errors: &mut [SchemaError], name: &str) {
+    for e in errors {
+        if e.anchor.is_none() {
+            e.anchor = Some(Box::new((name.to_owned(), None)));
+        }
+    }
+}
+
+/// The mappings merged by the value of a `<<` key, each with the alias it was reached through.
+fn merge_sources(value: &YamlInput) -> Option<Vec<(Option<&str>, &HashInput)>> {
+    let alias = match *value {
+        YamlInput::Aliased(ref name, _) => Some(name.as_str()),
+        _ => None,
+    };
+    match value.as_hash() {
+        Some(hash) => Some(vec![(alias, hash)]),
+        None => value
+            .as_vec()?
+            .iter()
+            .map(|v| match *v {
+                YamlInput::Aliased(ref name, _) => Some((Some(name.as_str()), v.as_hash()?)),
+                _ => Some((None, v.as_hash()?)),
+            })
+            .collect(),
+    }
+}
+
+impl HashSchema {
+    fn value_schema(&self, key: &str) -> Option<&Schema> {
+        self.properties.get(key).or(self.additional.as_deref())
+    }
+
+    fn validate_entry(
+        &self,
+        k: &YamlInput,
+        v: &YamlInput,
+        path: &str,
+        options: &ValidateOptions,
+        seen: &mut Vec<String>,
+        errors: &mut Vec<SchemaError>,
+    ) {
+        let k = match k.as_str() {
+            Some(k) => k,
+            None => {
+                errors.push(SchemaError::new(path, None, "mapping keys must be strings"));
+                return;
+            }
+        };
+        let key_path = key_path(path, k);
+        match self.value_schema(k) {
+            Some(value) => value.validate_node(v, &key_path, options, errors),
+            None => errors.push(SchemaError {
+                at_key: true,
+                ..SchemaError::new(&key_path, None, "unexpected key")
+            }),
+        }
+        seen.push(k.to_owned());
+    }
+
+    /// Check the entries merged by a `<<` key that are not overridden by `seen` keys.
+    fn validate_merge(
+        &self,
+        value: &YamlInput,
+        path: &str,
+        options: &ValidateOptions,
+        seen: &mut Vec<String>,
+        errors: &mut Vec<SchemaError>,
+    ) {
+        let sources = match merge_sources(value) {
+            Some(sources) => sources,
+            None => {
+                errors.push(SchemaError::new(
+                    &key_path(path, MERGE_KEY),
+                    None,
+                    "expected a mapping or a sequence of mappings to merge",
+                ));
+                return;
+            }
+        };
+        // earlier sources take precedence over later ones
+        for (alias, hash) in sources {
+            let first = errors.len();
+            for (k, v) in Entries::new(hash) {
+                if !k.as_str().is_some_and(|k| seen.iter().any(|s| s == k)) {
+                    self.validate_entry(k, v, path, options, seen, errors);
+                }
+            }
+            if let (true, Some(name)) = (options.report_anchors, alias) {
+                in_anchor(&mut errors[first..], name);
+            }
+        }
+    }
+
+    fn validate_required(
+        &self,
+        seen: &[String],
+        path: &str,
+        mark: Option<Marker>,
+        errors: &mut Vec<SchemaError>,
+    ) {
+        for k in &self.required {
+            if !seen.contains(k) {
+                errors.push(SchemaError::new(
+                    path,
+                    mark,
+                    &format!("missing required key {}", k),
+                ));
+            }
+        }
+    }
+}
+
+/// Validate every document of `source` against `schema` straight from the event stream,
+/// without building the documents, and stop at the first violation.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::schema::{self, HashSchema, Schema};
+///
+/// let mut pod = HashSchema::default();
+/// pod.properties.insert("name".to_owned(), Schema::String);
+/// pod.properties.insert("replicas".to_owned(), Schema::Integer);
+/// pod.required.push("name".to_owned());
+/// let schema = Schema::Hash(pod);
+///
+/// assert!(schema::validate_str("name: web\nreplicas: 3", &schema).is_ok());
+/// let err = schema::validate_str("name: web\nreplicas: many", &schema).unwrap_err();
+/// assert_eq!(err.to_string(), "replicas: expected integer, found string at line 2 column 11");
+/// ```
+pub fn validate_str(source: &str, schema: &Schema) -> Result<(), SchemaError> {
+    validate_str_with_options(source, schema, &ValidateOptions::default())
+}
+
+pub fn validate_str_with_options(
+    source: &str,
+    schema: &Schema,
+    options: &ValidateOptions,
+) -> Result<(), SchemaError> {
+    let mut validator = StreamValidator::new(schema, options);
+    let mut parser = Parser::new(source.chars());
+    loop {
+        let (ev, mark) = parser.next()?;
+        if ev == Event::StreamEnd {
+            return Ok(());
+        }
+        validator.on_event(ev, mark)?;
+    }
+}
+
+/// The violations found in one document of a stream checked by `validate_stream`.
+#[derive(Clone, Debug)]
+pub struct DocumentReport {
+    index: usize,
+    routed: bool,
+    errors: Vec<SchemaError>,
+}
+
+impl DocumentReport {
+    /// The position of the document in the stream, from 0.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Whether the router selected a schema for the document. Documents without one are
+    /// not checked.
+    pub fn is_routed(&self) -> bool {
+        self.routed
+    }
+
+    pub fn errors(&self) -> &[SchemaError] {
+        &self.errors
+    }
+
+    /// Whether the document was checked and matches its schema.
+    pub fn is_valid(&self) -> bool {
+        self.routed && self.errors.is_empty()
+    }
+}
+
+/// Selects the schema of a document from the values of some of its top-level keys, as
+/// `apiVersion` and `kind` tell what a Kubernetes manifest is.
+///
+/// Each key must hold a string for the document to be routed; a document matching no
+/// route gets the fallback schema, if any.
+#[derive(Clone, Debug, Default)]
+pub struct KeyRouter {
+    keys: Vec<String>,
+    routes: Vec<(Vec<String>, Schema)>,
+    fallback: Option<Schema>,
+}
+
+impl KeyRouter {
+    /// A router looking at the values of `keys`, in that order.
+    pub fn new(keys: &[&str]) -> KeyRouter {
+        KeyRouter {
+            keys: keys.iter().map(|&k| k.to_owned()).collect(),
+            ..KeyRouter::default()
+        }
+    }
+
+    /// Check the documents whose keys hold `values` against `schema`. The first route
+    /// added wins when several match.
+    pub fn route_to(&mut self, values: &[&str], schema: Schema) {
+        let values = values.iter().map(|&v| v.to_owned()).collect();
+        self.routes.push((values, schema));
+    }
+
+    /// The schema of the documents matching no route.
+    pub fn fallback(&mut self, schema: Schema) {
+        self.fallback = Some(schema);
+    }
+
+    pub fn get_fallback(&self) -> Option<&Schema> {
+        self.fallback.as_ref()
+    }
+
+    /// The schema `doc` is to be checked against.
+    pub fn route(&self, doc: &YamlInput) -> Option<&Schema> {
+        let values: Option<Vec<&str>> =
+            self.keys.iter().map(|k| doc[k.as_str()].as_str()).collect();
+        values
+            .and_then(|values| {
+                self.routes
+                    .iter()
+                    .find(|(route, _)| route.iter().map(String::as_str).eq(values.iter().copied()))
+                    .map(|(_, schema)| schema)
+            })
+            .or(self.fallback.as_ref())
+    }
+}
+
+/// Check every document of a stream against the schema `router` selects for it, giving
+/// one report per document, in stream order.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::schema::{self, KeyRouter, Schema};
+/// use yaml_rust_formatter::YamlLoader;
+///
+/// let deployment = Schema::from_yaml(
+///     &YamlLoader::load_from_str("{type: object, required: [spec]}").unwrap()[0],
+/// )
+/// .unwrap();
+/// let mut router = KeyRouter::new(&["apiVersion", "kind"]);
+/// router.route_to(&["apps/v1", "Deployment"], deployment);
+///
+/// let docs = YamlLoader::load_from_str(
+///     "apiVersion: apps/v1\nkind: Deployment\nspec: {}\n---\n\
+///      apiVersion: apps/v1\nkind: Deployment\n---\n\
+///      apiVersion: v1\nkind: ConfigMap\n",
+/// )
+/// .unwrap();
+/// let reports = schema::validate_stream(&docs, |doc| router.route(doc));
+/// assert!(reports[0].is_valid());
+/// assert_eq!(reports[1].errors()[0].to_string(), "missing required key spec");
+/// assert!(!reports[2].is_routed());
+/// ```
+pub fn validate_stream<'s, F>(docs: &[YamlInput], router: F) -> Vec<DocumentReport>
+where
+    F: FnMut(&YamlInput) -> Option<&'s Schema>,
+{
+    validate_stream_with_options(docs, router, &ValidateOptions::default())
+}
+
+pub fn validate_stream_with_options<'s, F>(
+    docs: &[YamlInput],
+    mut router: F,
+    options: &ValidateOptions,
+) -> Vec<DocumentReport>
+where
+    F: FnMut(&YamlInput) -> Option<&'s Schema>,
+{
+    docs.iter()
+        .enumerate()
+        .map(|(index, doc)| match router(doc) {
+            Some(schema) => DocumentReport {
+                index,
+                routed: true,
+                errors: schema.validate_with_options(doc, options),
+            },
+            None => DocumentReport {
+                index,
+                routed: false,
+                errors: Vec::new(),
+            },
+        })
+        .collect()
+}
+
+/// What the next node of a mapping is.
+enum Next<'a> {
+    Key,
+    /// The value of a key, with its schema and path.
+    Value(&'a Schema, String),
+    /// The value of a `<<` key.
+    Merge,
+}
+
+enum Frame<'a> {
+    Array {
+        item: &'a Schema,
+        path: String,
+        index: usize,
+    },
+    Hash {
+        schema: &'a HashSchema,
+        path: String,
+        mark: Marker,
+        seen: Vec<String>,
+        next: Next<'a>,
+        /// Values of `<<` keys, checked once every explicit key is known.
+        merges: Vec<(YamlInput, Marker)>,
+    },
+    /// A collection whose content is not checked.
+    Any,
+    /// The events of the value of a `<<` key, which is built as a tree.
+    Merge {
+        mark: Marker,
+        depth: usize,
+        events: Vec<(Event, Marker)>,
+    },
+}
+
+/// Where the next node of the stream goes.
+enum Slot<'a> {
+    Key,
+    Node(&'a Schema, String),
+}
+
+/// An anchored node whose events are still being recorded.
+struct Recording {
+    name: String,
+    depth: usize,
+    events: Vec<(Event, Marker)>,
+}
+
+struct StreamValidator<'a> {
+    schema: &'a Schema,
+    options: &'a ValidateOptions,
+    stack: Vec<Frame<'a>>,
+    /// The events of every anchored node, with aliases expanded, and where it was defined.
+    anchors: HashMap<String, (Vec<(Event, Marker)>, Marker)>,
+    recordings: Vec<Recording>,
+    replaying: bool,
+}
+
+impl<'a> StreamValidator<'a> {
+    fn new(schema: &'a Schema, options: &'a ValidateOptions) -> StreamValidator<'a> {
+        StreamValidator {
+            schema,
+            options,
+            stack: Vec::new(),
+            anchors: HashMap::new(),
+            recordings: Vec::new(),
+            replaying: false,
+        }
+    }
+
+    fn slot(&mut self) -> Slot<'a> {
+        match self.stack.last_mut() {
+            None => Slot::Node(self.schema, String::new()),
+            Some(Frame::Array { item, path, index }) => {
+                *index += 1;
+                Slot::Node(item, format!("{}[{}]", path, *index - 1))
+            }
+            Some(Frame::Hash { next, .. }) => match mem::replace(next, Next::Key) {
+                Next::Value(schema, path) => Slot::Node(schema, path),
+                _ => Slot::Key,
+            },
+            Some(Frame::Any) | Some(Frame::Merge { .. }) => Slot::Node(&ANY, String::new()),
+        }
+    }
+
+    /// Whether the next node belongs to the value of a `<<` key.
+    fn merging(&self) -> bool {
+        match self.stack.last() {
+            Some(Frame::Merge { .. }) => true,
+            Some(Frame::Hash { next, .. }) => matches!(next, Next::Merge),
+            _ => false,
+        }
+    }
+
+    fn record(&mut self, ev: &Event, mark: Marker) {
+        let delta: isize = match *ev {
+            Event::SequenceStart(..) | Event::MappingStart(..) => 1,
+            Event::SequenceEnd | Event::MappingEnd => -1,
+            _ => 0,
+        };
+        for recording in &mut self.recordings {
+            recording.events.push((ev.clone(), mark));
+            recording.depth = (recording.depth as isize + delta) as usize;
+        }
+        while self.recordings.last().is_some_and(|r| r.depth == 0) {
+            let recording = self.recordings.pop().unwrap();
+            let mark = recording.events[0].1;
+            self.anchors
+                .insert(recording.name, (recording.events, mark));
+        }
+    }
+
+    fn start_recording(&mut self, ev: &Event) {
+        if self.replaying {
+            return;
+        }
+        if let Event::Scalar(_, _, Some(ref name), _)
+        | Event::SequenceStart(Some(ref name), _)
+        | Event::MappingStart(Some(ref name), _) = *ev
+        {
+            self.recordings.push(Recording {
+                name: name.clone(),
+                depth: 0,
+                events: Vec::new(),
+            });
+        }
+    }
+
+    /// Point an error found inside an aliased node at the alias site.
+    fn at_alias(&self, mut e: SchemaError, name: &str, mark: Marker) -> SchemaError {
+        e.mark = Some(mark);
+        if self.options.report_anchors {
+            e.anchor = Some(Box::new((name.to_owned(), Some(self.anchors[name].1))));
+        }
+        e
+    }
+
+    fn on_event(&mut self, ev: Event, mark: Marker) -> Result<(), SchemaError> {
+        if ev == Event::DocumentStart {
+            self.anchors.clear();
+            return Ok(());
+        }
+        if let Event::Alias(ref name) = ev {
+            let events = match self.anchors.get(name) {
+                Some((events, _)) => events.clone(),
+                None => {
+                    let path = match self.slot() {
+                        Slot::Node(_, path) => path,
+                        Slot::Key => self.hash_path(),
+                    };
+                    return Err(SchemaError::new(
+                        &path,
+                        Some(mark),
+                        &format!("unknown anchor {}", name),
+                    ));
+                }
+            };
+            if !self.merging() {
+                let replaying = mem::replace(&mut self.replaying, true);
+                let result = events
+                    .into_iter()
+                    .try_for_each(|(ev, mark)| self.on_event(ev, mark));
+                self.replaying = replaying;
+                return match result {
+                    Err(e) if !replaying => Err(self.at_alias(e, name, mark)),
+                    result => result,
+                };
+            }
+            // merged nodes keep their aliases, which still have to be recorded expanded
+            for (ev, mark) in events {
+                self.record(&ev, mark);
+            }
+        } else {
+            self.start_recording(&ev);
+            self.record(&ev, mark);
+        }
+
+        if self.merging() {
+            return self.on_merge_event(ev, mark);
+        }
+        match ev {
+            Event::Scalar(v, style, _, tag) => match self.slot() {
+                Slot::Node(schema, path) => {
+                    if *schema != Schema::Any {
+                        schema.check_scalar(&resolve_scalar(v, style, &tag), &path, Some(mark))?;
+                    }
+                }
+                Slot::Key => self.on_key(v, style, mark)?,
+            },
+            Event::SequenceStart(..) => match self.slot() {
+                Slot::Node(Schema::Any, _) => self.stack.push(Frame::Any),
+                Slot::Node(Schema::Array(item), path) => self.stack.push(Frame::Array {
+                    item,
+                    path,
+                    index: 0,
+                }),
+                Slot::Node(schema, path) => {
+                    return Err(schema.mismatch("array", &path, Some(mark)))
+                }
+                Slot::Key => return Err(self.complex_key(mark)),
+            },
+            Event::MappingStart(..) => match self.slot() {
+                Slot::Node(Schema::Any, _) => self.stack.push(Frame::Any),
+                Slot::Node(Schema::Hash(schema), path) => self.stack.push(Frame::Hash {
+                    schema,
+                    path,
+                    mark,
+                    seen: Vec::new(),
+                    next: Next::Key,
+                    merges: Vec::new(),
+                }),
+                Slot::Node(schema, path) => {
+                    return Err(schema.mismatch("object", &path, Some(mark)))
+                }
+                Slot::Key => return Err(self.complex_key(mark)),
+            },
+            Event::SequenceEnd | Event::MappingEnd => {
+                if let Some(Frame::Hash {
+                    schema,
+                    path,
+                    mark,
+                    mut seen,
+                    merges,
+                    ..
+                }) = self.stack.pop()
+                {
+                    let mut errors = Vec::new();
+                    for (value, merge_mark) in merges {
+                        schema.validate_merge(&value, &path, self.options, &mut seen, &mut errors);
+                        if let Some(e) = errors.drain(..).next() {
+                            return Err(self.at_merge(e, merge_mark));
+                        }
+                    }
+                    schema.validate_required(&seen, &path, Some(mark), &mut errors);
+                    if let Some(e) = errors.into_iter().next() {
+                        return Err(e);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Collect the events of the value of a `<<` key.
+    fn on_merge_event(&mut self, ev: Event, mark: Marker) -> Result<(), SchemaError> {
+        let done = match self.stack.last_mut() {
+            Some(Frame::Merge { depth, events, .. }) => {
+                match ev {
+                    Event::SequenceStart(..) | Event::MappingStart(..) => *depth += 1,
+                    Event::SequenceEnd | Event::MappingEnd => *depth -= 1,
+                    _ => {}
+                }
+                events.push((ev, mark));
+                *depth == 0
+            }
+            _ => {
+                let path = match self.slot() {
+                    Slot::Key => key_path(&self.hash_path(), MERGE_KEY),
+                    Slot::Node(_, path) => path,
+                };
+                match ev {
+                    Event::Alias(_) => {
+                        self.stack.push(Frame::Merge {
+                            mark,
+                            depth: 0,
+                            events: vec![(ev, mark)],
+                        });
+                        true
+                    }
+                    Event::SequenceStart(..) | Event::MappingStart(..) => {
+                        self.stack.push(Frame::Merge {
+                            mark,
+                            depth: 1,
+                            events: vec![(ev, mark)],
+                        });
+                        false
+                    }
+                    _ => {
+                        return Err(SchemaError::new(
+                            &path,
+                            Some(mark),
+                            "expected a mapping or a sequence of mappings to merge",
+                        ))
+                    }
+                }
+            }
+        };
+        if done {
+            if let Some(Frame::Merge { mark, events, .. }) = self.stack.pop() {
+                let value = self.build(events);
+                if let Some(Frame::Hash { merges, .. }) = self.stack.last_mut() {
+                    merges.push((value, mark));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the tree of a merged value from its events.
+    fn build(&self, events: Vec<(Event, Marker)>) -> YamlInput {
+        let anchors = events
+            .iter()
+            .filter_map(|(ev, _)| match ev {
+                Event::Alias(ref name) => Some(name),
+                _ => None,
+            })
+            .map(|name| {
+                let events = self.anchors[name].0.clone();
+                (
+                    name.clone(),
+                    YamlLoader::load_from_events(events, LinkedHashMap::new()),
+                )
+            })
+            .collect();
+        YamlLoader::load_from_events(events, anchors)
+    }
+
+    /// Point an error found in a merged mapping at the value of its `<<` key.
+    fn at_merge(&self, mut e: SchemaError, mark: Marker) -> SchemaError {
+        e.mark = Some(mark);
+        if let Some((ref name, ref mut anchor_mark)) = e.anchor.as_deref_mut() {
+            *anchor_mark = self.anchors.get(name).map(|anchor| anchor.1);
+        }
+        e
+    }
+
+    fn hash_path(&self) -> String {
+        match self.stack.last() {
+            Some(Frame::Hash { path, .. }) => path.clone(),
+            _ => String::new(),
+        }
+    }
+
+    fn complex_key(&self, mark: Marker) -> SchemaError {
+        SchemaError::new(
+            &self.hash_path(),
+            Some(mark),
+            "mapping keys must be strings",
+        )
+    }
+
+    fn on_key(&mut self, v: String, style: TScalarStyle, mark: Marker) -> Result<(), SchemaError> {
+        if let Some(Frame::Hash {
+            schema,
+            path,
+            mark: start,
+            seen,
+            next,
+            ..
+        }) = self.stack.last_mut()
+        {
+            // block mappings are only recognized at the `:` of their first key
+            if seen.is_empty() && mark.index() < start.index() {
+                *start = mark;
+            }
+            if style == TScalarStyle::Plain && v == MERGE_KEY {
+                *next = Next::Merge;
+                return Ok(());
+            }
+            let key_path = key_path(path, &v);
+            // plain keys such as `1` or `true` are not strings
+            if style == TScalarStyle::Plain && YamlInput::from_str(&v).as_str().is_none() {
+                return Err(SchemaError::new(
+                    &key_path,
+                    Some(mark),
+                    "mapping keys must be strings",
+                ));
+            }
+            match schema.value_schema(&v) {
+                Some(schema) => *next = Next::Value(schema, key_path),
+                None => return Err(SchemaError::new(&key_path, Some(mark), "unexpected key")),
+            }
+            seen.push(v);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::yaml::{LoadOptions, YamlLoader};
+
+    fn pod_schema() -> Schema {
+        let schema = YamlLoader::load_from_str(
+            "
+type: object
+required: [spec]
+properties:
+  spec:
+    type: object
+    required: [containers]
+    additionalProperties: false
+    properties:
+      containers:
+        type: array
+        items:
+          type: object
+          required: [image]
+          properties:
+            image: {type: string}
+            port: {type: integer}
+            debug: {type: boolean}
+",
+        )
+        .unwrap();
+        Schema::from_yaml(&schema[0]).unwrap()
+    }
+
+    #[test]
+    fn test_validate_str() {
+        let schema = pod_schema();
+        let ok = "
+kind: Pod
+spec:
+  containers:
+  - image: nginx
+    port: 80
+  - {image: redis, debug: true}
+---
+spec: {containers: []}
+";
+        assert_eq!(validate_str(ok, &schema), Ok(()));
+
+        let cases = [
+            (
+                "spec:\n  containers:\n  - image: nginx\n    port: http",
+                "spec.containers[0].port: expected integer, found string at line 4 column 11",
+            ),
+            (
+                "spec:\n  containers:\n  - port: 80",
+                "spec.containers[0]: missing required key image at line 3 column 5",
+            ),
+            (
+                "spec:\n  containers: []\n  volumes: []",
+                "spec.volumes: unexpected key at line 3 column 3",
+            ),
+            (
+                "spec:\n  containers: {image: nginx}",
+                "spec.containers: expected array, found object at line 2 column 15",
+            ),
+            (
+                "spec: {containers: []}\n---\nkind: Pod",
+                "missing required key spec at line 3 column 1",
+            ),
+        ];
+        for &(source, expected) in &cases {
+            let err = validate_str(source, &schema).unwrap_err();
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_validate_str_fails_fast() {
+        let schema = pod_schema();
+        // the syntax error after the violation is never reached
+        let err = validate_str("spec:\n  containers: 1\n  [", &schema).unwrap_err();
+        assert_eq!(err.path(), "spec.containers");
+        let err = validate_str("spec:\n  containers: []\n  [", &schema).unwrap_err();
+        assert_eq!(err.path(), "");
+        assert_eq!(err.marker().unwrap().line(), 3);
+    }
+
+    #[test]
+    fn test_validate_str_alias() {
+        let schema = pod_schema();
+        let s = "
+base: &base {image: nginx, port: 80}
+spec:
+  containers: [*base, *base]
+";
+        assert_eq!(validate_str(s, &schema), Ok(()));
+        let s = "
+base: &base {port: 80}
+spec:
+  containers: [*base]
+";
+        let err = validate_str(s, &schema).unwrap_err();
+        assert_eq!(err.path(), "spec.containers[0]");
+        let err = validate_str("spec: *missing", &schema).unwrap_err();
+        assert_eq!(err.info(), "unknown anchor missing");
+    }
+
+    #[test]
+    fn test_validate_tree_matches_stream() {
+        let schema = pod_schema();
+        let sources = [
+            "spec:\n  containers:\n  - image: nginx\n    port: http",
+            "spec:\n  containers:\n  - port: 80",
+            "spec:\n  containers: []\n  volumes: []",
+            "spec:\n  containers: {image: nginx}",
+            "spec:\n  containers:\n  - image: 1.5\n    debug: yes",
+        ];
+        for source in &sources {
+            let (ref doc, ref marks) =
+                YamlLoader::load_from_str_with_marks(source, &LoadOptions::new()).unwrap()[0];
+            let errors = schema.validate_with_marks(doc, marks, &ValidateOptions::new());
+            let streamed = validate_str(source, &schema).unwrap_err();
+            assert_eq!(errors[0].path(), streamed.path());
+            assert_eq!(errors[0].info(), streamed.info());
+            assert_eq!(errors[0].marker(), streamed.marker(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_from_yaml_errors() {
+        let docs =
+            YamlLoader::load_from_str("properties: {a: {type: date}}\ntype: object").unwrap();
+        let err = Schema::from_yaml(&docs[0]).unwrap_err();
+        assert_eq!(err.to_string(), "properties.a.type: unknown type");
+    }
+
+    #[test]
+    fn test_validate_merge_keys() {
+        let schema = pod_schema();
+        let ok = "
+base: &base {image: nginx, port: http}
+extra: &extra {debug: true}
+spec:
+  containers:
+  - <<: *base
+    port: 80
+  - <<: [*extra, *base]
+    port: 8080
+  - <<: {image: redis}
+";
+        assert_eq!(validate_str(ok, &schema), Ok(()));
+        let doc = &YamlLoader::load_from_str(ok).unwrap()[0];
+        assert_eq!(schema.validate(doc), vec![]);
+
+        let s = "
+base: &base {image: nginx, port: http}
+spec:
+  containers:
+  - <<: *base
+";
+        let err = validate_str(s, &schema).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "spec.containers[0].port: expected integer, found string at line 5 column 9"
+        );
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        assert_eq!(schema.validate(doc)[0].path(), "spec.containers[0].port");
+
+        let err = validate_str("spec:\n  containers:\n  - <<: 1", &schema).unwrap_err();
+        assert_eq!(err.path(), "spec.containers[0].<<");
+    }
+
+    #[test]
+    fn test_validate_report_anchors() {
+        let schema = pod_schema();
+        let mut options = ValidateOptions::new();
+        options.report_anchors(true);
+        let s = "
+base: &base {image: nginx, port: http}
+spec:
+  containers:
+  - *base
+  - <<: *base
+";
+        let err = validate_str(s, &schema).unwrap_err();
+        assert_eq!(err.anchor(), None);
+        let err = validate_str_with_options(s, &schema, &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "spec.containers[0].port: expected integer, found string at line 5 column 5 \
+             (through anchor base defined at line 2 column 13)"
+        );
+
+        let s = "
+base: &base {image: nginx, port: http}
+spec:
+  containers:
+  - <<: *base
+";
+        let err = validate_str_with_options(s, &schema, &options).unwrap_err();
+        assert_eq!(err.anchor(), Some("base"));
+        assert_eq!(err.anchor_marker().unwrap().line(), 2);
+
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        let errors = schema.validate_with_options(doc, &options);
+        assert_eq!(errors[0].anchor(), Some("base"));
+        assert_eq!(errors[0].anchor_marker(), None);
+    }
+
+    #[test]
+    fn test_validate_with_marks_in_source_order() {
+        let schema = pod_schema();
+        let s = "spec:\n  containers:\n  - port: x\n";
+        let (ref doc, ref marks) =
+            YamlLoader::load_from_str_with_marks(s, &LoadOptions::new()).unwrap()[0];
+        let errors: Vec<_> = schema
+            .validate_with_marks(doc, marks, &ValidateOptions::new())
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "spec.containers[0]: missing required key image at line 3 column 5",
+                "spec.containers[0].port: expected integer, found string at line 3 column 11",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scaffold() {
+        let mut output = String::new();
+        crate::emitter::YamlEmitter::new(&mut output)
+            .dump(&pod_schema().scaffold())
+            .unwrap();
+        assert_eq!(
+            output,
+            "---
+spec: # required
+  containers: # required
+    - image: ~ # string, required
+      port: ~ # integer
+      debug: ~ # boolean"
+        );
+
+        // a document shaped like the example validates against its schema
+        let example = &YamlLoader::load_from_str("a: [{b: 1.5}]\nc: x\nd: ~").unwrap()[0];
+        let schema = Schema::from_example(example);
+        assert!(schema.validate(example).is_empty());
+        let doc = &YamlLoader::load_from_str("a: [{b: 2}, {}]\nc: y\nd: [1]").unwrap()[0];
+        assert!(schema.validate(doc).is_empty());
+        let doc = &YamlLoader::load_from_str("a: [{b: z}]").unwrap()[0];
+        assert_eq!(schema.validate(doc).len(), 1);
+    }
+
+    #[test]
+    fn test_validate_stream() {
+        let mut router = KeyRouter::new(&["apiVersion", "kind"]);
+        router.route_to(&["v1", "Pod"], pod_schema());
+        router.route_to(&["v1", "Pod"], Schema::Null);
+        let docs = YamlLoader::load_from_str(
+            "
+kind: &k Pod
+apiVersion: v1
+spec: {containers: [{image: web}]}
+---
+apiVersion: v1
+kind: Pod
+spec: {containers: [{port: 80}]}
+---
+apiVersion: v1
+kind: [Pod]
+---
+[v1, Pod]
+",
+        )
+        .unwrap();
+        let reports = validate_stream(&docs, |doc| router.route(doc));
+        assert_eq!(reports.len(), 4);
+        assert!(reports[0].is_valid());
+        assert_eq!(reports[1].index(), 1);
+        assert!(reports[1].is_routed());
+        assert_eq!(
+            reports[1].errors()[0].to_string(),
+            "spec.containers[0]: missing required key image"
+        );
+        assert!(!reports[2].is_routed() && !reports[2].is_valid());
+        assert!(!reports[3].is_routed());
+
+        router.fallback(Schema::Array(Box::new(Schema::String)));
+        let reports = validate_stream(&docs, |doc| router.route(doc));
+        assert_eq!(reports[2].errors().len(), 1);
+        assert!(reports[3].is_valid());
+    }
+}
@@ -0,0 +1,482 @@
+//! Serialize typed values as YAML with serde, see `to_string`.
+
+use crate::emitter::{EmitError, YamlEmitter};
+use crate::yaml::{HashOutput, YamlOutput};
+use serde::ser::{self, Serialize};
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+
+#[derive(Clone, Debug)]
+pub enum Error {
+    Emit(EmitError),
+    /// A value cannot be serialized, as reported by its `Serialize` implementation.
+    Message(String),
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Emit(ref err) => Some(err),
+            Error::Message(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Emit(ref err) => fmt::Display::fmt(err, formatter),
+            Error::Message(ref message) => formatter.write_str(message),
+        }
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl From<EmitError> for Error {
+    fn from(err: EmitError) -> Error {
+        Error::Emit(err)
+    }
+}
+
+/// Serialize `value` as a YAML document written by `YamlEmitter`. Unit variants are
+/// written as their name, and the other variants as a mapping from their name to
+/// their content.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Service {
+///     name: String,
+///     ports: Vec<u16>,
+/// }
+///
+/// let service = Service { name: "web".to_owned(), ports: vec![80, 443] };
+/// assert_eq!(
+///     yaml_rust_formatter::to_string(&service).unwrap(),
+///     "---\nname: web\nports:\n  - 80\n  - 443\n"
+/// );
+/// ```
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    let doc = to_yaml(value)?;
+    let mut out = String::new();
+    YamlEmitter::new(&mut out).dump(&doc)?;
+    out.push('\n');
+    Ok(out)
+}
+
+/// Serialize `value` as a node, to be changed before it is emitted.
+pub fn to_yaml<T: Serialize + ?Sized>(value: &T) -> Result<YamlOutput, Error> {
+    value.serialize(Serializer)
+}
+
+/// Builds the `YamlOutput` of a value.
+pub struct Serializer;
+
+/// A mapping from the name of `variant` to `node`.
+fn variant_node(variant: &'static str, node: YamlOutput) -> YamlOutput {
+    let mut h = HashOutput::new();
+    h.insert(YamlOutput::String(variant.to_owned()), node);
+    YamlOutput::Hash(h)
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = YamlOutput;
+    type Error = Error;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeHash;
+    type SerializeStruct = SerializeHash;
+    type SerializeStructVariant = SerializeHash;
+
+    fn serialize_bool(self, v: bool) -> Result<YamlOutput, Error> {
+        Ok(YamlOutput::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<YamlOutput, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<YamlOutput, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<YamlOutput, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<YamlOutput, Error> {
+        Ok(YamlOutput::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<YamlOutput, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<YamlOutput, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<YamlOutput, Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    /// Integers beyond `i64` are written as they are, and read back as reals.
+    fn serialize_u64(self, v: u64) -> Result<YamlOutput, Error> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => Ok(YamlOutput::Real(v.to_string())),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<YamlOutput, Error> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<YamlOutput, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_char(self, v: char) -> Result<YamlOutput, Error> {
+        Ok(YamlOutput::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<YamlOutput, Error> {
+        Ok(YamlOutput::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<YamlOutput, Error> {
+        Ok(YamlOutput::Binary(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<YamlOutput, Error> {
+        Ok(YamlOutput::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<YamlOutput, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<YamlOutput, Error> {
+        Ok(YamlOutput::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<YamlOutput, Error> {
+        Ok(YamlOutput::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<YamlOutput, Error> {
+        Ok(YamlOutput::String(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<YamlOutput, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<YamlOutput, Error> {
+        Ok(variant_node(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeHash, Error> {
+        Ok(SerializeHash {
+            hash: HashOutput::new(),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeHash, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeHash, Error> {
+        Ok(SerializeHash {
+            hash: HashOutput::new(),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+/// The items of a sequence, a tuple or a tuple variant.
+pub struct SerializeArray {
+    items: Vec<YamlOutput>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeArray {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<YamlOutput, Error> {
+        let node = YamlOutput::Array(self.items);
+        Ok(match self.variant {
+            Some(variant) => variant_node(variant, node),
+            None => node,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = YamlOutput;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<YamlOutput, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = YamlOutput;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<YamlOutput, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = YamlOutput;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<YamlOutput, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = YamlOutput;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<YamlOutput, Error> {
+        self.finish()
+    }
+}
+
+/// The entries of a map, a struct or a struct variant.
+pub struct SerializeHash {
+    hash: HashOutput,
+    key: Option<YamlOutput>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeHash {
+    fn finish(self) -> Result<YamlOutput, Error> {
+        let node = YamlOutput::Hash(self.hash);
+        Ok(match self.variant {
+            Some(variant) => variant_node(variant, node),
+            None => node,
+        })
+    }
+}
+
+impl ser::SerializeMap for SerializeHash {
+    type Ok = YamlOutput;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| <Error as ser::Error>::custom("value serialized before its key"))?;
+        self.hash.insert(key, value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<YamlOutput, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeHash {
+    type Ok = YamlOutput;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.hash.insert(
+            YamlOutput::String(key.to_owned()),
+            value.serialize(Serializer)?,
+        );
+        Ok(())
+    }
+
+    fn end(self) -> Result<YamlOutput, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeHash {
+    type Ok = YamlOutput;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<YamlOutput, Error> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Action {
+        Stop,
+        Wait(u32),
+        Move { x: i32, y: i32 },
+        Swap(String, String),
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Config {
+        name: String,
+        version: f64,
+        ratio: f32,
+        big: u64,
+        retries: Option<u8>,
+        actions: Vec<Action>,
+        env: BTreeMap<String, String>,
+    }
+
+    #[test]
+    fn test_to_string() {
+        let config = Config {
+            name: "true".to_owned(),
+            version: 2.0,
+            ratio: f32::INFINITY,
+            big: u64::MAX,
+            retries: None,
+            actions: vec![
+                Action::Stop,
+                Action::Wait(5),
+                Action::Move { x: 1, y: -2 },
+                Action::Swap("a".to_owned(), "b: c".to_owned()),
+            ],
+            env: vec![("HOME".to_owned(), "/root".to_owned())]
+                .into_iter()
+                .collect(),
+        };
+        let out = to_string(&config).unwrap();
+        assert_eq!(
+            out,
+            "---
+name: \"true\"
+version: 2.0
+ratio: .inf
+big: 18446744073709551615
+retries: ~
+actions:
+  - Stop
+  - Wait: 5
+  - Move:
+      x: 1
+      y: -2
+  - Swap:
+      - a
+      - \"b: c\"
+env:
+  HOME: /root
+"
+        );
+        assert_eq!(crate::from_str::<Config>(&out).unwrap(), config);
+    }
+}
@@ -0,0 +1,411 @@
+//! Reusable nodes with parameters, written in plain YAML with two tags.
+//!
+//! A node tagged `!template` is a template, named by its anchor. Its strings may hold
+//! placeholders like `${name}`, and `$${` stands for a literal `${`. A node written
+//! `!use {template: *anchor, with: {name: value}}` is replaced by a copy of the
+//! template with each placeholder replaced by its parameter: a string that is a
+//! single placeholder becomes the value itself, whatever its type, while a
+//! placeholder within a longer string is replaced by the text of a scalar.
+//!
+//! The templates themselves are left out of the expanded document, so they can be
+//! collected under a key of their own or written where they are first needed.
+
+use crate::diff::{key_segment, Path, Segment};
+use crate::yaml::{resolve, Entries, YamlInput};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// A `!template` or `!use` node `expand_templates` cannot expand.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TemplateError {
+    path: Path,
+    message: String,
+}
+
+impl TemplateError {
+    fn new(path: &Path, message: String) -> TemplateError {
+        TemplateError {
+            path: path.clone(),
+            message,
+        }
+    }
+
+    /// The path of the `!use` node, or of the alias to a template used without it.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Error for TemplateError {}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.segments().is_empty() {
+            write!(formatter, "{}", self.message)
+        } else {
+            write!(formatter, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// A copy of `doc` with its `!use` nodes expanded and its `!template` nodes left out,
+/// see the module documentation. `LoadOptions::expand_templates` does it as
+/// documents are loaded.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::template::expand_templates;
+/// use yaml_rust_formatter::YamlLoader;
+///
+/// let source = "
+/// templates:
+///   service: &service !template
+///     image: registry.local/${name}:${version}
+///     replicas: ${replicas}
+/// web: !use {template: *service, with: {name: web, version: 1.2, replicas: 3}}
+/// ";
+/// let doc = &YamlLoader::load_from_str(source).unwrap()[0];
+/// let doc = expand_templates(doc).unwrap();
+///
+/// assert_eq!(doc["web"]["image"].as_str(), Some("registry.local/web:1.2"));
+/// assert_eq!(doc["web"]["replicas"].as_i64(), Some(3));
+/// assert!(doc["templates"].as_hash().unwrap().is_empty());
+/// ```
+pub fn expand_templates(doc: &YamlInput) -> Result<YamlInput, TemplateError> {
+    Ok(expand(doc, &Path::new())?.unwrap_or(YamlInput::Null))
+}
+
+/// The body of `node` if it is a template.
+fn template_body(node: &YamlInput) -> Option<&YamlInput> {
+    match *node {
+        YamlInput::Anchored(_, ref node) | YamlInput::Commented(ref node, _) => template_body(node),
+        YamlInput::Tagged(ref tag, ref node) if tag == "!template" => Some(node),
+        _ => None,
+    }
+}
+
+/// The expanded copy of `node`, at `path`, or `None` for a template.
+fn expand(node: &YamlInput, path: &Path) -> Result<Option<YamlInput>, TemplateError> {
+    if template_body(node).is_some() {
+        return Ok(None);
+    }
+    Ok(Some(match *node {
+        YamlInput::Tagged(ref tag, ref node) if tag == "!use" => use_template(node, path)?,
+        YamlInput::Array(ref v) => {
+            let mut items = Vec::with_capacity(v.len());
+            for (i, x) in v.iter().enumerate() {
+                items.extend(expand(x, &path.child(Segment::Index(i)))?);
+            }
+            YamlInput::Array(items)
+        }
+        YamlInput::Hash(ref h) => {
+            let mut entries = h.clone();
+            entries.clear();
+            for (k, v) in Entries::new(h) {
+                let child = path.child(key_segment(k));
+                if let Some(v) = expand(v, &child)? {
+                    let k = expand(k, &child)?.unwrap_or_else(|| k.clone());
+                    entries.insert(k, v);
+                }
+            }
+            YamlInput::Hash(entries)
+        }
+        YamlInput::Aliased(ref name, Some(ref target)) => {
+            if template_body(target).is_some() {
+                return Err(TemplateError::new(
+                    path,
+                    format!("the template {} is only used by !use", name),
+                ));
+            }
+            let target = expand(target, path)?.unwrap_or(YamlInput::Null);
+            YamlInput::Aliased(name.clone(), Some(Box::new(target)))
+        }
+        YamlInput::Anchored(ref name, ref node) => {
+            wrap(node, path, |node| YamlInput::Anchored(name.clone(), node))?
+        }
+        YamlInput::Tagged(ref tag, ref node) => {
+            wrap(node, path, |node| YamlInput::Tagged(tag.clone(), node))?
+        }
+        YamlInput::Commented(ref node, ref comments) => wrap(node, path, |node| {
+            YamlInput::Commented(node, comments.clone())
+        })?,
+        YamlInput::Spelled(ref node, ref spelling) => wrap(node, path, |node| {
+            YamlInput::Spelled(node, spelling.clone())
+        })?,
+        YamlInput::Styled(ref node, style) => {
+            wrap(node, path, |node| YamlInput::Styled(node, style))?
+        }
+        ref node => node.clone(),
+    }))
+}
+
+/// The expanded copy of `node` wrapped by `wrapper`.
+fn wrap<F>(node: &YamlInput, path: &Path, wrapper: F) -> Result<YamlInput, TemplateError>
+where
+    F: FnOnce(Box<YamlInput>) -> YamlInput,
+{
+    let node = expand(node, path)?.unwrap_or(YamlInput::Null);
+    Ok(wrapper(Box::new(node)))
+}
+
+/// The text of the scalar `node`, such as a key. A null has no text.
+fn scalar_text(node: &YamlInput) -> Option<String> {
+    match *resolve(node) {
+        YamlInput::String(ref v) | YamlInput::Real(ref v) | YamlInput::Raw(ref v) => {
+            Some(v.clone())
+        }
+        YamlInput::Spelled(_, ref v) => Some(v.clone()),
+        YamlInput::Integer(v) => Some(v.to_string()),
+        YamlInput::Boolean(v) => Some(v.to_string()),
+        YamlInput::Null => Some(String::new()),
+        YamlInput::Commented(ref node, _) | YamlInput::Styled(ref node, _) => scalar_text(node),
+        _ => None,
+    }
+}
+
+/// Expand the `!use` node whose mapping is `node`.
+fn use_template(node: &YamlInput, path: &Path) -> Result<YamlInput, TemplateError> {
+    let error = |message: String| TemplateError::new(path, message);
+    let h = match *node {
+        YamlInput::Hash(ref h) => h,
+        _ => return Err(error("!use takes a mapping".to_owned())),
+    };
+    let mut template = None;
+    let mut params = HashMap::new();
+    for (k, v) in Entries::new(h) {
+        match scalar_text(k).as_deref() {
+            Some("template") => template = Some(v),
+            Some("with") => match *v {
+                YamlInput::Hash(ref with) => {
+                    for (name, value) in Entries::new(with) {
+                        let name = scalar_text(name)
+                            .ok_or_else(|| error("a parameter has no name".to_owned()))?;
+                        let child = path.child(Segment::Key("with".to_owned()));
+                        let value = expand(value, &child.child(Segment::Key(name.clone())))?;
+                        params.insert(name, value.unwrap_or(YamlInput::Null));
+                    }
+                }
+                YamlInput::Null => {}
+                _ => return Err(error("the parameters of !use are not a mapping".to_owned())),
+            },
+            _ => {
+                return Err(error(format!(
+                    "!use has an unknown key {}",
+                    scalar_text(k).unwrap_or_default()
+                )))
+            }
+        }
+    }
+    let (name, body) = match template {
+        Some(YamlInput::Aliased(ref name, Some(ref target))) => match template_body(target) {
+            Some(body) => (name, body),
+            None => return Err(error(format!("{} is not a !template", name))),
+        },
+        Some(YamlInput::Aliased(ref name, None)) => {
+            return Err(error(format!("unknown anchor {}", name)))
+        }
+        Some(_) => {
+            return Err(error(
+                "the template of !use is not an alias like *name".to_owned(),
+            ))
+        }
+        None => return Err(error("!use has no template".to_owned())),
+    };
+    let substituted = Substitution {
+        name,
+        params: &params,
+        path,
+    }
+    .node(body)?;
+    // the template can use other templates
+    Ok(expand(&substituted, path)?.unwrap_or(YamlInput::Null))
+}
+
+/// The parameters given to a template by a `!use` node.
+struct Substitution<'a> {
+    name: &'a str,
+    params: &'a HashMap<String, YamlInput>,
+    path: &'a Path,
+}
+
+impl<'a> Substitution<'a> {
+    fn param(&self, name: &str) -> Result<&'a YamlInput, TemplateError> {
+        self.params.get(name).ok_or_else(|| {
+            TemplateError::new(
+                self.path,
+                format!(
+                    "no parameter {} is given to the template {}",
+                    name, self.name
+                ),
+            )
+        })
+    }
+
+    /// `text` with its placeholders replaced, or the parameter it consists of.
+    fn text(&self, text: &str) -> Result<Result<String, YamlInput>, TemplateError> {
+        if let Some(name) = text.strip_prefix("${").and_then(|t| t.strip_suffix('}')) {
+            if !name.contains('}') {
+                return Ok(Err(self.param(name.trim())?.clone()));
+            }
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            if rest[..start].ends_with('$') {
+                out.push_str(&rest[..start - 1]);
+                out.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            out.push_str(&rest[..start]);
+            let name = rest[start + 2..end].trim();
+            match scalar_text(self.param(name)?) {
+                Some(value) => out.push_str(&value),
+                None => {
+                    return Err(TemplateError::new(
+                        self.path,
+                        format!("the parameter {} is not a scalar", name),
+                    ))
+                }
+            }
+            rest = &rest[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(Ok(out))
+    }
+
+    /// A copy of `node` with its placeholders replaced.
+    fn node(&self, node: &YamlInput) -> Result<YamlInput, TemplateError> {
+        Ok(match *node {
+            YamlInput::String(ref v) => match self.text(v)? {
+                Ok(text) => YamlInput::String(text),
+                Err(value) => value,
+            },
+            YamlInput::Raw(ref v) => match self.text(v)? {
+                Ok(text) => YamlInput::Raw(text),
+                Err(value) => value,
+            },
+            YamlInput::Array(ref v) => {
+                YamlInput::Array(v.iter().map(|x| self.node(x)).collect::<Result<_, _>>()?)
+            }
+            YamlInput::Hash(ref h) => {
+                let mut entries = h.clone();
+                entries.clear();
+                for (k, v) in Entries::new(h) {
+                    entries.insert(self.node(k)?, self.node(v)?);
+                }
+                YamlInput::Hash(entries)
+            }
+            YamlInput::Anchored(ref name, ref node) => {
+                YamlInput::Anchored(name.clone(), Box::new(self.node(node)?))
+            }
+            YamlInput::Tagged(ref tag, ref node) => {
+                YamlInput::Tagged(tag.clone(), Box::new(self.node(node)?))
+            }
+            YamlInput::Commented(ref node, ref comments) => {
+                YamlInput::Commented(Box::new(self.node(node)?), comments.clone())
+            }
+            ref node => node.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::yaml::YamlLoader;
+
+    fn expand_str(source: &str) -> Result<YamlInput, TemplateError> {
+        expand_templates(&YamlLoader::load_from_str(source).unwrap()[0])
+    }
+
+    #[test]
+    fn test_expand_templates() {
+        let source = "
+port: &port !template {name: '${name}', port: '${number}', path: '/$${not} ${name}/'}
+labels: &labels !template
+  app: ${app}
+  tier: ${tier}
+service: &service !template
+  ports: ${ports}
+  labels: !use {template: *labels, with: {app: '${name}', tier: backend}}
+- !template [unused]
+web: !use
+  template: *service
+  with:
+    name: web
+    ports:
+      - !use {template: *port, with: {name: http, number: 80}}
+      - !use {template: *port, with: {name: https, number: 443}}
+";
+        let doc = expand_str(&source.replace("- !template [unused]\n", "")).unwrap();
+        let expected = YamlLoader::load_from_str(
+            "
+web:
+  ports:
+    - {name: http, port: 80, path: '/${not} http/'}
+    - {name: https, port: 443, path: '/${not} https/'}
+  labels: {app: web, tier: backend}
+",
+        )
+        .unwrap()
+        .remove(0);
+        assert_eq!(doc, expected);
+
+        let doc =
+            expand_str("- &a !template {x: '${x}'}\n- 1\n- !use {template: *a, with: {x: [1]}}")
+                .unwrap();
+        assert_eq!(
+            doc,
+            YamlLoader::load_from_str("- 1\n- {x: [1]}").unwrap()[0]
+        );
+    }
+
+    #[test]
+    fn test_template_errors() {
+        let error = |source: &str| expand_str(source).unwrap_err().to_string();
+        let template = "t: &t !template {a: '${a}', b: 'x${b}'}\n";
+        assert_eq!(
+            error(&format!(
+                "{}u: !use {{template: *t, with: {{b: 1}}}}",
+                template
+            )),
+            "u: no parameter a is given to the template t"
+        );
+        assert_eq!(
+            error(&format!(
+                "{}u: !use {{template: *t, with: {{a: 1, b: [2]}}}}",
+                template
+            )),
+            "u: the parameter b is not a scalar"
+        );
+        assert_eq!(
+            error(&format!("{}u: [*t]", template)),
+            "u[0]: the template t is only used by !use"
+        );
+        assert_eq!(
+            error("a: &a 1\nu: !use {template: *a}"),
+            "u: a is not a !template"
+        );
+        assert_eq!(
+            error("u: !use {template: t}"),
+            "u: the template of !use is not an alias like *name"
+        );
+        assert_eq!(error("u: !use {with: {}}"), "u: !use has no template");
+        assert_eq!(
+            error("u: !use {using: 1}"),
+            "u: !use has an unknown key using"
+        );
+        assert_eq!(error("!use [1]"), "!use takes a mapping");
+    }
+}
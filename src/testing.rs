@@ -0,0 +1,174 @@
+//! Helpers for the tests of projects that format YAML with this crate, comparing the
+//! output of `format::format_str` with an expected text or with a golden file.
+
+use crate::format::{self, FormatOptions};
+use std::fs;
+use std::path::Path;
+
+/// The environment variable that makes `assert_formats_to_snapshot` write the
+/// snapshots instead of comparing with them, when set to anything but `0`.
+pub const UPDATE_SNAPSHOTS_VAR: &str = "UPDATE_YAML_SNAPSHOTS";
+
+/// Assert that formatting `input` with `options` gives `expected`. On failure, the
+/// message points at the first line that differs and holds the whole output.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::format::FormatOptions;
+/// use yaml_rust_formatter::testing::assert_formats_to;
+///
+/// assert_formats_to("a:   1\nb: [1,2]\n", "---\na: 1\nb:\n  - 1\n  - 2\n", &FormatOptions::new());
+/// ```
+#[track_caller]
+pub fn assert_formats_to(input: &str, expected: &str, options: &FormatOptions) {
+    if let Err(message) = compare(input, expected, options) {
+        panic!("{}", message);
+    }
+}
+
+/// Assert that formatting `input` with `options` gives the contents of the file
+/// `snapshot`. With the environment variable `UPDATE_YAML_SNAPSHOTS` set, the file is
+/// written with the output instead, creating it and its directory if needed, so
+/// fixtures are updated by running the tests once with it.
+///
+/// # Examples
+///
+/// ```no_run
+/// use yaml_rust_formatter::format::FormatOptions;
+/// use yaml_rust_formatter::testing::assert_formats_to_snapshot;
+///
+/// let input = std::fs::read_to_string("tests/fixtures/deployment.yaml").unwrap();
+/// assert_formats_to_snapshot(
+///     &input,
+///     "tests/fixtures/deployment.formatted.yaml",
+///     &FormatOptions::new(),
+/// );
+/// ```
+#[track_caller]
+pub fn assert_formats_to_snapshot<P: AsRef<Path>>(
+    input: &str,
+    snapshot: P,
+    options: &FormatOptions,
+) {
+    let update = std::env::var_os(UPDATE_SNAPSHOTS_VAR);
+    let update = update.is_some_and(|v| !v.is_empty() && v != "0");
+    if let Err(message) = check_snapshot(input, snapshot.as_ref(), options, update) {
+        panic!("{}", message);
+    }
+}
+
+/// Compare the output for `input` with the file `snapshot`, or write it there when
+/// `update` is set.
+fn check_snapshot(
+    input: &str,
+    snapshot: &Path,
+    options: &FormatOptions,
+    update: bool,
+) -> Result<(), String> {
+    if update {
+        let output = format::format_str(input, options)
+            .map_err(|err| format!("the input does not format: {}", err))?;
+        if let Some(dir) = snapshot.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| format!("cannot create {}: {}", dir.display(), err))?;
+        }
+        return fs::write(snapshot, output)
+            .map_err(|err| format!("cannot write {}: {}", snapshot.display(), err));
+    }
+    let expected = fs::read_to_string(snapshot).map_err(|err| {
+        format!(
+            "cannot read the snapshot {}: {}\nrun the tests with {}=1 to write it",
+            snapshot.display(),
+            err,
+            UPDATE_SNAPSHOTS_VAR
+        )
+    })?;
+    compare(input, &expected, options).map_err(|message| {
+        format!(
+            "{}\nthe snapshot is {}; run the tests with {}=1 to update it",
+            message,
+            snapshot.display(),
+            UPDATE_SNAPSHOTS_VAR
+        )
+    })
+}
+
+/// Format `input`, describing how the output differs from `expected` if it does.
+fn compare(input: &str, expected: &str, options: &FormatOptions) -> Result<(), String> {
+    let output = format::format_str(input, options)
+        .map_err(|err| format!("the input does not format: {}", err))?;
+    if output == expected {
+        return Ok(());
+    }
+    let mut expected_lines = expected.split_inclusive('\n');
+    let mut output_lines = output.split_inclusive('\n');
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), output_lines.next()) {
+            (Some(a), Some(b)) if a == b => line += 1,
+            (a, b) => {
+                return Err(format!(
+                    "the formatted output differs at line {}\nexpected: {:?}\n  output: {:?}\n\nthe whole output:\n{}",
+                    line,
+                    a.unwrap_or("<end>"),
+                    b.unwrap_or("<end>"),
+                    output
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let options = FormatOptions::new();
+        assert!(compare("a:   1", "---\na: 1\n", &options).is_ok());
+        let message = compare("a: 1\nb:   2\n", "---\na: 1\nb: 3\n", &options).unwrap_err();
+        assert!(
+            message.starts_with(
+                "the formatted output differs at line 3\nexpected: \"b: 3\\n\"\n  output: \"b: 2\\n\""
+            ),
+            "{}",
+            message
+        );
+        let message = compare("a: 1\n", "---\na: 1\nb: 2\n", &options).unwrap_err();
+        assert!(message.contains("output: \"<end>\""), "{}", message);
+        let message = compare("a: [", "", &options).unwrap_err();
+        assert!(
+            message.starts_with("the input does not format: "),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the formatted output differs at line 2")]
+    fn test_assert_formats_to_fails() {
+        assert_formats_to("a: 1", "---\na: 2\n", &FormatOptions::new());
+    }
+
+    #[test]
+    fn test_check_snapshot() {
+        let dir = std::env::temp_dir().join(format!("yaml-testing-{}", std::process::id()));
+        let snapshot = dir.join("fixtures").join("a.formatted.yaml");
+        let options = FormatOptions::new();
+        let message = check_snapshot("a:   1", &snapshot, &options, false).unwrap_err();
+        assert!(
+            message.contains("UPDATE_YAML_SNAPSHOTS=1 to write it"),
+            "{}",
+            message
+        );
+
+        check_snapshot("a:   1", &snapshot, &options, true).unwrap();
+        assert_eq!(fs::read_to_string(&snapshot).unwrap(), "---\na: 1\n");
+        check_snapshot("a:   1", &snapshot, &options, false).unwrap();
+        let message = check_snapshot("a: 2", &snapshot, &options, false).unwrap_err();
+        assert!(message.contains("to update it"), "{}", message);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
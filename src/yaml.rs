@@ -1,13 +1,29 @@
+use crate::base64;
+#[cfg(feature = "borrowed")]
+use crate::borrowed::{BorrowedLoader, Yaml};
+use crate::diff::{key_segment, Path, Segment};
+use crate::emitter::need_quotes;
+use crate::line_ending::{self, LineEnding, LineEndingPolicy, Tracker};
+use crate::metrics;
 use crate::parser::*;
 use crate::scanner::{Marker, ScanError, TScalarStyle, TokenType};
+#[cfg(feature = "template")]
+use crate::template;
 use linked_hash_map::LinkedHashMap;
-use std::collections::BTreeMap;
-use std::f64;
-use std::i64;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::hash;
+use std::io;
 use std::mem;
-use std::ops::Index;
+use std::ops::{Index, IndexMut};
+use std::ptr;
+#[cfg(feature = "stream")]
+use std::str::Chars;
 use std::string;
 use std::vec;
+#[cfg(feature = "unicode")]
+use unicode_normalization::UnicodeNormalization;
 
 /// Based on yaml_rust
 /// A read YAML node is stored as this `YamlInput` enumeration, which provides an easy way to
@@ -46,6 +62,23 @@ pub enum YamlInput {
     Anchored(string::String, Box<YamlInput>),
     /// Aliased: The name and the value, the value is only none if the anchor that is aliased doesn't exist
     Aliased(string::String, Option<Box<YamlInput>>),
+    /// A tag outside the core schema as written, such as `!Ref` or `!vault`, and the
+    /// node it applies to, loaded as if it had no tag. A handle declared by a `%TAG`
+    /// directive is resolved, giving a verbatim tag like `!<tag:example.com,2000:foo>`.
+    Tagged(string::String, Box<YamlInput>),
+    /// A node with its comments, see `LoadOptions::keep_comments`.
+    Commented(Box<YamlInput>, Comments),
+    /// A null, boolean or integer with its spelling in the source, e.g. `~`, `null`
+    /// or `0xFF`, see `LoadOptions::keep_spelling`.
+    Spelled(Box<YamlInput>, string::String),
+    /// A string with the style it is written in, when it is quoted or a block scalar,
+    /// see `LoadOptions::keep_scalar_style`.
+    Styled(Box<YamlInput>, TScalarStyle),
+    /// A plain scalar kept as its source text, see `LoadOptions::raw_scalars`. Its
+    /// value is only resolved when asked for, e.g. by `as_i64` or `raw_value`.
+    Raw(string::String),
+    /// The bytes of a `!!binary` scalar, decoded from base64.
+    Binary(Vec<u8>),
     /// YAML null, e.g. `null` or `~`.
     Null,
     /// Accessing a nonexistent node via the Index trait returns `BadValue`. This
@@ -54,7 +87,38 @@ pub enum YamlInput {
     BadValue,
 }
 
+/// The comments attached to a node, without their leading `#`.
+///
+/// The comments of a mapping entry are attached to its value, so keys stay plain.
+#[derive(Clone, PartialEq, PartialOrd, Debug, Eq, Ord, Hash, Default)]
+pub struct Comments {
+    /// Comment lines before the node, or before the key of its entry.
+    pub before: Vec<string::String>,
+    /// The comment ending the line on which the node, or its key, starts.
+    pub trailing: Option<string::String>,
+    /// The comment ending the line of the key of its entry, when the key is written
+    /// after a `?` indicator, apart from the value.
+    pub key_trailing: Option<string::String>,
+    /// Comment lines after the node, found on the root of a document and on the last
+    /// entry of a nested block collection, indented within that collection.
+    pub after: Vec<string::String>,
+    /// Blank lines the emitter writes before the entry of the node, and its comments,
+    /// when it is not the first entry of a block collection.
+    pub blank_lines: usize,
+}
+
+impl Comments {
+    pub fn is_empty(&self) -> bool {
+        self.before.is_empty()
+            && self.trailing.is_none()
+            && self.key_trailing.is_none()
+            && self.after.is_empty()
+            && self.blank_lines == 0
+    }
+}
+
 pub type ArrayInput = Vec<YamlInput>;
+/// Iterate it with [`YamlInput::entries`] to rely on the order guaranteed by [`Entries`].
 pub type HashInput = LinkedHashMap<YamlInput, YamlInput>;
 
 /// A write YAML node is stored as this `YamlOutput` enumeration, which provides an easy way to
@@ -65,6 +129,7 @@ pub type HashInput = LinkedHashMap<YamlInput, YamlInput>;
 /// ```
 /// use yaml_rust_formatter::YamlOutput;
 /// let vec = YamlOutput::Array(vec![YamlOutput::Integer(1), YamlOutput::Integer(2)]);
+/// assert_eq!(vec, YamlOutput::from(vec![1, 2]));
 /// ```
 #[derive(Clone, PartialEq, PartialOrd, Debug, Eq, Ord, Hash)]
 pub enum YamlOutput {
@@ -87,6 +152,20 @@ pub enum YamlOutput {
     Anchored(string::String, Box<YamlOutput>),
     /// Alias
     Alias(string::String),
+    /// A tag, written as it is, and the node it applies to.
+    Tagged(string::String, Box<YamlOutput>),
+    /// A node with its comments.
+    Commented(Box<YamlOutput>, Comments),
+    /// A null or boolean, written with the given spelling.
+    Spelled(Box<YamlOutput>, string::String),
+    /// A string written in the given style whenever it can be, such as the single
+    /// quotes it had in the source.
+    Styled(Box<YamlOutput>, TScalarStyle),
+    /// A string written as a literal block scalar (`|`) whenever it can be, such as
+    /// an embedded document.
+    Literal(string::String),
+    /// Raw bytes, emitted as a base64 encoded `!!binary` scalar.
+    Binary(Vec<u8>),
     /// YAML null, e.g. `null` or `~`.
     Null,
     /// Accessing a nonexistent node via the Index trait returns `BadValue`. This
@@ -97,331 +176,2805 @@ pub enum YamlOutput {
 
 pub type ArrayOutput = Vec<YamlOutput>;
 pub type HashOutput = LinkedHashMap<YamlOutput, YamlOutput>;
+/// The entry of a key in a `HashInput` or `HashOutput`, see `YamlInput::entry`.
+pub type HashEntry<'a, T> = linked_hash_map::Entry<'a, T, T>;
 
-impl std::convert::Into<YamlOutput> for YamlInput {
-    fn into(self) -> YamlOutput {
-        match self {
-            Self::Real(s) => YamlOutput::Real(s),
-            Self::Integer(i) => YamlOutput::Integer(i),
-            Self::String(s) => YamlOutput::String(s),
-            Self::Boolean(b) => YamlOutput::Boolean(b),
-            Self::Array(v) => YamlOutput::Array(v.into_iter().map(|a| a.into()).collect()),
-            Self::Hash(h) => {
-                YamlOutput::Hash(h.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
-            }
-            Self::Anchored(s, i) => YamlOutput::Anchored(s, Box::new((*i).into())),
-            Self::Aliased(s, _) => YamlOutput::Alias(s),
-            Self::Null => YamlOutput::Null,
-            Self::BadValue => YamlOutput::BadValue,
+/// The entries of a mapping, in input order: the order in which their keys were first
+/// inserted, which for a loaded document is the order they appear in the source.
+///
+/// This order is part of the API contract. The loader, the emitter and every other
+/// module of this crate walk mappings through this type, so swapping the map that
+/// backs `HashInput` and `HashOutput` cannot change what they produce.
+pub struct Entries<'a, T: 'a> {
+    iter: Option<linked_hash_map::Iter<'a, T, T>>,
+}
+
+impl<'a, T: hash::Hash + Eq> Entries<'a, T> {
+    pub(crate) fn new(hash: &'a LinkedHashMap<T, T>) -> Entries<'a, T> {
+        Entries {
+            iter: Some(hash.iter()),
         }
     }
 }
 
-// parse f64 as Core schema
-// See: https://github.com/chyh1990/yaml-rust/issues/51
-fn parse_f64(v: &str) -> Option<f64> {
-    match v {
-        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => Some(f64::INFINITY),
-        "-.inf" | "-.Inf" | "-.INF" => Some(f64::NEG_INFINITY),
-        ".nan" | "NaN" | ".NAN" => Some(f64::NAN),
-        _ => v.parse::<f64>().ok(),
+impl<'a, T> Iterator for Entries<'a, T> {
+    type Item = (&'a T, &'a T);
+
+    fn next(&mut self) -> Option<(&'a T, &'a T)> {
+        self.iter.as_mut()?.next()
     }
-}
 
-pub struct YamlLoader {
-    docs: Vec<YamlInput>,
-    // states
-    // (current node, anchor) tuple
-    doc_stack: Vec<(YamlInput, Option<String>)>,
-    key_stack: Vec<YamlInput>,
-    anchor_map: BTreeMap<String, YamlInput>,
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter
+            .as_ref()
+            .map_or((0, Some(0)), |iter| iter.size_hint())
+    }
 }
 
-impl MarkedEventReceiver for YamlLoader {
-    fn on_event(&mut self, ev: Event, _: Marker) {
-        // println!("EV {:?}", ev);
-        match ev {
-            Event::DocumentStart => {
-                // do nothing
-            }
-            Event::DocumentEnd => {
-                match self.doc_stack.len() {
-                    // empty document
-                    0 => self.docs.push(YamlInput::BadValue),
-                    1 => self.docs.push(self.doc_stack.pop().unwrap().0),
-                    _ => unreachable!(),
-                }
-            }
-            Event::SequenceStart(aid) => {
-                self.doc_stack.push((YamlInput::Array(Vec::new()), aid));
-            }
-            Event::SequenceEnd => {
-                let node = self.doc_stack.pop().unwrap();
-                if let Some(anchor) = node.1 {
-                    self.insert_new_node((
-                        YamlInput::Anchored(anchor.clone(), Box::new(node.0)),
-                        Some(anchor.clone()),
-                    ));
-                } else {
-                    self.insert_new_node(node);
-                }
-            }
-            Event::MappingStart(aid) => {
-                self.doc_stack
-                    .push((YamlInput::Hash(HashInput::new()), aid));
-                self.key_stack.push(YamlInput::BadValue);
-            }
-            Event::MappingEnd => {
-                self.key_stack.pop().unwrap();
-                let node = self.doc_stack.pop().unwrap();
-                if let Some(anchor) = node.1 {
-                    self.insert_new_node((
-                        YamlInput::Anchored(anchor.clone(), Box::new(node.0)),
-                        Some(anchor.clone()),
-                    ));
-                } else {
-                    self.insert_new_node(node);
-                }
-            }
-            Event::Scalar(v, style, aid, tag) => {
-                let node = if style != TScalarStyle::Plain {
-                    YamlInput::String(v)
-                } else if let Some(TokenType::Tag(ref handle, ref suffix)) = tag {
-                    // XXX tag:yaml.org,2002:
-                    if handle == "!!" {
-                        match suffix.as_ref() {
-                            "bool" => {
-                                // "true" or "false"
-                                match v.parse::<bool>() {
-                                    Err(_) => YamlInput::BadValue,
-                                    Ok(v) => YamlInput::Boolean(v),
-                                }
-                            }
-                            "int" => match v.parse::<i64>() {
-                                Err(_) => YamlInput::BadValue,
-                                Ok(v) => YamlInput::Integer(v),
-                            },
-                            "float" => match parse_f64(&v) {
-                                Some(_) => YamlInput::Real(v),
-                                None => YamlInput::BadValue,
-                            },
-                            "null" => match v.as_ref() {
-                                "~" | "null" => YamlInput::Null,
-                                _ => YamlInput::BadValue,
-                            },
-                            _ => YamlInput::String(v),
-                        }
-                    } else {
-                        YamlInput::String(v)
-                    }
-                } else {
-                    // Datatype is not specified, or unrecognized
-                    YamlInput::from_str(&v)
-                };
+impl<'a, T> ExactSizeIterator for Entries<'a, T> {}
 
-                if let Some(anchor) = aid {
-                    self.insert_new_node((
-                        YamlInput::Anchored(anchor.clone(), Box::new(node)),
-                        Some(anchor.clone()),
-                    ));
-                } else {
-                    self.insert_new_node((node, None));
-                }
-            }
-            Event::Alias(id) => {
-                let node = YamlInput::Aliased(
-                    id.clone(),
-                    self.anchor_map
-                        .get(&id)
-                        .clone()
-                        .map(|a| Box::new(a.clone())),
-                );
-                self.insert_new_node((node, None));
+impl From<YamlInput> for YamlOutput {
+    fn from(input: YamlInput) -> Self {
+        match input {
+            YamlInput::Real(s) => Self::Real(s),
+            YamlInput::Integer(i) => Self::Integer(i),
+            YamlInput::String(s) => Self::String(s),
+            YamlInput::Boolean(b) => Self::Boolean(b),
+            YamlInput::Array(v) => Self::Array(v.into_iter().map(|a| a.into()).collect()),
+            YamlInput::Hash(h) => {
+                Self::Hash(h.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
             }
-            _ => { /* ignore */ }
+            YamlInput::Anchored(s, i) => Self::Anchored(s, Box::new((*i).into())),
+            YamlInput::Aliased(s, _) => Self::Alias(s),
+            YamlInput::Tagged(t, i) => Self::Tagged(t, Box::new((*i).into())),
+            YamlInput::Commented(i, c) => Self::Commented(Box::new((*i).into()), c),
+            YamlInput::Spelled(i, s) => Self::Spelled(Box::new((*i).into()), s),
+            YamlInput::Styled(i, style) => Self::Styled(Box::new((*i).into()), style),
+            // a raw string the emitter writes as is needs no spelling, nor can a line
+            // break be written as is
+            YamlInput::Raw(s) => match YamlInput::from_str(&s) {
+                YamlInput::String(s) if !need_quotes(&s) || has_line_break(&s) => Self::String(s),
+                node => Self::Spelled(Box::new(node.into()), s),
+            },
+            YamlInput::Binary(b) => Self::Binary(b),
+            YamlInput::Null => Self::Null,
+            YamlInput::BadValue => Self::BadValue,
         }
-        // println!("DOC {:?}", self.doc_stack);
     }
 }
 
-impl YamlLoader {
-    fn insert_new_node(&mut self, node: (YamlInput, Option<String>)) {
-        // valid anchor id starts from 1
-        if let Some(anchor) = node.1.as_ref() {
-            self.anchor_map.insert(anchor.clone(), node.0.clone());
-        }
-        if self.doc_stack.is_empty() {
-            self.doc_stack.push(node);
-        } else {
-            let parent = self.doc_stack.last_mut().unwrap();
-            match *parent {
-                (YamlInput::Array(ref mut v), _) => v.push(node.0),
-                (YamlInput::Hash(ref mut h), _) => {
-                    let cur_key = self.key_stack.last_mut().unwrap();
-                    // current node is a key
-                    if cur_key.is_badvalue() {
-                        *cur_key = node.0;
-                    // current node is a value
-                    } else {
-                        let mut newkey = YamlInput::BadValue;
-                        mem::swap(&mut newkey, cur_key);
-                        h.insert(newkey, node.0);
-                    }
-                }
-                _ => unreachable!(),
-            }
-        }
+impl From<&str> for YamlOutput {
+    fn from(v: &str) -> Self {
+        YamlOutput::String(v.to_owned())
     }
+}
 
-    pub fn load_from_str(source: &str) -> Result<Vec<YamlInput>, ScanError> {
-        let mut loader = YamlLoader {
-            docs: Vec::new(),
-            doc_stack: Vec::new(),
-            key_stack: Vec::new(),
-            anchor_map: BTreeMap::new(),
-        };
-        let mut parser = Parser::new(source.chars());
-        parser.load(&mut loader, true)?;
-        Ok(loader.docs)
+impl From<string::String> for YamlOutput {
+    fn from(v: string::String) -> Self {
+        YamlOutput::String(v)
     }
 }
 
-macro_rules! define_as (
-    ($name:ident, $t:ident, $yt:ident) => (
-pub fn $name(&self) -> Option<$t> {
-    match *self {
-        Self::$yt(v) => Some(v),
-        Self::Aliased(ref _s, ref v_opt) => v_opt.as_ref().map(|v| v.$name()).flatten(),
-        Self::Anchored(ref _s, ref v) => v.$name(),
-        _ => None
+impl From<i64> for YamlOutput {
+    fn from(v: i64) -> Self {
+        YamlOutput::Integer(v)
     }
 }
-    );
-);
 
-macro_rules! define_as_ref (
-    ($name:ident, $t:ty, $yt:ident) => (
-pub fn $name(&self) -> Option<$t> {
-    match *self {
-        Self::$yt(ref v) => Some(v),
-        Self::Aliased(ref _s, ref v_opt) => v_opt.as_ref().map(|v| v.$name()).flatten(),
-        Self::Anchored(ref _s, ref v) => v.$name(),
-        _ => None
+impl From<bool> for YamlOutput {
+    fn from(v: bool) -> Self {
+        YamlOutput::Boolean(v)
     }
 }
-    );
-);
 
-macro_rules! define_into (
-    ($name:ident, $t:ty, $yt:ident) => (
-pub fn $name(self) -> Option<$t> {
-    match self {
-        Self::$yt(v) => Some(v),
-        Self::Aliased(_s, v_opt) => v_opt.map(|v| v.$name()).flatten(),
-        Self::Anchored(_s, v) => v.$name(),
-        _ => None
+impl From<f64> for YamlOutput {
+    fn from(v: f64) -> Self {
+        YamlOutput::Real(if v.is_nan() {
+            ".nan".to_owned()
+        } else if v.is_infinite() {
+            if v > 0.0 { ".inf" } else { "-.inf" }.to_owned()
+        } else {
+            // the debug form keeps a fraction, so the text is not read as an integer
+            format!("{:?}", v)
+        })
     }
 }
-    );
-);
 
-impl YamlInput {
-    define_as!(as_bool, bool, Boolean);
-    define_as!(as_i64, i64, Integer);
+impl<T: Into<YamlOutput>> From<Vec<T>> for YamlOutput {
+    fn from(v: Vec<T>) -> Self {
+        YamlOutput::Array(v.into_iter().map(Into::into).collect())
+    }
+}
 
-    define_as_ref!(as_str, &str, String);
-    define_as_ref!(as_hash, &HashInput, Hash);
-    define_as_ref!(as_vec, &ArrayInput, Array);
+/// The entries are kept in the order of their keys.
+impl<K: Into<YamlOutput>, V: Into<YamlOutput>> From<BTreeMap<K, V>> for YamlOutput {
+    fn from(h: BTreeMap<K, V>) -> Self {
+        YamlOutput::Hash(h.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
 
-    define_into!(into_bool, bool, Boolean);
-    define_into!(into_i64, i64, Integer);
-    define_into!(into_string, String, String);
-    define_into!(into_hash, HashInput, Hash);
-    define_into!(into_vec, ArrayInput, Array);
+/// How `YamlInput::merge` combines a document with an overlay.
+#[derive(Clone, Debug, Default)]
+pub struct MergeStrategy {
+    append_arrays: bool,
+    null_deletes: bool,
+}
 
-    pub fn is_null(&self) -> bool {
-        matches!(*self, Self::Null)
+impl MergeStrategy {
+    pub fn new() -> MergeStrategy {
+        MergeStrategy::default()
     }
 
-    pub fn is_badvalue(&self) -> bool {
-        matches!(*self, Self::BadValue)
+    /// Add the items of a sequence of the overlay after the ones of the sequence it
+    /// overrides, instead of replacing them.
+    pub fn append_arrays(&mut self, append_arrays: bool) {
+        self.append_arrays = append_arrays;
     }
 
-    pub fn is_array(&self) -> bool {
-        matches!(*self, Self::Array(_))
+    /// Determine if the sequences of the overlay are appended.
+    pub fn is_append_arrays(&self) -> bool {
+        self.append_arrays
     }
 
-    pub fn as_f64(&self) -> Option<f64> {
-        match *self {
-            Self::Real(ref v) => parse_f64(v),
-            _ => None,
-        }
+    /// Remove the keys whose value is null in the overlay, instead of setting them to
+    /// null.
+    pub fn null_deletes(&mut self, null_deletes: bool) {
+        self.null_deletes = null_deletes;
     }
 
-    pub fn into_f64(self) -> Option<f64> {
-        match self {
-            Self::Real(ref v) => parse_f64(v),
-            _ => None,
-        }
+    /// Determine if null values of the overlay remove their keys.
+    pub fn is_null_deletes(&self) -> bool {
+        self.null_deletes
     }
 }
 
-#[cfg_attr(feature = "cargo-clippy", allow(should_implement_trait))]
+/// How `YamlInput::to_output` converts the aliases of a document.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum AliasPolicy {
+    /// Every alias stays an alias, as with `YamlOutput::from`.
+    #[default]
+    KeepAlias,
+    /// An alias is replaced by a copy of the node it refers to, without the anchors
+    /// of that copy. An alias to an anchor that is not defined stays an alias.
+    InlineResolved,
+    /// Like `InlineResolved`, but an alias to an anchor that is not defined is an
+    /// error.
+    ErrorIfUnresolved,
+}
+
 impl YamlInput {
-    // Not implementing FromStr because there is no possibility of Error.
-    // This function falls back to Yaml::String if nothing else matches.
-    pub fn from_str(v: &str) -> Self {
-        if let Some(value) = v.strip_prefix("0x") {
-            if let Ok(i) = i64::from_str_radix(&value, 16) {
-                return Self::Integer(i);
-            }
+    /// Convert to a `YamlOutput`, keeping or inlining aliases as `policy` says.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{AliasPolicy, YamlLoader, YamlOutput};
+    ///
+    /// let doc = &YamlLoader::load_from_str("- &x 1\n- *x\n- *y").unwrap()[0];
+    /// let output = doc.to_output(AliasPolicy::InlineResolved).unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     YamlOutput::Array(vec![
+    ///         YamlOutput::Anchored("x".to_owned(), Box::new(YamlOutput::Integer(1))),
+    ///         YamlOutput::Integer(1),
+    ///         YamlOutput::Alias("y".to_owned()),
+    ///     ])
+    /// );
+    ///
+    /// let err = doc.to_output(AliasPolicy::ErrorIfUnresolved).unwrap_err();
+    /// assert_eq!(err.name(), "y");
+    /// ```
+    pub fn to_output(&self, policy: AliasPolicy) -> Result<YamlOutput, AnchorError> {
+        node_output(self, policy, false)
+    }
+}
+
+/// Convert `node`, dropping its anchors when it is the copy of an inlined alias.
+fn node_output(
+    node: &YamlInput,
+    policy: AliasPolicy,
+    inlined: bool,
+) -> Result<YamlOutput, AnchorError> {
+    let output = |node: &YamlInput| node_output(node, policy, inlined);
+    Ok(match *node {
+        YamlInput::Array(ref v) => {
+            YamlOutput::Array(v.iter().map(output).collect::<Result<_, _>>()?)
         }
-        if let Some(value) = v.strip_prefix("0o") {
-            if let Ok(i) = i64::from_str_radix(&value, 8) {
-                return Self::Integer(i);
-            }
+        YamlInput::Hash(ref h) => YamlOutput::Hash(
+            h.iter()
+                .map(|(k, v)| Ok((output(k)?, output(v)?)))
+                .collect::<Result<_, _>>()?,
+        ),
+        YamlInput::Anchored(_, ref node) if inlined => output(node)?,
+        YamlInput::Anchored(ref name, ref node) => {
+            YamlOutput::Anchored(name.clone(), Box::new(output(node)?))
         }
-        if let Some(value) = v.strip_prefix('+') {
-            if let Ok(i) = value.parse::<i64>() {
-                return Self::Integer(i);
+        YamlInput::Aliased(ref name, ref target) => match (policy, target) {
+            (AliasPolicy::KeepAlias, _) | (AliasPolicy::InlineResolved, None) => {
+                YamlOutput::Alias(name.clone())
             }
+            (_, Some(target)) => node_output(target, policy, true)?,
+            (AliasPolicy::ErrorIfUnresolved, None) => {
+                return Err(AnchorError { name: name.clone() })
+            }
+        },
+        YamlInput::Tagged(ref tag, ref node) => {
+            YamlOutput::Tagged(tag.clone(), Box::new(output(node)?))
         }
-        match v {
-            "~" | "null" => Self::Null,
-            "true" => Self::Boolean(true),
-            "false" => Self::Boolean(false),
-            _ if v.parse::<i64>().is_ok() => Self::Integer(v.parse::<i64>().unwrap()),
-            // try parsing as f64
-            _ if parse_f64(v).is_some() => Self::Real(v.to_owned()),
-            _ => Self::String(v.to_owned()),
+        YamlInput::Commented(ref node, ref comments) => {
+            YamlOutput::Commented(Box::new(output(node)?), comments.clone())
+        }
+        YamlInput::Spelled(ref node, ref spelling) => {
+            YamlOutput::Spelled(Box::new(output(node)?), spelling.clone())
+        }
+        YamlInput::Styled(ref node, style) => YamlOutput::Styled(Box::new(output(node)?), style),
+        _ => node.clone().into(),
+    })
+}
+
+/// Builds the anchors and aliases of a `YamlOutput` document, so that every alias
+/// refers to an anchor defined before it and the document loads back as built.
+/// Anchors count as defined in the order the builder is called, which must be the
+/// order of the document: build the nodes that hold anchors before the ones that
+/// refer to them.
+///
+/// # Examples
+///
+/// ```rust
+/// use yaml_rust_formatter::{DocumentBuilder, YamlOutput};
+///
+/// let mut builder = DocumentBuilder::new();
+/// let _base = builder.anchor("base", YamlOutput::Integer(1));
+/// assert!(builder.alias("base").is_ok());
+/// assert_eq!(builder.alias("other").unwrap_err().to_string(), "unknown anchor other");
+///
+/// // the first reference anchors the node, the next ones are aliases
+/// let first = builder.reference("limit", YamlOutput::Integer(2));
+/// let second = builder.reference("limit", YamlOutput::Integer(2));
+/// assert_eq!(first, YamlOutput::Anchored("limit".to_owned(), Box::new(YamlOutput::Integer(2))));
+/// assert_eq!(second, YamlOutput::Alias("limit".to_owned()));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DocumentBuilder {
+    anchors: HashSet<string::String>,
+}
+
+impl DocumentBuilder {
+    pub fn new() -> DocumentBuilder {
+        DocumentBuilder::default()
+    }
+
+    /// Anchor `node` as `name`, which later aliases may then refer to.
+    pub fn anchor(&mut self, name: &str, node: YamlOutput) -> YamlOutput {
+        self.anchors.insert(name.to_owned());
+        YamlOutput::Anchored(name.to_owned(), Box::new(node))
+    }
+
+    /// An alias of the anchor `name`, which must be defined already.
+    pub fn alias(&self, name: &str) -> Result<YamlOutput, AnchorError> {
+        if self.anchors.contains(name) {
+            Ok(YamlOutput::Alias(name.to_owned()))
+        } else {
+            Err(AnchorError {
+                name: name.to_owned(),
+            })
+        }
+    }
+
+    /// An alias of the anchor `name`, or `node` anchored as `name` when no anchor has
+    /// that name yet.
+    pub fn reference(&mut self, name: &str, node: YamlOutput) -> YamlOutput {
+        match self.alias(name) {
+            Ok(alias) => alias,
+            Err(_) => self.anchor(name, node),
         }
     }
+
+    /// Whether an anchor named `name` is defined.
+    pub fn is_anchored(&self, name: &str) -> bool {
+        self.anchors.contains(name)
+    }
 }
 
-static BAD_VALUE: YamlInput = YamlInput::BadValue;
-impl<'a> Index<&'a str> for YamlInput {
-    type Output = Self;
+/// An alias refers to an anchor that is not defined before it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnchorError {
+    name: string::String,
+}
 
-    fn index(&self, idx: &'a str) -> &Self {
-        let key = Self::String(idx.to_owned());
-        match self.as_hash() {
-            Some(h) => h.get(&key).unwrap_or(&BAD_VALUE),
-            None => &BAD_VALUE,
+impl AnchorError {
+    /// The name of the missing anchor.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Error for AnchorError {}
+
+impl fmt::Display for AnchorError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "unknown anchor {}", self.name)
+    }
+}
+
+impl YamlOutput {
+    /// The number of nodes this node would be written with once its aliases are
+    /// expanded, like `YamlInput::expanded_size`. Comparing it with the number of
+    /// nodes written tells how much inlining the anchors would grow the output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{YamlLoader, YamlOutput};
+    ///
+    /// let doc: YamlOutput = YamlLoader::load_from_str("- &a [x, x, x]\n- [*a, *a]").unwrap()[0]
+    ///     .clone()
+    ///     .into();
+    /// assert_eq!(doc.expanded_size(), 1 + 4 + (1 + 2 * 4));
+    /// ```
+    pub fn expanded_size(&self) -> usize {
+        output_expanded_size(self, &mut HashMap::new())
+    }
+}
+
+// parse f64 as Core schema
+// See: https://github.com/chyh1990/yaml-rust/issues/51
+pub(crate) fn parse_f64(v: &str) -> Option<f64> {
+    match v {
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => Some(f64::INFINITY),
+        "-.inf" | "-.Inf" | "-.INF" => Some(f64::NEG_INFINITY),
+        ".nan" | "NaN" | ".NAN" => Some(f64::NAN),
+        _ => v.parse::<f64>().ok(),
+    }
+}
+
+/// Resolve the value of a scalar event according to its style and tag.
+pub(crate) fn resolve_scalar(v: String, style: TScalarStyle, tag: &Option<TokenType>) -> YamlInput {
+    if let Some(TokenType::Tag(ref handle, ref suffix)) = *tag {
+        // base64 is often written as a literal block, so any style is decoded
+        if handle == "!!" && suffix == "binary" {
+            return match base64::decode(&v) {
+                Some(bytes) => YamlInput::Binary(bytes),
+                None => YamlInput::BadValue,
+            };
+        }
+    }
+    // the core tags give the type whatever the style, as in `!!int "1"`
+    let core_tag = match *tag {
+        Some(TokenType::Tag(ref handle, ref suffix)) => {
+            handle == "!!" && matches!(suffix.as_str(), "bool" | "int" | "float" | "null")
+        }
+        _ => false,
+    };
+    if style != TScalarStyle::Plain && !core_tag {
+        YamlInput::String(v)
+    } else if let Some(TokenType::Tag(ref handle, ref suffix)) = *tag {
+        // XXX tag:yaml.org,2002:
+        if handle == "!!" {
+            match suffix.as_ref() {
+                "bool" => {
+                    // "true" or "false"
+                    match v.parse::<bool>() {
+                        Err(_) => YamlInput::BadValue,
+                        Ok(v) => YamlInput::Boolean(v),
+                    }
+                }
+                "int" => match v.parse::<i64>() {
+                    Err(_) => YamlInput::BadValue,
+                    Ok(v) => YamlInput::Integer(v),
+                },
+                "float" => match parse_f64(&v) {
+                    Some(_) => YamlInput::Real(v),
+                    None => YamlInput::BadValue,
+                },
+                "null" => match v.as_ref() {
+                    "~" | "null" | "" => YamlInput::Null,
+                    _ => YamlInput::BadValue,
+                },
+                _ => YamlInput::String(v),
+            }
+        } else {
+            YamlInput::String(v)
+        }
+    } else {
+        // Datatype is not specified, or unrecognized
+        YamlInput::from_str(&v)
+    }
+}
+
+/// The null or boolean spelled `v` by the YAML 1.2 core schema, which unlike `from_str`
+/// also accepts the capitalized spellings.
+pub(crate) fn core_schema_scalar(v: &str) -> Option<YamlInput> {
+    match v {
+        "~" | "null" | "Null" | "NULL" => Some(YamlInput::Null),
+        "true" | "True" | "TRUE" => Some(YamlInput::Boolean(true)),
+        "false" | "False" | "FALSE" => Some(YamlInput::Boolean(false)),
+        _ => None,
+    }
+}
+
+/// The null or boolean spelled `v` by YAML 1.1, which adds `yes`, `no`, `on`, `off`
+/// and their single letter forms to the spellings of the core schema.
+pub(crate) fn yaml_1_1_scalar(v: &str) -> Option<YamlInput> {
+    match v {
+        "y" | "Y" | "yes" | "Yes" | "YES" | "on" | "On" | "ON" => Some(YamlInput::Boolean(true)),
+        "n" | "N" | "no" | "No" | "NO" | "off" | "Off" | "OFF" => Some(YamlInput::Boolean(false)),
+        _ => core_schema_scalar(v),
+    }
+}
+
+/// How plain scalars without a tag resolve to nulls and booleans, see
+/// `LoadOptions::resolution_scheme`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ResolutionScheme {
+    /// The YAML 1.2 core schema, as `YamlInput::from_str` reads it: `~`, `null`,
+    /// `true` and `false`.
+    #[default]
+    Core,
+    /// YAML 1.1: `yes`, `no`, `on`, `off`, `y` and `n` are booleans as well, and the
+    /// capitalized spellings like `NULL`, `Off` or `Yes` count too.
+    Yaml11,
+}
+
+/// Options controlling how `YamlLoader` turns a source into documents.
+#[derive(Clone, Debug, Default)]
+pub struct LoadOptions {
+    #[cfg(feature = "unicode")]
+    normalize_keys: bool,
+    keep_comments: bool,
+    keep_spelling: bool,
+    keep_scalar_style: bool,
+    raw_scalars: bool,
+    resolve_keys: bool,
+    line_endings: LineEndingPolicy,
+    duplicate_keys: DuplicateKeyPolicy,
+    max_expanded_nodes: Option<usize>,
+    max_alias_depth: Option<usize>,
+    resolution_scheme: ResolutionScheme,
+    replace_invalid_characters: bool,
+    #[cfg(feature = "template")]
+    expand_templates: bool,
+}
+
+/// What the loader does with an entry whose key is already in its mapping, see
+/// `LoadOptions::duplicate_keys`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum DuplicateKeyPolicy {
+    /// The later value replaces the earlier one.
+    #[default]
+    Overwrite,
+    /// The later entry is dropped.
+    KeepFirst,
+    /// The first duplicate key is an error, pointing at that key.
+    Error,
+}
+
+impl LoadOptions {
+    pub fn new() -> LoadOptions {
+        LoadOptions::default()
+    }
+
+    /// Convert string keys of mappings to Unicode normalization form C (NFC).
+    ///
+    /// Editors on different platforms may write the same key in composed or
+    /// decomposed form, which would otherwise load as two distinct keys.
+    #[cfg(feature = "unicode")]
+    pub fn normalize_keys(&mut self, normalize_keys: bool) {
+        self.normalize_keys = normalize_keys;
+    }
+
+    /// Determine if string keys are normalized to NFC while loading.
+    #[cfg(feature = "unicode")]
+    pub fn is_normalize_keys(&self) -> bool {
+        self.normalize_keys
+    }
+
+    /// Keep the `#` comments of the source, wrapping the nodes they belong to in
+    /// `YamlInput::Commented` so they survive a load and emit cycle.
+    pub fn keep_comments(&mut self, keep_comments: bool) {
+        self.keep_comments = keep_comments;
+    }
+
+    /// Determine if comments are kept while loading.
+    pub fn is_keep_comments(&self) -> bool {
+        self.keep_comments
+    }
+
+    /// Keep how plain nulls and booleans are spelled, wrapping them in
+    /// `YamlInput::Spelled` so that `~` or `null` are emitted as written. Nodes are
+    /// read as they are without it: `Null` or `True` stay strings, unless
+    /// `resolution_scheme` is `ResolutionScheme::Yaml11`.
+    ///
+    /// Hexadecimal and octal integers are kept the same way, so a file mode written
+    /// `0o644` is not emitted as `420`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{LoadOptions, YamlEmitter, YamlLoader, YamlOutput};
+    ///
+    /// let mut options = LoadOptions::new();
+    /// options.keep_spelling(true);
+    /// let doc = &YamlLoader::load_from_str_with_options("{mode: 0o644, mask: 0xFF}", &options).unwrap()[0];
+    /// assert_eq!(doc["mode"].as_i64(), Some(0o644));
+    ///
+    /// let mut out = String::new();
+    /// YamlEmitter::new(&mut out).dump(&YamlOutput::from(doc.clone())).unwrap();
+    /// assert_eq!(out, "---\nmode: 0o644\nmask: 0xFF");
+    /// ```
+    pub fn keep_spelling(&mut self, keep_spelling: bool) {
+        self.keep_spelling = keep_spelling;
+    }
+
+    /// Determine if the spelling of nulls, booleans and hexadecimal or octal integers
+    /// is kept while loading.
+    pub fn is_keep_spelling(&self) -> bool {
+        self.keep_spelling
+    }
+
+    /// Keep the style of the quoted strings and of the block scalars, wrapping them in
+    /// `YamlInput::Styled` so that the emitter writes them in the same style when it
+    /// can. Mapping keys stay plain strings, so that entries can be looked up by key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::scanner::TScalarStyle;
+    /// use yaml_rust_formatter::{LoadOptions, YamlEmitter, YamlInput, YamlLoader, YamlOutput};
+    ///
+    /// let mut options = LoadOptions::new();
+    /// options.keep_scalar_style(true);
+    /// let source = "name: 'web'\ncmd: |\n  make\n  make install\n";
+    /// let doc = &YamlLoader::load_from_str_with_options(source, &options).unwrap()[0];
+    /// assert_eq!(
+    ///     doc["name"],
+    ///     YamlInput::Styled(Box::new(YamlInput::String("web".to_owned())), TScalarStyle::SingleQuoted)
+    /// );
+    /// assert_eq!(doc["name"].as_str(), Some("web"));
+    ///
+    /// let mut out = String::new();
+    /// YamlEmitter::new(&mut out).dump(&YamlOutput::from(doc.clone())).unwrap();
+    /// assert_eq!(out, "---\nname: 'web'\ncmd: |\n  make\n  make install\n");
+    /// ```
+    pub fn keep_scalar_style(&mut self, keep_scalar_style: bool) {
+        self.keep_scalar_style = keep_scalar_style;
+    }
+
+    /// Determine if the style of quoted strings and block scalars is kept while
+    /// loading.
+    pub fn is_keep_scalar_style(&self) -> bool {
+        self.keep_scalar_style
+    }
+
+    /// Keep every plain scalar without a tag as `YamlInput::Raw`, its text in the
+    /// source, so that a load and emit cycle never changes how it is written: `0x1F`
+    /// stays `0x1F` and `1.50` stays `1.50`. Accessors like `as_i64` resolve the text
+    /// when called, and `as_str` returns it whatever it resolves to.
+    ///
+    /// Nulls and booleans kept by `keep_spelling` stay `YamlInput::Spelled`. A scalar
+    /// typed by a core tag such as `!!int` is kept as its text in a `YamlInput::Tagged`
+    /// with the tag, and an empty scalar is resolved as usual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{LoadOptions, YamlInput, YamlLoader};
+    ///
+    /// let mut options = LoadOptions::new();
+    /// options.raw_scalars(true);
+    /// let doc = &YamlLoader::load_from_str_with_options("{mode: 0o644, id: 007}", &options).unwrap()[0];
+    /// assert_eq!(doc["mode"], YamlInput::Raw("0o644".to_owned()));
+    /// assert_eq!(doc["mode"].as_i64(), Some(0o644));
+    /// assert_eq!(doc["id"].as_str(), Some("007"));
+    /// assert_eq!(doc["id"].raw_value(), Some(YamlInput::Integer(7)));
+    /// ```
+    pub fn raw_scalars(&mut self, raw_scalars: bool) {
+        self.raw_scalars = raw_scalars;
+    }
+
+    /// Determine if plain scalars are kept as their source text while loading.
+    pub fn is_raw_scalars(&self) -> bool {
+        self.raw_scalars
+    }
+
+    /// Compare mapping keys by the value they resolve to, as the YAML spec requires,
+    /// rather than by how they are written: `&a x`, `*a` and `x` are then the same key.
+    ///
+    /// A later entry with such a key is a duplicate like any other, see
+    /// `duplicate_keys`, and the spelling of the first key is kept so that aliases to
+    /// its anchor stay valid.
+    /// Lookups like `doc["x"]` find such keys whether this is set or not.
+    pub fn resolve_keys(&mut self, resolve_keys: bool) {
+        self.resolve_keys = resolve_keys;
+    }
+
+    /// Determine if mapping keys are compared by their resolved value while loading.
+    pub fn is_resolve_keys(&self) -> bool {
+        self.resolve_keys
+    }
+
+    /// The line endings a source may use. Every ending is read as a line break,
+    /// including a lone CR, and `LineEndingPolicy::Any` (the default) accepts them
+    /// mixed. With a stricter policy the first refused ending is an error.
+    pub fn line_endings(&mut self, line_endings: LineEndingPolicy) {
+        self.line_endings = line_endings;
+    }
+
+    pub fn get_line_endings(&self) -> LineEndingPolicy {
+        self.line_endings
+    }
+
+    /// What to do with an entry whose key is already in its mapping. By default the
+    /// later value replaces the earlier one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{DuplicateKeyPolicy, LoadOptions, YamlLoader};
+    ///
+    /// let source = "a: 1\nb: 2\na: 3\n";
+    /// let mut options = LoadOptions::new();
+    /// options.duplicate_keys(DuplicateKeyPolicy::KeepFirst);
+    /// let doc = &YamlLoader::load_from_str_with_options(source, &options).unwrap()[0];
+    /// assert_eq!(doc["a"].as_i64(), Some(1));
+    ///
+    /// options.duplicate_keys(DuplicateKeyPolicy::Error);
+    /// let err = YamlLoader::load_from_str_with_options(source, &options).unwrap_err();
+    /// assert_eq!(err.to_string(), "duplicate key a at line 3 column 1");
+    /// ```
+    pub fn duplicate_keys(&mut self, duplicate_keys: DuplicateKeyPolicy) {
+        self.duplicate_keys = duplicate_keys;
+    }
+
+    pub fn get_duplicate_keys(&self) -> DuplicateKeyPolicy {
+        self.duplicate_keys
+    }
+
+    /// Limit the number of nodes the aliases of a document copy into it, counting
+    /// the copies made by the aliases inside the aliased nodes. An alias going over
+    /// the limit is an error, which keeps documents nesting aliases like the
+    /// "billion laughs" from exhausting memory. No limit by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{LoadOptions, YamlLoader};
+    ///
+    /// let source = "a: &a [x, x, x]\nb: &b [*a, *a, *a]\nc: [*b, *b, *b]\n";
+    /// let mut options = LoadOptions::new();
+    /// options.max_expanded_nodes(Some(20));
+    /// let err = YamlLoader::load_from_str_with_options(source, &options).unwrap_err();
+    /// assert_eq!(err.to_string(), "aliases expand to more than 20 nodes at line 3 column 5");
+    /// ```
+    pub fn max_expanded_nodes(&mut self, max_expanded_nodes: Option<usize>) {
+        self.max_expanded_nodes = max_expanded_nodes;
+    }
+
+    pub fn get_max_expanded_nodes(&self) -> Option<usize> {
+        self.max_expanded_nodes
+    }
+
+    /// Limit how deeply aliases are nested: an alias of a node without aliases has a
+    /// depth of 1, and an alias of a node holding aliases is one deeper than them. An
+    /// alias going over the limit is an error. No limit by default.
+    pub fn max_alias_depth(&mut self, max_alias_depth: Option<usize>) {
+        self.max_alias_depth = max_alias_depth;
+    }
+
+    pub fn get_max_alias_depth(&self) -> Option<usize> {
+        self.max_alias_depth
+    }
+
+    /// Which plain scalars are nulls and booleans. The YAML 1.2 core schema by
+    /// default; `ResolutionScheme::Yaml11` reads documents written for YAML 1.1
+    /// parsers, where `enabled: yes` is a boolean. An empty value is a null either
+    /// way.
+    ///
+    /// The nulls and booleans of the scheme are kept as `YamlInput::Spelled` by
+    /// `keep_spelling` and `raw_scalars`, so they resolve the same and are emitted as
+    /// written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{LoadOptions, ResolutionScheme, YamlInput, YamlLoader};
+    ///
+    /// let source = "{a: yes, b: Off, c: NULL, d: 'yes'}";
+    /// let mut options = LoadOptions::new();
+    /// options.resolution_scheme(ResolutionScheme::Yaml11);
+    /// let doc = &YamlLoader::load_from_str_with_options(source, &options).unwrap()[0];
+    /// assert_eq!(doc["a"].as_bool(), Some(true));
+    /// assert_eq!(doc["b"].as_bool(), Some(false));
+    /// assert!(doc["c"].is_null());
+    /// assert_eq!(doc["d"].as_str(), Some("yes"));
+    ///
+    /// let doc = &YamlLoader::load_from_str(source).unwrap()[0];
+    /// assert_eq!(doc["a"].as_str(), Some("yes"));
+    /// ```
+    pub fn resolution_scheme(&mut self, resolution_scheme: ResolutionScheme) {
+        self.resolution_scheme = resolution_scheme;
+    }
+
+    pub fn get_resolution_scheme(&self) -> ResolutionScheme {
+        self.resolution_scheme
+    }
+
+    /// Replace the characters YAML does not allow, such as a NUL byte, and lone UTF-16
+    /// surrogates written as `\u` escapes with U+FFFD instead of failing the load.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{LoadOptions, YamlLoader};
+    ///
+    /// let source = "a: x\0y\nb: \"\\ud800\"\n";
+    /// assert!(YamlLoader::load_from_str(source).is_err());
+    ///
+    /// let mut options = LoadOptions::new();
+    /// options.replace_invalid_characters(true);
+    /// let doc = &YamlLoader::load_from_str_with_options(source, &options).unwrap()[0];
+    /// assert_eq!(doc["a"].as_str(), Some("x\u{fffd}y"));
+    /// assert_eq!(doc["b"].as_str(), Some("\u{fffd}"));
+    /// ```
+    pub fn replace_invalid_characters(&mut self, replace_invalid_characters: bool) {
+        self.replace_invalid_characters = replace_invalid_characters;
+    }
+
+    /// Determine if characters YAML does not allow are replaced while loading.
+    pub fn is_replace_invalid_characters(&self) -> bool {
+        self.replace_invalid_characters
+    }
+
+    /// Expand the `!use` nodes of each document with their `!template`, leaving the
+    /// templates out, see the `template` module. A node that cannot be expanded fails
+    /// the load at the end of its document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{LoadOptions, YamlLoader};
+    ///
+    /// let source = "
+    /// - &probe !template {path: '/${name}', port: '${port}'}
+    /// - liveness: !use {template: *probe, with: {name: live, port: 8080}}
+    /// ";
+    /// let mut options = LoadOptions::new();
+    /// options.expand_templates(true);
+    /// let doc = &YamlLoader::load_from_str_with_options(source, &options).unwrap()[0];
+    /// assert_eq!(doc[0]["liveness"]["path"].as_str(), Some("/live"));
+    /// assert_eq!(doc[0]["liveness"]["port"].as_i64(), Some(8080));
+    ///
+    /// let source = "- &t !template ${x}\n- !use {template: *t}\n";
+    /// let err = YamlLoader::load_from_str_with_options(source, &options).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "while expanding templates, [1]: no parameter x is given to the template t at line 3 column 1"
+    /// );
+    /// ```
+    #[cfg(feature = "template")]
+    pub fn expand_templates(&mut self, expand_templates: bool) {
+        self.expand_templates = expand_templates;
+    }
+
+    /// Determine if templates are expanded while loading.
+    #[cfg(feature = "template")]
+    pub fn is_expand_templates(&self) -> bool {
+        self.expand_templates
+    }
+}
+
+pub struct YamlLoader {
+    options: LoadOptions,
+    docs: Vec<YamlInput>,
+    // states
+    // (current node, anchor) tuple
+    doc_stack: Vec<(YamlInput, Option<String>)>,
+    key_stack: Vec<YamlInput>,
+    // start of the current key of each open mapping
+    key_marks: Vec<Marker>,
+    // anchored nodes in the order their names are first defined
+    anchor_map: LinkedHashMap<String, YamlInput>,
+    // the expansion of each anchored node, when it is limited
+    anchor_expansions: HashMap<String, Expansion>,
+    // nodes copied by the aliases of the current document
+    expanded_nodes: usize,
+    // comments of the entry being built, for the document root and each open collection
+    entry_comments: Vec<Comments>,
+    // comment lines waiting for the next entry, with their column
+    pending_comments: Vec<(String, usize)>,
+    // line of the last node that was completed
+    last_line: usize,
+    // custom tag of each open collection
+    tag_stack: Vec<Option<String>>,
+    // start of each open collection
+    start_marks: Vec<Marker>,
+    // marks of the current document, when they are recorded
+    marks: Option<Marks>,
+    doc_marks: Vec<Marks>,
+    // path of each open collection, `None` for a key
+    path_stack: Vec<Option<Path>>,
+    // the first duplicate key refused by the options
+    error: Option<ScanError>,
+}
+
+/// Where each node of a loaded document starts in its source, by path, along with
+/// the keys leading to them.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust_formatter::diff::Path;
+/// use yaml_rust_formatter::{LoadOptions, YamlLoader};
+///
+/// let docs = YamlLoader::load_from_str_with_marks("a:\n  - 1\n  - two\n", &LoadOptions::new())
+///     .unwrap();
+/// let (_, marks) = &docs[0];
+/// let mark = marks.get(&Path::parse("a[1]")).unwrap();
+/// assert_eq!((mark.line(), mark.col()), (3, 4));
+/// ```
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Marks {
+    marks: LinkedHashMap<Path, Marker>,
+    keys: HashMap<Path, Marker>,
+}
+
+impl Marks {
+    /// The position where the node at `path` starts.
+    pub fn get(&self, path: &Path) -> Option<&Marker> {
+        self.marks.get(path)
+    }
+
+    /// The position where the key of the mapping entry at `path` starts.
+    pub fn key(&self, path: &Path) -> Option<&Marker> {
+        self.keys.get(path)
+    }
+
+    /// The recorded nodes, in the order they start.
+    pub fn iter(&self) -> linked_hash_map::Iter<'_, Path, Marker> {
+        self.marks.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.marks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.marks.is_empty()
+    }
+}
+
+impl MarkedEventReceiver for YamlLoader {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        // println!("EV {:?}", ev);
+        match ev {
+            Event::Scalar(..) | Event::Alias(_) => {
+                self.begin_node(mark);
+                self.mark_node(mark);
+                self.last_line = mark.line();
+            }
+            Event::SequenceStart(..) | Event::MappingStart(..) => {
+                self.begin_node(mark);
+                let path = self.mark_node(mark);
+                if self.marks.is_some() {
+                    self.path_stack.push(path);
+                }
+                self.start_marks.push(mark);
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.close_comments();
+                self.start_marks.pop();
+                self.entry_comments.pop();
+                self.path_stack.pop();
+                self.last_line = mark.line();
+            }
+            _ => {}
+        }
+        match ev {
+            Event::DocumentStart => {
+                self.entry_comments = vec![Comments::default()];
+                self.expanded_nodes = 0;
+            }
+            Event::DocumentEnd => {
+                match self.doc_stack.len() {
+                    // empty document
+                    0 => self.docs.push(YamlInput::BadValue),
+                    1 => {
+                        let mut doc = self.doc_stack.pop().unwrap().0;
+                        if !self.pending_comments.is_empty() {
+                            let after = mem::take(&mut self.pending_comments);
+                            let after = after.into_iter().map(|(text, _)| text);
+                            comments_mut(&mut doc).after.extend(after);
+                        }
+                        #[cfg(feature = "template")]
+                        if self.options.expand_templates {
+                            match template::expand_templates(&doc) {
+                                Ok(expanded) => doc = expanded,
+                                Err(err) if self.error.is_none() => {
+                                    let message = format!("while expanding templates, {}", err);
+                                    self.error = Some(ScanError::new(mark, &message));
+                                }
+                                Err(_) => {}
+                            }
+                        }
+                        self.docs.push(doc)
+                    }
+                    _ => unreachable!(),
+                }
+                if let Some(ref mut marks) = self.marks {
+                    self.doc_marks.push(mem::take(marks));
+                }
+            }
+            Event::Comment(text, inline) => self.on_comment(text, inline, mark),
+            Event::SequenceStart(aid, tag) => {
+                self.doc_stack.push((YamlInput::Array(Vec::new()), aid));
+                self.tag_stack.push(custom_tag(&tag));
+                self.entry_comments.push(Comments::default());
+            }
+            Event::SequenceEnd => {
+                let node = self.pop_collection();
+                if let Some(anchor) = node.1 {
+                    self.insert_new_node((
+                        YamlInput::Anchored(anchor.clone(), Box::new(node.0)),
+                        Some(anchor.clone()),
+                    ));
+                } else {
+                    self.insert_new_node(node);
+                }
+            }
+            Event::MappingStart(aid, tag) => {
+                self.doc_stack
+                    .push((YamlInput::Hash(HashInput::new()), aid));
+                self.tag_stack.push(custom_tag(&tag));
+                self.key_stack.push(YamlInput::BadValue);
+                self.key_marks.push(mark);
+                self.entry_comments.push(Comments::default());
+            }
+            Event::MappingEnd => {
+                self.key_stack.pop().unwrap();
+                self.key_marks.pop();
+                let node = self.pop_collection();
+                if let Some(anchor) = node.1 {
+                    self.insert_new_node((
+                        YamlInput::Anchored(anchor.clone(), Box::new(node.0)),
+                        Some(anchor.clone()),
+                    ));
+                } else {
+                    self.insert_new_node(node);
+                }
+            }
+            Event::Scalar(v, style, aid, tag) => {
+                // a custom tag is kept apart, around the node loaded without it
+                let custom = custom_tag(&tag);
+                let tag = if custom.is_some() { None } else { tag };
+                let plain = style == TScalarStyle::Plain && tag.is_none();
+                // a plain scalar folded over a blank line cannot be written as it was read,
+                // nor an empty one, which is written as nothing
+                let verbatim = !v.is_empty() && !has_line_break(&v);
+                let spelled = plain && verbatim;
+                let yaml_1_1 = self.options.resolution_scheme == ResolutionScheme::Yaml11;
+                let scheme_scalar = if yaml_1_1 {
+                    yaml_1_1_scalar(&v)
+                } else {
+                    // what `from_str` reads, so that keeping the spelling changes no value
+                    plain_value(&v)
+                        .filter(|node| matches!(*node, YamlInput::Null | YamlInput::Boolean(_)))
+                };
+                let node = match scheme_scalar {
+                    Some(node)
+                        if spelled
+                            && (self.options.keep_spelling
+                                || (yaml_1_1 && self.options.raw_scalars)) =>
+                    {
+                        YamlInput::Spelled(Box::new(node), v)
+                    }
+                    Some(node) if plain && yaml_1_1 => node,
+                    _ if spelled && self.options.raw_scalars => YamlInput::Raw(v),
+                    // the text of a scalar given its type by a core tag, with the tag
+                    _ if verbatim
+                        && self.options.raw_scalars
+                        && core_scalar_tag(&tag).is_some() =>
+                    {
+                        let node = if style == TScalarStyle::Plain {
+                            YamlInput::Raw(v)
+                        } else {
+                            YamlInput::String(v)
+                        };
+                        YamlInput::Tagged(core_scalar_tag(&tag).unwrap(), Box::new(node))
+                    }
+                    // `0o644` means more to a reader than `420`
+                    _ if spelled
+                        && self.options.keep_spelling
+                        && (v.starts_with("0x") || v.starts_with("0o")) =>
+                    {
+                        match resolve_scalar(v.clone(), style, &tag) {
+                            node @ YamlInput::Integer(_) => YamlInput::Spelled(Box::new(node), v),
+                            node => node,
+                        }
+                    }
+                    _ => resolve_scalar(v, style, &tag),
+                };
+                let is_key = matches!(self.doc_stack.last(), Some((YamlInput::Hash(_), _)))
+                    && self.key_stack.last().is_some_and(|k| k.is_badvalue_exact());
+                let node = match node {
+                    YamlInput::String(_)
+                        if self.options.keep_scalar_style
+                            && style != TScalarStyle::Plain
+                            && !is_key =>
+                    {
+                        YamlInput::Styled(Box::new(node), style)
+                    }
+                    node => node,
+                };
+                let node = match custom {
+                    Some(custom) => YamlInput::Tagged(custom, Box::new(node)),
+                    None => node,
+                };
+
+                if let Some(anchor) = aid {
+                    self.insert_new_node((
+                        YamlInput::Anchored(anchor.clone(), Box::new(node)),
+                        Some(anchor.clone()),
+                    ));
+                } else {
+                    self.insert_new_node((node, None));
+                }
+            }
+            Event::Alias(id) => {
+                let node = YamlInput::Aliased(id.clone(), self.alias_target(&id, mark));
+                self.insert_new_node((node, None));
+            }
+            _ => { /* ignore */ }
+        }
+        // println!("DOC {:?}", self.doc_stack);
+    }
+}
+
+/// The text of `tag` if it is not one of the core schema, which the loader resolves
+/// itself, nor the `!` forcing a string.
+pub(crate) fn custom_tag(tag: &Option<TokenType>) -> Option<String> {
+    match *tag {
+        Some(TokenType::Tag(ref handle, _)) if handle == "!!" => None,
+        Some(TokenType::Tag(ref handle, ref suffix)) if handle.is_empty() => match &**suffix {
+            "!" => None,
+            suffix => Some(format!("!<{}>", suffix)),
+        },
+        Some(TokenType::Tag(ref handle, ref suffix)) => Some(format!("{}{}", handle, suffix)),
+        _ => None,
+    }
+}
+
+/// The text of `tag` if it is one of the core schema tags giving a scalar its type,
+/// which `raw_scalars` keeps with the text of the scalar.
+fn core_scalar_tag(tag: &Option<TokenType>) -> Option<String> {
+    match *tag {
+        Some(TokenType::Tag(ref handle, ref suffix))
+            if handle == "!!" && matches!(suffix.as_str(), "bool" | "int" | "float" | "null") =>
+        {
+            Some(format!("!!{}", suffix))
+        }
+        _ => None,
+    }
+}
+
+/// The comments of `node`, wrapping it in `YamlInput::Commented` if needed.
+pub(crate) fn comments_mut(node: &mut YamlInput) -> &mut Comments {
+    if !matches!(*node, YamlInput::Commented(..)) {
+        let inner = mem::replace(node, YamlInput::BadValue);
+        *node = YamlInput::Commented(Box::new(inner), Comments::default());
+    }
+    match *node {
+        YamlInput::Commented(_, ref mut comments) => comments,
+        _ => unreachable!(),
+    }
+}
+
+impl YamlLoader {
+    fn new(options: LoadOptions, anchor_map: LinkedHashMap<String, YamlInput>) -> YamlLoader {
+        YamlLoader {
+            options,
+            docs: Vec::new(),
+            doc_stack: Vec::new(),
+            key_stack: Vec::new(),
+            key_marks: Vec::new(),
+            anchor_map,
+            anchor_expansions: HashMap::new(),
+            expanded_nodes: 0,
+            entry_comments: vec![Comments::default()],
+            pending_comments: Vec::new(),
+            last_line: 0,
+            start_marks: Vec::new(),
+            tag_stack: Vec::new(),
+            marks: None,
+            doc_marks: Vec::new(),
+            error: None,
+            path_stack: Vec::new(),
+        }
+    }
+
+    /// Record that the node about to be loaded starts at `mark`, returning its path.
+    /// Returns `None` for a key, or when marks are not recorded.
+    fn mark_node(&mut self, mark: Marker) -> Option<Path> {
+        let marks = self.marks.as_mut()?;
+        let path = match (self.doc_stack.last(), self.path_stack.last()) {
+            (None, _) => Path::new(),
+            (Some((YamlInput::Array(ref v), _)), Some(Some(parent))) => {
+                parent.child(Segment::Index(v.len()))
+            }
+            (Some((YamlInput::Hash(_), _)), Some(Some(parent))) => match self.key_stack.last() {
+                Some(key) if !key.is_badvalue_exact() => {
+                    let path = parent.child(key_segment(key));
+                    marks
+                        .keys
+                        .insert(path.clone(), *self.key_marks.last().unwrap());
+                    path
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        marks.marks.insert(path.clone(), mark);
+        Some(path)
+    }
+
+    /// A copy of the node anchored as `name` for an alias at `mark`, unless the copy
+    /// would go over the expansion limits of the options, which is then an error.
+    fn alias_target(&mut self, name: &str, mark: Marker) -> Option<Box<YamlInput>> {
+        let node = self.anchor_map.get(name)?;
+        if let Some(expansion) = self.anchor_expansions.get(name) {
+            let refusal = match (
+                self.options.max_expanded_nodes,
+                self.options.max_alias_depth,
+            ) {
+                (Some(max), _) if self.expanded_nodes + expansion.nodes > max => {
+                    Some(format!("aliases expand to more than {} nodes", max))
+                }
+                (_, Some(max)) if expansion.depth + 1 > max => {
+                    Some(format!("aliases are nested more than {} deep", max))
+                }
+                _ => None,
+            };
+            if let Some(refusal) = refusal {
+                if self.error.is_none() {
+                    self.error = Some(ScanError::new(mark, &refusal));
+                }
+                return None;
+            }
+            self.expanded_nodes += expansion.nodes;
+        }
+        Some(Box::new(node.clone()))
+    }
+
+    /// Whether the next node starts an entry: a sequence item, a mapping key or the root.
+    fn starts_entry(&self) -> bool {
+        match self.doc_stack.last() {
+            None => true,
+            Some((YamlInput::Array(_), _)) => true,
+            Some((YamlInput::Hash(_), _)) => {
+                self.key_stack.last().is_some_and(|k| k.is_badvalue_exact())
+            }
+            _ => false,
+        }
+    }
+
+    fn begin_node(&mut self, mark: Marker) {
+        if let (Some((YamlInput::Hash(_), _)), Some(key)) =
+            (self.doc_stack.last(), self.key_stack.last())
+        {
+            if key.is_badvalue_exact() {
+                *self.key_marks.last_mut().unwrap() = mark;
+            }
+        }
+        if !self.pending_comments.is_empty() && self.starts_entry() {
+            let before = mem::take(&mut self.pending_comments);
+            if let Some(entry) = self.entry_comments.last_mut() {
+                entry
+                    .before
+                    .extend(before.into_iter().map(|(text, _)| text));
+            }
+        }
+    }
+
+    /// Close the innermost collection, wrapped in its custom tag if it has one.
+    fn pop_collection(&mut self) -> (YamlInput, Option<String>) {
+        let (node, anchor) = self.doc_stack.pop().unwrap();
+        match self.tag_stack.pop().unwrap() {
+            Some(tag) => (YamlInput::Tagged(tag, Box::new(node)), anchor),
+            None => (node, anchor),
+        }
+    }
+
+    fn on_comment(&mut self, text: String, inline: bool, mark: Marker) {
+        if !inline {
+            self.pending_comments.push((text, mark.col()));
+            return;
+        }
+        // open collections, the root is done once they are all closed
+        let open = self.entry_comments.len() - 1;
+        let in_hash = matches!(self.doc_stack.last(), Some((YamlInput::Hash(_), _)));
+        let key = self.key_stack.last().filter(|_| in_hash);
+        let value_pending = key.is_some_and(|k| !k.is_badvalue_exact());
+        // after a `?` whose key is still to come, or on the line of a complex key
+        let key_comment = match key {
+            Some(k) if k.is_badvalue_exact() => mark.line() != self.last_line,
+            Some(k) => {
+                matches!(*k.inner(), YamlInput::Array(_) | YamlInput::Hash(_))
+                    && self.key_marks.last().map(Marker::line) == Some(mark.line())
+            }
+            None => false,
+        };
+        if key_comment {
+            if let Some(entry) = self.entry_comments.last_mut() {
+                entry.key_trailing = Some(text);
+            }
+            return;
+        }
+        let root_done = open == 0 && !self.doc_stack.is_empty();
+        if value_pending || (mark.line() != self.last_line && !root_done) {
+            // after a key or a `-` whose node is still to come
+            if let Some(entry) = self.entry_comments.last_mut() {
+                entry.trailing = Some(text);
+            }
+            return;
+        }
+        let last = match self.doc_stack.last_mut() {
+            Some((ref mut node, _)) if open == 0 => Some(node),
+            _ => self.last_entry_mut(),
+        };
+        if let Some(node) = last {
+            comments_mut(node).trailing = Some(text);
+        }
+    }
+
+    /// The node of the last entry of the innermost open collection.
+    fn last_entry_mut(&mut self) -> Option<&mut YamlInput> {
+        match self.doc_stack.last_mut() {
+            Some((YamlInput::Array(ref mut v), _)) => v.last_mut(),
+            Some((YamlInput::Hash(ref mut h), _)) => match h.back().map(|(k, _)| k.clone()) {
+                Some(k) => h.get_mut(&k),
+                None => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Keep the comment lines that end a nested collection, indented within it, after
+    /// its last entry instead of before the entry that follows the collection.
+    fn close_comments(&mut self) {
+        let (start, parent) = match *self.start_marks.as_slice() {
+            [.., parent, start] => (start.col(), parent.col()),
+            // the comments ending the root go after it
+            _ => return,
+        };
+        let closing = self
+            .pending_comments
+            .iter()
+            .take_while(|&&(_, col)| col >= start && col > parent)
+            .count();
+        if closing == 0 || self.last_entry_mut().is_none() {
+            return;
+        }
+        let after: Vec<_> = self.pending_comments.drain(..closing).collect();
+        if let Some(node) = self.last_entry_mut() {
+            comments_mut(node)
+                .after
+                .extend(after.into_iter().map(|(text, _)| text));
+        }
+    }
+
+    /// Wrap a completed entry with the comments collected for it.
+    fn take_entry_comments(&mut self, node: YamlInput) -> YamlInput {
+        match self.entry_comments.last_mut() {
+            Some(entry) if !entry.is_empty() => {
+                YamlInput::Commented(Box::new(node), mem::take(entry))
+            }
+            _ => node,
+        }
+    }
+
+    fn insert_new_node(&mut self, node: (YamlInput, Option<String>)) {
+        // valid anchor id starts from 1
+        if let Some(anchor) = node.1.as_ref() {
+            // a redefined anchor keeps the place of its first definition
+            match self.anchor_map.get_mut(anchor) {
+                Some(anchored) => *anchored = node.0.clone(),
+                None => {
+                    self.anchor_map.insert(anchor.clone(), node.0.clone());
+                }
+            }
+            if self.options.max_expanded_nodes.is_some() || self.options.max_alias_depth.is_some() {
+                self.anchor_expansions
+                    .insert(anchor.clone(), Expansion::of(&node.0));
+            }
+        }
+        let completes_entry = match self.doc_stack.last() {
+            None | Some((YamlInput::Array(_), _)) => true,
+            Some((YamlInput::Hash(_), _)) => self
+                .key_stack
+                .last()
+                .is_some_and(|k| !k.is_badvalue_exact()),
+            _ => false,
+        };
+        let node = if completes_entry {
+            (self.take_entry_comments(node.0), node.1)
+        } else {
+            node
+        };
+        if self.doc_stack.is_empty() {
+            self.doc_stack.push(node);
+        } else {
+            let parent = self.doc_stack.last_mut().unwrap();
+            match *parent {
+                (YamlInput::Array(ref mut v), _) => v.push(node.0),
+                (YamlInput::Hash(ref mut h), _) => {
+                    let cur_key = self.key_stack.last_mut().unwrap();
+                    // current node is a key
+                    if cur_key.is_badvalue_exact() {
+                        *cur_key = match node.0 {
+                            #[cfg(feature = "unicode")]
+                            YamlInput::String(key) if self.options.normalize_keys => {
+                                YamlInput::String(key.nfc().collect())
+                            }
+                            key => key,
+                        };
+                    // current node is a value
+                    } else {
+                        let mut newkey = YamlInput::BadValue;
+                        mem::swap(&mut newkey, cur_key);
+                        if self.options.resolve_keys {
+                            if let Some(key) = find_resolved_key(h, &newkey) {
+                                newkey = key.clone();
+                            }
+                        }
+                        if !h.contains_key(&newkey) {
+                            h.insert(newkey, node.0);
+                            return;
+                        }
+                        match self.options.duplicate_keys {
+                            DuplicateKeyPolicy::Overwrite => {
+                                h.insert(newkey, node.0);
+                            }
+                            DuplicateKeyPolicy::KeepFirst => {}
+                            DuplicateKeyPolicy::Error => {
+                                if self.error.is_none() {
+                                    let name = Path::new().child(key_segment(&newkey));
+                                    self.error = Some(ScanError::new(
+                                        *self.key_marks.last().unwrap(),
+                                        &format!("duplicate key {}", name),
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// Build the node described by the events of a single node, resolving its aliases
+    /// with `anchors`.
+    #[cfg(feature = "lint")]
+    pub(crate) fn load_from_events(
+        events: Vec<(Event, Marker)>,
+        anchors: LinkedHashMap<String, YamlInput>,
+    ) -> YamlInput {
+        let mut loader = YamlLoader::new(LoadOptions::default(), anchors);
+        for (ev, mark) in events {
+            loader.on_event(ev, mark);
+        }
+        loader
+            .doc_stack
+            .pop()
+            .map_or(YamlInput::BadValue, |node| node.0)
+    }
+
+    pub fn load_from_str(source: &str) -> Result<Vec<YamlInput>, ScanError> {
+        Self::load_from_str_with_options(source, &LoadOptions::default())
+    }
+
+    pub fn load_from_str_with_options(
+        source: &str,
+        options: &LoadOptions,
+    ) -> Result<Vec<YamlInput>, ScanError> {
+        Ok(Self::load_str(source, options, false)?.docs)
+    }
+
+    /// Load the documents of `source` into the borrowed model, whose strings and floats
+    /// point into `source` when they are written there as they read: the scanner leaves
+    /// the text of these scalars in the source instead of reading it into a `String`,
+    /// so loading them allocates nothing for their text. Only scalars with escapes,
+    /// folded lines or block scalars are copied.
+    ///
+    /// Anchors, custom tags and comments are not kept, see `borrowed::Yaml`.
+    #[cfg(feature = "borrowed")]
+    pub fn load_from_str_borrowed(source: &str) -> Result<Vec<Yaml<'_>>, ScanError> {
+        Self::load_from_str_borrowed_with_options(source, &LoadOptions::default())
+    }
+
+    /// Like `load_from_str_borrowed`, with the alias limits of `options`, see
+    /// `LoadOptions::max_expanded_nodes` and `LoadOptions::max_alias_depth`. The other
+    /// options do not apply to the borrowed model.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{LoadOptions, YamlLoader};
+    ///
+    /// let source = "a: &a [x, x, x]\nb: &b [*a, *a, *a]\nc: [*b, *b, *b]\n";
+    /// let mut options = LoadOptions::new();
+    /// options.max_expanded_nodes(Some(20));
+    /// let err = YamlLoader::load_from_str_borrowed_with_options(source, &options).unwrap_err();
+    /// assert_eq!(err.to_string(), "aliases expand to more than 20 nodes at line 3 column 5");
+    /// ```
+    #[cfg(feature = "borrowed")]
+    pub fn load_from_str_borrowed_with_options<'a>(
+        source: &'a str,
+        options: &LoadOptions,
+    ) -> Result<Vec<Yaml<'a>>, ScanError> {
+        let start = metrics::Start::now();
+        let result = BorrowedLoader::load(source, options);
+        match result {
+            Ok(ref loader) => metrics::loaded(start, Some(source.len()), loader.docs.len()),
+            Err(_) => metrics::failed("parse"),
+        }
+        Ok(result?.docs)
+    }
+
+    /// Load the documents of `source`, each with the position where its nodes start,
+    /// so that problems found in them can be pointed at in the source.
+    pub fn load_from_str_with_marks(
+        source: &str,
+        options: &LoadOptions,
+    ) -> Result<Vec<(YamlInput, Marks)>, ScanError> {
+        let loader = Self::load_str(source, options, true)?;
+        Ok(loader.docs.into_iter().zip(loader.doc_marks).collect())
+    }
+
+    fn load_str(source: &str, options: &LoadOptions, marks: bool) -> Result<YamlLoader, ScanError> {
+        let start = metrics::Start::now();
+        let result = Self::parse_str(source, options, marks);
+        match result {
+            Ok(ref loader) => metrics::loaded(start, Some(source.len()), loader.docs.len()),
+            Err(_) => metrics::failed("parse"),
+        }
+        result
+    }
+
+    fn parse_str(
+        source: &str,
+        options: &LoadOptions,
+        marks: bool,
+    ) -> Result<YamlLoader, ScanError> {
+        if let Some((mark, message)) = line_ending::refused(source, options.line_endings).first() {
+            return Err(ScanError::new(*mark, message));
+        }
+        let mut loader = YamlLoader::new(options.clone(), LinkedHashMap::new());
+        if marks {
+            loader.marks = Some(Marks::default());
+        }
+        let mut parser = Parser::new(source.chars());
+        parser.keep_comments(options.keep_comments);
+        parser.replace_invalid_characters(options.replace_invalid_characters);
+        let result = parser.load(&mut loader, true);
+        // a duplicate key comes before any parse error
+        if let Some(err) = loader.error.take() {
+            return Err(err);
+        }
+        result?;
+        Ok(loader)
+    }
+
+    #[cfg(feature = "stream")]
+    pub fn iter_documents(source: &str) -> Documents<'_> {
+        Self::iter_documents_with_options(source, &LoadOptions::default())
+    }
+
+    /// Load the documents of `source` one at a time as the iterator is advanced,
+    /// keeping only the current one in memory. The first error ends the iteration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let mut docs = YamlLoader::iter_documents("kind: Service\n---\nkind: [\n");
+    /// let doc = docs.next().unwrap().unwrap();
+    /// assert_eq!(doc["kind"].as_str(), Some("Service"));
+    /// assert!(docs.next().unwrap().is_err());
+    /// assert!(docs.next().is_none());
+    /// ```
+    #[cfg(feature = "stream")]
+    pub fn iter_documents_with_options<'a>(
+        source: &'a str,
+        options: &LoadOptions,
+    ) -> Documents<'a> {
+        let refused = line_ending::refused(source, options.line_endings)
+            .first()
+            .map(|(mark, message)| ScanError::new(*mark, message));
+        let mut parser = Parser::new(source.chars());
+        parser.keep_comments(options.keep_comments);
+        parser.replace_invalid_characters(options.replace_invalid_characters);
+        Documents {
+            parser,
+            loader: YamlLoader::new(options.clone(), LinkedHashMap::new()),
+            refused,
+            done: false,
+        }
+    }
+
+    pub fn load_from_reader<R: io::Read>(reader: R) -> Result<Vec<YamlInput>, LoadError> {
+        Self::load_from_reader_with_options(reader, &LoadOptions::default())
+    }
+
+    /// Load every document of the UTF-8 text read from `reader`, decoding it as the
+    /// parser goes instead of reading it all first. Only the documents are kept in
+    /// memory, next to a small buffer of input.
+    ///
+    /// A line ending refused by `options.get_line_endings()` is reported when it is
+    /// reached, so an error earlier in the source comes first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let docs = YamlLoader::load_from_reader("a: 1\n---\nb: 2\n".as_bytes()).unwrap();
+    /// assert_eq!(docs.len(), 2);
+    /// assert_eq!(docs[1]["b"].as_i64(), Some(2));
+    /// ```
+    pub fn load_from_reader_with_options<R: io::Read>(
+        reader: R,
+        options: &LoadOptions,
+    ) -> Result<Vec<YamlInput>, LoadError> {
+        let start = metrics::Start::now();
+        let result = Self::parse_reader(reader, options);
+        match result {
+            Ok(ref docs) => metrics::loaded(start, None, docs.len()),
+            Err(LoadError::Io(_)) => metrics::failed("read"),
+            Err(LoadError::Scan(_)) => metrics::failed("parse"),
+        }
+        result
+    }
+
+    fn parse_reader<R: io::Read>(
+        reader: R,
+        options: &LoadOptions,
+    ) -> Result<Vec<YamlInput>, LoadError> {
+        let mut chars = ReadChars::new(reader, options.line_endings);
+        let mut loader = YamlLoader::new(options.clone(), LinkedHashMap::new());
+        let result = {
+            let mut parser = Parser::new(&mut chars);
+            parser.keep_comments(options.keep_comments);
+            parser.replace_invalid_characters(options.replace_invalid_characters);
+            parser.load(&mut loader, true)
+        };
+        // the input stops at the first failure, which then explains any parse error
+        if let Some(err) = chars.error.take() {
+            return Err(err);
+        }
+        if let Some(err) = loader.error.take() {
+            return Err(err.into());
+        }
+        result?;
+        Ok(loader.docs)
+    }
+}
+
+/// The documents of a source, loaded one at a time, see `YamlLoader::iter_documents`.
+#[cfg(feature = "stream")]
+pub struct Documents<'a> {
+    parser: Parser<Chars<'a>>,
+    loader: YamlLoader,
+    refused: Option<ScanError>,
+    done: bool,
+}
+
+#[cfg(feature = "stream")]
+impl<'a> Iterator for Documents<'a> {
+    type Item = Result<YamlInput, ScanError>;
+
+    fn next(&mut self) -> Option<Result<YamlInput, ScanError>> {
+        if self.done {
+            return None;
+        }
+        if let Some(refused) = self.refused.take() {
+            self.done = true;
+            metrics::failed("parse");
+            return Some(Err(refused));
+        }
+        let start = metrics::Start::now();
+        let result = self.parser.load(&mut self.loader, false);
+        if let Some(err) = self.loader.error.take().or(result.err()) {
+            self.done = true;
+            metrics::failed("parse");
+            return Some(Err(err));
+        }
+        // nothing is left once the end of the stream is reached
+        let doc = self.loader.docs.pop();
+        self.done = doc.is_none();
+        if doc.is_some() {
+            metrics::loaded(start, None, 1);
+        }
+        doc.map(Ok)
+    }
+}
+
+/// The reasons `YamlLoader::load_from_reader` can fail.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The reader failed, or the text is not valid UTF-8.
+    Io(io::Error),
+    Scan(ScanError),
+}
+
+impl Error for LoadError {
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            LoadError::Io(ref err) => Some(err),
+            LoadError::Scan(ref err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref err) => fmt::Display::fmt(err, formatter),
+            LoadError::Scan(ref err) => fmt::Display::fmt(err, formatter),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+impl From<ScanError> for LoadError {
+    fn from(err: ScanError) -> LoadError {
+        LoadError::Scan(err)
+    }
+}
+
+/// The characters of the UTF-8 text read from a reader, decoded through a small
+/// buffer. The first failure, or line ending refused by the policy, ends them and is
+/// kept in `error`.
+struct ReadChars<R> {
+    reader: R,
+    buf: Box<[u8]>,
+    start: usize,
+    end: usize,
+    policy: LineEndingPolicy,
+    tracker: Tracker,
+    expected: Option<LineEnding>,
+    error: Option<LoadError>,
+}
+
+impl<R: io::Read> ReadChars<R> {
+    fn new(reader: R, policy: LineEndingPolicy) -> ReadChars<R> {
+        ReadChars {
+            reader,
+            buf: vec![0; 8 * 1024].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            policy,
+            tracker: Tracker::new(),
+            expected: None,
+            error: None,
+        }
+    }
+
+    /// Make at least `len` bytes available, unless the input ends first.
+    fn fill(&mut self, len: usize) -> io::Result<()> {
+        if self.end - self.start >= len {
+            return Ok(());
+        }
+        self.buf.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+        while self.end < len {
+            match self.reader.read(&mut self.buf[self.end..]) {
+                Ok(0) => break,
+                Ok(n) => self.end += n,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    fn decode(&mut self) -> io::Result<Option<char>> {
+        self.fill(1)?;
+        let len = match self.buf[self.start..self.end].first() {
+            None => return Ok(None),
+            Some(0x00..=0x7f) => 1,
+            Some(0xc2..=0xdf) => 2,
+            Some(0xe0..=0xef) => 3,
+            Some(0xf0..=0xf4) => 4,
+            Some(_) => 0,
+        };
+        self.fill(len)?;
+        let bytes = &self.buf[self.start..self.end.min(self.start + len)];
+        match std::str::from_utf8(bytes) {
+            Ok(s) if len > 0 && bytes.len() == len => {
+                self.start += len;
+                Ok(s.chars().next())
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            )),
+        }
+    }
+
+    /// Record `ending` as an error if the policy refuses it.
+    fn check(&mut self, ending: Option<(LineEnding, Marker)>) {
+        if let Some((ending, mark)) = ending {
+            let expected = *self
+                .expected
+                .get_or_insert(self.policy.expected(ending).unwrap_or(ending));
+            if self.policy != LineEndingPolicy::Any && ending != expected {
+                let message = line_ending::refusal(ending, expected);
+                self.error = Some(LoadError::Scan(ScanError::new(mark, &message)));
+            }
+        }
+    }
+}
+
+impl<R: io::Read> Iterator for ReadChars<R> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.decode() {
+            Ok(Some(c)) => {
+                if self.policy != LineEndingPolicy::Any {
+                    let ending = self.tracker.push(c);
+                    self.check(ending);
+                }
+                if self.error.is_some() {
+                    None
+                } else {
+                    Some(c)
+                }
+            }
+            Ok(None) => {
+                let ending = self.tracker.finish();
+                self.check(ending);
+                None
+            }
+            Err(err) => {
+                self.error = Some(LoadError::Io(err));
+                None
+            }
+        }
+    }
+}
+
+macro_rules! define_as (
+    ($name:ident, $t:ident, $yt:ident) => (
+pub fn $name(&self) -> Option<$t> {
+    match *self {
+        Self::$yt(v) => Some(v),
+        Self::Aliased(ref _s, ref v_opt) => v_opt.as_ref().map(|v| v.$name()).flatten(),
+        Self::Anchored(ref _s, ref v) | Self::Tagged(ref _s, ref v) => v.$name(),
+        Self::Commented(ref v, _) | Self::Spelled(ref v, _) | Self::Styled(ref v, _) => v.$name(),
+        Self::Raw(ref v) => Self::from_str(v).$name(),
+        _ => None
+    }
+}
+    );
+);
+
+macro_rules! define_as_ref (
+    ($name:ident, $t:ty, $yt:ident) => (
+pub fn $name(&self) -> Option<$t> {
+    match *self {
+        Self::$yt(ref v) => Some(v),
+        Self::Aliased(ref _s, ref v_opt) => v_opt.as_ref().map(|v| v.$name()).flatten(),
+        Self::Anchored(ref _s, ref v) | Self::Tagged(ref _s, ref v) => v.$name(),
+        Self::Commented(ref v, _) | Self::Spelled(ref v, _) | Self::Styled(ref v, _) => v.$name(),
+        _ => None
+    }
+}
+    );
+);
+
+macro_rules! define_as_mut (
+    ($name:ident, $t:ty, $yt:ident) => (
+pub fn $name(&mut self) -> Option<$t> {
+    match *self {
+        Self::$yt(ref mut v) => Some(v),
+        Self::Anchored(_, ref mut v) | Self::Tagged(_, ref mut v) => v.$name(),
+        Self::Commented(ref mut v, _) | Self::Spelled(ref mut v, _) | Self::Styled(ref mut v, _) => v.$name(),
+        _ => None
+    }
+}
+    );
+);
+
+macro_rules! define_into (
+    ($name:ident, $t:ty, $yt:ident) => (
+pub fn $name(self) -> Option<$t> {
+    match self {
+        Self::$yt(v) => Some(v),
+        Self::Aliased(_s, v_opt) => v_opt.map(|v| v.$name()).flatten(),
+        Self::Anchored(_s, v) | Self::Tagged(_s, v) => v.$name(),
+        Self::Commented(v, _) | Self::Spelled(v, _) | Self::Styled(v, _) => v.$name(),
+        Self::Raw(ref v) => Self::from_str(v).$name(),
+        _ => None
+    }
+}
+    );
+);
+
+impl YamlOutput {
+    define_as_mut!(as_hash_mut, &mut HashOutput, Hash);
+    define_as_mut!(as_vec_mut, &mut ArrayOutput, Array);
+
+    /// The node this node wraps, seeing through anchors, tags, comments, spellings and
+    /// styles.
+    pub(crate) fn inner(&self) -> &Self {
+        match *self {
+            Self::Anchored(_, ref v)
+            | Self::Tagged(_, ref v)
+            | Self::Commented(ref v, _)
+            | Self::Spelled(ref v, _)
+            | Self::Styled(ref v, _) => v.inner(),
+            _ => self,
+        }
+    }
+
+    /// The value of the string key `key` of a mapping, to edit it in place.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::YamlOutput;
+    ///
+    /// let mut doc = YamlOutput::Hash(Default::default());
+    /// doc["name"] = "web".into();
+    /// *doc.get_mut("name").unwrap() = "db".into();
+    /// assert_eq!(doc["name"], YamlOutput::from("db"));
+    /// assert!(doc.get_mut("port").is_none());
+    /// ```
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.as_hash_mut()?.get_mut(&Self::String(key.to_owned()))
+    }
+
+    /// The entry of the string key `key` of a mapping, `None` for other nodes.
+    pub fn entry(&mut self, key: &str) -> Option<HashEntry<'_, Self>> {
+        Some(self.as_hash_mut()?.entry(Self::String(key.to_owned())))
+    }
+}
+
+impl YamlInput {
+    define_as!(as_bool, bool, Boolean);
+    define_as!(as_i64, i64, Integer);
+
+    define_as_ref!(as_hash, &HashInput, Hash);
+    define_as_ref!(as_vec, &ArrayInput, Array);
+
+    // an alias holds a copy of its node, so editing it would not change the anchor
+    define_as_mut!(as_hash_mut, &mut HashInput, Hash);
+    define_as_mut!(as_vec_mut, &mut ArrayInput, Array);
+
+    /// The node this node wraps, seeing through anchors, tags, comments, spellings and
+    /// styles but not aliases.
+    pub(crate) fn inner(&self) -> &Self {
+        match *self {
+            Self::Anchored(_, ref v)
+            | Self::Tagged(_, ref v)
+            | Self::Commented(ref v, _)
+            | Self::Spelled(ref v, _)
+            | Self::Styled(ref v, _) => v.inner(),
+            _ => self,
+        }
+    }
+
+    /// The node this node wraps, to edit it in place, see `inner`.
+    #[cfg(any(feature = "formatter", feature = "query"))]
+    pub(crate) fn inner_mut(&mut self) -> &mut Self {
+        match *self {
+            Self::Anchored(_, ref mut v)
+            | Self::Tagged(_, ref mut v)
+            | Self::Commented(ref mut v, _)
+            | Self::Spelled(ref mut v, _)
+            | Self::Styled(ref mut v, _) => v.inner_mut(),
+            _ => self,
+        }
+    }
+
+    define_into!(into_bool, bool, Boolean);
+    define_into!(into_i64, i64, Integer);
+    define_into!(into_hash, HashInput, Hash);
+    define_into!(into_vec, ArrayInput, Array);
+
+    /// The entries of a mapping in input order, see [`Entries`]. Nothing for other nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str("{b: 1, c: 2, a: 3}").unwrap()[0];
+    /// let keys: Vec<_> = doc.entries().map(|(k, _)| k.as_str().unwrap()).collect();
+    /// assert_eq!(keys, ["b", "c", "a"]);
+    /// ```
+    pub fn entries(&self) -> Entries<'_, Self> {
+        match self.as_hash() {
+            Some(h) => Entries::new(h),
+            None => Entries { iter: None },
+        }
+    }
+
+    /// The value of the string key `key` of a mapping, or else of the first key in
+    /// input order equal to it ignoring ASCII case. Only `A` to `Z` match their
+    /// lowercase forms, whatever the locale, so `Name` finds `name` while `İd` does not
+    /// find `id`. Keys are looked up as `self[key]` does; the document is unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str("Name: a\nname: b\nPORT: 80").unwrap()[0];
+    /// assert_eq!(doc.get_ignore_ascii_case("name").and_then(|v| v.as_str()), Some("b"));
+    /// assert_eq!(doc.get_ignore_ascii_case("NAME").and_then(|v| v.as_str()), Some("a"));
+    /// assert_eq!(doc.get_ignore_ascii_case("port").and_then(|v| v.as_i64()), Some(80));
+    /// assert!(doc.get_ignore_ascii_case("host").is_none());
+    /// ```
+    pub fn get_ignore_ascii_case(&self, key: &str) -> Option<&Self> {
+        match self[key] {
+            Self::BadValue => self
+                .entries()
+                .find(|(k, _)| k.as_str().is_some_and(|k| k.eq_ignore_ascii_case(key)))
+                .map(|(_, v)| v),
+            ref v => Some(v),
+        }
+    }
+
+    /// The node at `path`, written like `spec.containers[0].image`, see [`Path::parse`].
+    /// Anchors and aliases along the way are followed; the node is returned as it is
+    /// stored, so it may itself be anchored, tagged or commented.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str(
+    ///     "base: &c {image: web}\nspec:\n  containers: [*c, {image: db}]",
+    /// )
+    /// .unwrap()[0];
+    /// assert_eq!(doc.get_path("spec.containers[0].image").and_then(|v| v.as_str()), Some("web"));
+    /// assert_eq!(doc.get_path("spec.containers[1].image").and_then(|v| v.as_str()), Some("db"));
+    /// assert!(doc.get_path("spec.containers[2]").is_none());
+    /// ```
+    pub fn get_path(&self, path: &str) -> Option<&Self> {
+        self.get_at(&Path::parse(path))
+    }
+
+    /// The node at `path`, see [`get_path`](Self::get_path).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::diff::Path;
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str("ports: {80: http, 443: https}").unwrap()[0];
+    /// let path = Path::new().key("ports").key("443");
+    /// assert_eq!(doc.get_at(&path).and_then(|v| v.as_str()), Some("https"));
+    /// ```
+    pub fn get_at(&self, path: &Path) -> Option<&Self> {
+        path.segments().iter().try_fold(self, |node, segment| {
+            let (child, key) = match *segment {
+                Segment::Index(i) => (&node[i], Segment::Key(i.to_string())),
+                Segment::Key(ref k) => (&node[k.as_str()], segment.clone()),
+            };
+            // keys that are not strings are matched by their text, as `Path` writes them
+            let child = match *child {
+                Self::BadValue => node
+                    .entries()
+                    .find(|(k, _)| key_segment(k) == key)
+                    .map_or(&BAD_VALUE, |(_, v)| v),
+                _ => child,
+            };
+            match *child {
+                Self::BadValue => None,
+                _ => Some(child),
+            }
+        })
+    }
+
+    /// The node at the JSON Pointer (RFC 6901) `pointer`, like `/spec/containers/0`,
+    /// where `~1` stands for `/` and `~0` for `~`. The empty pointer is the node itself.
+    /// Nodes are found as [`get_at`](Self::get_at) finds them; `None` if the pointer is
+    /// not valid or nothing is there.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str(
+    ///     "spec: {containers: [{image: web}], a/b: 1, '~': 2, '0': zero}",
+    /// )
+    /// .unwrap()[0];
+    /// let image = doc.resolve_pointer("/spec/containers/0/image");
+    /// assert_eq!(image.and_then(|v| v.as_str()), Some("web"));
+    /// assert_eq!(doc.resolve_pointer("/spec/a~1b").and_then(|v| v.as_i64()), Some(1));
+    /// assert_eq!(doc.resolve_pointer("/spec/~0").and_then(|v| v.as_i64()), Some(2));
+    /// assert_eq!(doc.resolve_pointer("/spec/0").and_then(|v| v.as_str()), Some("zero"));
+    /// assert_eq!(doc.resolve_pointer(""), Some(doc));
+    /// assert!(doc.resolve_pointer("/spec/containers/1").is_none());
+    /// assert!(doc.resolve_pointer("spec").is_none());
+    /// ```
+    pub fn resolve_pointer(&self, pointer: &str) -> Option<&Self> {
+        self.get_at(&Path::from_pointer(pointer)?)
+    }
+
+    /// The value of the string key `key` of a mapping, to edit it in place. Keys are
+    /// looked up as `self[key]` does, but aliases are not followed: an alias holds a
+    /// copy of its node, so the anchored node would not change.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{YamlInput, YamlLoader};
+    ///
+    /// let mut doc = YamlLoader::load_from_str("spec: &s {replicas: 1}").unwrap().remove(0);
+    /// *doc["spec"].get_mut("replicas").unwrap() = YamlInput::Integer(3);
+    /// assert_eq!(doc["spec"]["replicas"].as_i64(), Some(3));
+    /// assert!(doc.get_mut("status").is_none());
+    /// ```
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Self> {
+        let h = self.as_hash_mut()?;
+        let key = hash_key(h, key)?;
+        h.get_mut(&key)
+    }
+
+    /// The entry of the string key `key` of a mapping, to insert or update its value;
+    /// `None` for other nodes. An existing key is found as with `get_mut`, a new one is
+    /// added as a string at the end of the mapping.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{YamlInput, YamlLoader};
+    ///
+    /// let mut doc = YamlLoader::load_from_str("replicas: 1").unwrap().remove(0);
+    /// doc.entry("replicas")
+    ///     .unwrap()
+    ///     .and_modify(|v| *v = YamlInput::Integer(v.as_i64().unwrap() + 1))
+    ///     .or_insert(YamlInput::Integer(1));
+    /// doc.entry("paused").unwrap().or_insert(YamlInput::Boolean(false));
+    /// assert_eq!(doc["replicas"].as_i64(), Some(2));
+    /// assert_eq!(doc["paused"].as_bool(), Some(false));
+    /// ```
+    pub fn entry(&mut self, key: &str) -> Option<HashEntry<'_, Self>> {
+        let h = self.as_hash_mut()?;
+        let key = hash_key(h, key).unwrap_or_else(|| Self::String(key.to_owned()));
+        Some(h.entry(key))
+    }
+
+    /// The text of a string, or of a raw scalar whatever it resolves to.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Self::String(ref v) | Self::Raw(ref v) => Some(v),
+            Self::Aliased(_, ref v) => v.as_ref().and_then(|v| v.as_str()),
+            Self::Anchored(_, ref v)
+            | Self::Tagged(_, ref v)
+            | Self::Commented(ref v, _)
+            | Self::Spelled(ref v, _)
+            | Self::Styled(ref v, _) => v.as_str(),
+            _ => None,
+        }
+    }
+
+    /// The bytes of a `!!binary` scalar.
+    pub fn as_binary(&self) -> Option<&[u8]> {
+        match *self {
+            Self::Binary(ref v) => Some(v),
+            Self::Aliased(_, ref v) => v.as_ref().and_then(|v| v.as_binary()),
+            Self::Anchored(_, ref v) | Self::Commented(ref v, _) => v.as_binary(),
+            _ => None,
+        }
+    }
+
+    pub fn into_string(self) -> Option<String> {
+        match self {
+            Self::String(v) | Self::Raw(v) => Some(v),
+            Self::Aliased(_, v) => v.and_then(|v| v.into_string()),
+            Self::Anchored(_, v)
+            | Self::Tagged(_, v)
+            | Self::Commented(v, _)
+            | Self::Spelled(v, _)
+            | Self::Styled(v, _) => v.into_string(),
+            _ => None,
+        }
+    }
+
+    /// The value the text of a raw scalar stands for, resolved on each call, e.g.
+    /// `Integer(31)` for `0x1F`. `None` for other nodes.
+    pub fn raw_value(&self) -> Option<YamlInput> {
+        match *self {
+            Self::Raw(ref v) => Some(Self::from_str(v)),
+            Self::Aliased(_, ref v) => v.as_ref().and_then(|v| v.raw_value()),
+            Self::Anchored(_, ref v) | Self::Tagged(_, ref v) | Self::Commented(ref v, _) => {
+                v.raw_value()
+            }
+            _ => None,
+        }
+    }
+
+    /// Check the node behind anchors, aliases, comments and spellings, or the value a
+    /// raw scalar resolves to, like the `as_*` accessors do.
+    fn is_resolved(&self, check: fn(&YamlInput) -> bool) -> bool {
+        match *resolve(self) {
+            Self::Raw(ref v) => check(&Self::from_str(v)),
+            ref node => check(node),
+        }
+    }
+
+    /// Determine if the node is a null, also behind an anchor or alias.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str("{a: &n ~, b: *n}").unwrap()[0];
+    /// assert!(doc["a"].is_null() && doc["b"].is_null());
+    /// assert!(!doc["b"].is_null_exact());
+    /// ```
+    pub fn is_null(&self) -> bool {
+        self.is_resolved(|node| matches!(*node, Self::Null))
+    }
+
+    /// Determine if the node is a `BadValue`, also behind an anchor or alias.
+    pub fn is_badvalue(&self) -> bool {
+        self.is_resolved(|node| matches!(*node, Self::BadValue))
+    }
+
+    /// Determine if the node is a sequence, also behind an anchor or alias.
+    pub fn is_array(&self) -> bool {
+        self.is_resolved(|node| matches!(*node, Self::Array(_)))
+    }
+
+    /// Determine if the node is a mapping, also behind an anchor or alias.
+    pub fn is_hash(&self) -> bool {
+        self.is_resolved(|node| matches!(*node, Self::Hash(_)))
+    }
+
+    /// Determine if the node is a string, also behind an anchor or alias. A raw scalar
+    /// is one if its text does not read as another type.
+    pub fn is_string(&self) -> bool {
+        self.is_resolved(|node| matches!(*node, Self::String(_)))
+    }
+
+    /// Determine if the node is an integer, also behind an anchor or alias.
+    pub fn is_integer(&self) -> bool {
+        self.is_resolved(|node| matches!(*node, Self::Integer(_)))
+    }
+
+    /// Determine if the node is a float, also behind an anchor or alias.
+    pub fn is_real(&self) -> bool {
+        self.is_resolved(|node| matches!(*node, Self::Real(_)))
+    }
+
+    /// Determine if the node itself is `YamlInput::Null`, not an anchor, alias or
+    /// other wrapper around one.
+    pub fn is_null_exact(&self) -> bool {
+        matches!(*self, Self::Null)
+    }
+
+    /// Determine if the node itself is `YamlInput::BadValue`.
+    pub fn is_badvalue_exact(&self) -> bool {
+        matches!(*self, Self::BadValue)
+    }
+
+    /// Determine if the node itself is `YamlInput::Array`.
+    pub fn is_array_exact(&self) -> bool {
+        matches!(*self, Self::Array(_))
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Self::Real(ref v) => parse_f64(v),
+            Self::Raw(ref v) => Self::from_str(v).as_f64(),
+            _ => None,
+        }
+    }
+
+    pub fn into_f64(self) -> Option<f64> {
+        match self {
+            Self::Real(ref v) => parse_f64(v),
+            Self::Raw(ref v) => Self::from_str(v).as_f64(),
+            _ => None,
+        }
+    }
+
+    /// The names of the anchors defined in this node and below it, each once, in the
+    /// order they are first defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str("b: &z 1\na: &y [&x 2, *z]\nc: &z 3").unwrap()[0];
+    /// assert_eq!(doc.anchors(), vec!["z", "y", "x"]);
+    /// ```
+    pub fn anchors(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        collect_anchors(self, &mut names);
+        names
+    }
+
+    /// The number of nodes of this node once its aliases are expanded, counting keys,
+    /// values and the collections holding them. It is computed from the anchored
+    /// nodes, in time linear in the size of the document as written, so that a quota
+    /// on the expanded size can be checked before anything is expanded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str("a: &a [x, x]\nb: [*a, *a, *a]").unwrap()[0];
+    /// // the mapping, 2 keys, `a` with 2 items and `b` with 3 copies of `a`
+    /// assert_eq!(doc.expanded_size(), 1 + 2 + 3 + (1 + 3 * 3));
+    /// ```
+    pub fn expanded_size(&self) -> usize {
+        input_expanded_size(self, &mut HashMap::new())
+    }
+
+    /// Layer `overlay` over this node, as an environment specific config is layered
+    /// over a base one. Mappings are merged key by key, deeply, the keys only in the
+    /// overlay being added at the end; sequences are replaced or appended to as
+    /// `strategy` says; any other value of the overlay replaces the one of this node.
+    ///
+    /// A merged collection keeps the anchor, tag and comments of this node. Keys are
+    /// matched whatever their anchors, and the merged aliases become copies.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::{MergeStrategy, YamlLoader};
+    ///
+    /// let base = &YamlLoader::load_from_str("image: web\nports: [80]\nenv: {A: 1, B: 2}")
+    ///     .unwrap()[0];
+    /// let env = &YamlLoader::load_from_str("ports: [443]\nenv: {B: ~, C: 3}\nreplicas: 2")
+    ///     .unwrap()[0];
+    /// let mut strategy = MergeStrategy::new();
+    /// strategy.append_arrays(true);
+    /// strategy.null_deletes(true);
+    /// let merged = base.merge(env, &strategy);
+    /// let expected = "image: web\nports: [80, 443]\nenv: {A: 1, C: 3}\nreplicas: 2";
+    /// assert_eq!(merged, YamlLoader::load_from_str(expected).unwrap()[0]);
+    /// ```
+    pub fn merge(&self, overlay: &YamlInput, strategy: &MergeStrategy) -> YamlInput {
+        let merged = match (resolve(self), resolve(overlay)) {
+            (Self::Hash(base), Self::Hash(over)) => {
+                let mut over: LinkedHashMap<Self, (&Self, &Self)> = Entries::new(over)
+                    .map(|(k, v)| (expand(k), (k, v)))
+                    .collect();
+                let mut h = HashInput::new();
+                for (k, v) in Entries::new(base) {
+                    match over.remove(&expand(k)) {
+                        Some((_, v)) if strategy.null_deletes && resolve(v).is_null() => {}
+                        Some((_, over)) => {
+                            h.insert(k.clone(), v.merge(over, strategy));
+                        }
+                        None => {
+                            h.insert(k.clone(), v.clone());
+                        }
+                    }
+                }
+                for (_, (k, v)) in over {
+                    if !(strategy.null_deletes && resolve(v).is_null()) {
+                        h.insert(k.clone(), v.clone());
+                    }
+                }
+                Self::Hash(h)
+            }
+            (Self::Array(base), Self::Array(over)) if strategy.append_arrays => {
+                Self::Array(base.iter().chain(over).cloned().collect())
+            }
+            _ => return overlay.clone(),
+        };
+        rewrap(self, merged)
+    }
+
+    /// Merge the mappings given to the `<<` keys of this node and the nodes below it
+    /// into the mappings holding those keys, as the YAML merge key type describes.
+    /// The keys written in a mapping override the merged ones, and in `<<: [*a, *b]`
+    /// the keys of `a` override the ones of `b`. The merged entries take the place of
+    /// the `<<` entry, which is kept as it is if its value is not a mapping or a
+    /// sequence of mappings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yaml_rust_formatter::YamlLoader;
+    ///
+    /// let source = "
+    /// base: &base {image: web, replicas: 1}
+    /// prod:
+    ///   <<: *base
+    ///   replicas: 3
+    /// ";
+    /// let mut doc = YamlLoader::load_from_str(source).unwrap().remove(0);
+    /// doc.resolve_merge_keys();
+    /// assert_eq!(doc["prod"]["image"].as_str(), Some("web"));
+    /// assert_eq!(doc["prod"]["replicas"].as_i64(), Some(3));
+    /// ```
+    pub fn resolve_merge_keys(&mut self) {
+        match *self {
+            Self::Hash(ref mut h) => {
+                let entries = mem::take(h);
+                let mut seen: HashSet<YamlInput> = entries
+                    .keys()
+                    .filter(|k| !is_merge_key(k))
+                    .map(expand)
+                    .collect();
+                for (k, mut v) in entries {
+                    v.resolve_merge_keys();
+                    let merged = match *resolve(&v) {
+                        _ if !is_merge_key(&k) => None,
+                        Self::Hash(ref merged) => Some(vec![merged]),
+                        Self::Array(ref items) => items
+                            .iter()
+                            .map(|item| match *resolve(item) {
+                                Self::Hash(ref merged) => Some(merged),
+                                _ => None,
+                            })
+                            .collect(),
+                        _ => None,
+                    };
+                    match merged {
+                        Some(merged) => {
+                            for (mk, mv) in merged.into_iter().flat_map(Entries::new) {
+                                if seen.insert(expand(mk)) {
+                                    h.insert(mk.clone(), mv.clone());
+                                }
+                            }
+                        }
+                        None => {
+                            h.insert(k, v);
+                        }
+                    }
+                }
+            }
+            Self::Array(ref mut v) => {
+                for x in v {
+                    x.resolve_merge_keys();
+                }
+            }
+            Self::Anchored(_, ref mut node)
+            | Self::Aliased(_, Some(ref mut node))
+            | Self::Tagged(_, ref mut node)
+            | Self::Commented(ref mut node, _)
+            | Self::Spelled(ref mut node, _)
+            | Self::Styled(ref mut node, _) => node.resolve_merge_keys(),
+            _ => {}
+        }
+    }
+}
+
+fn is_merge_key(key: &YamlInput) -> bool {
+    key.as_str() == Some("<<")
+}
+
+#[allow(clippy::should_implement_trait)]
+impl YamlInput {
+    // Not implementing FromStr because there is no possibility of Error.
+    // This function falls back to Yaml::String if nothing else matches.
+    pub fn from_str(v: &str) -> Self {
+        match plain_value(v) {
+            Some(node) => node,
+            // try parsing as f64
+            None if parse_f64(v).is_some() => Self::Real(v.to_owned()),
+            None => Self::String(v.to_owned()),
+        }
+    }
+}
+
+/// The null, boolean or integer `YamlInput::from_str` reads from `v`, so that a
+/// float or string can be built without copying `v` when the caller owns it.
+pub(crate) fn plain_value(v: &str) -> Option<YamlInput> {
+    if let Some(value) = v.strip_prefix("0x") {
+        if let Ok(i) = i64::from_str_radix(value, 16) {
+            return Some(YamlInput::Integer(i));
+        }
+    }
+    if let Some(value) = v.strip_prefix("0o") {
+        if let Ok(i) = i64::from_str_radix(value, 8) {
+            return Some(YamlInput::Integer(i));
+        }
+    }
+    if let Some(value) = v.strip_prefix('+') {
+        if let Ok(i) = value.parse::<i64>() {
+            return Some(YamlInput::Integer(i));
+        }
+    }
+    match v {
+        "~" | "null" => Some(YamlInput::Null),
+        "true" => Some(YamlInput::Boolean(true)),
+        "false" => Some(YamlInput::Boolean(false)),
+        _ => v.parse::<i64>().ok().map(YamlInput::Integer),
+    }
+}
+
+/// The size of a node once its aliases are expanded.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct Expansion {
+    pub(crate) nodes: usize,
+    // the depth of the most deeply nested alias
+    pub(crate) depth: usize,
+}
+
+impl Expansion {
+    fn of(node: &YamlInput) -> Expansion {
+        let children = |nodes: &mut dyn Iterator<Item = &YamlInput>| {
+            nodes.fold(Expansion { nodes: 1, depth: 0 }, |total, x| {
+                let x = Expansion::of(x);
+                Expansion {
+                    nodes: total.nodes + x.nodes,
+                    depth: total.depth.max(x.depth),
+                }
+            })
+        };
+        match *node {
+            YamlInput::Array(ref v) => children(&mut v.iter()),
+            YamlInput::Hash(ref h) => children(&mut h.iter().flat_map(|(k, v)| vec![k, v])),
+            YamlInput::Aliased(_, Some(ref node)) => {
+                let expansion = Expansion::of(node);
+                Expansion {
+                    depth: expansion.depth + 1,
+                    ..expansion
+                }
+            }
+            YamlInput::Anchored(_, ref node)
+            | YamlInput::Tagged(_, ref node)
+            | YamlInput::Commented(ref node, _)
+            | YamlInput::Spelled(ref node, _)
+            | YamlInput::Styled(ref node, _) => Expansion::of(node),
+            _ => Expansion { nodes: 1, depth: 0 },
+        }
+    }
+}
+
+/// The expanded size of `node`, given the expanded sizes of the anchors complete
+/// before it, to which the anchors of `node` are added.
+fn input_expanded_size(node: &YamlInput, sizes: &mut HashMap<String, usize>) -> usize {
+    match *node {
+        YamlInput::Anchored(ref name, ref node) => {
+            let size = input_expanded_size(node, sizes);
+            sizes.insert(name.clone(), size);
+            size
+        }
+        YamlInput::Aliased(ref name, ref target) => match (sizes.get(name), target) {
+            (Some(&size), _) => size,
+            // the anchor is outside of the node measured
+            (None, Some(target)) => input_expanded_size(target, &mut HashMap::new()),
+            (None, None) => 1,
+        },
+        YamlInput::Array(ref v) => {
+            1 + v
+                .iter()
+                .map(|x| input_expanded_size(x, sizes))
+                .sum::<usize>()
+        }
+        YamlInput::Hash(ref h) => {
+            1 + h
+                .iter()
+                .map(|(k, v)| input_expanded_size(k, sizes) + input_expanded_size(v, sizes))
+                .sum::<usize>()
+        }
+        YamlInput::Tagged(_, ref node)
+        | YamlInput::Commented(ref node, _)
+        | YamlInput::Spelled(ref node, _)
+        | YamlInput::Styled(ref node, _) => input_expanded_size(node, sizes),
+        _ => 1,
+    }
+}
+
+/// The expanded size of `node` like `input_expanded_size`. An alias whose anchor is
+/// not in `node` counts as a single node.
+fn output_expanded_size(node: &YamlOutput, sizes: &mut HashMap<String, usize>) -> usize {
+    match *node {
+        YamlOutput::Anchored(ref name, ref node) => {
+            let size = output_expanded_size(node, sizes);
+            sizes.insert(name.clone(), size);
+            size
+        }
+        YamlOutput::Alias(ref name) => sizes.get(name).copied().unwrap_or(1),
+        YamlOutput::Array(ref v) => {
+            1 + v
+                .iter()
+                .map(|x| output_expanded_size(x, sizes))
+                .sum::<usize>()
+        }
+        YamlOutput::Hash(ref h) => {
+            1 + h
+                .iter()
+                .map(|(k, v)| output_expanded_size(k, sizes) + output_expanded_size(v, sizes))
+                .sum::<usize>()
+        }
+        YamlOutput::Tagged(_, ref node)
+        | YamlOutput::Commented(ref node, _)
+        | YamlOutput::Spelled(ref node, _)
+        | YamlOutput::Styled(ref node, _) => output_expanded_size(node, sizes),
+        _ => 1,
+    }
+}
+
+/// Add the anchors defined in `node` to `names`, in document order.
+fn collect_anchors<'a>(node: &'a YamlInput, names: &mut Vec<&'a str>) {
+    match *node {
+        YamlInput::Anchored(ref name, ref node) => {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+            collect_anchors(node, names);
+        }
+        YamlInput::Array(ref v) => {
+            for x in v {
+                collect_anchors(x, names);
+            }
+        }
+        YamlInput::Hash(ref h) => {
+            for (k, v) in h {
+                collect_anchors(k, names);
+                collect_anchors(v, names);
+            }
+        }
+        YamlInput::Tagged(_, ref node)
+        | YamlInput::Commented(ref node, _)
+        | YamlInput::Spelled(ref node, _)
+        | YamlInput::Styled(ref node, _) => collect_anchors(node, names),
+        _ => {}
+    }
+}
+
+/// Follow anchors, aliases, tags and comments down to the node they stand for.
+pub(crate) fn resolve(node: &YamlInput) -> &YamlInput {
+    match *node.inner() {
+        YamlInput::Aliased(_, Some(ref node)) => resolve(node),
+        ref node => node,
+    }
+}
+
+/// Copy `node` with every anchor dropped and every alias replaced by its node. Tags
+/// are kept.
+pub(crate) fn expand(node: &YamlInput) -> YamlInput {
+    match *node {
+        YamlInput::Anchored(_, ref node)
+        | YamlInput::Aliased(_, Some(ref node))
+        | YamlInput::Commented(ref node, _)
+        | YamlInput::Spelled(ref node, _)
+        | YamlInput::Styled(ref node, _) => expand(node),
+        YamlInput::Tagged(ref tag, ref node) => {
+            YamlInput::Tagged(tag.clone(), Box::new(expand(node)))
+        }
+        YamlInput::Array(ref v) => YamlInput::Array(v.iter().map(expand).collect()),
+        YamlInput::Hash(ref h) => YamlInput::Hash(
+            Entries::new(h)
+                .map(|(k, v)| (expand(k), expand(v)))
+                .collect(),
+        ),
+        ref node => node.clone(),
+    }
+}
+
+/// Put `inner` in place of the collection that `node` wraps, keeping its anchor, tag
+/// and comments.
+fn rewrap(node: &YamlInput, inner: YamlInput) -> YamlInput {
+    match *node {
+        YamlInput::Anchored(ref name, ref node) => {
+            YamlInput::Anchored(name.clone(), Box::new(rewrap(node, inner)))
+        }
+        YamlInput::Tagged(ref tag, ref node) => {
+            YamlInput::Tagged(tag.clone(), Box::new(rewrap(node, inner)))
+        }
+        YamlInput::Commented(ref node, ref comments) => {
+            YamlInput::Commented(Box::new(rewrap(node, inner)), comments.clone())
+        }
+        _ => inner,
+    }
+}
+
+/// Determine if `v` holds a line break, which a spelling written as is cannot.
+pub(crate) fn has_line_break(v: &str) -> bool {
+    v.contains(&['\n', '\r'][..])
+}
+
+/// The key of `h` kept as a raw scalar that resolves to the integer `idx`.
+fn raw_integer_key(h: &HashInput, idx: usize) -> Option<&YamlInput> {
+    let key = YamlInput::Integer(idx as i64);
+    h.keys().find(|k| match *resolve(k) {
+        YamlInput::Raw(ref v) => YamlInput::from_str(v) == key,
+        _ => false,
+    })
+}
+
+/// Find the key of `h` that is written differently from `key` but resolves to its value.
+fn find_resolved_key<'a>(h: &'a HashInput, key: &YamlInput) -> Option<&'a YamlInput> {
+    if h.contains_key(key) {
+        return None;
+    }
+    let key = expand(key);
+    h.keys().find(|k| expand(k) == key)
+}
+
+/// Look `key` up in `h`, also matching keys written with an anchor or as an alias.
+fn get_resolved<'a>(h: &'a HashInput, key: &YamlInput) -> &'a YamlInput {
+    match h.get(key) {
+        Some(v) => v,
+        None => h
+            .iter()
+            .find(|(k, _)| !ptr::eq(resolve(k), *k) && expand(k) == *key)
+            .map_or(&BAD_VALUE, |(_, v)| v),
+    }
+}
+
+/// The key of `h` that `self[key]` finds, to look it up mutably.
+fn hash_key(h: &HashInput, key: &str) -> Option<YamlInput> {
+    let string = YamlInput::String(key.to_owned());
+    if h.contains_key(&string) {
+        return Some(string);
+    }
+    let raw = YamlInput::Raw(key.to_owned());
+    h.keys()
+        .find(|k| !ptr::eq(resolve(k), *k) && expand(k) == string)
+        .or_else(|| h.keys().find(|k| **k == raw))
+        .cloned()
+}
+
+static BAD_VALUE: YamlInput = YamlInput::BadValue;
+impl<'a> Index<&'a str> for YamlInput {
+    type Output = Self;
+
+    fn index(&self, idx: &'a str) -> &Self {
+        let key = Self::String(idx.to_owned());
+        match self.as_hash() {
+            Some(h) => match get_resolved(h, &key) {
+                Self::BadValue => get_resolved(h, &Self::Raw(idx.to_owned())),
+                v => v,
+            },
+            None => &BAD_VALUE,
+        }
+    }
+}
+
+impl Index<usize> for YamlInput {
+    type Output = Self;
+
+    fn index(&self, idx: usize) -> &Self {
+        if let Some(v) = self.as_vec() {
+            v.get(idx).unwrap_or(&BAD_VALUE)
+        } else if let Some(h) = self.as_hash() {
+            let key = Self::Integer(idx as i64);
+            match get_resolved(h, &key) {
+                Self::BadValue => raw_integer_key(h, idx).map_or(&BAD_VALUE, |k| &h[k]),
+                v => v,
+            }
+        } else {
+            &BAD_VALUE
+        }
+    }
+}
+
+/// Gives the value of a key of a mapping, which is added as null if it is missing.
+///
+/// # Panics
+///
+/// When the node is not a mapping.
+impl<'a> IndexMut<&'a str> for YamlInput {
+    fn index_mut(&mut self, idx: &'a str) -> &mut Self {
+        match self.entry(idx) {
+            Some(entry) => entry.or_insert(Self::Null),
+            None => panic!(
+                "cannot index a node that is not a mapping with the key {}",
+                idx
+            ),
+        }
+    }
+}
+
+/// Gives an item of a sequence, or the value of an integer key of a mapping, which is
+/// added as null if it is missing.
+///
+/// # Panics
+///
+/// When the index is out of the bounds of a sequence, or the node is neither a
+/// sequence nor a mapping.
+impl IndexMut<usize> for YamlInput {
+    fn index_mut(&mut self, idx: usize) -> &mut Self {
+        if self.as_vec_mut().is_some() {
+            let v = self.as_vec_mut().unwrap();
+            let len = v.len();
+            v.get_mut(idx)
+                .unwrap_or_else(|| panic!("index {} out of bounds of a sequence of {}", idx, len))
+        } else if let Some(h) = self.as_hash_mut() {
+            let key = raw_integer_key(h, idx).cloned();
+            h.entry(key.unwrap_or(Self::Integer(idx as i64)))
+                .or_insert(Self::Null)
+        } else {
+            panic!("cannot index a node that is not a sequence with {}", idx)
+        }
+    }
+}
+
+static OUTPUT_BAD_VALUE: YamlOutput = YamlOutput::BadValue;
+impl<'a> Index<&'a str> for YamlOutput {
+    type Output = Self;
+
+    fn index(&self, idx: &'a str) -> &Self {
+        match *self.inner() {
+            Self::Hash(ref h) => h
+                .get(&Self::String(idx.to_owned()))
+                .unwrap_or(&OUTPUT_BAD_VALUE),
+            _ => &OUTPUT_BAD_VALUE,
+        }
+    }
+}
+
+impl Index<usize> for YamlOutput {
+    type Output = Self;
+
+    fn index(&self, idx: usize) -> &Self {
+        match *self.inner() {
+            Self::Array(ref v) => v.get(idx).unwrap_or(&OUTPUT_BAD_VALUE),
+            Self::Hash(ref h) => h
+                .get(&Self::Integer(idx as i64))
+                .unwrap_or(&OUTPUT_BAD_VALUE),
+            _ => &OUTPUT_BAD_VALUE,
         }
     }
 }
 
-impl Index<usize> for YamlInput {
-    type Output = Self;
+/// Gives the value of a key of a mapping, which is added as null if it is missing.
+///
+/// # Panics
+///
+/// When the node is not a mapping.
+impl<'a> IndexMut<&'a str> for YamlOutput {
+    fn index_mut(&mut self, idx: &'a str) -> &mut Self {
+        match self.entry(idx) {
+            Some(entry) => entry.or_insert(Self::Null),
+            None => panic!(
+                "cannot index a node that is not a mapping with the key {}",
+                idx
+            ),
+        }
+    }
+}
 
-    fn index(&self, idx: usize) -> &Self {
-        if let Some(v) = self.as_vec() {
-            v.get(idx).unwrap_or(&BAD_VALUE)
-        } else if let Some(v) = self.as_hash() {
-            let key = Self::Integer(idx as i64);
-            v.get(&key).unwrap_or(&BAD_VALUE)
+/// Gives an item of a sequence, or the value of an integer key of a mapping, which is
+/// added as null if it is missing.
+///
+/// # Panics
+///
+/// When the index is out of the bounds of a sequence, or the node is neither a
+/// sequence nor a mapping.
+impl IndexMut<usize> for YamlOutput {
+    fn index_mut(&mut self, idx: usize) -> &mut Self {
+        if self.as_vec_mut().is_some() {
+            let v = self.as_vec_mut().unwrap();
+            let len = v.len();
+            v.get_mut(idx)
+                .unwrap_or_else(|| panic!("index {} out of bounds of a sequence of {}", idx, len))
+        } else if let Some(h) = self.as_hash_mut() {
+            h.entry(Self::Integer(idx as i64)).or_insert(Self::Null)
         } else {
-            &BAD_VALUE
+            panic!("cannot index a node that is not a sequence with {}", idx)
         }
     }
 }
@@ -432,7 +2985,7 @@ impl IntoIterator for YamlInput {
 
     fn into_iter(self) -> Self::IntoIter {
         YamlInputIter {
-            yaml: self.into_vec().unwrap_or_else(Vec::new).into_iter(),
+            yaml: self.into_vec().unwrap_or_default().into_iter(),
         }
     }
 }
@@ -450,6 +3003,7 @@ impl Iterator for YamlInputIter {
 }
 
 #[cfg(test)]
+#[allow(clippy::needless_borrow, clippy::bool_assert_comparison)]
 mod test {
     use crate::yaml::*;
     use std::f64;
@@ -581,6 +3135,10 @@ a1: &DEFAULT
 - [ 0xF, 0xF ]
 - +12345
 - [ true, false ]
+- !!int \"7\"
+- !!bool 'true'
+- !!null \"\"
+- !!float \"x\"
 ";
         let out = YamlLoader::load_from_str(&s).unwrap();
         let doc = &out[0];
@@ -613,6 +3171,11 @@ a1: &DEFAULT
         assert_eq!(doc[24].as_i64().unwrap(), 12345);
         assert!(doc[25][0].as_bool().unwrap());
         assert!(!doc[25][1].as_bool().unwrap());
+        // the core tags apply to quoted scalars too
+        assert_eq!(doc[26].as_i64(), Some(7));
+        assert_eq!(doc[27].as_bool(), Some(true));
+        assert!(doc[28].is_null());
+        assert!(doc[29].is_badvalue());
     }
 
     #[test]
@@ -720,6 +3283,32 @@ c: ~
         assert_eq!(None, iter.next());
     }
 
+    #[test]
+    fn test_entries_preserve_input_order() {
+        // more entries than any small-map optimisation, in an order no hash or sort gives
+        let keys: Vec<String> = (0..40).map(|i| format!("k{}", (i * 17) % 40)).collect();
+        let s: String = keys
+            .iter()
+            .map(|k| format!("{}: {{z: 1, a: 2}}\n", k))
+            .collect();
+        let doc = &YamlLoader::load_from_str(&s).unwrap()[0];
+        let entries = doc.entries();
+        assert_eq!(entries.len(), 40);
+        let loaded: Vec<_> = entries.map(|(k, _)| k.as_str().unwrap()).collect();
+        assert_eq!(loaded, keys);
+        for (_, v) in doc.entries() {
+            let nested: Vec<_> = v.entries().map(|(k, _)| k.as_str().unwrap()).collect();
+            assert_eq!(nested, ["z", "a"]);
+        }
+
+        // aliases and anchors lead to their mapping, other nodes have no entries
+        let doc = &YamlLoader::load_from_str("[&a {y: 1, x: 2}, *a, 3]").unwrap()[0];
+        assert_eq!(doc[1].entries().count(), 2);
+        assert_eq!(doc[1].entries().next().unwrap().0.as_str(), Some("y"));
+        assert_eq!(doc[2].entries().len(), 0);
+        assert_eq!(doc.entries().next(), None);
+    }
+
     #[test]
     fn test_integer_key() {
         let s = "
@@ -813,6 +3402,335 @@ subcommands3:
         assert!(doc["subcommands3"][0]["server"].as_hash().is_some());
     }
 
+    #[test]
+    #[cfg(feature = "unicode")]
+    fn test_normalize_keys() {
+        // "café" once with a precomposed and once with a combining accent
+        let s = "caf\u{e9}: 1\ncafe\u{301}: 2\n";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out[0].as_hash().unwrap().len(), 2);
+
+        let mut options = LoadOptions::new();
+        options.normalize_keys(true);
+        let out = YamlLoader::load_from_str_with_options(s, &options).unwrap();
+        let doc = &out[0];
+        assert_eq!(doc.as_hash().unwrap().len(), 1);
+        assert_eq!(doc["caf\u{e9}"].as_i64().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_keep_comments() {
+        let s = "
+# head
+a: 1 # one
+# about b
+b: # nested
+  - x
+  # about y
+  - y # why
+# the end
+";
+        let plain = YamlLoader::load_from_str(s).unwrap();
+        assert!(!matches!(plain[0], YamlInput::Commented(..)));
+
+        let mut options = LoadOptions::new();
+        options.keep_comments(true);
+        let out = YamlLoader::load_from_str_with_options(s, &options).unwrap();
+        let doc = &out[0];
+        let comments = |node: &YamlInput| match *node {
+            YamlInput::Commented(_, ref comments) => comments.clone(),
+            _ => Comments::default(),
+        };
+        // comments before the first node belong to the document
+        assert_eq!(comments(doc).before, vec![" head"]);
+        assert_eq!(comments(doc).after, vec![" the end"]);
+        let hash = match *doc {
+            YamlInput::Commented(ref node, _) => node.as_hash().unwrap(),
+            _ => unreachable!(),
+        };
+        let a = &hash[&YamlInput::String("a".to_owned())];
+        assert_eq!(comments(a).trailing.as_deref(), Some(" one"));
+        let b = &hash[&YamlInput::String("b".to_owned())];
+        assert_eq!(comments(b).before, vec![" about b"]);
+        assert_eq!(comments(b).trailing.as_deref(), Some(" nested"));
+        let y = &doc["b"][1];
+        assert_eq!(comments(y).before, vec![" about y"]);
+        assert_eq!(comments(y).trailing.as_deref(), Some(" why"));
+        assert_eq!(y.as_str(), Some("y"));
+        assert!(comments(&doc["b"][0]).is_empty());
+    }
+
+    #[test]
+    fn test_keep_key_and_closing_comments() {
+        let s = "
+? [a, b] # key
+: value # value
+? # explicit
+  c
+: 1
+d:
+  e: 2
+  # end of d
+f: 3
+";
+        let mut options = LoadOptions::new();
+        options.keep_comments(true);
+        let out = YamlLoader::load_from_str_with_options(s, &options).unwrap();
+        let doc = &out[0];
+        let comments = |node: &YamlInput| match *node {
+            YamlInput::Commented(_, ref comments) => comments.clone(),
+            _ => Comments::default(),
+        };
+        let ab = YamlInput::Array(vec![
+            YamlInput::String("a".to_owned()),
+            YamlInput::String("b".to_owned()),
+        ]);
+        let value = &doc.as_hash().unwrap()[&ab];
+        assert_eq!(comments(value).key_trailing.as_deref(), Some(" key"));
+        assert_eq!(comments(value).trailing.as_deref(), Some(" value"));
+        assert_eq!(
+            comments(&doc["c"]).key_trailing.as_deref(),
+            Some(" explicit")
+        );
+        assert_eq!(comments(&doc["c"]).trailing, None);
+        // the comment indented within `d` stays at its end
+        assert_eq!(comments(&doc["d"]["e"]).after, vec![" end of d"]);
+        assert!(comments(&doc["f"]).is_empty());
+    }
+
+    #[test]
+    fn test_keep_spelling() {
+        let s = "[True, NULL, null, 'TRUE', !!str False, Yes]";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out[0][0].as_str(), Some("True"));
+        assert_eq!(out[0][1].as_str(), Some("NULL"));
+        assert!(out[0][2].is_null());
+
+        let mut options = LoadOptions::new();
+        options.keep_spelling(true);
+        let out = YamlLoader::load_from_str_with_options(s, &options).unwrap();
+        let doc = &out[0];
+        // the spelling is kept, the values are read as without it
+        assert_eq!(doc[0], YamlInput::String("True".to_owned()));
+        assert_eq!(doc[1], YamlInput::String("NULL".to_owned()));
+        assert_eq!(
+            doc[2],
+            YamlInput::Spelled(Box::new(YamlInput::Null), "null".to_owned())
+        );
+        assert_eq!(doc[3].as_str(), Some("TRUE"));
+        assert_eq!(doc[4].as_str(), Some("False"));
+        assert_eq!(doc[5].as_str(), Some("Yes"));
+    }
+
+    #[test]
+    fn test_keep_spelling_radix() {
+        let s = "[0x1F, 0o17, 0xZZ, '0x1F', 31, 0o8, !!str 0x10]";
+        let mut options = LoadOptions::new();
+        options.keep_spelling(true);
+        let doc = &YamlLoader::load_from_str_with_options(s, &options).unwrap()[0];
+        assert_eq!(
+            doc[0],
+            YamlInput::Spelled(Box::new(YamlInput::Integer(31)), "0x1F".to_owned())
+        );
+        assert_eq!(
+            doc[1],
+            YamlInput::Spelled(Box::new(YamlInput::Integer(0o17)), "0o17".to_owned())
+        );
+        // only plain scalars that are integers keep their spelling
+        assert_eq!(doc[2], YamlInput::String("0xZZ".to_owned()));
+        assert_eq!(doc[3], YamlInput::String("0x1F".to_owned()));
+        assert_eq!(doc[4], YamlInput::Integer(31));
+        assert_eq!(doc[5], YamlInput::String("0o8".to_owned()));
+        assert_eq!(doc[6], YamlInput::String("0x10".to_owned()));
+
+        // the values are those read without the option
+        let plain = &YamlLoader::load_from_str(s).unwrap()[0];
+        for (spelled, plain) in doc.as_vec().unwrap().iter().zip(plain.as_vec().unwrap()) {
+            assert_eq!(spelled.as_i64(), plain.as_i64());
+            assert_eq!(spelled.as_str(), plain.as_str());
+        }
+
+        // and written back as they were read
+        let mut out = String::new();
+        crate::emitter::YamlEmitter::new(&mut out)
+            .dump(&doc.clone().into())
+            .unwrap();
+        assert_eq!(
+            out,
+            "---\n- 0x1F\n- 0o17\n- \"0xZZ\"\n- \"0x1F\"\n- 31\n- 0o8\n- \"0x10\""
+        );
+        let again = &YamlLoader::load_from_str_with_options(&out, &options).unwrap()[0];
+        assert_eq!(again[0], doc[0]);
+        assert_eq!(again[1], doc[1]);
+    }
+
+    #[test]
+    fn test_predicates() {
+        let s = "
+list: &l [1]
+map: &m {a: 1}
+none: &n ~
+str: &s x
+int: &i 1
+real: &r 1.5
+copies: [*l, *m, *n, *s, *i, *r]
+";
+        let mut options = LoadOptions::new();
+        options.keep_comments(true);
+        let doc = &YamlLoader::load_from_str_with_options(s, &options).unwrap()[0];
+        let copies = &doc["copies"];
+        for node in &[&doc["list"], &copies[0]] {
+            assert!(node.is_array() && !node.is_array_exact() && !node.is_hash());
+        }
+        for node in &[&doc["map"], &copies[1]] {
+            assert!(node.is_hash() && !node.is_array());
+        }
+        for node in &[&doc["none"], &copies[2]] {
+            assert!(node.is_null() && !node.is_null_exact());
+        }
+        assert!(doc["str"].is_string() && copies[3].is_string());
+        assert!(doc["int"].is_integer() && copies[4].is_integer() && !copies[4].is_real());
+        assert!(doc["real"].is_real() && copies[5].is_real());
+        assert!(doc["missing"].is_badvalue() && doc["missing"].is_badvalue_exact());
+        assert!(YamlInput::Null.is_null_exact());
+
+        options.raw_scalars(true);
+        let doc =
+            &YamlLoader::load_from_str_with_options("[~, 0x10, '1', x]", &options).unwrap()[0];
+        assert!(doc[0].is_null() && !doc[0].is_null_exact());
+        assert!(doc[1].is_integer() && !doc[1].is_string());
+        assert!(doc[2].is_string() && doc[3].is_string());
+    }
+
+    #[test]
+    fn test_raw_scalars() {
+        let s = "
+0x1F: hex
+version: 1.10
+mode: 0o755
+ratio: .5
+flag: yes
+none: Null
+quoted: '010'
+tagged: !!int 010
+list: [+12, -0, true]
+";
+        let mut options = LoadOptions::new();
+        options.raw_scalars(true);
+        let doc = &YamlLoader::load_from_str_with_options(s, &options).unwrap()[0];
+        assert_eq!(doc["0x1F"], YamlInput::Raw("hex".to_owned()));
+        assert_eq!(doc["version"], YamlInput::Raw("1.10".to_owned()));
+        assert_eq!(doc["version"].as_f64(), Some(1.1));
+        assert_eq!(doc["version"].as_str(), Some("1.10"));
+        assert_eq!(doc["mode"].as_i64(), Some(0o755));
+        assert_eq!(
+            doc["ratio"].raw_value(),
+            Some(YamlInput::Real(".5".to_owned()))
+        );
+        assert_eq!(doc["flag"].as_bool(), None);
+        assert_eq!(
+            doc["none"].raw_value(),
+            Some(YamlInput::String("Null".to_owned()))
+        );
+        assert_eq!(doc["quoted"], YamlInput::String("010".to_owned()));
+        // a core tag is kept with the text it types
+        assert_eq!(
+            doc["tagged"],
+            YamlInput::Tagged(
+                "!!int".to_owned(),
+                Box::new(YamlInput::Raw("010".to_owned()))
+            )
+        );
+        assert_eq!(doc["tagged"].as_i64(), Some(10));
+        assert_eq!(doc["list"][0].as_i64(), Some(12));
+        assert_eq!(doc["list"][2].as_bool(), Some(true));
+        assert_eq!(
+            doc["list"][2].clone().into_string(),
+            Some("true".to_owned())
+        );
+        assert_eq!(doc["quoted"].raw_value(), None);
+
+        // the text is emitted as it was read
+        let mut out = String::new();
+        crate::emitter::YamlEmitter::new(&mut out)
+            .dump(&doc.clone().into())
+            .unwrap();
+        assert_eq!(
+            out,
+            "---
+0x1F: hex
+version: 1.10
+mode: 0o755
+ratio: .5
+flag: yes
+none: Null
+quoted: \"010\"
+tagged: !!int 010
+list:
+  - +12
+  - -0
+  - true"
+        );
+    }
+
+    #[test]
+    fn test_raw_scalars_line_breaks() {
+        let s = "a: 1st\n\n  2nd\n5: five\n";
+        let mut options = LoadOptions::new();
+        options.raw_scalars(true);
+        options.keep_spelling(true);
+        let doc = &YamlLoader::load_from_str_with_options(s, &options).unwrap()[0];
+        // a folded line break has no spelling to keep
+        assert_eq!(doc["a"], YamlInput::String("1st\n2nd".to_owned()));
+        assert_eq!(doc[5].as_str(), Some("five"));
+
+        let mut out = String::new();
+        crate::emitter::YamlEmitter::new(&mut out)
+            .dump(&doc.clone().into())
+            .unwrap();
+        assert_eq!(out, "---\na: \"1st\\n2nd\"\n5: five");
+        let again = &YamlLoader::load_from_str_with_options(&out, &options).unwrap()[0];
+        assert_eq!(again, doc);
+
+        // nor has a raw scalar built with one
+        let raw: YamlOutput = YamlInput::Raw("1st\n2nd".to_owned()).into();
+        assert_eq!(raw, YamlOutput::String("1st\n2nd".to_owned()));
+    }
+
+    #[test]
+    fn test_resolve_keys() {
+        let s = "
+&k name: first
+other: 1
+*k : second
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out[0].as_hash().unwrap().len(), 3);
+        assert_eq!(out[0]["name"].as_str(), Some("first"));
+
+        let mut options = LoadOptions::new();
+        options.resolve_keys(true);
+        let out = YamlLoader::load_from_str_with_options(s, &options).unwrap();
+        let doc = &out[0];
+        let keys: Vec<_> = doc.entries().map(|(k, _)| k.clone()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                YamlInput::String("other".to_owned()),
+                YamlInput::Anchored(
+                    "k".to_owned(),
+                    Box::new(YamlInput::String("name".to_owned()))
+                ),
+            ]
+        );
+        assert_eq!(doc["name"].as_str(), Some("second"));
+
+        // plain keys equal to an anchored one are duplicates too
+        let out = YamlLoader::load_from_str_with_options("{&k 1: a, 1: b}", &options).unwrap();
+        assert_eq!(out[0].as_hash().unwrap().len(), 1);
+        assert_eq!(out[0][1].as_str(), Some("b"));
+    }
+
     #[test]
     fn test_recursion_depth_check_objects() {
         let s = "{a:".repeat(10_000) + &"}".repeat(10_000);
@@ -824,4 +3742,644 @@ subcommands3:
         let s = "[".repeat(10_000) + &"]".repeat(10_000);
         assert!(YamlLoader::load_from_str(&s).is_err());
     }
+    /// Hands out `data` a byte at a time, then fails with `error` if there is one.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        error: Option<io::ErrorKind>,
+    }
+
+    impl<'a> io::Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.data.split_first() {
+                Some((&byte, rest)) => {
+                    buf[0] = byte;
+                    self.data = rest;
+                    Ok(1)
+                }
+                None => match self.error {
+                    Some(kind) => Err(io::Error::new(kind, "trickle")),
+                    None => Ok(0),
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn test_load_from_reader() {
+        let s = "# café\nname: \"naïve ☃\"\nlist: [1, 2.5, 😀]\n---\nlong: |\n  ü\r\n  end\n";
+        let expected = YamlLoader::load_from_str(s).unwrap();
+        let reader = Trickle {
+            data: s.as_bytes(),
+            error: None,
+        };
+        assert_eq!(YamlLoader::load_from_reader(reader).unwrap(), expected);
+
+        let big = "- x\n".repeat(10_000);
+        let docs = YamlLoader::load_from_reader(big.as_bytes()).unwrap();
+        assert_eq!(docs[0].as_vec().unwrap().len(), 10_000);
+
+        for &data in &[&b"a: \xff\n"[..], b"a: \xc3(", b"a: \xe2\x98"] {
+            match YamlLoader::load_from_reader(data) {
+                Err(LoadError::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::InvalidData),
+                other => panic!("{:?}", other),
+            }
+        }
+
+        let reader = Trickle {
+            data: b"a: [1,",
+            error: Some(io::ErrorKind::BrokenPipe),
+        };
+        match YamlLoader::load_from_reader(reader) {
+            Err(LoadError::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::BrokenPipe),
+            other => panic!("{:?}", other),
+        }
+        match YamlLoader::load_from_reader(&b"a: [1,"[..]) {
+            Err(LoadError::Scan(err)) => {
+                assert_eq!(err, YamlLoader::load_from_str("a: [1,").unwrap_err())
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resolve_merge_keys() {
+        let s = "
+a: &a {x: 1, y: 1}
+b: &b {y: 2, z: 2, <<: *a}
+c:
+  w: 0
+  <<: [*b, {v: 3, z: 3}]
+  z: 0
+d: {<<: 1, e: 2}
+list:
+  - <<: *a
+    x: 4
+";
+        let mut doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        doc.resolve_merge_keys();
+        let expected = YamlLoader::load_from_str(
+            "
+a: {x: 1, y: 1}
+b: {y: 2, z: 2, x: 1}
+c: {w: 0, y: 2, x: 1, v: 3, z: 0}
+d: {<<: 1, e: 2}
+list:
+  - {y: 1, x: 4}
+",
+        )
+        .unwrap()
+        .remove(0);
+        let entries = |node: &YamlInput| -> Vec<YamlInput> {
+            Entries::new(node.as_hash().unwrap())
+                .map(|(k, v)| YamlInput::Array(vec![expand(k), expand(v)]))
+                .collect()
+        };
+        for key in &["a", "b", "c", "d"] {
+            assert_eq!(entries(&doc[*key]), entries(&expected[*key]), "{}", key);
+        }
+        assert_eq!(entries(&doc["list"][0]), entries(&expected["list"][0]));
+    }
+
+    #[test]
+    fn test_custom_tags() {
+        let s = "
+bucket: !Ref MyBucket
+name: !Sub [a, b]
+attr: !GetAtt {a: b}
+long: !<tag:x> v
+anchored: &a !t x
+str: !!str 1
+plain: ! 2
+";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        let tagged = |tag: &str, node: YamlInput| YamlInput::Tagged(tag.to_owned(), Box::new(node));
+        assert_eq!(
+            doc["bucket"],
+            tagged("!Ref", YamlInput::String("MyBucket".to_owned()))
+        );
+        assert_eq!(doc["bucket"].as_str(), Some("MyBucket"));
+        assert!(matches!(doc["name"], YamlInput::Tagged(ref tag, _) if tag == "!Sub"));
+        assert_eq!(doc["name"][1].as_str(), Some("b"));
+        assert_eq!(doc["attr"]["a"].as_str(), Some("b"));
+        assert_eq!(
+            doc["long"],
+            tagged("!<tag:x>", YamlInput::String("v".to_owned()))
+        );
+        assert_eq!(
+            doc["anchored"],
+            YamlInput::Anchored(
+                "a".to_owned(),
+                Box::new(tagged("!t", YamlInput::String("x".to_owned())))
+            )
+        );
+        // the core and non-specific tags are resolved as before
+        assert_eq!(doc["str"], YamlInput::String("1".to_owned()));
+        assert_eq!(doc["plain"].as_str(), Some("2"));
+    }
+
+    #[test]
+    fn test_yaml_1_1_scheme() {
+        let s = "[yes, No, ON, off, y, N, Null, ~, '', !!str yes, yes please, 1]";
+        let mut options = LoadOptions::new();
+        options.resolution_scheme(ResolutionScheme::Yaml11);
+        let doc = &YamlLoader::load_from_str_with_options(s, &options).unwrap()[0];
+        let bools: Vec<_> = doc.as_vec().unwrap()[..6]
+            .iter()
+            .map(|x| x.as_bool())
+            .collect();
+        let expected = [true, false, true, false, true, false];
+        assert_eq!(bools, expected.iter().map(|&b| Some(b)).collect::<Vec<_>>());
+        assert!(doc[6].is_null() && doc[7].is_null());
+        for i in 8..11 {
+            assert!(doc[i].as_str().is_some(), "{:?}", doc[i]);
+        }
+        assert_eq!(doc[11].as_i64(), Some(1));
+
+        // the default core schema keeps them strings
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        assert_eq!(doc[0].as_str(), Some("yes"));
+        assert_eq!(doc[6].as_str(), Some("Null"));
+
+        // the spelling survives a load and emit cycle
+        options.raw_scalars(true);
+        let doc = YamlLoader::load_from_str_with_options("a: Yes\nb: 0x10\n", &options).unwrap();
+        assert_eq!(doc[0]["a"].as_bool(), Some(true));
+        let mut output = String::new();
+        crate::emitter::YamlEmitter::new(&mut output)
+            .dump(&doc[0].clone().into())
+            .unwrap();
+        assert_eq!(output, "---\na: Yes\nb: 0x10");
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let s = "a: 1\nb:\n  x: [1]\n  y: 2\n  x: {z: 3}\na: 4\n";
+        let load = |policy: DuplicateKeyPolicy| {
+            let mut options = LoadOptions::new();
+            options.duplicate_keys(policy);
+            assert_eq!(options.get_duplicate_keys(), policy);
+            YamlLoader::load_from_str_with_options(s, &options)
+        };
+        let doc = &load(DuplicateKeyPolicy::Overwrite).unwrap()[0];
+        assert_eq!(doc["a"].as_i64(), Some(4));
+        assert_eq!(doc["b"]["x"]["z"].as_i64(), Some(3));
+        assert_eq!(*doc, YamlLoader::load_from_str(s).unwrap()[0]);
+
+        let doc = &load(DuplicateKeyPolicy::KeepFirst).unwrap()[0];
+        assert_eq!(doc["a"].as_i64(), Some(1));
+        assert_eq!(doc["b"]["x"][0].as_i64(), Some(1));
+
+        let err = load(DuplicateKeyPolicy::Error).unwrap_err();
+        assert_eq!(err.info(), "duplicate key x");
+        assert_eq!((err.marker().line(), err.marker().col()), (5, 2));
+
+        let mut options = LoadOptions::new();
+        options.duplicate_keys(DuplicateKeyPolicy::Error);
+        #[cfg(feature = "stream")]
+        {
+            let mut docs =
+                YamlLoader::iter_documents_with_options("a: 1\n---\n{b: 1, b: 2}", &options);
+            assert!(docs.next().unwrap().is_ok());
+            assert_eq!(docs.next().unwrap().unwrap_err().marker().col(), 7);
+            assert!(docs.next().is_none());
+        }
+        let err = YamlLoader::load_from_reader_with_options("[{a: 1, a: 2}]".as_bytes(), &options)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "duplicate key a at line 1 column 9");
+    }
+
+    #[test]
+    fn test_alias_limits() {
+        // every level multiplies the nodes by nine
+        let mut s = "a0: &a0 [x, x, x, x, x, x, x, x, x]\n".to_owned();
+        for i in 1..10 {
+            let p = i - 1;
+            s += &format!(
+                "a{i}: &a{i} [*a{p}, *a{p}, *a{p}, *a{p}, *a{p}, *a{p}, *a{p}, *a{p}, *a{p}]\n",
+                i = i,
+                p = p
+            );
+        }
+        let mut options = LoadOptions::new();
+        options.max_expanded_nodes(Some(100_000));
+        let err = YamlLoader::load_from_str_with_options(&s, &options).unwrap_err();
+        assert_eq!(err.info(), "aliases expand to more than 100000 nodes");
+        assert_eq!(err.marker().line(), 6);
+
+        options.max_expanded_nodes(None);
+        options.max_alias_depth(Some(3));
+        assert_eq!(options.get_max_alias_depth(), Some(3));
+        let err = YamlLoader::load_from_str_with_options(&s, &options).unwrap_err();
+        assert_eq!(err.info(), "aliases are nested more than 3 deep");
+        assert_eq!(err.marker().line(), 5);
+
+        // the limits are per document, and within them aliases load as usual
+        let s = "a: &a [1, 2]\nb: [*a, *a]\n---\na: &a [1, 2]\nb: [*a, *a]\n";
+        options.max_expanded_nodes(Some(6));
+        let docs = YamlLoader::load_from_str_with_options(s, &options).unwrap();
+        assert_eq!(docs, YamlLoader::load_from_str(s).unwrap());
+        options.max_expanded_nodes(Some(5));
+        assert!(YamlLoader::load_from_str_with_options(s, &options).is_err());
+    }
+
+    #[test]
+    fn test_load_with_marks() {
+        let s = "a: 1\n---\n# top\nlist:\n  - {x: [1, 2]}\n  - &n !t y\n? [k]\n: v\n";
+        let docs = YamlLoader::load_from_str_with_marks(s, &LoadOptions::new()).unwrap();
+        assert_eq!(docs.len(), 2);
+        let at = |marks: &Marks, path: &str| {
+            let mark = marks.get(&Path::parse(path)).unwrap();
+            (mark.line(), mark.col())
+        };
+        assert_eq!(at(&docs[0].1, ""), (1, 0));
+        assert_eq!(at(&docs[0].1, "a"), (1, 3));
+        let marks = &docs[1].1;
+        assert_eq!(at(marks, ""), (4, 0));
+        assert_eq!(at(marks, "list"), (5, 2));
+        assert_eq!(at(marks, "list[0].x[1]"), (5, 12));
+        // after the anchor and tag of the node
+        assert_eq!(at(marks, "list[1]"), (6, 10));
+        let key = marks.key(&Path::parse("list[0].x")).unwrap();
+        assert_eq!((key.line(), key.col()), (5, 5));
+        // the nodes of a collection key have no path, unlike its value
+        assert_eq!(marks.len(), 8);
+        assert_eq!(
+            docs.into_iter().map(|(doc, _)| doc).collect::<Vec<_>>(),
+            YamlLoader::load_from_str(s).unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "stream")]
+    fn test_iter_documents() {
+        let s = "# a\na: 1\n---\n- &x b # c\n- *x\n...\n# d\n---\n---\nlast\n";
+        let mut options = LoadOptions::new();
+        options.keep_comments(true);
+        let docs: Vec<_> = YamlLoader::iter_documents_with_options(s, &options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            docs,
+            YamlLoader::load_from_str_with_options(s, &options).unwrap()
+        );
+        assert_eq!(docs.len(), 4);
+        assert_eq!(YamlLoader::iter_documents("").count(), 0);
+
+        let mut docs = YamlLoader::iter_documents("a: 1\n---\n- [\n---\nb: 2\n");
+        assert_eq!(docs.next().unwrap().unwrap()["a"].as_i64(), Some(1));
+        let err = docs.next().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            YamlLoader::load_from_str("a: 1\n---\n- [\n").unwrap_err()
+        );
+        assert!(docs.next().is_none());
+
+        options.line_endings(LineEndingPolicy::Only(LineEnding::Lf));
+        let mut docs = YamlLoader::iter_documents_with_options("a: 1\n---\nb: 2\r\n", &options);
+        assert_eq!(docs.next().unwrap().unwrap_err().marker().line(), 3);
+        assert!(docs.next().is_none());
+    }
+
+    #[test]
+    fn test_load_from_reader_line_endings() {
+        let mut options = LoadOptions::new();
+        options.line_endings(LineEndingPolicy::Consistent);
+        for &s in &["a: 1\r\nb: 2\rc: 3\n", "a: 1\nb: 2\r", "a: 1\rb: 2\r\n"] {
+            let expected = YamlLoader::load_from_str_with_options(s, &options).unwrap_err();
+            match YamlLoader::load_from_reader_with_options(s.as_bytes(), &options) {
+                Err(LoadError::Scan(err)) => assert_eq!(err, expected),
+                other => panic!("{:?}", other),
+            }
+        }
+        let s = "a: 1\rb: 2\r";
+        assert_eq!(
+            YamlLoader::load_from_reader_with_options(s.as_bytes(), &options).unwrap(),
+            YamlLoader::load_from_str(s).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_document_builder() {
+        let mut builder = DocumentBuilder::new();
+        let mut doc = HashOutput::new();
+        doc.insert(
+            YamlOutput::String("base".to_owned()),
+            builder.anchor("b", YamlOutput::String("x".to_owned())),
+        );
+        doc.insert(
+            YamlOutput::String("copy".to_owned()),
+            builder.alias("b").unwrap(),
+        );
+        for key in &["first", "second"] {
+            doc.insert(
+                YamlOutput::String(key.to_string()),
+                builder.reference("n", YamlOutput::Integer(1)),
+            );
+        }
+        assert_eq!(builder.alias("missing").unwrap_err().name(), "missing");
+        assert!(builder.is_anchored("n"));
+
+        let mut output = String::new();
+        crate::emitter::YamlEmitter::new(&mut output)
+            .dump(&YamlOutput::Hash(doc))
+            .unwrap();
+        assert_eq!(output, "---\nbase: &b x\ncopy: *b\nfirst: &n 1\nsecond: *n");
+        let docs = YamlLoader::load_from_str(&output).unwrap();
+        assert_eq!(docs[0]["second"].as_i64(), Some(1));
+    }
+
+    #[test]
+    fn test_expanded_size() {
+        let doc = &YamlLoader::load_from_str("a: &a [x, &b y]\nb: [*a, *b, *a]").unwrap()[0];
+        assert_eq!(doc.expanded_size(), 1 + 2 + 3 + (1 + 3 + 1 + 3));
+        // aliases outside of the node measured count as their anchored node
+        assert_eq!(doc["b"].expanded_size(), 1 + 3 + 1 + 3);
+
+        // the billion laughs, measured without expanding them
+        let mut builder = DocumentBuilder::new();
+        let mut h = HashOutput::new();
+        let lols = vec![YamlOutput::String("lol".to_owned()); 10];
+        h.insert(
+            YamlOutput::String("a0".to_owned()),
+            builder.anchor("a0", YamlOutput::Array(lols)),
+        );
+        for i in 1..10 {
+            let aliases = vec![builder.alias(&format!("a{}", i - 1)).unwrap(); 10];
+            let name = format!("a{}", i);
+            h.insert(
+                YamlOutput::String(name.clone()),
+                builder.anchor(&name, YamlOutput::Array(aliases)),
+            );
+        }
+        // `a{i}` expands to 11, 111, 1111 and so on nodes
+        let anchored: usize = (2..12).map(|n| (10usize.pow(n) - 1) / 9).sum();
+        assert_eq!(YamlOutput::Hash(h).expanded_size(), 1 + 10 + anchored);
+        let unknown = YamlOutput::Array(vec![YamlOutput::Alias("x".to_owned())]);
+        assert_eq!(unknown.expanded_size(), 2);
+    }
+
+    #[test]
+    fn test_output_from_values() {
+        use crate::emitter::YamlEmitter;
+        use std::collections::BTreeMap;
+
+        let mut ports = BTreeMap::new();
+        ports.insert("https", 443i64);
+        ports.insert("http", 80);
+        let doc = YamlOutput::from(vec![
+            YamlOutput::from("name"),
+            "web".to_owned().into(),
+            true.into(),
+            2.0.into(),
+            f64::NEG_INFINITY.into(),
+            ports.into(),
+            Vec::<YamlOutput>::new().into(),
+        ]);
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump(&doc).unwrap();
+        assert_eq!(
+            out,
+            "---\n- name\n- web\n- true\n- 2.0\n- -.inf\n- http: 80\n  https: 443\n- []"
+        );
+        // a real keeps its fraction, so it is read back as a real
+        let loaded = &YamlLoader::load_from_str(&out).unwrap()[0];
+        assert_eq!(loaded[3].as_f64(), Some(2.0));
+        assert_eq!(
+            YamlOutput::from(vec![vec![1i64]]),
+            YamlOutput::Array(vec![YamlOutput::Array(vec![YamlOutput::Integer(1)])])
+        );
+    }
+
+    #[test]
+    fn test_to_output() {
+        let source = "base: &base\n  x: &x 1\nuse: *base\nagain: *x\nmissing: *none\n";
+        let doc = &YamlLoader::load_from_str(source).unwrap()[0];
+        assert_eq!(
+            doc.to_output(AliasPolicy::KeepAlias).unwrap(),
+            YamlOutput::from(doc.clone())
+        );
+
+        let output = doc.to_output(AliasPolicy::InlineResolved).unwrap();
+        let mut written = String::new();
+        crate::emitter::YamlEmitter::new(&mut written)
+            .dump(&output)
+            .unwrap();
+        // the anchors stay on the nodes that had them and not on their copies
+        assert_eq!(
+            written,
+            "---\nbase: &base\n  x: &x 1\nuse:\n  x: 1\nagain: 1\nmissing: *none"
+        );
+
+        let err = doc.to_output(AliasPolicy::ErrorIfUnresolved).unwrap_err();
+        assert_eq!(err.to_string(), "unknown anchor none");
+        let doc = &YamlLoader::load_from_str("- &a [1]\n- *a").unwrap()[0];
+        assert_eq!(
+            doc.to_output(AliasPolicy::ErrorIfUnresolved).unwrap(),
+            doc.to_output(AliasPolicy::InlineResolved).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_binary() {
+        let s = "
+small: !!binary AAEC/w==
+large: !!binary |
+  R0lGODlhDAAMAIQAAP//9/X17unp5WZmZgAAAOfn515eXvPz7Y6OjuDg4J+fn5
+  OTk6enp56enmlpaWNjY6Ojo4SEhP/++f/++f/++f/++f/++f/++f/++f/++f/+
+bad: !!binary not base64
+text: AAEC/w==
+";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        assert_eq!(doc["small"].as_binary(), Some(&[0, 1, 2, 0xff][..]));
+        let large = doc["large"].as_binary().unwrap();
+        assert_eq!(&large[..6], b"GIF89a");
+        assert_eq!(large.len(), 93);
+        assert!(doc["bad"].is_badvalue());
+        assert_eq!(doc["text"].as_str(), Some("AAEC/w=="));
+
+        // the long payload is wrapped again in a literal block
+        let mut output = String::new();
+        crate::emitter::YamlEmitter::new(&mut output)
+            .dump(&doc.clone().into())
+            .unwrap();
+        let reloaded = &YamlLoader::load_from_str(&output).unwrap()[0];
+        assert_eq!(reloaded["large"].as_binary(), Some(large));
+        assert!(output.contains("large: !!binary |\n  R0lGODlh"));
+        assert!(output.lines().all(|line| line.len() <= 80));
+    }
+
+    #[test]
+    fn test_get_ignore_ascii_case() {
+        let s = "
+Image: a
+&k Tag: b
+nested: {Key: c}
+\u{130}d: d
+list: [1]
+";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        let get = |node: &YamlInput, key: &str| node.get_ignore_ascii_case(key).cloned();
+        assert_eq!(get(doc, "image"), Some(YamlInput::String("a".to_owned())));
+        assert_eq!(get(doc, "TAG"), Some(YamlInput::String("b".to_owned())));
+        assert_eq!(
+            get(&doc["nested"], "key"),
+            Some(YamlInput::String("c".to_owned()))
+        );
+        // no Unicode case folding
+        assert_eq!(get(doc, "id"), None);
+        assert_eq!(get(doc, "İd"), Some(YamlInput::String("d".to_owned())));
+        assert_eq!(get(&doc["list"], "x"), None);
+    }
+
+    #[test]
+    fn test_get_path() {
+        let s = "
+defaults: &d
+  image: web
+  env: [{name: A}]
+spec: !pod
+  containers:
+    - *d
+    - image: db # primary
+  true: yes
+  ~: none
+";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        let get = |path: &str| doc.get_path(path).and_then(|v| v.as_str());
+        assert_eq!(get("spec.containers[0].image"), Some("web"));
+        assert_eq!(get("spec.containers[0].env[0].name"), Some("A"));
+        assert_eq!(get("spec.containers[1].image"), Some("db"));
+        assert_eq!(get("spec.true"), Some("yes"));
+        assert_eq!(get("spec.~"), Some("none"));
+        assert_eq!(get("spec.containers[2]"), None);
+        assert_eq!(get("spec.containers.image"), None);
+        assert_eq!(get("spec.missing"), None);
+        assert_eq!(doc.get_path(""), Some(doc));
+
+        let pointer = |pointer: &str| doc.resolve_pointer(pointer).and_then(|v| v.as_str());
+        assert_eq!(pointer("/spec/containers/0/env/0/name"), Some("A"));
+        assert_eq!(pointer("/spec/true"), Some("yes"));
+        assert_eq!(pointer("/spec/containers/-"), None);
+        assert_eq!(pointer("/spec/containers/01"), None);
+        assert_eq!(pointer("/spec/containers/+1/image"), None);
+        assert_eq!(
+            Path::from_pointer("/spec/+1"),
+            Some(Path::new().key("spec").key("+1"))
+        );
+        assert!(matches!(
+            doc.get_path("defaults"),
+            Some(YamlInput::Anchored(ref name, _)) if name == "d"
+        ));
+        let path = Path::new().key("spec").key("containers").index(1);
+        assert_eq!(
+            doc.get_at(&path).map(|v| v["image"].as_str()),
+            Some(Some("db"))
+        );
+    }
+
+    #[test]
+    fn test_mutable_access() {
+        let s = "
+&k name: web
+spec: !pod
+  replicas: 1 # scaled
+  ports: [80, 443]
+  7: seven
+";
+        let mut doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        *doc.get_mut("name").unwrap() = YamlInput::String("db".to_owned());
+        assert_eq!(doc["name"].as_str(), Some("db"));
+        doc["spec"]["replicas"] = YamlInput::Integer(3);
+        doc["spec"]["ports"][1] = YamlInput::Integer(8443);
+        doc["spec"][7] = YamlInput::Null;
+        doc["spec"]["paused"] = YamlInput::Boolean(true);
+        doc["spec"]
+            .entry("replicas")
+            .unwrap()
+            .and_modify(|v| *v = YamlInput::Integer(v.as_i64().unwrap() * 2));
+        doc["spec"]["ports"]
+            .as_vec_mut()
+            .unwrap()
+            .push(YamlInput::Integer(9000));
+        assert_eq!(doc["spec"]["replicas"].as_i64(), Some(6));
+        assert_eq!(doc["spec"]["ports"][1].as_i64(), Some(8443));
+        assert_eq!(doc["spec"]["ports"][2].as_i64(), Some(9000));
+        assert_eq!(doc["spec"][7], YamlInput::Null);
+        assert_eq!(doc["spec"]["paused"].as_bool(), Some(true));
+        assert!(matches!(doc["spec"], YamlInput::Tagged(..)));
+        assert!(doc["spec"]["ports"].entry("x").is_none());
+        // the anchored key is kept rather than a second one added
+        assert_eq!(doc.as_hash().unwrap().len(), 2);
+
+        let mut out = YamlOutput::Hash(HashOutput::new());
+        out["list"] = vec![1, 2].into();
+        out["list"][0] = 5.into();
+        out.entry("list").unwrap().or_insert(YamlOutput::Null);
+        assert_eq!(out["list"], YamlOutput::from(vec![5, 2]));
+        assert_eq!(out["list"][9], YamlOutput::BadValue);
+        assert_eq!(out["missing"], YamlOutput::BadValue);
+    }
+
+    #[test]
+    #[should_panic(expected = "index 2 out of bounds of a sequence of 2")]
+    fn test_index_mut_out_of_bounds() {
+        let mut doc = YamlLoader::load_from_str("[1, 2]").unwrap().remove(0);
+        doc[2] = YamlInput::Null;
+    }
+
+    #[test]
+    fn test_merge() {
+        let load = |s: &str| YamlLoader::load_from_str(s).unwrap().remove(0);
+        let base = load(
+            "
+&k name: web
+spec: &spec
+  ports: [80]
+  env: {A: 1, B: 2}
+tags: [a]
+",
+        );
+        let overlay = load(
+            "
+name: api
+spec: {ports: [443], env: {B: ~, C: 3}}
+tags: x
+extra: ~
+",
+        );
+        let merged = base.merge(&overlay, &MergeStrategy::new());
+        assert_eq!(
+            merged,
+            load(
+                "
+&k name: api
+spec: &spec
+  ports: [443]
+  env: {A: 1, B: ~, C: 3}
+tags: x
+extra: ~
+"
+            )
+        );
+
+        let mut strategy = MergeStrategy::new();
+        strategy.append_arrays(true);
+        strategy.null_deletes(true);
+        let merged = base.merge(&overlay, &strategy);
+        assert_eq!(merged["spec"]["ports"], load("[80, 443]"));
+        assert_eq!(merged["spec"]["env"], load("{A: 1, C: 3}"));
+        assert!(merged.as_hash().unwrap().len() == 3);
+        assert!(matches!(merged["spec"], YamlInput::Anchored(..)));
+
+        // comments of the base stay on the merged collections
+        let mut options = LoadOptions::new();
+        options.keep_comments(true);
+        let base =
+            &YamlLoader::load_from_str_with_options("a: # note\n  b: 1\n", &options).unwrap()[0];
+        let merged = base.merge(&load("a: {c: 2}"), &strategy);
+        assert!(matches!(merged["a"], YamlInput::Commented(..)));
+        assert_eq!(merged["a"]["c"].as_i64(), Some(2));
+        // a scalar overlay replaces a collection
+        assert_eq!(base.merge(&load("a: 1"), &strategy)["a"].as_i64(), Some(1));
+    }
 }
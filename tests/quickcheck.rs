@@ -18,4 +18,36 @@ quickcheck! {
             Err(err) => TestResult::error(err.to_string()),
         }
     }
+
+    fn test_check_weird_mapping_keys(xs: Vec<String>) -> TestResult {
+        let mut out_str = String::new();
+        let input = YamlOutput::Hash(
+            xs.into_iter()
+                .map(|x| (YamlOutput::String(x), YamlOutput::Null))
+                .collect(),
+        );
+        {
+            let mut emitter = YamlEmitter::new(&mut out_str);
+            emitter.multiline_strings(true);
+            emitter.dump(&input).unwrap();
+        }
+        match YamlLoader::load_from_str(&out_str) {
+            Ok(output) => TestResult::from_bool(output.len() == 1 && input == output[0].clone().into()),
+            Err(err) => TestResult::error(err.to_string()),
+        }
+    }
+
+    fn test_check_wrapped_strings(words: Vec<String>, width: u8) -> TestResult {
+        let mut out_str = String::new();
+        let input = YamlOutput::Array(vec![YamlOutput::String(words.join(" "))]);
+        {
+            let mut emitter = YamlEmitter::new(&mut out_str);
+            emitter.max_line_width(Some(usize::from(width)));
+            emitter.dump(&input).unwrap();
+        }
+        match YamlLoader::load_from_str(&out_str) {
+            Ok(output) => TestResult::from_bool(output.len() == 1 && input == output[0].clone().into()),
+            Err(err) => TestResult::error(err.to_string()),
+        }
+    }
 }
@@ -1,12 +1,13 @@
 #![allow(dead_code)]
 #![allow(non_upper_case_globals)]
+#![allow(clippy::redundant_static_lifetimes, clippy::vec_init_then_push)]
 extern crate yaml_rust_formatter;
 
 use yaml_rust_formatter::parser::{Event, EventReceiver, Parser};
 use yaml_rust_formatter::scanner::TScalarStyle;
 
 // These names match the names used in the C++ test suite.
-#[cfg_attr(feature = "cargo-clippy", allow(enum_variant_names))]
+#[allow(clippy::enum_variant_names)]
 #[derive(Clone, PartialEq, PartialOrd, Debug)]
 enum TestEvent {
     OnDocumentStart,
@@ -0,0 +1,126 @@
+extern crate yaml_rust_formatter;
+
+use yaml_rust_formatter::{EmitProfile, YamlEmitter, YamlLoader, YamlOutput};
+
+const SOURCE: &str = "
+name: x
+b:
+  - 1
+  - 2
+  - c: ~
+    d: 'true'
+a:
+  e: []
+  f: {}
+  g: ''
+  h: '123'
+  i: 1.5
+  j: true
+  k: plain text
+";
+
+fn emit(profile: EmitProfile) -> String {
+    let docs = YamlLoader::load_from_str(SOURCE).unwrap();
+    let doc: YamlOutput = docs[0].clone().into();
+    let mut writer = String::new();
+    {
+        let mut emitter = YamlEmitter::new(&mut writer);
+        emitter.profile(profile);
+        emitter.dump(&doc).unwrap();
+    }
+    writer
+}
+
+#[test]
+fn test_native_snapshot() {
+    let expected = r#"---
+name: x
+b:
+  - 1
+  - 2
+  - c: ~
+    d: "true"
+a:
+  e: []
+  f: {}
+  g: ""
+  h: "123"
+  i: 1.5
+  j: true
+  k: plain text"#;
+    assert_eq!(emit(EmitProfile::Native), expected);
+}
+
+#[test]
+fn test_pyyaml_safe_dump_snapshot() {
+    let expected = "a:
+  e: []
+  f: {}
+  g: ''
+  h: '123'
+  i: 1.5
+  j: true
+  k: plain text
+b:
+- 1
+- 2
+- c: null
+  d: 'true'
+name: x
+";
+    assert_eq!(emit(EmitProfile::PyYamlSafeDump), expected);
+}
+
+#[test]
+fn test_go_yaml_v3_snapshot() {
+    let expected = r#"name: x
+b:
+    - 1
+    - 2
+    - c: null
+      d: "true"
+a:
+    e: []
+    f: {}
+    g: ""
+    h: "123"
+    i: 1.5
+    j: true
+    k: plain text
+"#;
+    assert_eq!(emit(EmitProfile::GoYamlV3), expected);
+}
+
+#[test]
+fn test_serde_yaml_snapshot() {
+    let expected = "name: x
+b:
+- 1
+- 2
+- c: null
+  d: 'true'
+a:
+  e: []
+  f: {}
+  g: ''
+  h: '123'
+  i: 1.5
+  j: true
+  k: plain text
+";
+    assert_eq!(emit(EmitProfile::SerdeYaml), expected);
+}
+
+#[test]
+fn test_profiles_round_trip() {
+    let original = YamlLoader::load_from_str(SOURCE).unwrap();
+    // PyYAML sorts the keys, so its output only matches up to key order
+    for profile in &[
+        EmitProfile::Native,
+        EmitProfile::GoYamlV3,
+        EmitProfile::SerdeYaml,
+    ] {
+        let reloaded = YamlLoader::load_from_str(&emit(*profile)).unwrap();
+        assert_eq!(original, reloaded, "{:?}", profile);
+    }
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "formatter")]
+#![allow(dead_code)]
+#![allow(non_upper_case_globals)]
+#![allow(clippy::redundant_static_lifetimes)]
+extern crate yaml_rust_formatter;
+
+use yaml_rust_formatter::cst::{Cst, SyntaxElement, SyntaxKind};
+use yaml_rust_formatter::YamlLoader;
+
+include!("specexamples.rs.inc");
+
+const EXAMPLES: &[&str] = &[
+    EX2_1, EX2_2, EX2_3, EX2_4, EX2_5, EX2_6, EX2_7, EX2_8, EX2_9, EX2_10, EX2_11, EX2_12, EX2_13,
+    EX2_14, EX2_15, EX2_16, EX2_17, EX2_18, EX2_23, EX2_24, EX2_25, EX2_26, EX2_27, EX2_28, EX5_3,
+    EX5_4, EX5_5, EX5_6, EX5_7, EX5_8, EX5_11, EX5_12, EX5_13, EX5_14, EX6_1, EX6_2, EX6_3, EX6_4,
+    EX6_5, EX6_6, EX6_7, EX6_8, EX6_9, EX6_10, EX6_11, EX6_12, EX6_13, EX6_14, EX6_15, EX6_16,
+    EX6_17, EX6_18, EX6_19, EX6_20, EX6_21, EX6_22, EX6_23, EX6_24, EX6_25, EX6_26, EX6_27a,
+    EX6_27b, EX6_28, EX6_29, EX7_1, EX7_2, EX7_3, EX7_4, EX7_5, EX7_6, EX7_7, EX7_8, EX7_9, EX7_10,
+    EX7_11, EX7_12, EX7_13, EX7_14, EX7_15, EX7_16, EX7_17, EX7_18, EX7_19, EX7_20, EX7_21, EX7_22,
+    EX7_23, EX7_24, EX8_1, EX8_2, EX8_3a, EX8_3b, EX8_3c, EX8_4, EX8_5, EX8_6, EX8_7, EX8_8, EX8_9,
+    EX8_10, EX8_11, EX8_12, EX8_13, EX8_14, EX8_15, EX8_16, EX8_17, EX8_18, EX8_19, EX8_20, EX8_21,
+    EX8_22,
+];
+
+#[test]
+fn test_spec_examples_lossless() {
+    let mut parsed = 0;
+    for (i, source) in EXAMPLES.iter().enumerate() {
+        // some examples are beyond the loader
+        let docs = match YamlLoader::load_from_str(source) {
+            Ok(docs) => docs,
+            Err(_) => continue,
+        };
+        let cst = Cst::parse(source).unwrap();
+        assert_eq!(cst.green().to_string(), *source, "example {}", i);
+        let errors = cst
+            .root()
+            .descendants()
+            .iter()
+            .flat_map(|node| node.children_with_tokens())
+            .filter(|child| match *child {
+                SyntaxElement::Node(ref node) => node.kind() == SyntaxKind::Error,
+                SyntaxElement::Token(ref token) => token.kind() == SyntaxKind::Error,
+            })
+            .count();
+        assert_eq!(errors, 0, "example {}: {:?}", i, cst);
+        for (doc, expected) in cst.documents().zip(&docs) {
+            for node in doc.descendants() {
+                assert_eq!(&source[node.text_range()], node.text());
+            }
+            // the tag handles of the directives are not in the text of the node
+            if let (Some(content), false) = (doc.content(), source.contains('%')) {
+                assert_eq!(content.to_yaml().unwrap(), *expected, "example {}", i);
+            }
+        }
+        parsed += 1;
+    }
+    assert!(parsed > 90, "{} examples parsed", parsed);
+}
@@ -0,0 +1,127 @@
+#![cfg(feature = "formatter")]
+#![allow(dead_code)]
+#![allow(non_upper_case_globals)]
+#![allow(clippy::redundant_static_lifetimes)]
+extern crate yaml_rust_formatter;
+
+use yaml_rust_formatter::format::{format_str, FormatOptions};
+use yaml_rust_formatter::YamlLoader;
+
+include!("specexamples.rs.inc");
+
+const EXAMPLES: &[&str] = &[
+    EX2_1, EX2_2, EX2_3, EX2_4, EX2_5, EX2_6, EX2_7, EX2_8, EX2_9, EX2_10, EX2_11, EX2_12, EX2_13,
+    EX2_14, EX2_15, EX2_16, EX2_17, EX2_18, EX2_23, EX2_24, EX2_25, EX2_26, EX2_27, EX2_28, EX5_3,
+    EX5_4, EX5_5, EX5_6, EX5_7, EX5_8, EX5_11, EX5_12, EX5_13, EX5_14, EX6_1, EX6_2, EX6_3, EX6_4,
+    EX6_5, EX6_6, EX6_7, EX6_8, EX6_9, EX6_10, EX6_11, EX6_12, EX6_13, EX6_14, EX6_15, EX6_16,
+    EX6_17, EX6_18, EX6_19, EX6_20, EX6_21, EX6_22, EX6_23, EX6_24, EX6_25, EX6_26, EX6_27a,
+    EX6_27b, EX6_28, EX6_29, EX7_1, EX7_2, EX7_3, EX7_4, EX7_5, EX7_6, EX7_7, EX7_8, EX7_9, EX7_10,
+    EX7_11, EX7_12, EX7_13, EX7_14, EX7_15, EX7_16, EX7_17, EX7_18, EX7_19, EX7_20, EX7_21, EX7_22,
+    EX7_23, EX7_24, EX8_1, EX8_2, EX8_3a, EX8_3b, EX8_3c, EX8_4, EX8_5, EX8_6, EX8_7, EX8_8, EX8_9,
+    EX8_10, EX8_11, EX8_12, EX8_13, EX8_14, EX8_15, EX8_16, EX8_17, EX8_18, EX8_19, EX8_20, EX8_21,
+    EX8_22,
+];
+
+fn configs() -> Vec<FormatOptions> {
+    let mut indented = FormatOptions::new();
+    indented.indent(4);
+    indented.indent_sequences(false);
+    let mut narrow = FormatOptions::new();
+    narrow.line_width(Some(20));
+    vec![FormatOptions::new(), indented, narrow]
+}
+
+/// Formatting formatted text changes nothing, comments included.
+fn check_idempotent(source: &str, options: &FormatOptions) {
+    let first = format_str(source, options).unwrap();
+    let second = format_str(&first, options).unwrap();
+    assert_eq!(first, second, "formatting again changed\n{}", source);
+    assert_eq!(first.matches('#').count(), second.matches('#').count());
+}
+
+#[test]
+fn test_spec_examples_idempotent() {
+    let mut formatted = 0;
+    for source in EXAMPLES {
+        // some examples are beyond the loader
+        if YamlLoader::load_from_str(source).is_err() {
+            continue;
+        }
+        for options in &configs() {
+            check_idempotent(source, options);
+        }
+        formatted += 1;
+    }
+    assert!(formatted > 80);
+}
+
+#[test]
+fn test_comments_idempotent() {
+    let sources = [
+        "? [a, b] # c1\n: value # c2\n",
+        "x:\n  ? [a, b] # c1\n  : value # c2\ny: 1\n",
+        "? # c0\n  a: 1\n: v\n",
+        "a:\n  b: 1\n  c: 2\n  # end of a\nd: 3\n",
+        "- a: 1\n  # end of the item\n- b\n",
+        "a: 1\nb:\n  # first\n  c: 2\n",
+        "a: aaaa bbbb cccc dddd eeee # c\nb: 1\n",
+        "aaaa bbbb cccc dddd # root\n",
+    ];
+    for source in &sources {
+        for options in &configs() {
+            check_idempotent(source, options);
+            let formatted = format_str(source, options).unwrap();
+            assert_eq!(
+                formatted.matches('#').count(),
+                source.matches('#').count(),
+                "comments lost from\n{}",
+                formatted
+            );
+        }
+    }
+}
+
+#[test]
+fn test_tagged_and_empty_scalars_idempotent() {
+    let sources = [
+        "a: !!bool yes\n",
+        "a: !!int '0x10'\n",
+        "a: !!int 0x10\n",
+        "a: !!float 1\n",
+        "a: &x\n",
+        "a: &x\nb: *x\n",
+    ];
+    let mut inlined = FormatOptions::new();
+    inlined.inline_anchors(2);
+    let mut configs = configs();
+    configs.push(inlined);
+    for source in &sources {
+        for options in &configs {
+            check_idempotent(source, options);
+            // the values are the ones of the source, whether or not anchors are inlined
+            let formatted = format_str(source, options).unwrap();
+            let values = |text: &str| {
+                let doc = YamlLoader::load_from_str(text).unwrap().remove(0);
+                ["a", "b"]
+                    .iter()
+                    .map(|key| {
+                        let v = &doc[*key];
+                        let text = v.as_str().map(str::to_owned);
+                        (text, v.as_i64(), v.as_bool(), v.as_f64(), v.is_null())
+                    })
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                values(&formatted),
+                values(source),
+                "values changed in\n{}",
+                formatted
+            );
+            assert!(
+                !formatted.contains(" \n"),
+                "trailing space in\n{}",
+                formatted
+            );
+        }
+    }
+}
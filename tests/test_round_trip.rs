@@ -1,6 +1,8 @@
+#![allow(clippy::needless_borrow)]
 extern crate yaml_rust_formatter;
 
-use yaml_rust_formatter::{YamlEmitter, YamlInput, YamlLoader, YamlOutput};
+use yaml_rust_formatter::yaml::HashInput;
+use yaml_rust_formatter::{EmitProfile, YamlEmitter, YamlInput, YamlLoader, YamlOutput};
 
 fn roundtrip(original: &YamlInput) {
     let output: YamlOutput = original.clone().into();
@@ -71,3 +73,143 @@ fn test_issue133() {
     let doc2 = YamlLoader::load_from_str(&out_str).unwrap().pop().unwrap();
     assert_eq!(doc, doc2); // This failed because the type has changed to a number now
 }
+
+#[test]
+fn test_dump_all() {
+    let original = "a: 1\n---\n- x\n- y\n---\nplain\n";
+    let parsed = YamlLoader::load_from_str(original).unwrap();
+    let output: Vec<YamlOutput> = parsed.iter().cloned().map(YamlOutput::from).collect();
+
+    let mut serialized = String::new();
+    YamlEmitter::new(&mut serialized).dump_all(&output).unwrap();
+    assert_eq!(serialized, "---\na: 1\n---\n- x\n- y\n---\nplain");
+    assert_eq!(YamlLoader::load_from_str(&serialized).unwrap(), parsed);
+
+    let mut serialized = String::new();
+    {
+        let mut emitter = YamlEmitter::new(&mut serialized);
+        emitter.profile(EmitProfile::GoYamlV3);
+        emitter.explicit_end(true);
+        emitter.dump_all(&output).unwrap();
+    }
+    assert_eq!(
+        serialized,
+        "a: 1\n...\n---\n- x\n- y\n...\n---\nplain\n...\n"
+    );
+    assert_eq!(YamlLoader::load_from_str(&serialized).unwrap(), parsed);
+
+    let mut serialized = String::new();
+    YamlEmitter::new(&mut serialized).dump_all(&[]).unwrap();
+    assert!(serialized.is_empty());
+}
+
+#[test]
+fn test_literal_block_scalars() {
+    let strings = [
+        "a\nb",
+        "a\nb\n",
+        "a\nb\n\n",
+        "a\nb\n\n\n",
+        "a\n\nb\n",
+        "  leading\nspace\n",
+        "\n  after an empty line",
+        "x\n  more indented\n",
+        "# not a comment\n- not an item\n",
+        "tab\there\n",
+    ];
+    for s in &strings {
+        let value = YamlInput::String((*s).to_owned());
+        let docs = vec![
+            value.clone(),
+            YamlInput::Array(vec![value.clone(), YamlInput::Integer(1)]),
+            YamlInput::Array(vec![YamlInput::Array(vec![value.clone()])]),
+            YamlLoader::load_from_str("{a: {b: x}, c: 1}").unwrap()[0].clone(),
+        ];
+        for doc in docs {
+            let doc = match doc {
+                YamlInput::Hash(mut h) => {
+                    let a = YamlInput::String("a".to_owned());
+                    let mut inner = h[&a].clone().into_hash().unwrap();
+                    inner.insert(YamlInput::String("b".to_owned()), value.clone());
+                    h.insert(a, YamlInput::Hash(inner));
+                    YamlInput::Hash(h)
+                }
+                doc => doc,
+            };
+            let mut emitted = String::new();
+            {
+                let mut emitter = YamlEmitter::new(&mut emitted);
+                emitter.multiline_strings(true);
+                emitter.dump(&doc.clone().into()).unwrap();
+            }
+            assert!(emitted.contains('|'), "{:?}", emitted);
+            assert_eq!(
+                YamlLoader::load_from_str(&emitted).unwrap(),
+                vec![doc],
+                "{:?}",
+                emitted
+            );
+        }
+    }
+}
+
+#[test]
+fn test_folded_block_scalars() {
+    let strings = [
+        "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor",
+        "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor\n",
+        "double  spaces  are   never   broken so they stay where they are in the text",
+        "averyveryveryverylongwordthatdoesnotfitonaline and then some more words",
+        "caf\u{e9} cr\u{e8}me br\u{fb}l\u{e9}e \u{2014} \u{e0} la carte, s'il vous pla\u{ee}t, merci",
+        "- looks like: a mapping # or a comment, but it is plain text all along",
+    ];
+    for s in &strings {
+        let value = YamlInput::String((*s).to_owned());
+        let mut hash = YamlLoader::load_from_str("{a: {b: x}}").unwrap()[0].clone();
+        if let YamlInput::Hash(ref mut h) = hash {
+            let mut inner = HashInput::new();
+            inner.insert(YamlInput::String("b".to_owned()), value.clone());
+            h.insert(YamlInput::String("a".to_owned()), YamlInput::Hash(inner));
+        }
+        let docs = vec![
+            value.clone(),
+            YamlInput::Array(vec![value.clone(), YamlInput::Integer(1)]),
+            hash,
+        ];
+        for doc in docs {
+            let mut emitted = String::new();
+            {
+                let mut emitter = YamlEmitter::new(&mut emitted);
+                emitter.fold_width(Some(20));
+                emitter.dump(&doc.clone().into()).unwrap();
+            }
+            assert!(emitted.contains('>'), "{:?}", emitted);
+            assert!(
+                emitted.lines().all(|line| !line.ends_with(' ')),
+                "{:?}",
+                emitted
+            );
+            assert_eq!(
+                YamlLoader::load_from_str(&emitted).unwrap(),
+                vec![doc],
+                "{:?}",
+                emitted
+            );
+        }
+    }
+
+    // short strings and multi-line ones are left alone
+    let mut emitted = String::new();
+    {
+        let mut emitter = YamlEmitter::new(&mut emitted);
+        emitter.fold_width(Some(20));
+        let doc = YamlLoader::load_from_str("[short text, \"first line\\nsecond line is long\"]")
+            .unwrap()[0]
+            .clone();
+        emitter.dump(&doc.into()).unwrap();
+    }
+    assert_eq!(
+        emitted,
+        "---\n- short text\n- \"first line\\nsecond line is long\""
+    );
+}